@@ -0,0 +1,114 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Decoding of the ATC formats of Adreno GPUs.
+//!
+//! ATC blocks are laid out like BC1 and BC2/BC3 blocks, with a different
+//! color palette, so they share most of the BC decoding.
+
+use super::decode::{decode_atc_color, decode_bc3_alpha, pixel_size};
+use super::etc::{image_blocks, store_pixel, write_block};
+use super::raw::channel_positions;
+use super::{BcnDecoderFormat, BcnEncoding, Error};
+
+/// ATC format of the source data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AtcFormat {
+    /// Opaque color in 8-byte blocks (`GL_ATC_RGB_AMD`)
+    Rgb,
+    /// Color with 4 bits of alpha per pixel, stored like BC2 in 16-byte
+    /// blocks (`GL_ATC_RGBA_EXPLICIT_ALPHA_AMD`)
+    RgbaExplicitAlpha,
+    /// Color with interpolated alpha, stored like BC3 in 16-byte blocks
+    /// (`GL_ATC_RGBA_INTERPOLATED_ALPHA_AMD`)
+    RgbaInterpolatedAlpha,
+}
+
+impl AtcFormat {
+    /// Returns the size of a 4x4 block in bytes.
+    pub fn block_size(self) -> usize {
+        match self {
+            AtcFormat::Rgb => 8,
+            AtcFormat::RgbaExplicitAlpha | AtcFormat::RgbaInterpolatedAlpha => 16,
+        }
+    }
+}
+
+/// Decodes ATC data into the given output format.
+///
+/// `Rgb` decodes to opaque pixels. Rows are tightly packed, top row first,
+/// and are never flipped. The LUM format is not accepted.
+///
+/// # Errors
+///
+/// This function will return an error if the size of the image is 0,
+/// `source` is too short for the image or the output format is LUM.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::{AtcFormat, BcnDecoderFormat};
+///
+/// // red and blue, with the pixel index 3 (blue) everywhere
+/// let block = [0x00, 0x7c, 0x1f, 0x00, 0xff, 0xff, 0xff, 0xff];
+/// let pixels = bcndecode::decode_atc(&block, 4, 4, AtcFormat::Rgb, BcnDecoderFormat::RGBA).unwrap();
+/// assert_eq!(pixels[..4], [0, 0, 0xff, 0xff]);
+/// ```
+pub fn decode_atc(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    atc_format: AtcFormat,
+    format: BcnDecoderFormat,
+) -> Result<Vec<u8>, Error> {
+    if format == BcnDecoderFormat::LUM {
+        return Err(Error::InvalidPixelFormat);
+    }
+    let blocks = image_blocks(source, width, height, atc_format.block_size())?;
+
+    // the size of the 8-bit formats does not depend on the encoding
+    let size = pixel_size(BcnEncoding::Bc1, format);
+    let positions = channel_positions(format);
+    let mut pixels = vec![0; size * width * height];
+    for (index, block) in blocks.enumerate() {
+        let mut decoded = decode_atc_color(&block[block.len() - 8..]);
+        match atc_format {
+            AtcFormat::Rgb => {}
+            AtcFormat::RgbaExplicitAlpha => {
+                for (n, pixel) in decoded.iter_mut().enumerate() {
+                    let a = 0xf & (block[n / 2] >> (4 * (n & 1)));
+                    pixel[3] = (a << 4) | a;
+                }
+            }
+            AtcFormat::RgbaInterpolatedAlpha => {
+                let mut alpha = [0; 16];
+                decode_bc3_alpha(&mut alpha, block, 1, 0);
+                for (pixel, a) in decoded.iter_mut().zip(alpha) {
+                    pixel[3] = a;
+                }
+            }
+        }
+        write_block(&mut pixels, index, width, height, size, |dst, n| {
+            store_pixel(dst, decoded[n], format, positions)
+        });
+    }
+    Ok(pixels)
+}
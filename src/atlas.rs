@@ -0,0 +1,163 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Extraction of sub-rectangles, e.g. the sprites of an atlas.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use super::decode::{block_size, decode_rust, output_params, pixel_size};
+use super::{BcnDecoderFormat, BcnEncoding, Error};
+
+/// A rectangle of pixels, with the origin at the top left of the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    /// Left edge in pixels
+    pub x: usize,
+    /// Top edge in pixels
+    pub y: usize,
+    /// Width in pixels
+    pub width: usize,
+    /// Height in pixels
+    pub height: usize,
+}
+
+/// Decodes the given rectangles of a BCN encoded image.
+///
+/// Only the blocks covering at least one of the rectangles are decoded, and
+/// each of them only once. Every rectangle is returned as a tightly packed
+/// buffer of its own, top row first, in the pixel layout of `decode`.
+/// Blocks missing from `source` decode to zeros.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the
+/// given parameters or a rectangle does not fit in the image.
+pub fn decode_rects(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    rects: &[Rect],
+) -> Result<Vec<Vec<u8>>, Error> {
+    output_params(width, height, encoding, format)?;
+    if rects.iter().any(|r| r.x + r.width > width || r.y + r.height > height) {
+        return Err(Error::InvalidImageSize);
+    }
+
+    let pixel_size = pixel_size(encoding, format);
+    let block_size = block_size(encoding);
+    let blocks_per_row = width.div_ceil(4);
+
+    // decoded 4x4 blocks by block index
+    let mut blocks: HashMap<usize, Vec<u8>> = HashMap::new();
+    let mut result = Vec::with_capacity(rects.len());
+    for rect in rects {
+        let mut pixels = Vec::with_capacity(pixel_size * rect.width * rect.height);
+        for y in rect.y..rect.y + rect.height {
+            for x in rect.x..rect.x + rect.width {
+                let index = (y / 4) * blocks_per_row + x / 4;
+                let block = match blocks.entry(index) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        entry.insert(decode_block(source, index * block_size, block_size, encoding, format)?)
+                    }
+                };
+                let offset = pixel_size * ((y % 4) * 4 + x % 4);
+                pixels.extend_from_slice(&block[offset..offset + pixel_size]);
+            }
+        }
+        result.push(pixels);
+    }
+    Ok(result)
+}
+
+/// Decodes a single rectangle of a BCN encoded image.
+///
+/// Only the blocks intersecting the rectangle are decoded, a row of blocks
+/// at a time. The rectangle is returned tightly packed, top row first, in
+/// the pixel layout of `decode`. Blocks missing from `source` decode to
+/// zeros.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the
+/// given parameters or the rectangle does not fit in the image.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::{decode_region, BcnDecoderFormat, BcnEncoding, Rect};
+///
+/// // a white block next to a black one
+/// let data = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// let rect = Rect { x: 3, y: 1, width: 2, height: 1 };
+/// let pixels = decode_region(&data, 8, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, &rect).unwrap();
+/// assert_eq!(pixels, [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0xff]);
+/// ```
+pub fn decode_region(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    rect: &Rect,
+) -> Result<Vec<u8>, Error> {
+    output_params(width, height, encoding, format)?;
+    if rect.x + rect.width > width || rect.y + rect.height > height {
+        return Err(Error::InvalidImageSize);
+    }
+    let pixel_size = pixel_size(encoding, format);
+    let mut pixels = Vec::with_capacity(pixel_size * rect.width * rect.height);
+    if rect.width == 0 || rect.height == 0 {
+        return Ok(pixels);
+    }
+
+    // the blocks of a block row that intersect the rectangle are adjacent
+    // in the source, and decode as an image of whole blocks, never flipped
+    let block_size = block_size(encoding);
+    let blocks_per_row = width.div_ceil(4);
+    let (bx0, bx1) = (rect.x / 4, (rect.x + rect.width).div_ceil(4));
+    let band_width = 4 * (bx1 - bx0);
+    let band_row_size = pixel_size * band_width;
+    let (x0, x1) = (pixel_size * (rect.x - 4 * bx0), pixel_size * (rect.x + rect.width - 4 * bx0));
+    for by in rect.y / 4..(rect.y + rect.height).div_ceil(4) {
+        let start = ((by * blocks_per_row + bx0) * block_size).min(source.len());
+        let end = (start + (bx1 - bx0) * block_size).min(source.len());
+        let band = decode_rust(&source[start..end], band_width, 4, encoding, format)?;
+        for y in (4 * by).max(rect.y)..(4 * by + 4).min(rect.y + rect.height) {
+            let row = &band[(y - 4 * by) * band_row_size..];
+            pixels.extend_from_slice(&row[x0..x1]);
+        }
+    }
+    Ok(pixels)
+}
+
+fn decode_block(
+    source: &[u8],
+    offset: usize,
+    block_size: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+) -> Result<Vec<u8>, Error> {
+    let data = source.get(offset..offset + block_size).unwrap_or(&[]);
+    decode_rust(data, 4, 4, encoding, format)
+}
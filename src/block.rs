@@ -0,0 +1,63 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Decoding of single 4x4 blocks.
+
+use super::decode::{block_size, decode_single_block};
+use super::{BcnEncoding, Error};
+
+/// Pixels of a decoded 4x4 block, row by row, top row first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Block4x4 {
+    /// RGBA pixels of BC1, BC2, BC3, BC5 and BC7 blocks. BC5 blocks have
+    /// blue set to 0 and alpha set to 0.
+    Rgba([[u8; 4]; 16]),
+    /// Values of BC4 blocks.
+    Luminance([u8; 16]),
+    /// RGB values of BC6H blocks.
+    RgbFloat([[f32; 3]; 16]),
+}
+
+/// Decodes a single 4x4 block of the given encoding.
+///
+/// The pixels are the same as `decode` writes for the block, before any
+/// swizzling, so tiling, virtual texturing or partial decoding can be built
+/// on top of it. Reserved BC6H and BC7 blocks decode to zeros. Bytes past
+/// the block size of the encoding are ignored.
+///
+/// # Errors
+///
+/// This function will return an error if `block` is shorter than a block of the encoding.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::{decode_block, BcnEncoding, Block4x4};
+///
+/// let block = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+/// assert_eq!(decode_block(BcnEncoding::Bc1, &block).unwrap(), Block4x4::Rgba([[0xff; 4]; 16]));
+/// ```
+pub fn decode_block(encoding: BcnEncoding, block: &[u8]) -> Result<Block4x4, Error> {
+    if block.len() < block_size(encoding) {
+        return Err(Error::ImageDecodingError);
+    }
+    Ok(decode_single_block(encoding, block))
+}
@@ -0,0 +1,213 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Compile-time decoding of BC1 and BC4 data.
+//!
+//! The functions in this module are `const fn`, so small assets can be
+//! decoded into static arrays while compiling:
+//!
+//! ```
+//! use bcndecode::const_decode;
+//!
+//! // an 8x4 icon made of a white and a black BC1 block
+//! static ICON: [[u8; 4]; 32] = const_decode::decode_bc1::<32>(
+//!     &[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+//!     8,
+//! );
+//! assert_eq!(ICON[0], [0xff, 0xff, 0xff, 0xff]);
+//! assert_eq!(ICON[4], [0, 0, 0, 0xff]);
+//! ```
+//!
+//! The output matches `decode` with the RGBA and LUM formats respectively,
+//! except that images are never flipped: pixels are always stored row by
+//! row, top row first.
+
+/// Decodes a BC1 block into 16 rgba pixels, row by row.
+pub const fn decode_bc1_block(block: &[u8; 8]) -> [[u8; 4]; 16] {
+    let c0 = block[0] as u16 | (block[1] as u16) << 8;
+    let c1 = block[2] as u16 | (block[3] as u16) << 8;
+    let lut = block[4] as u32 | (block[5] as u32) << 8 | (block[6] as u32) << 16 | (block[7] as u32) << 24;
+
+    let p0 = decode_565(c0);
+    let p1 = decode_565(c1);
+    let (r0, g0, b0) = (p0[0] as u16, p0[1] as u16, p0[2] as u16);
+    let (r1, g1, b1) = (p1[0] as u16, p1[1] as u16, p1[2] as u16);
+
+    let palette = if c0 > c1 {
+        [
+            p0,
+            p1,
+            [((2 * r0 + r1) / 3) as u8, ((2 * g0 + g1) / 3) as u8, ((2 * b0 + b1) / 3) as u8, 0xff],
+            [((r0 + 2 * r1) / 3) as u8, ((g0 + 2 * g1) / 3) as u8, ((b0 + 2 * b1) / 3) as u8, 0xff],
+        ]
+    } else {
+        [
+            p0,
+            p1,
+            [((r0 + r1) / 2) as u8, ((g0 + g1) / 2) as u8, ((b0 + b1) / 2) as u8, 0xff],
+            [0, 0, 0, 0],
+        ]
+    };
+
+    let mut pixels = [[0; 4]; 16];
+    let mut n = 0;
+    while n < 16 {
+        pixels[n] = palette[(3 & (lut >> (2 * n))) as usize];
+        n += 1;
+    }
+    pixels
+}
+
+/// Decodes a BC4 block into 16 luminance values, row by row.
+pub const fn decode_bc4_block(block: &[u8; 8]) -> [u8; 16] {
+    let a0 = block[0] as u16;
+    let a1 = block[1] as u16;
+    let palette = if a0 > a1 {
+        [
+            a0 as u8,
+            a1 as u8,
+            ((6 * a0 + a1) / 7) as u8,
+            ((5 * a0 + 2 * a1) / 7) as u8,
+            ((4 * a0 + 3 * a1) / 7) as u8,
+            ((3 * a0 + 4 * a1) / 7) as u8,
+            ((2 * a0 + 5 * a1) / 7) as u8,
+            ((a0 + 6 * a1) / 7) as u8,
+        ]
+    } else {
+        [
+            a0 as u8,
+            a1 as u8,
+            ((4 * a0 + a1) / 5) as u8,
+            ((3 * a0 + 2 * a1) / 5) as u8,
+            ((2 * a0 + 3 * a1) / 5) as u8,
+            ((a0 + 4 * a1) / 5) as u8,
+            0,
+            0xff,
+        ]
+    };
+
+    let mut lut = 0u64;
+    let mut i = 0;
+    while i < 6 {
+        lut |= (block[2 + i] as u64) << (8 * i);
+        i += 1;
+    }
+
+    let mut values = [0; 16];
+    let mut n = 0;
+    while n < 16 {
+        values[n] = palette[(7 & (lut >> (3 * n))) as usize];
+        n += 1;
+    }
+    values
+}
+
+/// Decodes a BC1 image of `N` pixels and the given width into rgba pixels.
+///
+/// # Panics
+///
+/// Panics, or fails to compile when evaluated in a constant, if `N` is not
+/// a multiple of `width` or `source` is too short for the image.
+pub const fn decode_bc1<const N: usize>(source: &[u8], width: usize) -> [[u8; 4]; N] {
+    let blocks_per_row = check_image_size(source, N, width);
+    let height = N / width;
+
+    let mut pixels = [[0; 4]; N];
+    let mut block_index = 0;
+    while block_index < blocks_per_row * height.div_ceil(4) {
+        let block = decode_bc1_block(&load_block(source, block_index));
+        let bx = 4 * (block_index % blocks_per_row);
+        let by = 4 * (block_index / blocks_per_row);
+
+        let mut n = 0;
+        while n < 16 {
+            let (x, y) = (bx + n % 4, by + n / 4);
+            if x < width && y < height {
+                pixels[y * width + x] = block[n];
+            }
+            n += 1;
+        }
+        block_index += 1;
+    }
+    pixels
+}
+
+/// Decodes a BC4 image of `N` pixels and the given width into luminance
+/// values.
+///
+/// # Panics
+///
+/// Panics, or fails to compile when evaluated in a constant, if `N` is not
+/// a multiple of `width` or `source` is too short for the image.
+pub const fn decode_bc4<const N: usize>(source: &[u8], width: usize) -> [u8; N] {
+    let blocks_per_row = check_image_size(source, N, width);
+    let height = N / width;
+
+    let mut values = [0; N];
+    let mut block_index = 0;
+    while block_index < blocks_per_row * height.div_ceil(4) {
+        let block = decode_bc4_block(&load_block(source, block_index));
+        let bx = 4 * (block_index % blocks_per_row);
+        let by = 4 * (block_index / blocks_per_row);
+
+        let mut n = 0;
+        while n < 16 {
+            let (x, y) = (bx + n % 4, by + n / 4);
+            if x < width && y < height {
+                values[y * width + x] = block[n];
+            }
+            n += 1;
+        }
+        block_index += 1;
+    }
+    values
+}
+
+const fn decode_565(x: u16) -> [u8; 4] {
+    let mut r = (x & 0xf800) >> 8;
+    r |= r >> 5;
+    let mut g = (x & 0x7e0) >> 3;
+    g |= g >> 6;
+    let mut b = (x & 0x1f) << 3;
+    b |= b >> 5;
+    [r as u8, g as u8, b as u8, 0xff]
+}
+
+/// Validates the image size and returns the number of blocks per row.
+const fn check_image_size(source: &[u8], pixels: usize, width: usize) -> usize {
+    if width == 0 || !pixels.is_multiple_of(width) {
+        panic!("pixel count is not a multiple of the width");
+    }
+    let blocks_per_row = width.div_ceil(4);
+    if source.len() < blocks_per_row * (pixels / width).div_ceil(4) * 8 {
+        panic!("source is too short for the image");
+    }
+    blocks_per_row
+}
+
+const fn load_block(source: &[u8], index: usize) -> [u8; 8] {
+    let mut block = [0; 8];
+    let mut i = 0;
+    while i < 8 {
+        block[i] = source[8 * index + i];
+        i += 1;
+    }
+    block
+}
@@ -0,0 +1,629 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Parser and unpacker for crunch (.crn) files.
+//!
+//! Crunch stores DXT textures as palettes of endpoints and selectors that
+//! the blocks of 8x8 pixel chunks refer to, Huffman coded. Unpacking a mip
+//! level turns it back into plain BCn blocks, which `CrnTexture::blocks`
+//! returns and `CrnTexture::decode` decodes. This is the format of the
+//! original crunch library; the checksums of the file are not verified.
+
+use super::decode::block_size;
+use super::{decode, BcnDecoderFormat, BcnEncoding, Error};
+
+/// `Hx`, the first two bytes of every file
+const SIGNATURE: usize = 0x4878;
+
+/// Size of the header with a single mip level
+const HEADER_SIZE: usize = 74;
+
+/// Largest number of symbols of a Huffman model
+const MAX_SYMBOLS: usize = 8192;
+
+/// Longest Huffman code
+const MAX_CODE_SIZE: usize = 16;
+
+// symbols of the code sizes of a Huffman model above the sizes themselves
+const SMALL_ZERO_RUN: u32 = 17;
+const LARGE_ZERO_RUN: u32 = 18;
+const SMALL_REPEAT: u32 = 19;
+const LARGE_REPEAT: u32 = 20;
+
+/// Order in which the code sizes of the code size symbols are sent
+const CODE_SIZE_ORDER: [usize; 21] = [18, 17, 20, 19, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16];
+
+/// Number of distinct endpoint pairs used by the 2x2 blocks of a chunk, by
+/// chunk encoding.
+const CHUNK_TILE_COUNTS: [usize; 8] = [1, 2, 2, 3, 3, 3, 3, 4];
+
+/// Endpoint pair of the top left, top right, bottom left and bottom right
+/// block of a chunk, by chunk encoding.
+const CHUNK_TILES: [[usize; 4]; 8] = [
+    [0, 0, 0, 0],
+    [0, 0, 1, 1],
+    [0, 1, 0, 1],
+    [0, 0, 1, 2],
+    [1, 2, 0, 0],
+    [0, 1, 0, 2],
+    [1, 0, 2, 0],
+    [0, 1, 2, 3],
+];
+
+/// DXT1 selector of the palette entries ordered from the first to the second
+/// endpoint.
+const DXT1_FROM_LINEAR: [u32; 4] = [0, 2, 3, 1];
+
+/// DXT5 alpha selector of the palette entries ordered from the first to the
+/// second endpoint.
+const DXT5_FROM_LINEAR: [u64; 8] = [0, 2, 3, 4, 5, 6, 7, 1];
+
+/// Block format of a crunch file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CrnFormat {
+    /// BC1
+    Dxt1,
+    /// BC3
+    Dxt5,
+    /// BC3 with YCoCg colors, scaled Co and Cg in red and green, scale in
+    /// blue and Y in alpha
+    Dxt5CCxY,
+    /// BC3 with a normal map, X in alpha and Y in green
+    Dxt5xGxR,
+    /// BC3 with X in alpha, Y in green and Z in blue
+    Dxt5xGBR,
+    /// BC3 with red in alpha and the original alpha in red
+    Dxt5AGBR,
+    /// BC5 with X in red and Y in green
+    DxnXY,
+    /// BC5 with Y in red and X in green (ATI2)
+    DxnYX,
+    /// BC4
+    Dxt5A,
+}
+
+impl CrnFormat {
+    fn from_u8(format: u8) -> Result<CrnFormat, Error> {
+        match format {
+            0 => Ok(CrnFormat::Dxt1),
+            2 => Ok(CrnFormat::Dxt5),
+            3 => Ok(CrnFormat::Dxt5CCxY),
+            4 => Ok(CrnFormat::Dxt5xGxR),
+            5 => Ok(CrnFormat::Dxt5xGBR),
+            6 => Ok(CrnFormat::Dxt5AGBR),
+            7 => Ok(CrnFormat::DxnXY),
+            8 => Ok(CrnFormat::DxnYX),
+            9 => Ok(CrnFormat::Dxt5A),
+            // DXT3, which crunch cannot compress, and the ETC formats of
+            // later versions
+            _ => Err(Error::FeatureNotImplemented),
+        }
+    }
+
+    /// Returns the encoding of the unpacked blocks.
+    pub fn encoding(self) -> BcnEncoding {
+        match self {
+            CrnFormat::Dxt1 => BcnEncoding::Bc1,
+            CrnFormat::DxnXY | CrnFormat::DxnYX => BcnEncoding::Bc5,
+            CrnFormat::Dxt5A => BcnEncoding::Bc4,
+            _ => BcnEncoding::Bc3,
+        }
+    }
+
+    /// Returns the palettes the blocks are made of, in the order a chunk
+    /// stores them.
+    fn channels(self) -> &'static [Palette] {
+        match self {
+            CrnFormat::Dxt1 => &[Palette::Color],
+            CrnFormat::DxnXY | CrnFormat::DxnYX => &[Palette::Alpha, Palette::Alpha],
+            CrnFormat::Dxt5A => &[Palette::Alpha],
+            _ => &[Palette::Color, Palette::Alpha],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Palette {
+    Color,
+    Alpha,
+}
+
+/// A parsed crunch texture with its palettes unpacked.
+#[derive(Debug)]
+pub struct CrnTexture<'a> {
+    /// Width of the base level in pixels
+    pub width: usize,
+    /// Height of the base level in pixels
+    pub height: usize,
+    /// Number of mip levels
+    pub mip_count: usize,
+    /// Number of cube faces, 6 for cubemaps and 1 otherwise
+    pub faces: usize,
+    /// Block format of the texture
+    pub format: CrnFormat,
+    data: &'a [u8],
+    level_offsets: Vec<usize>,
+    chunk_encoding: HuffmanModel,
+    // endpoint and selector index deltas of the color and the alpha palettes
+    endpoint_deltas: [HuffmanModel; 2],
+    selector_deltas: [HuffmanModel; 2],
+    color_endpoints: Vec<u32>,
+    color_selectors: Vec<u32>,
+    alpha_endpoints: Vec<u16>,
+    alpha_selectors: Vec<u64>,
+}
+
+impl<'a> CrnTexture<'a> {
+    /// Unpacks a face of the given mip level into BCn blocks of
+    /// `CrnFormat::encoding`, stored row by row.
+    ///
+    /// The faces of a level are coded one after the other, so unpacking a
+    /// face also unpacks the faces before it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidImageSize` if the face or level does not
+    /// exist, or `Error::ImageDecodingError` if its data is corrupt.
+    pub fn blocks(&self, level: usize, face: usize) -> Result<Vec<u8>, Error> {
+        if level >= self.mip_count || face >= self.faces {
+            return Err(Error::InvalidImageSize);
+        }
+        let end = self.level_offsets.get(level + 1).cloned().unwrap_or(self.data.len());
+        let data = self.data.get(self.level_offsets[level]..end).ok_or(Error::ImageDecodingError)?;
+        let mut reader = BitReader::new(data);
+
+        let (width, height) = level_size(self.width, self.height, level);
+        let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+        let (chunks_x, chunks_y) = (blocks_x.div_ceil(2), blocks_y.div_ceil(2));
+        let encoding = self.format.encoding();
+        let size = block_size(encoding);
+        let channels = self.format.channels();
+
+        // the indices continue from chunk to chunk and from face to face
+        let mut encodings = 1;
+        let mut endpoint_indices = [0; 2];
+        let mut selector_indices = [0; 2];
+
+        let mut blocks = Vec::new();
+        for _ in 0..=face {
+            blocks = vec![0; blocks_x * blocks_y * size];
+            for cy in 0..chunks_y {
+                for i in 0..chunks_x {
+                    // odd rows of chunks run from right to left
+                    let cx = if cy & 1 == 1 { chunks_x - 1 - i } else { i };
+
+                    // three chunk encodings per symbol, the 1 marks the end
+                    if encodings == 1 {
+                        encodings = reader.decode(&self.chunk_encoding)? | 512;
+                    }
+                    let chunk_encoding = (encodings & 7) as usize;
+                    encodings >>= 3;
+
+                    let mut endpoints = [[0; 4]; 2];
+                    for (c, &palette) in channels.iter().enumerate() {
+                        for endpoint in &mut endpoints[c][..CHUNK_TILE_COUNTS[chunk_encoding]] {
+                            let model = &self.endpoint_deltas[palette as usize];
+                            let count = self.endpoint_count(palette);
+                            endpoint_indices[c] = advance(endpoint_indices[c], reader.decode(model)?, count)?;
+                            *endpoint = endpoint_indices[c];
+                        }
+                    }
+
+                    for (n, &tile) in CHUNK_TILES[chunk_encoding].iter().enumerate() {
+                        let mut selectors = [0; 2];
+                        for (c, &palette) in channels.iter().enumerate() {
+                            let model = &self.selector_deltas[palette as usize];
+                            let count = self.selector_count(palette);
+                            selector_indices[c] = advance(selector_indices[c], reader.decode(model)?, count)?;
+                            selectors[c] = selector_indices[c];
+                        }
+
+                        let (bx, by) = (2 * cx + (n & 1), 2 * cy + (n >> 1));
+                        if bx >= blocks_x || by >= blocks_y {
+                            continue;
+                        }
+                        let offset = size * (by * blocks_x + bx);
+                        let block = &mut blocks[offset..offset + size];
+                        let mut alpha_offset = 0;
+                        for (c, &palette) in channels.iter().enumerate() {
+                            match palette {
+                                Palette::Color => {
+                                    let endpoint = self.color_endpoints[endpoints[c][tile]];
+                                    let selector = self.color_selectors[selectors[c]];
+                                    block[size - 8..size - 4].copy_from_slice(&endpoint.to_le_bytes());
+                                    block[size - 4..].copy_from_slice(&selector.to_le_bytes());
+                                }
+                                Palette::Alpha => {
+                                    let endpoint = self.alpha_endpoints[endpoints[c][tile]];
+                                    let selector = self.alpha_selectors[selectors[c]];
+                                    let alpha = &mut block[alpha_offset..alpha_offset + 8];
+                                    alpha[..2].copy_from_slice(&endpoint.to_le_bytes());
+                                    alpha[2..].copy_from_slice(&selector.to_le_bytes()[..6]);
+                                    alpha_offset += 8;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(blocks)
+    }
+
+    /// Unpacks and decodes a face of the given mip level, with the layout of
+    /// `decode`.
+    ///
+    /// The channels are decoded as they are stored; the swizzled DXT5
+    /// formats are not converted back.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error of `blocks` or `decode`.
+    pub fn decode(&self, level: usize, face: usize, format: BcnDecoderFormat) -> Result<Vec<u8>, Error> {
+        let blocks = self.blocks(level, face)?;
+        let (width, height) = level_size(self.width, self.height, level);
+        decode(&blocks, width, height, self.format.encoding(), format)
+    }
+
+    fn endpoint_count(&self, palette: Palette) -> usize {
+        match palette {
+            Palette::Color => self.color_endpoints.len(),
+            Palette::Alpha => self.alpha_endpoints.len(),
+        }
+    }
+
+    fn selector_count(&self, palette: Palette) -> usize {
+        match palette {
+            Palette::Color => self.color_selectors.len(),
+            Palette::Alpha => self.alpha_selectors.len(),
+        }
+    }
+}
+
+/// Parses a crunch file and unpacks its palettes.
+///
+/// # Errors
+///
+/// Returns `Error::ImageDecodingError` if the header or the palettes are
+/// malformed, `Error::FeatureNotImplemented` for the DXT3 and ETC formats,
+/// or `Error::InvalidImageSize` if the texture is empty.
+///
+/// # Examples
+///
+/// ```no_run
+/// use bcndecode::{crn, BcnDecoderFormat};
+/// use std::fs::File;
+/// use std::io::Read;
+///
+/// # use std::io;
+/// # fn foo() -> io::Result<()> {
+/// let mut data = Vec::new();
+/// File::open("albedo.crn")?.read_to_end(&mut data)?;
+///
+/// let texture = crn::parse(&data)?;
+/// let pixels = texture.decode(0, 0, BcnDecoderFormat::RGBA)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse<'a>(data: &'a [u8]) -> Result<CrnTexture<'a>, Error> {
+    if read_be(data, 0, 2)? != SIGNATURE {
+        return Err(Error::ImageDecodingError);
+    }
+    let header_size = read_be(data, 2, 2)?;
+    let width = read_be(data, 12, 2)?;
+    let height = read_be(data, 14, 2)?;
+    let mip_count = read_be(data, 16, 1)?;
+    let faces = read_be(data, 17, 1)?;
+    let format = CrnFormat::from_u8(read_be(data, 18, 1)? as u8)?;
+
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidImageSize);
+    }
+    let max_levels = usize::BITS - width.max(height).leading_zeros();
+    if mip_count == 0 || mip_count > max_levels as usize || (faces != 1 && faces != 6) {
+        return Err(Error::ImageDecodingError);
+    }
+    if header_size < HEADER_SIZE + 4 * (mip_count - 1) || header_size > data.len() {
+        return Err(Error::ImageDecodingError);
+    }
+    let level_offsets = (0..mip_count)
+        .map(|level| read_be(data, 70 + 4 * level, 4))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut texture = CrnTexture {
+        width,
+        height,
+        mip_count,
+        faces,
+        format,
+        data,
+        level_offsets,
+        chunk_encoding: HuffmanModel::default(),
+        endpoint_deltas: Default::default(),
+        selector_deltas: Default::default(),
+        color_endpoints: Vec::new(),
+        color_selectors: Vec::new(),
+        alpha_endpoints: Vec::new(),
+        alpha_selectors: Vec::new(),
+    };
+
+    let has_color = format.channels().contains(&Palette::Color);
+    let has_alpha = format.channels().contains(&Palette::Alpha);
+    let mut tables = BitReader::new(section(data, read_be(data, 67, 3)?, read_be(data, 65, 2)?)?);
+    texture.chunk_encoding = tables.read_model()?;
+    for (palette, &used) in [has_color, has_alpha].iter().enumerate() {
+        if used {
+            texture.endpoint_deltas[palette] = tables.read_model()?;
+            texture.selector_deltas[palette] = tables.read_model()?;
+        }
+    }
+
+    let palette = |offset| -> Result<(BitReader<'a>, usize), Error> {
+        let (start, size, count) = (read_be(data, offset, 3)?, read_be(data, offset + 3, 3)?, read_be(data, offset + 6, 2)?);
+        if count == 0 {
+            return Err(Error::ImageDecodingError);
+        }
+        Ok((BitReader::new(section(data, start, size)?), count))
+    };
+    if has_color {
+        let (mut reader, count) = palette(33)?;
+        texture.color_endpoints = decode_color_endpoints(&mut reader, count)?;
+        let (mut reader, count) = palette(41)?;
+        texture.color_selectors = decode_color_selectors(&mut reader, count)?;
+    }
+    if has_alpha {
+        let (mut reader, count) = palette(49)?;
+        texture.alpha_endpoints = decode_alpha_endpoints(&mut reader, count)?;
+        let (mut reader, count) = palette(57)?;
+        texture.alpha_selectors = decode_alpha_selectors(&mut reader, count)?;
+    }
+    Ok(texture)
+}
+
+/// Decodes pairs of RGB565 endpoints, stored as deltas of every channel.
+fn decode_color_endpoints(reader: &mut BitReader, count: usize) -> Result<Vec<u32>, Error> {
+    // one model for the 5-bit and one for the 6-bit channels
+    let models = [reader.read_model()?, reader.read_model()?];
+    let mut channels = [0u32; 6];
+    let mut endpoints = Vec::with_capacity(count);
+    for _ in 0..count {
+        for (c, channel) in channels.iter_mut().enumerate() {
+            let (model, mask) = if c % 3 == 1 { (&models[1], 63) } else { (&models[0], 31) };
+            *channel = (*channel + reader.decode(model)?) & mask;
+        }
+        let [r0, g0, b0, r1, g1, b1] = channels;
+        endpoints.push(b0 | (g0 << 5) | (r0 << 11) | (b1 << 16) | (g1 << 21) | (r1 << 27));
+    }
+    Ok(endpoints)
+}
+
+/// Decodes the 2-bit selectors of DXT1 blocks, stored as deltas of pairs of
+/// pixels.
+fn decode_color_selectors(reader: &mut BitReader, count: usize) -> Result<Vec<u32>, Error> {
+    let model = reader.read_model()?;
+    let mut linear = [0u32; 16];
+    let mut selectors = Vec::with_capacity(count);
+    for _ in 0..count {
+        for pair in linear.chunks_exact_mut(2) {
+            // every symbol holds the deltas of both pixels from -3 to 3
+            let symbol = reader.decode(&model)?;
+            pair[0] = (pair[0] + symbol % 7 + 1) & 3;
+            pair[1] = (pair[1] + symbol / 7 + 1) & 3;
+        }
+        let selector = linear
+            .iter()
+            .enumerate()
+            .fold(0, |selector, (n, &s)| selector | DXT1_FROM_LINEAR[s as usize] << (2 * n));
+        selectors.push(selector);
+    }
+    Ok(selectors)
+}
+
+/// Decodes pairs of 8-bit alpha endpoints, stored as deltas.
+fn decode_alpha_endpoints(reader: &mut BitReader, count: usize) -> Result<Vec<u16>, Error> {
+    let model = reader.read_model()?;
+    let (mut a0, mut a1) = (0u32, 0u32);
+    let mut endpoints = Vec::with_capacity(count);
+    for _ in 0..count {
+        a0 = (a0 + reader.decode(&model)?) & 0xff;
+        a1 = (a1 + reader.decode(&model)?) & 0xff;
+        endpoints.push((a0 | (a1 << 8)) as u16);
+    }
+    Ok(endpoints)
+}
+
+/// Decodes the 3-bit selectors of DXT5 alpha blocks, stored as deltas of
+/// pairs of pixels.
+fn decode_alpha_selectors(reader: &mut BitReader, count: usize) -> Result<Vec<u64>, Error> {
+    let model = reader.read_model()?;
+    let mut linear = [0u32; 16];
+    let mut selectors = Vec::with_capacity(count);
+    for _ in 0..count {
+        for pair in linear.chunks_exact_mut(2) {
+            // every symbol holds the deltas of both pixels from -7 to 7
+            let symbol = reader.decode(&model)?;
+            pair[0] = (pair[0] + symbol % 15 + 1) & 7;
+            pair[1] = (pair[1] + symbol / 15 + 1) & 7;
+        }
+        let selector = linear
+            .iter()
+            .enumerate()
+            .fold(0, |selector, (n, &s)| selector | DXT5_FROM_LINEAR[s as usize] << (3 * n));
+        selectors.push(selector);
+    }
+    Ok(selectors)
+}
+
+/// Adds a delta to a palette index, wrapping around at the palette size.
+fn advance(index: usize, delta: u32, count: usize) -> Result<usize, Error> {
+    let index = index + delta as usize;
+    let index = if index >= count { index - count } else { index };
+    if index >= count {
+        return Err(Error::ImageDecodingError);
+    }
+    Ok(index)
+}
+
+/// A canonical Huffman code, as a count of codes of every size and the
+/// symbols ordered by code.
+#[derive(Debug, Default)]
+struct HuffmanModel {
+    counts: [u32; MAX_CODE_SIZE + 1],
+    symbols: Vec<u32>,
+}
+
+impl HuffmanModel {
+    fn new(code_sizes: &[u8]) -> Result<HuffmanModel, Error> {
+        let mut model = HuffmanModel::default();
+        for &size in code_sizes {
+            model.counts[size as usize] += 1;
+        }
+        model.counts[0] = 0;
+
+        // reject codes that do not fit into their sizes
+        let mut available = 1u32;
+        for &count in &model.counts[1..] {
+            available *= 2;
+            if count > available {
+                return Err(Error::ImageDecodingError);
+            }
+            available -= count;
+        }
+
+        for size in 1..=MAX_CODE_SIZE {
+            for (symbol, &s) in code_sizes.iter().enumerate() {
+                if s as usize == size {
+                    model.symbols.push(symbol as u32);
+                }
+            }
+        }
+        Ok(model)
+    }
+}
+
+/// Reads the bits of a stream, most significant bit first. Reading past
+/// the end returns zeros.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            data,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn read(&mut self, bits: u32) -> u32 {
+        let mut result = 0;
+        for _ in 0..bits {
+            if self.count == 0 {
+                self.buffer = self.data.get(self.position).cloned().unwrap_or(0) as u32;
+                self.position += 1;
+                self.count = 8;
+            }
+            self.count -= 1;
+            result = (result << 1) | ((self.buffer >> self.count) & 1);
+        }
+        result
+    }
+
+    fn decode(&mut self, model: &HuffmanModel) -> Result<u32, Error> {
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &model.counts[1..] {
+            code |= self.read(1);
+            if code < first + count {
+                return Ok(model.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(Error::ImageDecodingError)
+    }
+
+    /// Reads a Huffman model: the code sizes of its symbols, themselves
+    /// Huffman coded with runs of zeros and repeats.
+    fn read_model(&mut self) -> Result<HuffmanModel, Error> {
+        let symbol_count = self.read(14) as usize;
+        if symbol_count == 0 {
+            return Ok(HuffmanModel::default());
+        }
+        if symbol_count > MAX_SYMBOLS {
+            return Err(Error::ImageDecodingError);
+        }
+
+        let code_size_count = self.read(5) as usize;
+        if code_size_count == 0 || code_size_count > CODE_SIZE_ORDER.len() {
+            return Err(Error::ImageDecodingError);
+        }
+        let mut code_size_sizes = [0; 21];
+        for &symbol in &CODE_SIZE_ORDER[..code_size_count] {
+            code_size_sizes[symbol] = self.read(3) as u8;
+        }
+        let code_size_model = HuffmanModel::new(&code_size_sizes)?;
+
+        let mut code_sizes = vec![0u8; symbol_count];
+        let mut n = 0;
+        while n < symbol_count {
+            let symbol = self.decode(&code_size_model)?;
+            let (run, size) = match symbol {
+                0..=16 => (1, symbol as u8),
+                SMALL_ZERO_RUN => (self.read(3) as usize + 3, 0),
+                LARGE_ZERO_RUN => (self.read(7) as usize + 11, 0),
+                SMALL_REPEAT | LARGE_REPEAT => {
+                    let run = if symbol == SMALL_REPEAT { self.read(2) as usize + 3 } else { self.read(6) as usize + 7 };
+                    match n.checked_sub(1).map(|p| code_sizes[p]) {
+                        Some(size) if size != 0 => (run, size),
+                        _ => return Err(Error::ImageDecodingError),
+                    }
+                }
+                _ => return Err(Error::ImageDecodingError),
+            };
+            if run > symbol_count - n {
+                return Err(Error::ImageDecodingError);
+            }
+            code_sizes[n..n + run].fill(size);
+            n += run;
+        }
+        HuffmanModel::new(&code_sizes)
+    }
+}
+
+/// Reads a big-endian number of `size` bytes.
+fn read_be(data: &[u8], offset: usize, size: usize) -> Result<usize, Error> {
+    let bytes = data.get(offset..offset + size).ok_or(Error::ImageDecodingError)?;
+    Ok(bytes.iter().fold(0, |value, &b| (value << 8) | b as usize))
+}
+
+fn section(data: &[u8], offset: usize, size: usize) -> Result<&[u8], Error> {
+    data.get(offset..offset + size).ok_or(Error::ImageDecodingError)
+}
+
+fn level_size(width: usize, height: usize, level: usize) -> (usize, usize) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
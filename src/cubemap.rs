@@ -0,0 +1,209 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Decoding of cubemaps, e.g. BC6H environment maps.
+
+use super::decode::{compressed_size, decode_with_options, pixel_size};
+use super::{BcnDecoderFormat, BcnEncoding, DecodeOptions, Error, OutputLayout, SourceLayout};
+
+/// A face of a cubemap, in the order the faces are stored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CubeFace {
+    PositiveX = 0,
+    NegativeX = 1,
+    PositiveY = 2,
+    NegativeY = 3,
+    PositiveZ = 4,
+    NegativeZ = 5,
+}
+
+impl CubeFace {
+    /// All faces in storage order.
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+}
+
+/// What to do with the faces a cubemap source leaves out.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MissingFaces {
+    /// Missing faces are left out of the `Cubemap`.
+    #[default]
+    Skip,
+    /// Missing faces are filled with zeros in every level, e.g. for
+    /// uploads that need all six faces.
+    Fill,
+}
+
+/// A decoded cubemap.
+///
+/// Every level of every face is stored top row first and tightly packed,
+/// without the unused space `decode` leaves after BC6H pixels.
+#[derive(Debug, Clone)]
+pub struct Cubemap {
+    /// Width and height of the base level of the faces in pixels
+    pub size: usize,
+    /// Number of mip levels of every face
+    pub mip_count: usize,
+    /// Pixel format of the decoded faces
+    pub format: BcnDecoderFormat,
+    // Whether every face was decoded from the source
+    decoded: [bool; 6],
+    // Pixels of every level of every face, face by face, or `None` for the
+    // levels of skipped faces
+    levels: Vec<Option<Vec<u8>>>,
+}
+
+impl Cubemap {
+    /// Returns the width and height of a mip level in pixels.
+    pub fn level_size(&self, level: usize) -> usize {
+        (self.size >> level).max(1)
+    }
+
+    /// Returns whether a face was decoded from the source, rather than
+    /// skipped or filled because the source leaves it out.
+    pub fn has_face(&self, face: CubeFace) -> bool {
+        self.decoded[face as usize]
+    }
+
+    /// Returns the pixels of the base level of a face, or `None` if the
+    /// face was skipped.
+    pub fn face(&self, face: CubeFace) -> Option<&[u8]> {
+        self.level(face, 0)
+    }
+
+    /// Returns the pixels of a mip level of a face, or `None` if the level
+    /// does not exist or the face was skipped.
+    pub fn level(&self, face: CubeFace, level: usize) -> Option<&[u8]> {
+        if level >= self.mip_count {
+            return None;
+        }
+        self.levels[face as usize * self.mip_count + level].as_deref()
+    }
+}
+
+/// Decodes a cubemap of six consecutive faces, each followed by its mip
+/// levels, as DDS files store them.
+///
+/// `size` is the width and height of the base level of the faces; every
+/// further level halves it, down to 1x1 pixels.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with
+/// the given parameters, `mip_count` is 0 or larger than the mip chain, or
+/// the options ask for a tiled layout, a row pitch or a swizzled source.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::{BcnDecoderFormat, BcnEncoding, CubeFace, DecodeOptions};
+///
+/// // 4x4 faces of solid white BC1 blocks, with their 2x2 and 1x1 levels
+/// let source = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0].repeat(6 * 3);
+/// let cubemap = bcndecode::decode_cubemap(
+///     &source,
+///     4,
+///     3,
+///     BcnEncoding::Bc1,
+///     BcnDecoderFormat::RGBA,
+///     &DecodeOptions::default(),
+/// ).unwrap();
+/// assert_eq!(cubemap.face(CubeFace::NegativeY).unwrap(), &[0xff; 64][..]);
+/// assert_eq!(cubemap.level(CubeFace::PositiveZ, 2).unwrap().len(), 4);
+/// ```
+pub fn decode_cubemap(
+    source: &[u8],
+    size: usize,
+    mip_count: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: &DecodeOptions,
+) -> Result<Cubemap, Error> {
+    decode_faces(source, size, mip_count, &CubeFace::ALL, MissingFaces::Skip, encoding, format, options)
+}
+
+/// Decodes the given faces of a cubemap, stored like `decode_cubemap`
+/// expects them, but only those faces, in storage order. The other faces
+/// are skipped or filled as `missing` says.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn decode_faces(
+    source: &[u8],
+    size: usize,
+    mip_count: usize,
+    faces: &[CubeFace],
+    missing: MissingFaces,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: &DecodeOptions,
+) -> Result<Cubemap, Error> {
+    if options.layout != OutputLayout::Linear || options.row_pitch > 0 || options.source_layout != SourceLayout::Linear {
+        return Err(Error::InvalidImageSize);
+    }
+    let max_levels = usize::BITS - size.leading_zeros();
+    if mip_count == 0 || mip_count > max_levels as usize {
+        return Err(Error::InvalidImageSize);
+    }
+
+    let pixel_size = pixel_size(encoding, format);
+    let options = DecodeOptions {
+        flip_y: Some(false),
+        ..*options
+    };
+    let mut decoded = [false; 6];
+    let mut levels = Vec::with_capacity(6 * mip_count);
+    let mut offset = 0;
+    for face in CubeFace::ALL {
+        let present = faces.contains(&face);
+        decoded[face as usize] = present;
+        for level in 0..mip_count {
+            let level_size = (size >> level).max(1);
+            if !present {
+                levels.push(match missing {
+                    MissingFaces::Skip => None,
+                    MissingFaces::Fill => Some(vec![0; pixel_size * level_size * level_size]),
+                });
+                continue;
+            }
+            let end = (offset + compressed_size(level_size, level_size, encoding)).min(source.len());
+            let mut pixels = decode_with_options(&source[offset..end], level_size, level_size, encoding, format, &options)?;
+            offset = end;
+
+            // the decoder leaves unused space after BC6H pixels
+            pixels.truncate(pixel_size * level_size * level_size);
+            levels.push(Some(pixels));
+        }
+    }
+
+    Ok(Cubemap {
+        size,
+        mip_count,
+        format,
+        decoded,
+        levels,
+    })
+}
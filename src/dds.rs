@@ -0,0 +1,827 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Parser for DirectDraw Surface (.dds) files.
+//!
+//! Both the legacy header, which names the encoding with a FourCC code, and
+//! the DX10 extension header, which names it with a `DXGI_FORMAT`, are
+//! supported. Files may hold texture arrays, cubemaps and volume textures,
+//! each with a mip chain. Besides the block-compressed formats, the
+//! uncompressed formats of `RawFormat` are decoded with `decode_raw`; other
+//! formats are rejected with `Error::FeatureNotImplemented`. Files from
+//! writers that break the format in common ways are read with
+//! `parse_lenient`.
+
+#[cfg(feature = "png")]
+use std::fs::{self, File};
+#[cfg(feature = "png")]
+use std::io::BufWriter;
+use std::io::{self, Read};
+#[cfg(feature = "png")]
+use std::path::Path;
+
+use super::cubemap::decode_faces;
+use super::decode::pixel_size;
+use super::reader::read_u32;
+use super::{
+    decode_raw, decode_with_options, BcnDecoderFormat, BcnEncoding, CubeFace, Cubemap, DecodeOptions, Error, MipLevel,
+    MissingFaces, RawFormat,
+};
+#[cfg(feature = "png")]
+use super::{decode_tone_mapped, ToneMapping};
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: u32 = 124;
+const DX10_HEADER_SIZE: usize = 20;
+
+// dwFlags bits of the pitch and linear size fields
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+
+// DDS_PIXELFORMAT flags
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_ALPHA: u32 = 0x2;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x20000;
+
+// D3DFORMAT values of the float formats, stored in place of a FourCC code
+const D3DFMT_R16F: u32 = 111;
+const D3DFMT_G16R16F: u32 = 112;
+const D3DFMT_A16B16G16R16F: u32 = 113;
+
+// dwCaps2 flags
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_CUBEMAP_ALLFACES: u32 = 0xfc00;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+
+// D3D10_RESOURCE_DIMENSION
+const RESOURCE_DIMENSION_TEXTURE3D: u32 = 4;
+
+// D3D10_RESOURCE_MISC_FLAG
+const RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+// DDS_ALPHA_MODE in the low bits of miscFlags2
+const ALPHA_MODE_MASK: u32 = 0x7;
+const ALPHA_MODE_PREMULTIPLIED: u32 = 2;
+
+// DXGI_FORMAT values of the block-compressed formats
+const DXGI_FORMAT_BC1_TYPELESS: u32 = 70;
+const DXGI_FORMAT_BC1_UNORM_SRGB: u32 = 72;
+const DXGI_FORMAT_BC2_TYPELESS: u32 = 73;
+const DXGI_FORMAT_BC2_UNORM_SRGB: u32 = 75;
+const DXGI_FORMAT_BC3_TYPELESS: u32 = 76;
+const DXGI_FORMAT_BC3_UNORM_SRGB: u32 = 78;
+const DXGI_FORMAT_BC4_TYPELESS: u32 = 79;
+const DXGI_FORMAT_BC4_SNORM: u32 = 81;
+const DXGI_FORMAT_BC5_TYPELESS: u32 = 82;
+const DXGI_FORMAT_BC5_SNORM: u32 = 84;
+const DXGI_FORMAT_BC6H_TYPELESS: u32 = 94;
+const DXGI_FORMAT_BC6H_SF16: u32 = 96;
+const DXGI_FORMAT_BC7_TYPELESS: u32 = 97;
+const DXGI_FORMAT_BC7_UNORM_SRGB: u32 = 99;
+
+// DXGI_FORMAT values of the uncompressed formats
+const DXGI_FORMAT_R16G16B16A16_FLOAT: u32 = 10;
+const DXGI_FORMAT_R16G16_FLOAT: u32 = 34;
+const DXGI_FORMAT_R8G8_UNORM: u32 = 49;
+const DXGI_FORMAT_R16_FLOAT: u32 = 54;
+const DXGI_FORMAT_A8_UNORM: u32 = 65;
+const DXGI_FORMAT_B5G6R5_UNORM: u32 = 85;
+const DXGI_FORMAT_B5G5R5A1_UNORM: u32 = 86;
+
+/// A parsed DDS texture.
+#[derive(Debug)]
+pub struct DdsTexture<'a> {
+    /// Width of the base level in pixels
+    pub width: usize,
+    /// Height of the base level in pixels
+    pub height: usize,
+    /// Number of depth slices of the base level, 1 unless the texture is a
+    /// volume texture
+    pub depth: usize,
+    /// Number of mip levels
+    pub mip_count: usize,
+    /// Number of array elements, 1 unless the texture is an array
+    pub array_size: usize,
+    /// Number of cube faces of every array element (up to 6 for cubemaps,
+    /// 1 otherwise)
+    pub faces: usize,
+    /// The `DDSCAPS2_CUBEMAP_*` flags of the faces present, 0xfc00 for all
+    /// six, or 0 if the texture is not a cubemap
+    pub face_mask: u32,
+    /// Encoding of the stored image data, if it is block compressed
+    pub encoding: BcnEncoding,
+    /// Pixel format of the stored image data if it is uncompressed, in
+    /// which case `encoding` does not apply
+    pub raw_format: Option<RawFormat>,
+    /// Whether the data holds signed values (BC4_SNORM, BC5_SNORM,
+    /// BC6H_SF16, or the BC4S and BC5S FourCC codes)
+    pub signed: bool,
+    /// Whether the colors are sRGB encoded (the `*_UNORM_SRGB` formats)
+    pub srgb: bool,
+    /// Whether the colors are premultiplied by alpha (the DXT2 and DXT4
+    /// FourCC codes, or the premultiplied alpha mode of the DX10 header),
+    /// see `DecodeOptions::unpremultiply`
+    pub premultiplied: bool,
+    /// The raw `DXGI_FORMAT` value if the file has a DX10 header
+    pub dxgi_format: Option<u32>,
+    // Image data following the headers
+    data: &'a [u8],
+}
+
+impl<'a> DdsTexture<'a> {
+    /// Returns the number of surfaces of every mip level: the array
+    /// elements times the cube faces, or the depth slices of the level for
+    /// volume textures.
+    pub fn surface_count(&self, level: usize) -> usize {
+        if level >= self.mip_count {
+            0
+        } else if self.depth > 1 {
+            (self.depth >> level).max(1)
+        } else {
+            self.array_size * self.faces
+        }
+    }
+
+    /// Returns the cube faces present in every array element, in storage
+    /// order, or nothing if the texture is not a cubemap.
+    pub fn cube_faces(&self) -> Vec<CubeFace> {
+        CubeFace::ALL.iter().copied().filter(|&face| self.face_mask & (0x400 << face as u32) != 0).collect()
+    }
+
+    /// Returns a single surface of the given mip level.
+    ///
+    /// For arrays and cubemaps, `index` is the array element times the
+    /// number of faces plus the face, in the order +X, -X, +Y, -Y, +Z, -Z of
+    /// the faces present; for volume textures it is the depth slice.
+    pub fn surface(&self, level: usize, index: usize) -> Option<MipLevel<'a>> {
+        if index >= self.surface_count(level) {
+            return None;
+        }
+        let (width, height) = level_size(self.width, self.height, level);
+        let size = self.surface_size(width, height)?;
+        let offset = if self.depth > 1 {
+            // every level holds all of its slices
+            let levels: usize = (0..level)
+                .map(|l| {
+                    let (w, h) = level_size(self.width, self.height, l);
+                    self.surface_size(w, h).map(|size| size * (self.depth >> l).max(1))
+                })
+                .sum::<Option<usize>>()?;
+            levels + index * size
+        } else {
+            // every surface holds its whole mip chain
+            let chain = self.chain_size(self.mip_count)?;
+            let levels = self.chain_size(level)?;
+            index * chain + levels
+        };
+        Some(MipLevel {
+            width,
+            height,
+            data: &self.data[offset..offset + size],
+        })
+    }
+
+    /// Decodes a single surface of the given mip level, as `surface`
+    /// selects it. Signed data is decoded with `DecodeOptions::signed`;
+    /// uncompressed data is decoded with `decode_raw`, which never flips
+    /// the rows.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidImageSize` if the surface does not exist, or
+    /// any error returned by `decode_with_options` or `decode_raw`.
+    pub fn decode(&self, level: usize, index: usize, format: BcnDecoderFormat) -> Result<Vec<u8>, Error> {
+        self.decode_surface(level, index, format, None)
+    }
+
+    fn decode_surface(
+        &self,
+        level: usize,
+        index: usize,
+        format: BcnDecoderFormat,
+        flip_y: Option<bool>,
+    ) -> Result<Vec<u8>, Error> {
+        let surface = self.surface(level, index).ok_or(Error::InvalidImageSize)?;
+        if let Some(raw_format) = self.raw_format {
+            return decode_raw(surface.data, surface.width, surface.height, raw_format, format);
+        }
+        let options = DecodeOptions {
+            signed: self.signed,
+            flip_y,
+            ..Default::default()
+        };
+        decode_with_options(surface.data, surface.width, surface.height, self.encoding, format, &options)
+    }
+
+    /// Decodes a surface top row first and tightly packed.
+    fn decode_packed(&self, level: usize, index: usize, format: BcnDecoderFormat) -> Result<DdsSurface, Error> {
+        let mut pixels = self.decode_surface(level, index, format, Some(false))?;
+        let (width, height) = level_size(self.width, self.height, level);
+        if self.raw_format.is_none() {
+            // the decoder leaves unused space after BC6H pixels
+            pixels.truncate(pixel_size(self.encoding, format) * width * height);
+        }
+        Ok(DdsSurface {
+            level,
+            index,
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Decodes the cubemap of the given array element with all of its mip
+    /// levels. Only the faces in `face_mask` are decoded; the others are
+    /// skipped or filled as `missing` says, and `Cubemap::has_face` tells
+    /// them apart.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidImageSize` if the texture is not a cubemap of
+    /// square faces or the element does not exist,
+    /// `Error::FeatureNotImplemented` if it is uncompressed, or any error
+    /// returned by `decode_cubemap`.
+    pub fn decode_cubemap(&self, element: usize, format: BcnDecoderFormat, missing: MissingFaces) -> Result<Cubemap, Error> {
+        let faces = self.cube_faces();
+        if faces.is_empty() || self.width != self.height || element >= self.array_size {
+            return Err(Error::InvalidImageSize);
+        }
+        if self.raw_format.is_some() {
+            return Err(Error::FeatureNotImplemented);
+        }
+        let cube_size = faces.len() * self.chain_size(self.mip_count).ok_or(Error::InvalidImageSize)?;
+        let options = DecodeOptions {
+            signed: self.signed,
+            ..Default::default()
+        };
+        let data = &self.data[element * cube_size..(element + 1) * cube_size];
+        decode_faces(data, self.width, self.mip_count, &faces, missing, self.encoding, format, &options)
+    }
+
+    /// Returns the size of a surface of the given size, or `None` if it
+    /// overflows.
+    fn surface_size(&self, width: usize, height: usize) -> Option<usize> {
+        match self.raw_format {
+            Some(raw_format) => width.checked_mul(height)?.checked_mul(raw_format.pixel_size()),
+            None => self.encoding.compressed_size(width, height).ok(),
+        }
+    }
+
+    /// Returns the size of the first `levels` levels of a mip chain, or
+    /// `None` if it overflows.
+    fn chain_size(&self, levels: usize) -> Option<usize> {
+        (0..levels).try_fold(0usize, |size, level| {
+            let (w, h) = level_size(self.width, self.height, level);
+            size.checked_add(self.surface_size(w, h)?)
+        })
+    }
+}
+
+/// A violation of the DDS format that `parse_lenient` recovered from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DdsWarning {
+    /// The size field of the header was not 124
+    HeaderSize(u32),
+    /// The pixel format held a FourCC code without the `DDPF_FOURCC` flag
+    MissingFourccFlag,
+    /// The pitch or linear size field did not match the base level; it is
+    /// ignored, as the surfaces are always tightly packed
+    Pitch {
+        /// Value of the field
+        stored: u32,
+        /// Row pitch of uncompressed, or size of compressed base levels
+        expected: usize,
+    },
+    /// The mip count exceeded the levels the size allows and was clamped
+    MipCount {
+        /// Mip count of the header
+        stored: usize,
+        /// Number of levels used instead
+        used: usize,
+    },
+    /// The file was too short for the whole mip chain, so only the levels
+    /// present are used
+    TruncatedMipChain {
+        /// Mip count of the header, after clamping
+        stored: usize,
+        /// Number of levels present in the file
+        used: usize,
+    },
+}
+
+/// A decoded surface of a `DdsImage`.
+#[derive(Debug, Clone)]
+pub struct DdsSurface {
+    /// Mip level of the surface
+    pub level: usize,
+    /// Index of the surface in its level, see `DdsTexture::surface`
+    pub index: usize,
+    /// Width of the surface in pixels
+    pub width: usize,
+    /// Height of the surface in pixels
+    pub height: usize,
+    /// Pixels of the surface, top row first and tightly packed, without the
+    /// unused space `decode` leaves after BC6H pixels
+    pub pixels: Vec<u8>,
+}
+
+/// A DDS file with all of its surfaces decoded.
+#[derive(Debug, Clone)]
+pub struct DdsImage {
+    /// Width of the base level in pixels
+    pub width: usize,
+    /// Height of the base level in pixels
+    pub height: usize,
+    /// Number of depth slices of the base level
+    pub depth: usize,
+    /// Number of mip levels
+    pub mip_count: usize,
+    /// Number of array elements
+    pub array_size: usize,
+    /// Number of cube faces of every array element
+    pub faces: usize,
+    /// Encoding of the file, if it is block compressed
+    pub encoding: BcnEncoding,
+    /// Pixel format of the file if it is uncompressed
+    pub raw_format: Option<RawFormat>,
+    /// Pixel format of the decoded surfaces
+    pub format: BcnDecoderFormat,
+    /// The decoded surfaces, ordered by level, then by index
+    pub surfaces: Vec<DdsSurface>,
+}
+
+impl DdsImage {
+    /// Returns a surface of the given mip level, see `DdsTexture::surface`.
+    pub fn surface(&self, level: usize, index: usize) -> Option<&DdsSurface> {
+        self.surfaces.iter().find(|s| s.level == level && s.index == index)
+    }
+}
+
+/// Returns the encoding of a `DXGI_FORMAT` value and whether it is signed,
+/// if it is supported.
+pub fn dxgi_format_encoding(dxgi_format: u32) -> Option<(BcnEncoding, bool)> {
+    match dxgi_format {
+        DXGI_FORMAT_BC1_TYPELESS..=DXGI_FORMAT_BC1_UNORM_SRGB => Some((BcnEncoding::Bc1, false)),
+        DXGI_FORMAT_BC2_TYPELESS..=DXGI_FORMAT_BC2_UNORM_SRGB => Some((BcnEncoding::Bc2, false)),
+        DXGI_FORMAT_BC3_TYPELESS..=DXGI_FORMAT_BC3_UNORM_SRGB => Some((BcnEncoding::Bc3, false)),
+        DXGI_FORMAT_BC4_TYPELESS..=DXGI_FORMAT_BC4_SNORM => {
+            Some((BcnEncoding::Bc4, dxgi_format == DXGI_FORMAT_BC4_SNORM))
+        }
+        DXGI_FORMAT_BC5_TYPELESS..=DXGI_FORMAT_BC5_SNORM => {
+            Some((BcnEncoding::Bc5, dxgi_format == DXGI_FORMAT_BC5_SNORM))
+        }
+        DXGI_FORMAT_BC6H_TYPELESS..=DXGI_FORMAT_BC6H_SF16 => {
+            Some((BcnEncoding::Bc6H, dxgi_format == DXGI_FORMAT_BC6H_SF16))
+        }
+        DXGI_FORMAT_BC7_TYPELESS..=DXGI_FORMAT_BC7_UNORM_SRGB => Some((BcnEncoding::Bc7, false)),
+        _ => None,
+    }
+}
+
+/// Returns the uncompressed format of a `DXGI_FORMAT` value, if it is
+/// supported.
+fn dxgi_raw_format(dxgi_format: u32) -> Option<RawFormat> {
+    match dxgi_format {
+        DXGI_FORMAT_R16G16B16A16_FLOAT => Some(RawFormat::RGBA16F),
+        DXGI_FORMAT_R16G16_FLOAT => Some(RawFormat::RG16F),
+        DXGI_FORMAT_R8G8_UNORM => Some(RawFormat::R8G8),
+        DXGI_FORMAT_R16_FLOAT => Some(RawFormat::R16F),
+        DXGI_FORMAT_A8_UNORM => Some(RawFormat::A8),
+        DXGI_FORMAT_B5G6R5_UNORM => Some(RawFormat::B5G6R5),
+        DXGI_FORMAT_B5G5R5A1_UNORM => Some(RawFormat::B5G5R5A1),
+        _ => None,
+    }
+}
+
+/// Returns the uncompressed format described by the flags, bit count and
+/// red, green, blue and alpha masks of a legacy pixel format, if it is
+/// supported.
+fn mask_raw_format(flags: u32, bit_count: u32, masks: [u32; 4]) -> Option<RawFormat> {
+    if flags & DDPF_LUMINANCE != 0 {
+        match (bit_count, masks[0], masks[3], flags & DDPF_ALPHAPIXELS != 0) {
+            (8, 0xff, _, false) => Some(RawFormat::L8),
+            (16, 0xff, 0xff00, true) => Some(RawFormat::A8L8),
+            _ => None,
+        }
+    } else if flags & DDPF_ALPHA != 0 {
+        match (bit_count, masks[3]) {
+            (8, 0xff) => Some(RawFormat::A8),
+            _ => None,
+        }
+    } else if flags & DDPF_RGB != 0 {
+        // without alpha pixels, the alpha mask is meaningless
+        let alpha = if flags & DDPF_ALPHAPIXELS != 0 { masks[3] } else { 0 };
+        match (bit_count, [masks[0], masks[1], masks[2], alpha]) {
+            (24, [0xff0000, 0xff00, 0xff, 0]) => Some(RawFormat::R8G8B8),
+            (24, [0xff, 0xff00, 0xff0000, 0]) => Some(RawFormat::B8G8R8),
+            (16, [0xf800, 0x7e0, 0x1f, 0]) => Some(RawFormat::B5G6R5),
+            (16, [0x7c00, 0x3e0, 0x1f, 0x8000]) => Some(RawFormat::B5G5R5A1),
+            (16, [0xff, 0xff00, 0, 0]) => Some(RawFormat::R8G8),
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+/// Parses a DDS file.
+///
+/// # Errors
+///
+/// Returns `Error::ImageDecodingError` if the header is malformed or the
+/// file is too short for its surfaces, `Error::FeatureNotImplemented` if the
+/// texture uses an unsupported format, or `Error::InvalidImageSize` if the
+/// texture is empty.
+///
+/// # Examples
+///
+/// ```no_run
+/// use bcndecode::{dds, BcnDecoderFormat};
+/// use std::fs::File;
+/// use std::io::Read;
+///
+/// # use std::io;
+/// # fn foo() -> io::Result<()> {
+/// let mut data = Vec::new();
+/// File::open("albedo.dds")?.read_to_end(&mut data)?;
+///
+/// let texture = dds::parse(&data)?;
+/// let pixels = texture.decode(0, 0, BcnDecoderFormat::RGBA)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse<'a>(data: &'a [u8]) -> Result<DdsTexture<'a>, Error> {
+    parse_with(data, None)
+}
+
+/// Parses a DDS file like `parse`, but recovers from the violations of the
+/// format that common writers make instead of rejecting the file, and
+/// returns a warning for each of them.
+///
+/// A wrong header size or pitch is ignored, a FourCC code is used even
+/// without its flag, a mip count beyond the size of the texture is clamped,
+/// and the mip chain of a file holding a single surface is cut to the
+/// levels present.
+///
+/// # Errors
+///
+/// Returns the errors of `parse` for files it cannot recover.
+///
+/// # Examples
+///
+/// ```no_run
+/// use bcndecode::dds;
+///
+/// # use std::io;
+/// # fn foo() -> io::Result<()> {
+/// let data = std::fs::read("exported.dds")?;
+/// let (texture, warnings) = dds::parse_lenient(&data)?;
+/// for warning in &warnings {
+///     eprintln!("exported.dds: {:?}", warning);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_lenient<'a>(data: &'a [u8]) -> Result<(DdsTexture<'a>, Vec<DdsWarning>), Error> {
+    let mut warnings = Vec::new();
+    let texture = parse_with(data, Some(&mut warnings))?;
+    Ok((texture, warnings))
+}
+
+fn parse_with<'a>(data: &'a [u8], mut warnings: Option<&mut Vec<DdsWarning>>) -> Result<DdsTexture<'a>, Error> {
+    // records a violation when parsing leniently, and tells whether to
+    // recover from it
+    let mut warn = |warning| match warnings {
+        Some(ref mut warnings) => {
+            warnings.push(warning);
+            true
+        }
+        None => false,
+    };
+
+    if data.get(0..4) != Some(&MAGIC[..]) {
+        return Err(Error::ImageDecodingError);
+    }
+    let header_size = read_u32(data, 4)?;
+    if header_size != HEADER_SIZE && !warn(DdsWarning::HeaderSize(header_size)) {
+        return Err(Error::ImageDecodingError);
+    }
+    let flags = read_u32(data, 8)?;
+    let height = read_u32(data, 12)? as usize;
+    let width = read_u32(data, 16)? as usize;
+    let pitch = read_u32(data, 20)?;
+    let depth = read_u32(data, 24)? as usize;
+    let mip_count = (read_u32(data, 28)? as usize).max(1);
+    let pixel_format_flags = read_u32(data, 80)?;
+    let fourcc = data.get(84..88).ok_or(Error::ImageDecodingError)?;
+    let bit_count = read_u32(data, 88)?;
+    let masks = [read_u32(data, 92)?, read_u32(data, 96)?, read_u32(data, 100)?, read_u32(data, 104)?];
+    let caps2 = read_u32(data, 112)?;
+    let has_fourcc = pixel_format_flags & DDPF_FOURCC != 0
+        || (fourcc != [0; 4]
+            && mask_raw_format(pixel_format_flags, bit_count, masks).is_none()
+            && warn(DdsWarning::MissingFourccFlag));
+
+    let mut texture = DdsTexture {
+        width,
+        height,
+        depth: 1,
+        mip_count,
+        array_size: 1,
+        faces: 1,
+        face_mask: 0,
+        encoding: BcnEncoding::Bc1,
+        raw_format: None,
+        signed: false,
+        srgb: false,
+        premultiplied: false,
+        dxgi_format: None,
+        data: &[],
+    };
+    let data_offset = if has_fourcc && fourcc == b"DX10" {
+        let dxgi_format = read_u32(data, 128)?;
+        let resource_dimension = read_u32(data, 132)?;
+        let misc_flag = read_u32(data, 136)?;
+        let array_size = read_u32(data, 140)? as usize;
+        let misc_flags2 = read_u32(data, 144)?;
+
+        match dxgi_format_encoding(dxgi_format) {
+            Some((encoding, signed)) => {
+                texture.encoding = encoding;
+                texture.signed = signed;
+            }
+            None => texture.raw_format = Some(dxgi_raw_format(dxgi_format).ok_or(Error::FeatureNotImplemented)?),
+        }
+        texture.srgb = matches!(
+            dxgi_format,
+            DXGI_FORMAT_BC1_UNORM_SRGB | DXGI_FORMAT_BC2_UNORM_SRGB | DXGI_FORMAT_BC3_UNORM_SRGB | DXGI_FORMAT_BC7_UNORM_SRGB
+        );
+        texture.dxgi_format = Some(dxgi_format);
+        texture.premultiplied = misc_flags2 & ALPHA_MODE_MASK == ALPHA_MODE_PREMULTIPLIED;
+        if resource_dimension == RESOURCE_DIMENSION_TEXTURE3D {
+            texture.depth = depth.max(1);
+        } else {
+            texture.array_size = array_size.max(1);
+            if misc_flag & RESOURCE_MISC_TEXTURECUBE != 0 {
+                texture.faces = 6;
+                texture.face_mask = DDSCAPS2_CUBEMAP_ALLFACES;
+            }
+        }
+        128 + DX10_HEADER_SIZE
+    } else {
+        let fourcc = [fourcc[0], fourcc[1], fourcc[2], fourcc[3]];
+        if !has_fourcc {
+            texture.raw_format =
+                Some(mask_raw_format(pixel_format_flags, bit_count, masks).ok_or(Error::FeatureNotImplemented)?);
+        } else {
+            match u32::from_le_bytes(fourcc) {
+                D3DFMT_R16F => texture.raw_format = Some(RawFormat::R16F),
+                D3DFMT_G16R16F => texture.raw_format = Some(RawFormat::RG16F),
+                D3DFMT_A16B16G16R16F => texture.raw_format = Some(RawFormat::RGBA16F),
+                _ => {
+                    let (encoding, signed) =
+                        BcnEncoding::from_fourcc_signed(fourcc).map_err(|_| Error::FeatureNotImplemented)?;
+                    texture.encoding = encoding;
+                    texture.signed = signed;
+                }
+            }
+        }
+        texture.premultiplied = fourcc == *b"DXT2" || fourcc == *b"DXT4";
+        if caps2 & DDSCAPS2_VOLUME != 0 {
+            texture.depth = depth.max(1);
+        } else if caps2 & DDSCAPS2_CUBEMAP != 0 {
+            // a cubemap may leave out faces
+            texture.face_mask = caps2 & DDSCAPS2_CUBEMAP_ALLFACES;
+            texture.faces = texture.face_mask.count_ones().max(1) as usize;
+        }
+        128
+    };
+
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidImageSize);
+    }
+    let expected_pitch = if flags & DDSD_PITCH != 0 && texture.raw_format.is_some() {
+        texture.surface_size(width, 1)
+    } else if flags & DDSD_LINEARSIZE != 0 && texture.raw_format.is_none() {
+        texture.surface_size(width, height)
+    } else {
+        None
+    };
+    if let Some(expected) = expected_pitch.filter(|&expected| pitch as usize != expected) {
+        warn(DdsWarning::Pitch { stored: pitch, expected });
+    }
+    let max_levels = (usize::BITS - width.max(height).leading_zeros()) as usize;
+    if mip_count > max_levels
+        && warn(DdsWarning::MipCount {
+            stored: mip_count,
+            used: max_levels,
+        })
+    {
+        texture.mip_count = max_levels;
+    }
+    // levels are only dropped from a single surface, as the surfaces of
+    // arrays and cubemaps would move
+    let available = data.len().saturating_sub(data_offset);
+    if texture.array_size * texture.faces == 1 && data_size(&texture).is_some_and(|size| size > available) {
+        let stored = texture.mip_count;
+        while texture.mip_count > 1 && data_size(&texture).is_none_or(|size| size > available) {
+            texture.mip_count -= 1;
+        }
+        let fits = data_size(&texture).is_some_and(|size| size <= available);
+        if !fits
+            || !warn(DdsWarning::TruncatedMipChain {
+                stored,
+                used: texture.mip_count,
+            })
+        {
+            texture.mip_count = stored;
+        }
+    }
+    let size = data_size(&texture).ok_or(Error::ImageDecodingError)?;
+    texture.data = data
+        .get(data_offset..)
+        .and_then(|d| d.get(..size))
+        .ok_or(Error::ImageDecodingError)?;
+    Ok(texture)
+}
+
+/// Reads a DDS file and decodes all of its surfaces into the given format.
+///
+/// # Errors
+///
+/// Returns any error of reading the file, or of `parse` and
+/// `DdsTexture::decode` converted to an `io::Error`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use bcndecode::{dds, BcnDecoderFormat};
+/// use std::fs::File;
+///
+/// # use std::io;
+/// # fn foo() -> io::Result<()> {
+/// let image = dds::decode_file(File::open("skybox.dds")?, BcnDecoderFormat::RGBA)?;
+/// for surface in &image.surfaces {
+///     println!("level {} surface {}: {}x{}", surface.level, surface.index, surface.width, surface.height);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn decode_file<R: Read>(mut reader: R, format: BcnDecoderFormat) -> io::Result<DdsImage> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let texture = parse(&data)?;
+
+    let mut surfaces = Vec::new();
+    for level in 0..texture.mip_count {
+        for index in 0..texture.surface_count(level) {
+            surfaces.push(texture.decode_packed(level, index, format)?);
+        }
+    }
+
+    Ok(DdsImage {
+        width: texture.width,
+        height: texture.height,
+        depth: texture.depth,
+        mip_count: texture.mip_count,
+        array_size: texture.array_size,
+        faces: texture.faces,
+        encoding: texture.encoding,
+        raw_format: texture.raw_format,
+        format,
+        surfaces,
+    })
+}
+
+/// Decodes a thumbnail of a DDS file from the smallest mip level whose
+/// width or height is at least `max_size`, or from the base level if the
+/// texture is smaller.
+///
+/// Only the headers and the chosen level are read, which makes this cheap
+/// for files with full mip chains. The first surface of the level is
+/// decoded, as `decode_file` decodes it; scaling it down to `max_size` is
+/// left to the caller.
+///
+/// # Errors
+///
+/// Returns any error of `parse` or `DdsTexture::decode`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use bcndecode::{dds, BcnDecoderFormat};
+///
+/// # use std::io;
+/// # fn foo() -> io::Result<()> {
+/// let data = std::fs::read("albedo.dds")?;
+/// let thumbnail = dds::decode_thumbnail(&data, 128, BcnDecoderFormat::RGBA)?;
+/// assert!(thumbnail.width.max(thumbnail.height) >= 128 || thumbnail.level == 0);
+/// # Ok(())
+/// # }
+/// ```
+pub fn decode_thumbnail(data: &[u8], max_size: usize, format: BcnDecoderFormat) -> Result<DdsSurface, Error> {
+    let texture = parse(data)?;
+    let level = (0..texture.mip_count)
+        .rev()
+        .find(|&level| {
+            let (width, height) = level_size(texture.width, texture.height, level);
+            width.max(height) >= max_size
+        })
+        .unwrap_or(0);
+    texture.decode_packed(level, 0, format)
+}
+
+/// Converts the base level of a DDS file into an 8-bit RGBA PNG file.
+///
+/// Only the first surface of the base level is written, e.g. the +X face
+/// of a cubemap or the first slice of a volume texture. BC6H textures are
+/// tone mapped by `decode_tone_mapped` at an exposure of 0. Requires the
+/// `png` feature.
+///
+/// # Errors
+///
+/// Returns any error of reading or writing the files, or of `parse`,
+/// `DdsTexture::decode` and `decode_tone_mapped` converted to an
+/// `io::Error`. Signed BC6H textures are rejected with
+/// `Error::FeatureNotImplemented`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use bcndecode::dds;
+///
+/// # use std::io;
+/// # fn foo() -> io::Result<()> {
+/// dds::decode_to_png("albedo.dds", "albedo.png")?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "png")]
+pub fn decode_to_png<P: AsRef<Path>, Q: AsRef<Path>>(path_in: P, path_out: Q) -> io::Result<()> {
+    let data = fs::read(path_in)?;
+    let texture = parse(&data)?;
+    let pixels = if texture.raw_format.is_none() && texture.encoding == BcnEncoding::Bc6H {
+        if texture.signed {
+            return Err(Error::FeatureNotImplemented.into());
+        }
+        let surface = texture.surface(0, 0).ok_or(Error::InvalidImageSize)?;
+        decode_tone_mapped(surface.data, surface.width, surface.height, 0.0, ToneMapping::default())?
+    } else {
+        texture.decode_surface(0, 0, BcnDecoderFormat::RGBA, Some(false))?
+    };
+
+    let file = BufWriter::new(File::create(path_out)?);
+    let mut encoder = png::Encoder::new(file, texture.width as u32, texture.height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+    Ok(())
+}
+
+fn level_size(width: usize, height: usize, level: usize) -> (usize, usize) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
+
+/// Returns the size of the image data of a texture, or `None` if the
+/// header describes more levels than the size allows or the size
+/// overflows.
+fn data_size(texture: &DdsTexture) -> Option<usize> {
+    let max_levels = usize::BITS - texture.width.max(texture.height).leading_zeros();
+    if texture.mip_count > max_levels as usize {
+        return None;
+    }
+    if texture.depth > 1 {
+        let mut size = 0usize;
+        for level in 0..texture.mip_count {
+            let (w, h) = level_size(texture.width, texture.height, level);
+            let slices = (texture.depth >> level).max(1);
+            size = size.checked_add(texture.surface_size(w, h)?.checked_mul(slices)?)?;
+        }
+        Some(size)
+    } else {
+        let chain = texture.chain_size(texture.mip_count)?;
+        chain.checked_mul(texture.array_size)?.checked_mul(texture.faces)
+    }
+}
@@ -18,17 +18,45 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-use super::{BcnDecoderFormat, BcnEncoding, Error};
-use std::mem;
+use super::{
+    BcnDecoderFormat, BcnEncoding, Block4x4, BlockError, BlockErrorReason, DecodeOptions, DecodeStrategy, Error, Lut3d, NormalMap, OutputLayout,
+    SignedMapping, SourceLayout, Swizzle, ValueTransform,
+};
+use super::gnf::detile_gnf;
+use super::morton;
+use super::tegra::deswizzle_tegra;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use simd;
+#[cfg(feature = "allocator_api")]
+use std::alloc::Allocator;
+use std::array;
+use std::borrow::Cow;
+use std::mem::{self, MaybeUninit};
+#[cfg(not(feature = "safe"))]
+use std::ptr;
+#[cfg(not(feature = "safe"))]
 use std::slice;
+use std::sync::OnceLock;
+#[cfg(not(feature = "rayon"))]
+use std::thread;
+
+// Images with fewer pixels are decoded on the calling thread by
+// `DecodeStrategy::Auto`, as spawning threads would cost more than it saves.
+const PARALLEL_MIN_PIXELS: usize = 512 * 512;
+// Minimum number of block rows decoded per thread by `DecodeStrategy::Auto`.
+const PARALLEL_MIN_BLOCK_ROWS: usize = 16;
+// Largest finite value of a half float.
+const HALF_MAX: f32 = 65504.0;
 
 #[derive(Default)]
-struct BcnDecoderState {
-    // Destination buffer, a bitmap.
-    // For N=1, 2, 3, 5, 7: 4 bytes-per-pixel
-    // For N=4, 1 byte-per-pixel
-    // For N=6, 16 bytes-per-pixel (32-bit float)
-    buffer: Vec<u8>,
+struct BcnDecoderState<'a> {
+    // Destination buffer, a bitmap. Starts out uninitialized, every pixel
+    // is written exactly once.
+    // For N=1, 2, 3, 5, 7: 4 bytes-per-pixel, 3 for the RGB and BGR formats
+    // For N=4, 1 byte-per-pixel with the LUM format, else as above
+    // For N=6, 12 bytes-per-pixel (32-bit float)
+    buffer: &'a mut [OutputByte],
     // Destination region size
     width: usize,
     height: usize,
@@ -37,17 +65,45 @@ struct BcnDecoderState {
     y: usize,
     // If < 0, the image will be flipped on the y-axis
     y_step: i8,
-    // For bc6, data is signed numbers if true.
+    // For bc4, bc5 and bc6, data is signed numbers if true.
     sign: bool,
+    // For bc4 and bc5, how the signed values are stored
+    signed_mapping: SignedMapping,
+    // For bc6, non-finite values are replaced if true.
+    sanitize: bool,
+    // If > 0, the buffer holds square tiles of this many pixels instead of
+    // rows.
+    tile_size: usize,
+    // If > 0, the distance between the starts of two rows in bytes,
+    // otherwise rows are tightly packed
+    row_pitch: usize,
+    // Color grading applied before swizzling, if any
+    lut: Option<&'a Lut3d>,
+    // For bc4, mapping of the decoded values
+    value_transform: ValueTransform<'a>,
+    // For bc5, how the normals are reconstructed
+    normal_map: NormalMap,
+    // For bc5, the two channels are swapped if true
+    swap_channels: bool,
     // Swizzle components as necessary to match the bitmap format
     // 2 bits per component; least-significant two are index of red channel,
     // then green, blue, alpha
     swizzle: u8,
+    // For the 3-byte formats, alpha is dropped after swizzling
+    drop_alpha: bool,
+    // For custom formats, the sources of the output bytes
+    custom: Option<Swizzle>,
+    // For sRGB sources, colors are converted to linear values
+    srgb: bool,
+    // For premultiplied sources, colors are divided by alpha
+    unpremultiply: bool,
+    // For bc1, the 3-color mode is opaque if true
+    bc1_opaque: bool,
 }
 
 #[derive(Clone, Copy, Default)]
-#[repr(packed)]
-struct RGBA {
+#[repr(C)]
+struct Rgba {
     r: u8,
     g: u8,
     b: u8,
@@ -55,22 +111,77 @@ struct RGBA {
 }
 
 #[derive(Clone, Copy, Default)]
-#[repr(packed)]
-struct LUM {
-    #[allow(dead_code)]
+#[repr(C)]
+struct Lum {
     l: u8,
 }
 
 #[derive(Clone, Copy, Default)]
-#[repr(packed)]
-struct RGB32f {
+#[repr(C)]
+struct Rgb32f {
     r: f32,
     g: f32,
     b: f32,
 }
 
+/// Output buffer element: the buffer is filled in place without zeroing
+/// it first, except in the safe build.
+#[cfg(not(feature = "safe"))]
+type OutputByte = MaybeUninit<u8>;
+#[cfg(feature = "safe")]
+type OutputByte = u8;
+
+/// Pixel types of the block decoders, stored as they are laid out in
+/// memory.
+#[cfg(feature = "safe")]
+trait Pixel: Copy {
+    const SIZE: usize;
+
+    fn store(&self, dst: &mut [u8]);
+    fn load(src: &[u8]) -> Self;
+}
+
+#[cfg(feature = "safe")]
+impl Pixel for Rgba {
+    const SIZE: usize = 4;
+
+    fn store(&self, dst: &mut [u8]) {
+        dst.copy_from_slice(&[self.r, self.g, self.b, self.a]);
+    }
+    fn load(src: &[u8]) -> Self {
+        Rgba { r: src[0], g: src[1], b: src[2], a: src[3] }
+    }
+}
+
+#[cfg(feature = "safe")]
+impl Pixel for Lum {
+    const SIZE: usize = 1;
+
+    fn store(&self, dst: &mut [u8]) {
+        dst[0] = self.l;
+    }
+    fn load(src: &[u8]) -> Self {
+        Lum { l: src[0] }
+    }
+}
+
+#[cfg(feature = "safe")]
+impl Pixel for Rgb32f {
+    const SIZE: usize = 12;
+
+    fn store(&self, dst: &mut [u8]) {
+        dst[0..4].copy_from_slice(&self.r.to_ne_bytes());
+        dst[4..8].copy_from_slice(&self.g.to_ne_bytes());
+        dst[8..12].copy_from_slice(&self.b.to_ne_bytes());
+    }
+    fn load(src: &[u8]) -> Self {
+        let channel = |i: usize| f32::from_ne_bytes([src[i], src[i + 1], src[i + 2], src[i + 3]]);
+        Rgb32f { r: channel(0), g: channel(4), b: channel(8) }
+    }
+}
+
 #[derive(Default)]
-#[repr(packed)]
+#[repr(C)]
 struct Bc1Color {
     c0: u16,
     c1: u16,
@@ -86,7 +197,7 @@ impl Bc1Color {
 }
 
 #[derive(Default)]
-#[repr(packed)]
+#[repr(C)]
 struct Bc3Alpha {
     a0: u8,
     a1: u8,
@@ -108,270 +219,1384 @@ pub fn decode_rust(
     encoding: BcnEncoding,
     format: BcnDecoderFormat,
 ) -> Result<Vec<u8>, Error> {
+    decode_with_options(source, width, height, encoding, format, &DecodeOptions::default())
+}
+
+#[cfg(not(feature = "safe"))]
+pub fn decode_with_options(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: &DecodeOptions,
+) -> Result<Vec<u8>, Error> {
+    let dst_size = output_params(width, height, encoding, format)?;
+    let dst_size = layout_size(dst_size, width, height, encoding, format, options)?;
+    check_options(encoding, options)?;
+    let linear = linear_source(source, width, height, encoding, options)?;
+    let source = &linear[..];
+    check_source(source, width, height, encoding, options)?;
+
+    // The buffer is filled in place without zeroing it first
+    let mut buffer = Vec::with_capacity(dst_size);
+    decode_uninit(&mut buffer.spare_capacity_mut()[..dst_size], source, width, height, encoding, format, options);
+    unsafe {
+        buffer.set_len(dst_size);
+    }
+    Ok(buffer)
+}
+
+#[cfg(feature = "safe")]
+pub fn decode_with_options(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: &DecodeOptions,
+) -> Result<Vec<u8>, Error> {
+    let dst_size = output_params(width, height, encoding, format)?;
+    let dst_size = layout_size(dst_size, width, height, encoding, format, options)?;
+    check_options(encoding, options)?;
+    let linear = linear_source(source, width, height, encoding, options)?;
+    let source = &linear[..];
+    check_source(source, width, height, encoding, options)?;
+
+    let mut buffer = vec![0; dst_size];
+    decode_uninit(&mut buffer, source, width, height, encoding, format, options);
+    Ok(buffer)
+}
+
+#[cfg(not(feature = "safe"))]
+pub fn decode_into_uninit<'a>(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: &DecodeOptions,
+    dst: &'a mut [MaybeUninit<u8>],
+) -> Result<&'a mut [u8], Error> {
+    let dst_size = output_params(width, height, encoding, format)?;
+    let dst_size = layout_size(dst_size, width, height, encoding, format, options)?;
+    check_options(encoding, options)?;
+    let linear = linear_source(source, width, height, encoding, options)?;
+    let source = &linear[..];
+    check_source(source, width, height, encoding, options)?;
+    if dst.len() < dst_size {
+        return Err(Error::InvalidImageSize);
+    }
+
+    let dst = &mut dst[..dst_size];
+    decode_uninit(dst, source, width, height, encoding, format, options);
+    // decode_uninit writes every byte of the buffer
+    unsafe { Ok(&mut *(dst as *mut [MaybeUninit<u8>] as *mut [u8])) }
+}
+
+#[cfg(feature = "safe")]
+pub fn decode_into_uninit<'a>(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: &DecodeOptions,
+    dst: &'a mut [MaybeUninit<u8>],
+) -> Result<&'a mut [u8], Error> {
+    // the safe build has to go through an initialized buffer
+    let decoded = decode_with_options(source, width, height, encoding, format, options)?;
+    if dst.len() < decoded.len() {
+        return Err(Error::InvalidImageSize);
+    }
+    Ok(dst[..decoded.len()].write_copy_of_slice(&decoded))
+}
+
+#[cfg(feature = "allocator_api")]
+pub fn decode_in<A: Allocator>(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: &DecodeOptions,
+    alloc: A,
+) -> Result<Vec<u8, A>, Error> {
+    let dst_size = output_params(width, height, encoding, format)?;
+    let dst_size = layout_size(dst_size, width, height, encoding, format, options)?;
+    check_options(encoding, options)?;
+    let linear = linear_source(source, width, height, encoding, options)?;
+    let source = &linear[..];
+    check_source(source, width, height, encoding, options)?;
+
+    let mut buffer = Vec::with_capacity_in(dst_size, alloc);
+    #[cfg(not(feature = "safe"))]
+    {
+        decode_uninit(&mut buffer.spare_capacity_mut()[..dst_size], source, width, height, encoding, format, options);
+        unsafe {
+            buffer.set_len(dst_size);
+        }
+    }
+    #[cfg(feature = "safe")]
+    {
+        buffer.resize(dst_size, 0);
+        decode_uninit(&mut buffer, source, width, height, encoding, format, options);
+    }
+    Ok(buffer)
+}
+
+/// Decodes into initialized memory, leaving the bytes that are not part of
+/// a pixel as they are.
+pub fn decode_into(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: &DecodeOptions,
+    dst: &mut [u8],
+) -> Result<(), Error> {
+    let dst_size = output_params(width, height, encoding, format)?;
+    let dst_size = layout_size(dst_size, width, height, encoding, format, options)?;
+    check_options(encoding, options)?;
+    let linear = linear_source(source, width, height, encoding, options)?;
+    let source = &linear[..];
+    check_source(source, width, height, encoding, options)?;
+    if dst.len() < dst_size {
+        return Err(Error::InvalidImageSize);
+    }
+
+    // BC6H pixels take 12 bytes, the rest of its output is left alone
+    let (padded_width, padded_height) = layout_dims(width, height, options.layout);
+    let row_pitch = if options.row_pitch > 0 { options.row_pitch } else { pixel_size(encoding, format) * padded_width };
+    let pixels = as_output_bytes(&mut dst[..row_pitch * padded_height]);
+    decode_pixels(pixels, source, width, height, encoding, format, options);
+    Ok(())
+}
+
+/// Views initialized memory as an output buffer, for `decode_into`.
+///
+/// The view must only be written by the decoders, which never store an
+/// uninitialized value: every byte goes through `write_row`, `store_lane`,
+/// `write_bytes` or `write_zeros`, which copy from or write initialized
+/// values. Do not pass the view to code that can store
+/// `MaybeUninit::uninit()`.
+#[cfg(not(feature = "safe"))]
+fn as_output_bytes(dst: &mut [u8]) -> &mut [OutputByte] {
+    // SAFETY: `MaybeUninit<u8>` has the layout of `u8`, and the borrow of
+    // `dst` lasts as long as the view, so `dst` can only be read again
+    // after the decoders are done with it. They only write initialized
+    // bytes, as said above, so every byte of `dst` stays initialized.
+    unsafe { &mut *(dst as *mut [u8] as *mut [OutputByte]) }
+}
+
+#[cfg(feature = "safe")]
+fn as_output_bytes(dst: &mut [u8]) -> &mut [OutputByte] {
+    dst
+}
+
+/// Validates the decoding parameters and returns the size of the output in
+/// bytes.
+pub fn output_params(
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+) -> Result<usize, Error> {
 
     // check input data validity
     if width == 0 || height == 0 {
         return Err(Error::InvalidImageSize);
     }
 
-    // create target buffer
-    let mut dst_size = 4 * width * height;
+    // BC6H takes 16 bytes per pixel, and no buffer can be larger than
+    // isize::MAX bytes
+    let pixels = width.checked_mul(height).ok_or(Error::InvalidImageSize)?;
+    if pixels > isize::MAX as usize / 16 {
+        return Err(Error::InvalidImageSize);
+    }
+
+    match (format, encoding) {
+        (BcnDecoderFormat::LUM, BcnEncoding::Bc4) => {}
+        (BcnDecoderFormat::LUM, _) => return Err(Error::InvalidPixelFormat),
+        (BcnDecoderFormat::RGB | BcnDecoderFormat::BGR | BcnDecoderFormat::Custom(_), BcnEncoding::Bc6H) => {
+            return Err(Error::InvalidPixelFormat);
+        }
+        _ => {}
+    }
 
     match encoding {
-        BcnEncoding::Bc4 => {
-            dst_size >>= 2;
+        BcnEncoding::Bc6H => Ok(16 * pixels),
+        _ => Ok(pixel_size(encoding, format) * pixels),
+    }
+}
+
+/// Returns the swizzle of the pixel format: the output position of red,
+/// green, blue and alpha in 2 bits each. Alpha is dropped afterwards from
+/// the 3-byte formats, and custom formats are applied afterwards.
+fn format_swizzle(format: BcnDecoderFormat) -> u8 {
+    match format {
+        BcnDecoderFormat::RGBA | BcnDecoderFormat::RGB | BcnDecoderFormat::Custom(_) => 0b11100100,
+        BcnDecoderFormat::BGRA | BcnDecoderFormat::BGR => 0b11000110,
+        BcnDecoderFormat::ARGB => 0b00111001,
+        BcnDecoderFormat::ABGR => 0b00011011,
+        BcnDecoderFormat::LUM => 0,
+    }
+}
+
+/// Returns the size of the output in bytes for the layout and row pitch of
+/// the options, from the size `output_params` returned.
+pub fn layout_size(
+    dst_size: usize,
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: &DecodeOptions,
+) -> Result<usize, Error> {
+    if let OutputLayout::Tiled(tile_size) = options.layout {
+        if tile_size == 0 || !tile_size.is_multiple_of(4) || options.row_pitch > 0 || options.flip_y == Some(true) {
+            return Err(Error::InvalidImageSize);
         }
-        BcnEncoding::Bc6H => {
-            dst_size <<= 2;
+    }
+    if options.row_pitch > 0 {
+        if options.row_pitch < pixel_size(encoding, format) * width {
+            return Err(Error::InvalidImageSize);
         }
-        _ => {}
+        return options
+            .row_pitch
+            .checked_mul(height)
+            .filter(|&size| size <= isize::MAX as usize)
+            .ok_or(Error::InvalidImageSize);
+    }
+    let (padded_width, padded_height) = layout_dims(width, height, options.layout);
+    (dst_size / (width * height))
+        .checked_mul(padded_width)
+        .and_then(|size| size.checked_mul(padded_height))
+        .filter(|&size| size <= isize::MAX as usize)
+        .ok_or(Error::InvalidImageSize)
+}
+
+/// Checks that the post-processing options apply to the encoding.
+fn check_options(encoding: BcnEncoding, options: &DecodeOptions) -> Result<(), Error> {
+    let single_channel = matches!(encoding, BcnEncoding::Bc4);
+    if options.lut.is_some() && single_channel {
+        return Err(Error::InvalidPixelFormat);
+    }
+    if options.value_transform != ValueTransform::Identity && !single_channel {
+        return Err(Error::InvalidPixelFormat);
+    }
+    let two_channel = matches!(encoding, BcnEncoding::Bc5);
+    if (options.normal_map != NormalMap::Raw || options.swap_bc5_channels) && !two_channel {
+        return Err(Error::InvalidPixelFormat);
+    }
+    let signed_encoding = matches!(encoding, BcnEncoding::Bc4 | BcnEncoding::Bc5 | BcnEncoding::Bc6H);
+    if options.signed && !signed_encoding {
+        return Err(Error::InvalidPixelFormat);
+    }
+    if options.signed_mapping != SignedMapping::Unorm && (!options.signed || options.normal_map != NormalMap::Raw) {
+        return Err(Error::InvalidPixelFormat);
+    }
+    if options.bc1_opaque && encoding != BcnEncoding::Bc1 {
+        return Err(Error::InvalidPixelFormat);
+    }
+    let color_encoding = matches!(encoding, BcnEncoding::Bc1 | BcnEncoding::Bc2 | BcnEncoding::Bc3 | BcnEncoding::Bc7);
+    if (options.srgb || options.unpremultiply) && !color_encoding {
+        return Err(Error::InvalidPixelFormat);
+    }
+    Ok(())
+}
+
+/// Returns the blocks of the source row by row, reordering them first if
+/// the options name a swizzled layout.
+fn linear_source<'s>(
+    source: &'s [u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    options: &DecodeOptions,
+) -> Result<Cow<'s, [u8]>, Error> {
+    match options.source_layout {
+        SourceLayout::Linear => Ok(Cow::Borrowed(source)),
+        SourceLayout::TegraBlockLinear(block_height) => {
+            deswizzle_tegra(source, width, height, encoding, block_height).map(Cow::Owned)
+        }
+        SourceLayout::GnfTiled(tile_mode) => detile_gnf(source, width, height, encoding, tile_mode).map(Cow::Owned),
+        SourceLayout::Morton => {
+            morton::deswizzle(source, width.div_ceil(4), height.div_ceil(4), block_size(encoding)).map(Cow::Owned)
+        }
+    }
+}
+
+/// Checks that the source holds exactly the blocks of the image, without
+/// reserved modes, if the options ask for it.
+fn check_source(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    options: &DecodeOptions,
+) -> Result<(), Error> {
+    if !options.strict {
+        return Ok(());
+    }
+    let block_size = block_size(encoding);
+    let blocks_per_row = width.div_ceil(4);
+    let expected = blocks_per_row * height.div_ceil(4);
+    let error = |index: usize, reason: BlockErrorReason| {
+        Error::InvalidBlock(BlockError {
+            offset: index * block_size,
+            x: 4 * (index % blocks_per_row),
+            y: 4 * (index / blocks_per_row),
+            encoding,
+            reason,
+        })
+    };
+
+    let provided = source.len() / block_size;
+    if let Some(index) = source
+        .chunks_exact(block_size)
+        .take(expected)
+        .position(|block| is_reserved_block(encoding, block))
+    {
+        return Err(error(index, BlockErrorReason::InvalidMode));
+    }
+    if source.len() < expected * block_size {
+        return Err(error(provided, BlockErrorReason::Truncated { expected, provided }));
+    }
+    if source.len() > expected * block_size {
+        let trailing_bytes = source.len() - expected * block_size;
+        return Err(error(expected, BlockErrorReason::SizeMismatch { expected, trailing_bytes }));
+    }
+    Ok(())
+}
+
+/// Returns the size of the image padded to whole tiles.
+fn layout_dims(width: usize, height: usize, layout: OutputLayout) -> (usize, usize) {
+    match layout {
+        OutputLayout::Linear => (width, height),
+        OutputLayout::Tiled(n) => (width.div_ceil(n) * n, height.div_ceil(n) * n),
+    }
+}
+
+/// Returns the number of bytes of a decoded pixel.
+pub fn pixel_size(encoding: BcnEncoding, format: BcnDecoderFormat) -> usize {
+    match (encoding, format) {
+        (_, BcnDecoderFormat::LUM) => 1,
+        (BcnEncoding::Bc6H, _) => 12,
+        (_, BcnDecoderFormat::RGB | BcnDecoderFormat::BGR) => 3,
+        _ => 4,
+    }
+}
+
+/// Decodes the image into `buffer`, writing every byte of it.
+fn decode_uninit(
+    buffer: &mut [OutputByte],
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: &DecodeOptions,
+) {
+    // BC6H pixels are 12 bytes apart, the rest of its buffer stays empty
+    let (padded_width, padded_height) = layout_dims(width, height, options.layout);
+    let row_size = pixel_size(encoding, format) * padded_width;
+    let row_pitch = if options.row_pitch > 0 { options.row_pitch } else { row_size };
+    let (pixels, rest) = buffer.split_at_mut(row_pitch * padded_height);
+    write_zeros(rest);
+    if padded_width != width || padded_height != height || row_pitch != row_size {
+        // only pixels inside the image are written into the edge tiles, and
+        // nothing between the rows
+        write_zeros(pixels);
+    }
+    decode_pixels(pixels, source, width, height, encoding, format, options);
+}
+
+/// Decodes the image into `pixels`, which holds rows of `row_pitch` bytes
+/// or tiles. Bytes that are not part of a pixel of the image are left as
+/// they are.
+fn decode_pixels(
+    pixels: &mut [OutputByte],
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: &DecodeOptions,
+) {
+    // tiles are never flipped
+    let partial = ((width & 3) | (height & 3)) != 0;
+    let flip = options.layout == OutputLayout::Linear && options.flip_y.unwrap_or(partial);
+    let block_rows = height.div_ceil(4);
+    let threads = thread_count(options.strategy, width, height).clamp(1, block_rows);
+    if threads == 1 {
+        decode_band(pixels, source, width, height, encoding, format, flip, options);
+    } else {
+        decode_bands(pixels, source, width, height, encoding, format, flip, threads, options);
+    }
+}
+
+/// Splits the image into bands of whole block rows and decodes them on
+/// the given number of threads.
+#[allow(clippy::too_many_arguments)]
+fn decode_bands(
+    pixels: &mut [OutputByte],
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    flip: bool,
+    threads: usize,
+    options: &DecodeOptions,
+) {
+    let (_, padded_height) = layout_dims(width, height, options.layout);
+    let row_pitch = pixels.len() / padded_height;
+    let block_rows = height.div_ceil(4);
+    // bands of tiles hold whole rows of tiles
+    let band_multiple = match options.layout {
+        OutputLayout::Linear => 1,
+        OutputLayout::Tiled(n) => n / 4,
     };
 
-    let mut state = BcnDecoderState::default();
-    state.width = width;
-    state.height = height;
-    state.buffer = vec![0; dst_size];
+    // Every band is decoded as an image of its own. A flipped image stores
+    // the first band last.
+    let band_rows = block_rows.div_ceil(threads).div_ceil(band_multiple) * band_multiple;
+    let band_size = row_pitch * 4 * band_rows;
+    let band_source_size = compressed_size(width, 4 * band_rows, encoding);
+    let bands: Box<dyn Iterator<Item = &mut [OutputByte]>> = if flip {
+        Box::new(pixels.rchunks_mut(band_size))
+    } else {
+        Box::new(pixels.chunks_mut(band_size))
+    };
+    let bands = bands.enumerate().map(|(i, band)| {
+        let start = (i * band_source_size).min(source.len());
+        let end = (start + band_source_size).min(source.len());
+        let band_height = (4 * band_rows).min(height - 4 * band_rows * i);
+        (band, &source[start..end], band_height)
+    });
+
+    #[cfg(not(feature = "rayon"))]
+    thread::scope(|scope| {
+        for (band, band_source, band_height) in bands {
+            scope.spawn(move || decode_band(band, band_source, width, band_height, encoding, format, flip, options));
+        }
+    });
+    #[cfg(feature = "rayon")]
+    {
+        let bands: Vec<_> = bands.collect();
+        bands.into_par_iter().for_each(|(band, band_source, band_height)| {
+            decode_band(band, band_source, width, band_height, encoding, format, flip, options)
+        });
+    }
+}
 
-    match format {
-        BcnDecoderFormat::RGBA => state.swizzle = 0b11100100,
-        BcnDecoderFormat::BGRA => state.swizzle = 0b11000110,
-        BcnDecoderFormat::ARGB => state.swizzle = 0b10010011,
-        BcnDecoderFormat::ABGR => state.swizzle = 0b00011011,
-        BcnDecoderFormat::LUM => match encoding {
-            BcnEncoding::Bc4 => {
-                state.swizzle = 0;
-            }
-            _ => {
-                return Err(Error::InvalidPixelFormat);
+fn thread_count(strategy: DecodeStrategy, width: usize, height: usize) -> usize {
+    #[cfg(not(feature = "rayon"))]
+    let available = || thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    #[cfg(feature = "rayon")]
+    let available = rayon::current_num_threads;
+    match strategy {
+        DecodeStrategy::Serial => 1,
+        DecodeStrategy::Threaded(0) => available(),
+        DecodeStrategy::Threaded(threads) => threads,
+        DecodeStrategy::Auto => {
+            if width * height < PARALLEL_MIN_PIXELS {
+                1
+            } else {
+                available().min(height.div_ceil(4) / PARALLEL_MIN_BLOCK_ROWS)
             }
+        }
+    }
+}
+
+/// Decodes `source` as an image of its own into `buffer`. Pixels of blocks
+/// missing from `source` are set to zero.
+#[allow(clippy::too_many_arguments)]
+fn decode_band(
+    buffer: &mut [OutputByte],
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    flip: bool,
+    options: &DecodeOptions,
+) {
+    let mut state = BcnDecoderState {
+        buffer,
+        width,
+        height,
+        swizzle: format_swizzle(format),
+        drop_alpha: matches!(format, BcnDecoderFormat::RGB | BcnDecoderFormat::BGR),
+        custom: match format {
+            BcnDecoderFormat::Custom(swizzle) => Some(swizzle),
+            _ => None,
         },
+        y_step: if flip { -1 } else { 1 },
+        sign: options.signed,
+        signed_mapping: options.signed_mapping,
+        srgb: options.srgb,
+        unpremultiply: options.unpremultiply,
+        bc1_opaque: options.bc1_opaque,
+        sanitize: options.sanitize_floats,
+        lut: options.lut,
+        value_transform: options.value_transform,
+        normal_map: options.normal_map,
+        swap_channels: options.swap_bc5_channels,
+        row_pitch: options.row_pitch,
+        tile_size: match options.layout {
+            OutputLayout::Linear => 0,
+            OutputLayout::Tiled(n) => n,
+        },
+        ..Default::default()
+    };
+    decode_bcn(&mut state, source, encoding);
+}
+
+/// Returns the number of bytes in one 4x4 block of the given encoding.
+pub fn block_size(encoding: BcnEncoding) -> usize {
+    match encoding {
+        BcnEncoding::Bc1 | BcnEncoding::Bc4 => 8,
+        _ => 16,
     }
+}
 
-    if ((width & 3) | (height & 3)) != 0 {
-        state.y_step = -1;
-        decode_bcn(&mut state, source, encoding, true);
+/// Returns whether the block uses a mode reserved by the format. Such
+/// blocks decode to zeros.
+pub fn is_reserved_block(encoding: BcnEncoding, block: &[u8]) -> bool {
+    match encoding {
+        // the four 5-bit modes ending in 11 that start with 1
+        BcnEncoding::Bc6H => block[0] & 0x13 == 0x13,
+        BcnEncoding::Bc7 => block[0] == 0,
+        _ => false,
+    }
+}
+
+/// Returns the mode and the partition of a BC6H or BC7 block, or `None` for
+/// other encodings and reserved blocks.
+///
+/// BC6H modes are numbered 0 to 13 in the order of the format
+/// specification, BC7 modes 0 to 7. Blocks with a single subset report
+/// partition 0.
+pub fn block_mode(encoding: BcnEncoding, block: &[u8]) -> Option<(u8, u8)> {
+    if is_reserved_block(encoding, block) {
+        return None;
+    }
+    match encoding {
+        BcnEncoding::Bc6H => {
+            let mode = match block[0] & 3 {
+                0 | 1 => block[0] & 3,
+                2 => 2 + ((block[0] & 0x1f) >> 2),
+                _ => 10 + ((block[0] & 0x1f) >> 2),
+            };
+            // the partition follows 77 bits of mode and endpoints
+            let pb = Bc6ModeInfo::new(mode as usize).pb;
+            Some((mode, BitReader::new(block).peek_at(77, pb) as u8))
+        }
+        BcnEncoding::Bc7 => {
+            let mode = block[0].trailing_zeros();
+            let pb = BC7_MODES[mode as usize].pb;
+            Some((mode as u8, BitReader::new(block).peek_at(mode + 1, pb) as u8))
+        }
+        _ => None,
+    }
+}
+
+/// Builds a BC6H block of the given mode, numbered as in `block_mode`,
+/// holding colors between two half float endpoints.
+///
+/// Pixel `n` gets the color at `t[n]`, from 0 at `e0` to 1 at `e1`, rounded
+/// to the nearest weight of the mode. The endpoints are quantized to the
+/// precision of the mode; in modes that store deltas, `e1` is moved as
+/// close as the delta bits allow. Two-region modes use partition 0 with the
+/// same endpoints in both regions.
+pub fn encode_bc6h_block(mode: usize, e0: [u16; 3], e1: [u16; 3], t: &[f32; 16]) -> [u8; 16] {
+    let info = Bc6ModeInfo::new(mode);
+    let regions = info.ns as usize;
+    let ib = if regions == 2 { 3 } else { 4 };
+    let weights = bc7_get_weights(ib);
+    let max_index = weights.len() as u8 - 1;
+
+    let mut endpoints = [0u16; 12];
+    for c in 0..3 {
+        let (q0, q1) = (bc6_quantize(e0[c], info.epb), bc6_quantize(e1[c], info.epb));
+        for region in 0..regions {
+            endpoints[6 * region + c] = q0;
+            endpoints[6 * region + 3 + c] = q1;
+        }
+    }
+    let mut indices = [0u8; 16];
+    for (index, &t) in indices.iter_mut().zip(t.iter()) {
+        let w = 64.0 * t.clamp(0.0, 1.0);
+        *index = (0..=max_index)
+            .min_by(|&a, &b| (weights[a as usize] as f32 - w).abs().total_cmp(&(weights[b as usize] as f32 - w).abs()))
+            .unwrap();
+    }
+
+    // the top index bit of the first pixel of every region is implied 0,
+    // swapping the endpoints of the region flips its indices
+    for region in 0..regions {
+        let anchor = if region == 0 { 0 } else { BC7_AI0[0] as usize };
+        if indices[anchor] > max_index / 2 {
+            for c in 0..3 {
+                endpoints.swap(6 * region + c, 6 * region + 3 + c);
+            }
+            for (n, index) in indices.iter_mut().enumerate() {
+                if bc7_get_subset(info.ns, 0, n) == region {
+                    *index = max_index - *index;
+                }
+            }
+        }
+    }
+
+    if info.tr > 0 {
+        let bits = [info.rb, info.gb, info.bb];
+        for i in 3..6 * regions {
+            let b = bits[i % 3] as i32;
+            let delta = (endpoints[i] as i32 - endpoints[i % 3] as i32).clamp(-(1 << (b - 1)), (1 << (b - 1)) - 1);
+            endpoints[i] = (delta & ((1 << b) - 1)) as u16;
+        }
+    }
+
+    let mut bits: u128 = match mode {
+        0 | 1 => mode as u128,
+        2..=9 => (2 | (mode - 2) << 2) as u128,
+        _ => (3 | (mode - 10) << 2) as u128,
+    };
+    let layout = &BC6_BIT_RUNS[mode];
+    for run in &layout.runs[..layout.count] {
+        let value = (endpoints[run.endpoint as usize] as u32 >> run.shift) & ((1 << run.len) - 1);
+        bits |= (value as u128) << run.offset;
+    }
+    // partition 0 is all zeros
+    let mut offset = if regions == 2 { 77 + info.pb as u32 } else { 65 };
+    for (n, &index) in indices.iter().enumerate() {
+        bits |= (index as u128) << offset;
+        let anchor = n == 0 || (regions == 2 && n == BC7_AI0[0] as usize);
+        offset += if anchor { ib as u32 - 1 } else { ib as u32 };
+    }
+    bits.to_le_bytes()
+}
+
+/// Returns the unsigned endpoint value of `prec` bits that comes closest
+/// to the half float after unquantization and finalization.
+fn bc6_quantize(half: u16, prec: u8) -> u16 {
+    // finalization scales the unquantized value by 31/64
+    let target = (half.min(0x7bff) as isize * 64 + 30) / 31;
+    let max = ((1u32 << prec) - 1) as u16;
+    let (mut lo, mut hi) = (0, max);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if bc6_unquantize(mid, prec as isize, false) < target {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    let error = |q: u16| (bc6_unquantize(q, prec as isize, false) - target).abs();
+    if lo > 0 && error(lo - 1) <= error(lo) {
+        lo - 1
     } else {
-        state.y_step = 1;
-        decode_bcn(&mut state, source, encoding, false);
+        lo
     }
+}
 
-    Ok(state.buffer)
+/// Returns the number of bytes needed to store an image of the given size.
+pub fn compressed_size(width: usize, height: usize, encoding: BcnEncoding) -> usize {
+    width.div_ceil(4) * height.div_ceil(4) * block_size(encoding)
+}
+
+/// Decodes BC6H data into floats, three per pixel, in the layout of
+/// `decode_with_options` without the unused space at its end.
+pub fn decode_bc6h(source: &[u8], width: usize, height: usize, options: &DecodeOptions) -> Result<Vec<f32>, Error> {
+    if !options.row_pitch.is_multiple_of(4) {
+        return Err(Error::InvalidImageSize);
+    }
+    let decoded = decode_with_options(source, width, height, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA, options)?;
+    let size = layout_size(12 * width * height, width, height, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA, options)?;
+    Ok(decoded[..size]
+        .chunks_exact(4)
+        .map(|v| f32::from_ne_bytes([v[0], v[1], v[2], v[3]]))
+        .collect())
+}
+
+/// Decodes BC6H data into half float RGBA, top row first. Only one block
+/// row is decoded into floats at a time.
+pub fn decode_bc6h_f16(source: &[u8], width: usize, height: usize, options: &DecodeOptions) -> Result<Vec<u16>, Error> {
+    if options.layout != OutputLayout::Linear || options.row_pitch > 0 {
+        return Err(Error::InvalidImageSize);
+    }
+    output_params(width, height, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA)?;
+    let linear = linear_source(source, width, height, BcnEncoding::Bc6H, options)?;
+    let source = &linear[..];
+    check_source(source, width, height, BcnEncoding::Bc6H, options)?;
+
+    let band_source_size = compressed_size(width, 4, BcnEncoding::Bc6H);
+    let options = DecodeOptions {
+        flip_y: Some(false),
+        source_layout: SourceLayout::Linear,
+        ..*options
+    };
+    let mut pixels = Vec::with_capacity(4 * width * height);
+    for band_index in 0..height.div_ceil(4) {
+        let band_height = (height - 4 * band_index).min(4);
+        let start = (band_index * band_source_size).min(source.len());
+        let end = (start + band_source_size).min(source.len());
+        let band = decode_with_options(&source[start..end], width, band_height, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA, &options)?;
+        for row in 0..band_height {
+            for p in band[12 * width * row..12 * width * (row + 1)].chunks_exact(12) {
+                let channel = |c: usize| f32::from_ne_bytes([p[4 * c], p[4 * c + 1], p[4 * c + 2], p[4 * c + 3]]);
+                // the floats were expanded from halves, so this is exact
+                pixels.extend_from_slice(&[float_to_half(channel(0)), float_to_half(channel(1)), float_to_half(channel(2)), 0x3c00]);
+            }
+        }
+    }
+    Ok(pixels)
+}
+
+/// Decodes signed BC4 or BC5 data into 16-bit values from -32767 to 32767,
+/// one per channel and pixel, top row first.
+pub fn decode_snorm16(source: &[u8], width: usize, height: usize, encoding: BcnEncoding) -> Result<Vec<i16>, Error> {
+    let (channels, format) = match encoding {
+        BcnEncoding::Bc4 => (1, BcnDecoderFormat::LUM),
+        BcnEncoding::Bc5 => (2, BcnDecoderFormat::RGBA),
+        _ => return Err(Error::InvalidPixelFormat),
+    };
+    output_params(width, height, encoding, format)?;
+
+    let blocks_x = width.div_ceil(4);
+    let mut values = vec![0; channels * width * height];
+    // blocks past the end of the source are left as zeros
+    for (index, block) in source.chunks_exact(8 * channels).take(blocks_x * height.div_ceil(4)).enumerate() {
+        let (x0, y0) = (4 * (index % blocks_x), 4 * (index / blocks_x));
+        for (channel, source) in block.chunks_exact(8).enumerate() {
+            let (a, lut) = signed_alpha_values(source);
+            for n in 0..16 {
+                let (x, y) = (x0 + n % 4, y0 + n / 4);
+                if x < width && y < height {
+                    let value = a[(7 & (lut >> (3 * n))) as usize];
+                    values[channels * (y * width + x) + channel] = (value * 32767.0 / 127.0).round() as i16;
+                }
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Decodes the image into rgba values, top row first. 8-bit channels are
+/// normalized to [0, 1], BC6H values are passed through with an alpha of 1.
+pub fn decode_rgba_f32(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+) -> Result<Vec<[f32; 4]>, Error> {
+    decode_rgba_f32_with_options(source, width, height, encoding, &DecodeOptions::default())
+}
+
+/// Works like `decode_rgba_f32` with the given options. Signed BC4 and BC5
+/// channels are normalized to [-1, 1] instead.
+pub fn decode_rgba_f32_with_options(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    options: &DecodeOptions,
+) -> Result<Vec<[f32; 4]>, Error> {
+    if options.layout != OutputLayout::Linear || options.row_pitch > 0 {
+        return Err(Error::InvalidImageSize);
+    }
+    let format = match encoding {
+        BcnEncoding::Bc4 => BcnDecoderFormat::LUM,
+        _ => BcnDecoderFormat::RGBA,
+    };
+    let options = DecodeOptions {
+        flip_y: Some(false),
+        signed_mapping: SignedMapping::Unorm,
+        ..*options
+    };
+    let decoded = decode_with_options(source, width, height, encoding, format, &options)?;
+    let count = width * height;
+
+    // signed values from -1 to 1 were mapped to 0 to 255
+    let signed = |v: u8| if options.signed { v as f32 / 127.5 - 1.0 } else { v as f32 / 255.0 };
+    let pixels: Vec<[f32; 4]> = match encoding {
+        BcnEncoding::Bc4 => decoded[..count]
+            .iter()
+            .map(|&l| {
+                let l = signed(l);
+                [l, l, l, 1.0]
+            })
+            .collect(),
+        BcnEncoding::Bc5 => decoded[..count * 4]
+            .chunks(4)
+            .map(|p| [signed(p[0]), signed(p[1]), p[2] as f32 / 255.0, p[3] as f32 / 255.0])
+            .collect(),
+        BcnEncoding::Bc6H => decoded[..count * 12]
+            .chunks(12)
+            .map(|p| {
+                let channel = |c: usize| f32::from_ne_bytes([p[4 * c], p[4 * c + 1], p[4 * c + 2], p[4 * c + 3]]);
+                [channel(0), channel(1), channel(2), 1.0]
+            })
+            .collect(),
+        _ => decoded[..count * 4]
+            .chunks(4)
+            .map(|p| {
+                [
+                    p[0] as f32 / 255.0,
+                    p[1] as f32 / 255.0,
+                    p[2] as f32 / 255.0,
+                    p[3] as f32 / 255.0,
+                ]
+            })
+            .collect(),
+    };
+    Ok(pixels)
 }
 
 macro_rules! decode_loop {
     ( $decode_fn:ident, $block_size:expr, $T:ident,
-    $source:expr, $state:expr, $flip:expr ) => {
+    $source:expr, $state:expr ) => {
+        let mut bytes = $source.len();
+        let mut source_ptr = 0;
+        let y_max = $state.height;
+
+        // blocks past the end of the source are written as zeros
+        while $state.y < y_max {
+            let mut col = [$T::default(); 16];
+            if bytes >= $block_size {
+                $decode_fn(&mut col, &$source[source_ptr..]);
+                source_ptr += $block_size;
+                bytes -= $block_size;
+            }
+
+            with_bytes(&col, |col| put_block($state, col, mem::size_of::<$T>()));
+        }
+    };
+
+    ( $decode_fn:ident, $block_size:expr, $T:ident,
+    $source:expr, $state:expr, $($arg:expr),+ ) => {
         let mut bytes = $source.len();
         let mut source_ptr = 0;
         let y_max = $state.height;
 
-        while bytes >= $block_size {
+        // blocks past the end of the source are written as zeros
+        while $state.y < y_max {
             let mut col = [$T::default(); 16];
-            $decode_fn(&mut col, &$source[source_ptr..]);
+            if bytes >= $block_size {
+                $decode_fn(&mut col, &$source[source_ptr..], $($arg),+);
+                source_ptr += $block_size;
+                bytes -= $block_size;
+            }
+
+            with_bytes(&col, |col| put_block($state, col, mem::size_of::<$T>()));
+        }
+    }
+}
+
+fn decode_bcn(state: &mut BcnDecoderState, source: &[u8], encoding: BcnEncoding) {
+    match encoding {
+        BcnEncoding::Bc1 => {
+            decode_loop!(decode_bc1_block, 8, Rgba, source, state, state.bc1_opaque);
+        }
+        BcnEncoding::Bc2 => {
+            decode_loop!(decode_bc2_block, 16, Rgba, source, state);
+        }
+        BcnEncoding::Bc3 => {
+            decode_loop!(decode_bc3_block, 16, Rgba, source, state);
+        }
+        BcnEncoding::Bc4 => {
+            decode_loop!(decode_bc4_block, 8, Lum, source, state, state.sign.then_some(state.signed_mapping));
+        }
+        BcnEncoding::Bc5 => {
+            decode_loop!(
+                decode_bc5_block,
+                16,
+                Rgba,
+                source,
+                state,
+                state.swap_channels,
+                state.sign.then_some(state.signed_mapping)
+            );
+        }
+        BcnEncoding::Bc6H => {
+            decode_loop!(decode_bc6h_block, 16, Rgb32f, source, state, state.sign, state.sanitize);
+        }
+        BcnEncoding::Bc7 => {
+            decode_loop!(decode_bc7_block, 16, Rgba, source, state);
+        }
+    };
+}
+
+#[cfg(not(feature = "safe"))]
+unsafe fn to_byte_ptr<T>(a: &[T]) -> &[u8] {
+    let p: *const u8 = (a as *const [T]) as *const u8;
+    slice::from_raw_parts(p, mem::size_of_val(a))
+}
+
+#[cfg(not(feature = "safe"))]
+unsafe fn to_byte_ptr_mut<T>(a: &mut [T]) -> &mut [u8] {
+    let p: *mut u8 = (a as *mut [T]) as *mut u8;
+    slice::from_raw_parts_mut(p, mem::size_of_val(a))
+}
+
+/// Calls `f` with the bytes of a block of pixels.
+#[cfg(not(feature = "safe"))]
+fn with_bytes<T: Copy, F: FnOnce(&[u8])>(col: &[T], f: F) {
+    unsafe { f(to_byte_ptr(col)) }
+}
+
+/// Calls `f` with the bytes of a block of pixels, which it may change.
+#[cfg(not(feature = "safe"))]
+fn with_bytes_mut<T: Copy, F: FnOnce(&mut [u8])>(col: &mut [T], f: F) {
+    unsafe { f(to_byte_ptr_mut(col)) }
+}
+
+/// Calls `f` with the bytes of a block of pixels.
+#[cfg(feature = "safe")]
+fn with_bytes<T: Pixel, F: FnOnce(&[u8])>(col: &[T], f: F) {
+    let mut bytes = [0; 16 * 12];
+    let bytes = &mut bytes[..T::SIZE * col.len()];
+    for (pixel, dst) in col.iter().zip(bytes.chunks_exact_mut(T::SIZE)) {
+        pixel.store(dst);
+    }
+    f(bytes)
+}
+
+/// Calls `f` with the bytes of a block of pixels, which it may change.
+#[cfg(feature = "safe")]
+fn with_bytes_mut<T: Pixel, F: FnOnce(&mut [u8])>(col: &mut [T], f: F) {
+    let mut bytes = [0; 16 * 12];
+    let bytes = &mut bytes[..T::SIZE * col.len()];
+    for (pixel, dst) in col.iter().zip(bytes.chunks_exact_mut(T::SIZE)) {
+        pixel.store(dst);
+    }
+    f(bytes);
+    for (pixel, src) in col.iter_mut().zip(bytes.chunks_exact(T::SIZE)) {
+        *pixel = T::load(src);
+    }
+}
+
+fn put_block(state: &mut BcnDecoderState, col: &[u8], block_size: usize) {
+    let straight;
+    let col = if state.unpremultiply {
+        straight = unpremultiply_block(col);
+        &straight[..]
+    } else {
+        col
+    };
+    let linear;
+    let col = if state.srgb {
+        linear = linearize_block(col);
+        &linear[..]
+    } else {
+        col
+    };
+    let graded;
+    let col = match state.lut {
+        Some(lut) => {
+            graded = grade_block(lut, col, block_size);
+            &graded[..16 * block_size]
+        }
+        None => col,
+    };
+    let transformed: [u8; 16];
+    let col = match state.value_transform {
+        ValueTransform::Identity => col,
+        ValueTransform::Invert => {
+            transformed = array::from_fn(|i| 255 - col[i]);
+            &transformed
+        }
+        ValueTransform::Curve(curve) => {
+            transformed = array::from_fn(|i| curve[col[i] as usize]);
+            &transformed
+        }
+    };
+    let normals;
+    let col = if state.normal_map == NormalMap::Raw {
+        col
+    } else {
+        normals = reconstruct_normals(col, state.normal_map);
+        &normals
+    };
+
+    // BC4 is repeated in red, green and blue for the color formats
+    let expanded;
+    let (col, block_size) = if block_size == 1 && state.swizzle != 0 {
+        expanded = expand_luminance(col);
+        (&expanded[..], 4)
+    } else {
+        (col, block_size)
+    };
+
+    // bring the channels into the output order first, so that every row of
+    // the block is a single copy
+    let swizzled;
+    let col = if state.swizzle == 0 || state.swizzle == 0xe4 {
+        col
+    } else {
+        swizzled = swizzle_block(state.swizzle, col, block_size);
+        &swizzled[..16 * block_size]
+    };
+    let gathered;
+    let col = match state.custom {
+        Some(swizzle) => {
+            gathered = gather_block(swizzle, col);
+            &gathered[..]
+        }
+        None => col,
+    };
+    let packed;
+    let (col, block_size) = if state.drop_alpha {
+        packed = drop_alpha(col);
+        (&packed[..], 3)
+    } else {
+        (col, block_size)
+    };
+
+    if state.tile_size > 0 {
+        put_block_tiled(state, col, block_size);
+    } else {
+        put_block_rows(state, col, block_size);
+    }
+
+    state.x += 4;
+    if state.x >= state.width {
+        state.y += 4;
+        state.x = 0;
+    }
+}
+
+fn put_block_rows(state: &mut BcnDecoderState, col: &[u8], block_size: usize) {
+    let columns = (state.width - state.x).min(4);
+    let row_size = block_size * columns;
+    let row_pitch = if state.row_pitch > 0 { state.row_pitch } else { block_size * state.width };
+
+    for j in 0..4 {
+        let mut y = state.y + j;
+        if y >= state.height {
+            break;
+        }
+        if state.y_step < 0 {
+            y = state.height - y - 1;
+        }
+        let dst_ptr = row_pitch * y + block_size * state.x;
+        let src_ptr = block_size * (j * 4);
+        write_row(
+            &mut state.buffer[dst_ptr..dst_ptr + row_size],
+            &col[src_ptr..src_ptr + row_size],
+        );
+    }
+}
 
-            unsafe {
-                put_block($state, to_byte_ptr(&col), mem::size_of::<$T>(), $flip);
-            }
+fn put_block_tiled(state: &mut BcnDecoderState, col: &[u8], block_size: usize) {
+    let n = state.tile_size;
+    let tile = (state.y / n) * state.width.div_ceil(n) + state.x / n;
+    let tile_ptr = block_size * n * (n * tile + state.y % n) + block_size * (state.x % n);
 
-            source_ptr += $block_size;
-            bytes -= $block_size;
-            if $state.y >= y_max {
-                break;
-            }
-        }
-    };
+    let inside = state.x + 4 <= state.width && state.y + 4 <= state.height;
+    if inside && n == 4 {
+        // the tile is the block
+        write_bytes(&mut state.buffer[tile_ptr..tile_ptr + 16 * block_size], col);
+        return;
+    }
+    let row_size = block_size * (state.width - state.x).min(4);
+    for j in 0..(state.height - state.y).min(4) {
+        let dst_ptr = tile_ptr + block_size * n * j;
+        let src_ptr = block_size * (j * 4);
+        write_row(
+            &mut state.buffer[dst_ptr..dst_ptr + row_size],
+            &col[src_ptr..src_ptr + row_size],
+        );
+    }
+}
 
-    ( $decode_fn:ident, $block_size:expr, $T:ident,
-    $source:expr, $state:expr, $flip:expr, $sign:expr ) => {
-        let mut bytes = $source.len();
-        let mut source_ptr = 0;
-        let y_max = $state.height;
+fn load_16(source: &[u8]) -> u16 {
+    (source[0] as u16) | (source[1] as u16) << 8
+}
 
-        while bytes >= $block_size {
-            let mut col = [$T::default(); 16];
-            $decode_fn(&mut col, &$source[source_ptr..], $sign);
+fn load_32(source: &[u8]) -> u32 {
+    (source[0] as u32) | ((source[1] as u32) << 8) | ((source[2] as u32) << 16) |
+        ((source[3] as u32) << 24)
+}
 
-            unsafe {
-                put_block($state, to_byte_ptr(&col), mem::size_of::<$T>(), $flip);
+/// Applies the color grading to the RGB channels of a block of `Rgba` or
+/// `Rgb32f` pixels.
+fn grade_block(lut: &Lut3d, col: &[u8], block_size: usize) -> [u8; 16 * 12] {
+    let mut graded = [0; 16 * 12];
+    graded[..16 * block_size].copy_from_slice(col);
+    if block_size == 12 {
+        for pixel in graded.chunks_exact_mut(12) {
+            let mut rgb = [0.0; 3];
+            for (c, bytes) in rgb.iter_mut().zip(pixel.chunks_exact(4)) {
+                *c = f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
             }
-
-            source_ptr += $block_size;
-            bytes -= $block_size;
-            if $state.y >= y_max {
-                break;
+            for (bytes, c) in pixel.chunks_exact_mut(4).zip(&lut.sample(rgb)) {
+                bytes.copy_from_slice(&c.to_ne_bytes());
+            }
+        }
+    } else {
+        for pixel in graded[..16 * block_size].chunks_exact_mut(4) {
+            let rgb = lut.sample([pixel[0] as f32 / 255.0, pixel[1] as f32 / 255.0, pixel[2] as f32 / 255.0]);
+            for (byte, c) in pixel.iter_mut().zip(&rgb) {
+                *byte = (c.clamp(0.0, 1.0) * 255.0).round() as u8;
             }
         }
     }
+    graded
 }
 
-fn decode_bcn(state: &mut BcnDecoderState, source: &[u8], encoding: BcnEncoding, flip: bool) {
-    match encoding {
-        BcnEncoding::Bc1 => {
-            decode_loop!(decode_bc1_block, 8, RGBA, source, state, flip);
-        }
-        BcnEncoding::Bc2 => {
-            decode_loop!(decode_bc2_block, 16, RGBA, source, state, flip);
-        }
-        BcnEncoding::Bc3 => {
-            decode_loop!(decode_bc3_block, 16, RGBA, source, state, flip);
-        }
-        BcnEncoding::Bc4 => {
-            decode_loop!(decode_bc4_block, 8, LUM, source, state, flip);
+/// Divides the premultiplied colors of a block of `Rgba` pixels by their
+/// alpha.
+fn unpremultiply_block(col: &[u8]) -> [u8; 64] {
+    let mut straight = [0; 64];
+    straight.copy_from_slice(col);
+    for pixel in straight.chunks_exact_mut(4) {
+        let a = pixel[3] as u16;
+        if a == 0 {
+            continue;
         }
-        BcnEncoding::Bc5 => {
-            decode_loop!(decode_bc5_block, 16, RGBA, source, state, flip);
+        for c in pixel[..3].iter_mut() {
+            *c = ((*c as u16 * 255 + a / 2) / a).min(255) as u8;
         }
-        BcnEncoding::Bc6H => {
-            decode_loop!(
-                decode_bc6h_block,
-                16,
-                RGB32f,
-                source,
-                state,
-                flip,
-                state.sign
-            );
+    }
+    straight
+}
+
+/// Converts the sRGB encoded colors of a block of `Rgba` pixels to linear
+/// values.
+fn linearize_block(col: &[u8]) -> [u8; 64] {
+    static TABLE: OnceLock<[u8; 256]> = OnceLock::new();
+    let table = TABLE.get_or_init(|| array::from_fn(|i| (srgb_to_linear(i as f32 / 255.0) * 255.0).round() as u8));
+
+    let mut linear = [0; 64];
+    linear.copy_from_slice(col);
+    for pixel in linear.chunks_exact_mut(4) {
+        for c in pixel[..3].iter_mut() {
+            *c = table[*c as usize];
         }
-        /*BcnEncoding::Bc7 => {
-            //decode_loop!(decode_bc7_block, 16, RGBA, source, state, flip);
-            unimplemented!();
+    }
+    linear
+}
+
+/// Applies the sRGB EOTF to a value from 0 to 1.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Turns the red and green channels of a block of `Rgba` pixels into unit
+/// normals as described by `mode`, written back to red, green and blue.
+fn reconstruct_normals(col: &[u8], mode: NormalMap) -> [u8; 64] {
+    let mut normals = [0; 64];
+    for (src, dst) in col.chunks_exact(4).zip(normals.chunks_exact_mut(4)) {
+        let u = src[0] as f32 / 127.5 - 1.0;
+        let v = src[1] as f32 / 127.5 - 1.0;
+        let (x, y, z) = if mode == NormalMap::Octahedral {
+            let z = 1.0 - u.abs() - v.abs();
+            if z < 0.0 {
+                // the lower hemisphere is folded over the diagonals
+                ((1.0 - v.abs()).copysign(u), (1.0 - u.abs()).copysign(v), z)
+            } else {
+                (u, v, z)
+            }
+        } else {
+            (u, v, (1.0 - u * u - v * v).max(0.0).sqrt())
+        };
+        // octahedral normals are generally not unit length, reconstructed ones are not
+        // outside the unit circle
+        let length = (x * x + y * y + z * z).sqrt();
+        for (d, v) in dst.iter_mut().zip(&[x, y, z]) {
+            *d = ((v / length + 1.0) * 127.5).round() as u8;
         }
-        _ => {
-            // TODO: RAW
-            unimplemented!();
-        }*/
-    };
+        dst[3] = src[3];
+    }
+    normals
 }
 
-unsafe fn to_byte_ptr<T>(a: &[T]) -> &[u8] {
-    let p: *const u8 = (a as *const [T]) as *const u8;
-    slice::from_raw_parts(p, mem::size_of::<T>() * a.len())
+/// Expands a block of `Lum` pixels into opaque gray `Rgba` pixels.
+fn expand_luminance(col: &[u8]) -> [u8; 64] {
+    let mut expanded = [0xff; 64];
+    for (&l, dst) in col.iter().zip(expanded.chunks_exact_mut(4)) {
+        dst[..3].fill(l);
+    }
+    expanded
 }
 
-unsafe fn to_byte_ptr_mut<T>(a: &mut [T]) -> &mut [u8] {
-    let p: *mut u8 = (a as *mut [T]) as *mut u8;
-    slice::from_raw_parts_mut(p, mem::size_of::<T>() * a.len())
+/// Arranges the channels of a block of `Rgba` pixels as a custom format.
+fn gather_block(swizzle: Swizzle, col: &[u8]) -> [u8; 64] {
+    let mut gathered = [0; 64];
+    for (src, dst) in col.chunks_exact(4).zip(gathered.chunks_exact_mut(4)) {
+        dst.copy_from_slice(&swizzle.apply([src[0], src[1], src[2], src[3]]));
+    }
+    gathered
 }
 
-fn put_block(state: &mut BcnDecoderState, col: &[u8], block_size: usize, flip: bool) {
-    let xmax = state.width;
-    let ymax = state.height;
+/// Packs a block of `Rgba` pixels, whose alpha is last after swizzling,
+/// into 3 bytes per pixel.
+fn drop_alpha(col: &[u8]) -> [u8; 48] {
+    let mut packed = [0; 48];
+    for (src, dst) in col.chunks_exact(4).zip(packed.chunks_exact_mut(3)) {
+        dst.copy_from_slice(&src[..3]);
+    }
+    packed
+}
 
-    for j in 0..4 {
-        let mut y = state.y + j;
-        if flip {
-            if y >= state.height {
-                continue;
-            }
-            if state.y_step < 0 {
-                y = ymax - y - 1;
-            }
-            let dst_ptr = block_size * state.width * y;
-            for i in 0..4 {
-                let x = state.x + i;
-                if x >= state.width {
-                    continue;
-                }
-                swizzle_copy(
-                    state.swizzle,
-                    &mut state.buffer[dst_ptr + block_size * x..],
-                    &col[block_size * (j * 4 + i)..],
-                    block_size,
-                );
-            }
-        } else {
-            if state.y_step < 0 {
-                y = ymax - y - 1;
-            }
-            let x = state.x;
-            let mut dst_ptr = (block_size * state.width * y) + block_size * x;
-            let mut src_ptr = block_size * (j * 4);
-            for _ in 0..4 {
-                swizzle_copy(
-                    state.swizzle,
-                    &mut state.buffer[dst_ptr..],
-                    &col[src_ptr..],
-                    block_size,
-                );
-                dst_ptr += block_size;
-                src_ptr += block_size;
+/// Reorders the channels of every pixel of a block. The swizzle holds the
+/// output position of red, green, blue and alpha in 2 bits each.
+fn swizzle_block(swizzle: u8, col: &[u8], block_size: usize) -> [u8; 16 * 12] {
+    let mut swizzled = [0; 16 * 12];
+    let positions = [
+        swizzle as usize & 3,
+        (swizzle as usize >> 2) & 3,
+        (swizzle as usize >> 4) & 3,
+        (swizzle as usize >> 6) & 3,
+    ];
+    if block_size == 4 {
+        for (src, dst) in col.chunks_exact(4).zip(swizzled.chunks_exact_mut(4)) {
+            for c in 0..4 {
+                dst[positions[c]] = src[c];
             }
         }
+        return swizzled;
     }
-    state.x += 4;
-    if state.x >= xmax {
-        state.y += 4;
-        state.x = 0;
+
+    // size per component
+    let size = block_size >> 2;
+    for (src, dst) in col.chunks_exact(block_size).zip(swizzled.chunks_exact_mut(block_size)) {
+        for (c, &position) in positions.iter().enumerate() {
+            let start = size * position;
+            dst[start..start + size].copy_from_slice(&src[size * c..size * (c + 1)]);
+        }
     }
+    swizzled
 }
 
-fn load_16(source: &[u8]) -> u16 {
-    (source[0] as u16) | (source[1] as u16) << 8
+/// Writes a row of a block, using 128-bit stores for the full rows of
+/// 4-byte and 12-byte pixels.
+fn write_row(dst: &mut [OutputByte], src: &[u8]) {
+    assert_eq!(dst.len(), src.len());
+    if !src.len().is_multiple_of(16) {
+        write_bytes(dst, src);
+        return;
+    }
+    for (dst, src) in dst.chunks_exact_mut(16).zip(src.chunks_exact(16)) {
+        let mut lane = [0; 16];
+        lane.copy_from_slice(src);
+        store_lane(dst, lane);
+    }
 }
 
-fn load_32(source: &[u8]) -> u32 {
-    (source[0] as u32) | ((source[1] as u32) << 8) | ((source[2] as u32) << 16) |
-        ((source[3] as u32) << 24)
+#[cfg(not(feature = "safe"))]
+fn store_lane(dst: &mut [OutputByte], lane: [u8; 16]) {
+    assert_eq!(dst.len(), 16);
+    unsafe {
+        ptr::write_unaligned(dst.as_mut_ptr() as *mut u128, u128::from_ne_bytes(lane));
+    }
 }
 
-fn swizzle_copy(swizzle: u8, dst: &mut [u8], src: &[u8], mut block_size: usize) {
-    if swizzle == 0 || swizzle == 0xe4 {
-        dst[0..block_size].copy_from_slice(&src[0..block_size]);
-        return;
+#[cfg(feature = "safe")]
+fn store_lane(dst: &mut [OutputByte], lane: [u8; 16]) {
+    dst.copy_from_slice(&lane);
+}
+
+#[cfg(not(feature = "safe"))]
+fn write_bytes(dst: &mut [OutputByte], src: &[u8]) {
+    assert_eq!(dst.len(), src.len());
+    unsafe {
+        ptr::copy_nonoverlapping(src.as_ptr(), dst.as_mut_ptr() as *mut u8, src.len());
     }
+}
 
-    // bring sz down to size-per-component
-    block_size >>= 2;
-    let mut start_ptr = block_size * (((swizzle as usize) & 3));
-    dst[start_ptr..start_ptr + block_size].copy_from_slice(&src[0..block_size]);
+#[cfg(feature = "safe")]
+fn write_bytes(dst: &mut [OutputByte], src: &[u8]) {
+    dst.copy_from_slice(src);
+}
 
-    start_ptr = block_size * (((swizzle as usize) & 0x0c) >> 2);
-    dst[start_ptr..start_ptr + block_size].copy_from_slice(&src[block_size..2 * block_size]);
+#[cfg(not(feature = "safe"))]
+fn write_zeros(dst: &mut [OutputByte]) {
+    for byte in dst {
+        byte.write(0);
+    }
+}
 
-    start_ptr = block_size * (((swizzle as usize) & 0x30) >> 4);
-    dst[start_ptr..start_ptr + block_size].copy_from_slice(&src[2 * block_size..3 * block_size]);
+#[cfg(feature = "safe")]
+fn write_zeros(dst: &mut [OutputByte]) {
+    dst.fill(0);
+}
 
-    start_ptr = block_size * (((swizzle as usize) & 0xc0) >> 6);
-    dst[start_ptr..start_ptr + block_size].copy_from_slice(&src[3 * block_size..4 * block_size]);
+/// Decodes a single block, which must hold at least `block_size` bytes.
+pub fn decode_single_block(encoding: BcnEncoding, block: &[u8]) -> Block4x4 {
+    match encoding {
+        BcnEncoding::Bc4 => {
+            let mut col = [Lum::default(); 16];
+            decode_bc4_block(&mut col, block, None);
+            Block4x4::Luminance(col.map(|p| p.l))
+        }
+        BcnEncoding::Bc6H => {
+            let mut col = [Rgb32f::default(); 16];
+            decode_bc6h_block(&mut col, block, false, false);
+            Block4x4::RgbFloat(col.map(|p| [p.r, p.g, p.b]))
+        }
+        _ => {
+            let mut col = [Rgba::default(); 16];
+            match encoding {
+                BcnEncoding::Bc1 => decode_bc1_block(&mut col, block, false),
+                BcnEncoding::Bc2 => decode_bc2_block(&mut col, block),
+                BcnEncoding::Bc3 => decode_bc3_block(&mut col, block),
+                BcnEncoding::Bc5 => decode_bc5_block(&mut col, block, false, None),
+                _ => decode_bc7_block(&mut col, block),
+            }
+            Block4x4::Rgba(col.map(|p| [p.r, p.g, p.b, p.a]))
+        }
+    }
 }
 
-fn decode_bc1_block(col: &mut [RGBA], source: &[u8]) {
-    decode_bc1_color(col, source);
+fn decode_bc1_block(col: &mut [Rgba], source: &[u8], opaque: bool) {
+    decode_bc1_color(col, source, opaque);
 }
 
-fn decode_bc2_block(col: &mut [RGBA], source: &[u8]) {
-    decode_bc1_color(col, &source[8..]);
-    for n in 0..16 {
+fn decode_bc2_block(col: &mut [Rgba], source: &[u8]) {
+    decode_bc1_color(col, &source[8..], false);
+    for (n, c) in col.iter_mut().enumerate().take(16) {
         let bit_i: usize = n * 4;
         let by_i: usize = bit_i >> 3;
         let mut av = 0xf & (source[by_i] >> (bit_i & 7));
         av = (av << 4) | av;
-        col[n].a = av;
+        c.a = av;
     }
 }
 
-fn decode_bc3_block(col: &mut [RGBA], source: &[u8]) {
-    decode_bc1_color(col, &source[8..]);
-    unsafe {
-        decode_bc3_alpha(to_byte_ptr_mut(col), source, mem::size_of::<RGBA>(), 3);
-    }
+fn decode_bc3_block(col: &mut [Rgba], source: &[u8]) {
+    decode_bc1_color(col, &source[8..], false);
+    with_bytes_mut(col, |dst| decode_bc3_alpha(dst, source, mem::size_of::<Rgba>(), 3));
 }
 
-fn decode_bc4_block(col: &mut [LUM], source: &[u8]) {
-    unsafe {
-        decode_bc3_alpha(to_byte_ptr_mut(col), source, mem::size_of::<LUM>(), 0);
-    }
+fn decode_bc4_block(col: &mut [Lum], source: &[u8], signed: Option<SignedMapping>) {
+    with_bytes_mut(col, |dst| match signed {
+        Some(mapping) => decode_signed_alpha(dst, source, mem::size_of::<Lum>(), 0, mapping),
+        None => decode_bc3_alpha(dst, source, mem::size_of::<Lum>(), 0),
+    });
 }
 
-fn decode_bc5_block(col: &mut [RGBA], source: &[u8]) {
-    unsafe {
-        let dst = to_byte_ptr_mut(col);
-        decode_bc3_alpha(dst, source, mem::size_of::<RGBA>(), 0);
-        decode_bc3_alpha(dst, &source[8..], mem::size_of::<RGBA>(), 1);
-    }
+fn decode_bc5_block(col: &mut [Rgba], source: &[u8], swap: bool, signed: Option<SignedMapping>) {
+    let (x, y) = if swap { (1, 0) } else { (0, 1) };
+    with_bytes_mut(col, |dst| {
+        for (source, o) in [(source, x), (&source[8..], y)] {
+            match signed {
+                Some(mapping) => decode_signed_alpha(dst, source, mem::size_of::<Rgba>(), o, mapping),
+                None => decode_bc3_alpha(dst, source, mem::size_of::<Rgba>(), o),
+            }
+        }
+    });
 }
 
-fn decode_bc6h_block(col: &mut [RGB32f], source: &[u8], sign: bool) {
+fn decode_bc6h_block(col: &mut [Rgb32f], source: &[u8], sign: bool, sanitize: bool) {
     let mut bit = 5;
     let mut epbits = 75;
     let mut ib = 3;
@@ -398,17 +1623,26 @@ fn decode_bc6h_block(col: &mut [RGB32f], source: &[u8], sign: bool) {
 
     let numep = if info.ns == 2 { 12 } else { 6 };
 
-    let mut endpoints: [u16; 12] = [0; 12];
-    for i in 0..epbits {
-        let mut di = BC6_BIT_PACKINGS[mode][i];
-        let dw = di >> 4;
-        di &= 15;
-        endpoints[dw as usize] |= ((get_bit(source, bit + i) as usize) << di) as u16;
-    }
+    let mut reader = BitReader::new(source);
+    let mut endpoints = match mode {
+        0 => bc6_endpoints::<0>(&reader),
+        1 => bc6_endpoints::<1>(&reader),
+        2 => bc6_endpoints::<2>(&reader),
+        3 => bc6_endpoints::<3>(&reader),
+        4 => bc6_endpoints::<4>(&reader),
+        5 => bc6_endpoints::<5>(&reader),
+        6 => bc6_endpoints::<6>(&reader),
+        7 => bc6_endpoints::<7>(&reader),
+        8 => bc6_endpoints::<8>(&reader),
+        9 => bc6_endpoints::<9>(&reader),
+        10 => bc6_endpoints::<10>(&reader),
+        11 => bc6_endpoints::<11>(&reader),
+        12 => bc6_endpoints::<12>(&reader),
+        _ => bc6_endpoints::<13>(&reader),
+    };
 
-    bit += epbits;
-    let partition: u8 = get_bits(source, bit, info.pb as usize);
-    bit += info.pb as usize;
+    reader.skip((bit + epbits) as u32);
+    let partition = reader.read(info.pb) as u8;
 
     let mask: u16 = (((1 << info.epb) as usize) - 1) as u16;
     if sign {
@@ -421,7 +1655,7 @@ fn decode_bc6h_block(col: &mut [RGB32f], source: &[u8], sign: bool) {
         // sign-extend e1,2,3 if signed or deltas
         let mut i = 3;
         while i < numep {
-            bc6_sign_extend(&mut endpoints[i + 0], info.rb as isize);
+            bc6_sign_extend(&mut endpoints[i], info.rb as isize);
             bc6_sign_extend(&mut endpoints[i + 1], info.gb as isize);
             bc6_sign_extend(&mut endpoints[i + 2], info.bb as isize);
             i += 3;
@@ -430,15 +1664,12 @@ fn decode_bc6h_block(col: &mut [RGB32f], source: &[u8], sign: bool) {
     if info.tr > 0 {
         // apply deltas
         for i in 3..numep {
-            endpoints[i] = ((endpoints[i] as usize + endpoints[0] as usize) & mask as usize) as u16;
+            endpoints[i] = ((endpoints[i] as usize + endpoints[i % 3] as usize) & mask as usize) as u16;
         }
         if sign {
-            let mut i = 3;
-            while i < numep {
-                bc6_sign_extend(&mut endpoints[i + 0], info.rb as isize);
-                bc6_sign_extend(&mut endpoints[i + 1], info.gb as isize);
-                bc6_sign_extend(&mut endpoints[i + 2], info.bb as isize);
-                i += 3;
+            // the endpoints are full precision now
+            for endpoint in endpoints[3..numep].iter_mut() {
+                bc6_sign_extend(endpoint, info.epb as isize);
             }
         }
     }
@@ -446,87 +1677,120 @@ fn decode_bc6h_block(col: &mut [RGB32f], source: &[u8], sign: bool) {
     for i in 0..numep {
         ueps[i] = bc6_unquantize(endpoints[i], info.epb as isize, sign);
     }
-    for i in 0..16 {
+    for (i, c) in col.iter_mut().enumerate().take(16) {
         let s = bc7_get_subset(info.ns, partition as usize, i) * 6;
-        let mut ib2 = ib as usize;
-        if i == 0 {
+        let mut ib2 = ib;
+        if i == 0 || (info.ns == 2 && i == BC7_AI0[partition as usize] as usize) {
             ib2 -= 1;
-        } else if info.ns == 2 {
-            if i == BC7_AI0[partition as usize] as usize {
-                ib2 -= 1;
-            }
         }
-        let i0 = get_bits(source, bit, ib2) as usize;
-        bit += ib2;
+        let i0 = reader.read(ib2) as usize;
+
+        bc6_lerp(c, &ueps[s..], &ueps[(s + 3)..], cw[i0], sign, sanitize);
+    }
+}
 
-        bc6_lerp(&mut col[i], &ueps[s..], &ueps[(s + 3)..], cw[i0], sign);
+/// Extracts the endpoint values of a block of the given mode. Each mode is
+/// compiled separately, with the bit runs of its layout as constants.
+fn bc6_endpoints<const MODE: usize>(reader: &BitReader) -> [u16; 12] {
+    let layout = &BC6_BIT_RUNS[MODE];
+    let mut endpoints = [0u16; 12];
+    for run in &layout.runs[..layout.count] {
+        let value = reader.peek_at(run.offset as u32, run.len);
+        endpoints[run.endpoint as usize] |= (value << run.shift) as u16;
     }
+    endpoints
 }
 
-fn bc6_lerp(col: &mut RGB32f, e0: &[isize], e1: &[isize], s: u8, sign: bool) {
+fn bc6_lerp(col: &mut Rgb32f, e0: &[isize], e1: &[isize], s: u8, sign: bool, sanitize: bool) {
     let t: isize = 64 - s as isize;
-    let r: isize = (e0[0] * t + e1[0] * s as isize) >> 6;
-    let g: isize = (e0[1] * t + e1[1] * s as isize) >> 6;
-    let b: isize = (e0[2] * t + e1[2] * s as isize) >> 6;
-    col.r = bc6_finalize(r, sign);
-    col.g = bc6_finalize(g, sign);
-    col.b = bc6_finalize(b, sign);
+    let r: isize = (e0[0] * t + e1[0] * s as isize + 32) >> 6;
+    let g: isize = (e0[1] * t + e1[1] * s as isize + 32) >> 6;
+    let b: isize = (e0[2] * t + e1[2] * s as isize + 32) >> 6;
+    col.r = bc6_finalize(r, sign, sanitize);
+    col.g = bc6_finalize(g, sign, sanitize);
+    col.b = bc6_finalize(b, sign, sanitize);
 }
 
-fn bc6_finalize(v: isize, sign: bool) -> f32 {
+fn bc6_finalize(v: isize, sign: bool, sanitize: bool) -> f32 {
+    let value = bc6_to_float(v, sign);
+    if sanitize {
+        sanitize_float(value)
+    } else {
+        value
+    }
+}
+
+fn bc6_to_float(v: isize, sign: bool) -> f32 {
     if sign {
         if v < 0 {
             let _v = ((-v) * 31) / 32;
-            return half_to_float((0x8000 | _v) as u16);
+            half_to_float((0x8000 | _v) as u16)
         } else {
-            return half_to_float(((v * 31) / 32) as u16);
+            half_to_float(((v * 31) / 32) as u16)
         }
     } else {
-        return half_to_float(((v * 31) / 64) as u16);
+        half_to_float(((v * 31) / 64) as u16)
     }
 }
 
-#[repr(C)]
-#[derive(Default)]
-struct FloatUnion {
-    data: u32,
+/// Flushes NaN to 0 and clamps infinities to the largest finite half
+/// float, keeping their sign.
+pub fn sanitize_float(value: f32) -> f32 {
+    if value.is_nan() {
+        0.0
+    } else {
+        value.clamp(-HALF_MAX, HALF_MAX)
+    }
 }
 
-impl FloatUnion {
-    unsafe fn as_f32_mut(&mut self) -> &mut f32 {
-        let p = self as *mut _ as *mut f32;
-        &mut *p
+/// Converts a half float, given by its bits, to a float.
+pub fn half_to_float(h: u16) -> f32 {
+    // https://gist.github.com/rygorous/2144712
+    let magic = f32::from_bits(0x77800000);
+    let was_inf_nan = f32::from_bits(0x47800000);
+
+    let mut o = f32::from_bits((h as u32 & 0x7fff) << 13) * magic;
+    if o >= was_inf_nan {
+        o = f32::from_bits(o.to_bits() | (255 << 23));
     }
-    unsafe fn as_f32(&self) -> &f32 {
-        let p = self as *const _ as *const f32;
-        &*p
+    f32::from_bits(o.to_bits() | ((h as u32 & 0x8000) << 16))
+}
+
+/// Converts a float to the bits of the nearest half float, rounding ties to
+/// even. Values beyond the half float range become infinities.
+pub fn float_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff - 127 + 15 {
+        // infinity, or NaN kept quiet
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
     }
-    unsafe fn as_u32_mut(&mut self) -> &mut u32 {
-        let p = self as *mut _ as *mut u32;
-        &mut *p
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
     }
-    unsafe fn as_u32(&self) -> &u32 {
-        let p = self as *const _ as *const u32;
-        &*p
+    if exponent < -10 {
+        return sign;
     }
-}
 
-fn half_to_float(h: u16) -> f32 {
-    unsafe {
-        // https://gist.github.com/rygorous/2144712
-        let mut o = FloatUnion::default();
-        let mut m = FloatUnion::default();
-
-        *m.as_u32_mut() = 0x77800000;
-        *o.as_u32_mut() = (h as u32 & 0x7fff) << 13;
-        *o.as_f32_mut() = *o.as_f32() * *m.as_f32(); // o.f *= m.f;
-        *m.as_u32_mut() = 0x47800000;
-        if o.as_f32() >= m.as_f32() {
-            *o.as_u32_mut() = *o.as_u32() | (255 << 23);
-        }
-        *o.as_u32_mut() = *o.as_u32() | ((h as u32 & 0x8000) << 16);
-        return *o.as_f32();
-    }
+    // subnormal halves lose the implicit bit and more of the mantissa
+    let (half, shift) = if exponent <= 0 {
+        (0, (14 - exponent) as u32)
+    } else {
+        ((exponent as u32) << 10, 13)
+    };
+    let mantissa = if exponent <= 0 { mantissa | 0x80_0000 } else { mantissa };
+    let half = half | (mantissa >> shift);
+    let rest = mantissa & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    // a carry out of the mantissa correctly bumps the exponent
+    let rounded = if rest > halfway || (rest == halfway && half & 1 == 1) {
+        half + 1
+    } else {
+        half
+    };
+    sign | rounded as u16
 }
 
 #[derive(Default)]
@@ -674,6 +1938,225 @@ impl Bc6ModeInfo {
     }
 }
 
+struct Bc7ModeInfo {
+    ns: u8,  /* number of subsets */
+    pb: u8,  /* partition bits */
+    rb: u8,  /* rotation bits */
+    isb: u8, /* index selection bits */
+    cb: u8,  /* color bits */
+    ab: u8,  /* alpha bits */
+    epb: u8, /* endpoint p-bits */
+    spb: u8, /* shared p-bits */
+    ib: u8,  /* index bits */
+    ib2: u8, /* secondary index bits */
+}
+
+static BC7_MODES: [Bc7ModeInfo; 8] = [
+    Bc7ModeInfo { ns: 3, pb: 4, rb: 0, isb: 0, cb: 4, ab: 0, epb: 1, spb: 0, ib: 3, ib2: 0 },
+    Bc7ModeInfo { ns: 2, pb: 6, rb: 0, isb: 0, cb: 6, ab: 0, epb: 0, spb: 1, ib: 3, ib2: 0 },
+    Bc7ModeInfo { ns: 3, pb: 6, rb: 0, isb: 0, cb: 5, ab: 0, epb: 0, spb: 0, ib: 2, ib2: 0 },
+    Bc7ModeInfo { ns: 2, pb: 6, rb: 0, isb: 0, cb: 7, ab: 0, epb: 1, spb: 0, ib: 2, ib2: 0 },
+    Bc7ModeInfo { ns: 1, pb: 0, rb: 2, isb: 1, cb: 5, ab: 6, epb: 0, spb: 0, ib: 2, ib2: 3 },
+    Bc7ModeInfo { ns: 1, pb: 0, rb: 2, isb: 0, cb: 7, ab: 8, epb: 0, spb: 0, ib: 2, ib2: 2 },
+    Bc7ModeInfo { ns: 1, pb: 0, rb: 0, isb: 0, cb: 7, ab: 7, epb: 1, spb: 0, ib: 4, ib2: 0 },
+    Bc7ModeInfo { ns: 2, pb: 6, rb: 0, isb: 0, cb: 5, ab: 5, epb: 1, spb: 0, ib: 2, ib2: 0 },
+];
+
+// The block is decoded in two steps: the endpoints and weights of all 16
+// texels are gathered into flat arrays first, then a single vector lerp
+// interpolates all 64 channels at once.
+fn decode_bc7_block(col: &mut [Rgba], source: &[u8]) {
+    if source[0] == 0 {
+        // reserved mode, decodes to transparent black
+        return;
+    }
+
+    let mut reader = BitReader::new(source);
+    let mut load = |count: u8| reader.read(count) as u8;
+
+    // the mode is the number of unset bits before the first set bit
+    let mode = source[0].trailing_zeros() as usize;
+    load(mode as u8 + 1);
+    let info = &BC7_MODES[mode];
+
+    let partition = load(info.pb) as usize;
+    let rotation = load(info.rb);
+    let index_sel = load(info.isb);
+
+    // endpoints[subset * 2 + n], all reds first, then greens, blues, alphas
+    let numep = (info.ns as usize) << 1;
+    let mut endpoints = [[0u8; 4]; 6];
+    for c in 0..3 {
+        for endpoint in endpoints.iter_mut().take(numep) {
+            endpoint[c] = load(info.cb);
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(numep) {
+        endpoint[3] = if info.ab > 0 { load(info.ab) } else { 255 };
+    }
+
+    let mut cb = info.cb;
+    let mut ab = info.ab;
+    if info.epb > 0 || info.spb > 0 {
+        cb += 1;
+        if ab > 0 {
+            ab += 1;
+        }
+        let channels = if ab > 0 { 4 } else { 3 };
+        // one p-bit per endpoint, or one per subset
+        let step = if info.epb > 0 { 1 } else { 2 };
+        for pair in endpoints[..numep].chunks_mut(step) {
+            let p = load(1);
+            for endpoint in pair {
+                for value in endpoint.iter_mut().take(channels) {
+                    *value = (*value << 1) | p;
+                }
+            }
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(numep) {
+        for value in endpoint.iter_mut().take(3) {
+            *value = bc7_expand_quantized(*value, cb);
+        }
+        if ab > 0 {
+            endpoint[3] = bc7_expand_quantized(endpoint[3], ab);
+        }
+    }
+
+    // extract the indices of all texels at once; the first texel of every
+    // subset, its anchor, is stored with one bit less
+    let anchors = match info.ns {
+        2 => [0, BC7_AI0[partition] as usize, 0],
+        3 => [0, BC7_AI1[partition] as usize, BC7_AI2[partition] as usize],
+        _ => [0, 0, 0],
+    };
+    let mut subsets = [0usize; 16];
+    for (i, subset) in subsets.iter_mut().enumerate() {
+        *subset = bc7_get_subset(info.ns, partition, i);
+    }
+    let extract = |mut reader: BitReader, ib: u8| {
+        let mut indices = [0u8; 16];
+        for (i, index) in indices.iter_mut().enumerate() {
+            let n = if anchors[subsets[i]] == i { ib - 1 } else { ib };
+            *index = reader.read(n) as u8;
+        }
+        indices
+    };
+    let color_indices = extract(reader, info.ib);
+    let (alpha_indices, alpha_ib) = if info.ib2 > 0 {
+        // the secondary indices follow the primary ones
+        let mut alpha_reader = reader;
+        alpha_reader.skip(16 * info.ib as u32 - info.ns as u32);
+        (extract(alpha_reader, info.ib2), info.ib2)
+    } else {
+        (color_indices, info.ib)
+    };
+
+    let cw = bc7_get_weights(info.ib);
+    let aw = bc7_get_weights(alpha_ib);
+    let mut e0 = [0u8; 64];
+    let mut e1 = [0u8; 64];
+    let mut weights = [0u8; 64];
+    for i in 0..16 {
+        let s = subsets[i] << 1;
+        e0[4 * i..4 * i + 4].copy_from_slice(&endpoints[s]);
+        e1[4 * i..4 * i + 4].copy_from_slice(&endpoints[s + 1]);
+        let (mut w, mut wa) = (cw[color_indices[i] as usize], aw[alpha_indices[i] as usize]);
+        if index_sel != 0 {
+            // the secondary indices are used for color and the primary ones
+            // for alpha
+            w = aw[alpha_indices[i] as usize];
+            wa = cw[color_indices[i] as usize];
+        }
+        weights[4 * i..4 * i + 3].copy_from_slice(&[w, w, w]);
+        weights[4 * i + 3] = wa;
+    }
+
+    let mut pixels = simd::lerp_64(&e0, &e1, &weights);
+    if rotation > 0 {
+        // the alpha channel was swapped with red, green or blue
+        let c = rotation as usize - 1;
+        for pixel in pixels.chunks_mut(4) {
+            pixel.swap(c, 3);
+        }
+    }
+    with_bytes_mut(col, |dst| dst.copy_from_slice(&pixels));
+}
+
+fn bc7_expand_quantized(v: u8, bits: u8) -> u8 {
+    let v = v << (8 - bits);
+    v | v.checked_shr(bits as u32).unwrap_or(0)
+}
+
+/// Consecutive bits of a BC6H block that hold consecutive bits of one
+/// endpoint value.
+#[derive(Clone, Copy)]
+struct Bc6BitRun {
+    offset: u8,   /* position of the first bit in the block */
+    endpoint: u8, /* index of the endpoint value */
+    shift: u8,    /* position of the first bit in the endpoint value */
+    len: u8,      /* number of bits */
+}
+
+struct Bc6BitRuns {
+    runs: [Bc6BitRun; 75],
+    count: usize,
+}
+
+/// Merges the bit packing of a mode into runs, so that most endpoint values
+/// are read with a single shift and mask.
+const fn bc6_bit_runs(mode: usize) -> Bc6BitRuns {
+    // modes 0 and 1 have 2 mode bits, the others 5
+    let (first_bit, epbits) = match mode {
+        0 | 1 => (2, 75),
+        2..=9 => (5, 72),
+        _ => (5, 60),
+    };
+    let packing = BC6_BIT_PACKINGS[mode];
+    let mut runs = [Bc6BitRun {
+        offset: 0,
+        endpoint: 0,
+        shift: 0,
+        len: 0,
+    }; 75];
+    let mut count = 0;
+    let mut i = 0;
+    while i < epbits {
+        let endpoint = packing[i] >> 4;
+        let shift = packing[i] & 15;
+        if count > 0 && runs[count - 1].endpoint == endpoint && runs[count - 1].shift + runs[count - 1].len == shift {
+            runs[count - 1].len += 1;
+        } else {
+            runs[count] = Bc6BitRun {
+                offset: (first_bit + i) as u8,
+                endpoint,
+                shift,
+                len: 1,
+            };
+            count += 1;
+        }
+        i += 1;
+    }
+    Bc6BitRuns { runs, count }
+}
+
+const BC6_BIT_RUNS: [Bc6BitRuns; 14] = [
+    bc6_bit_runs(0),
+    bc6_bit_runs(1),
+    bc6_bit_runs(2),
+    bc6_bit_runs(3),
+    bc6_bit_runs(4),
+    bc6_bit_runs(5),
+    bc6_bit_runs(6),
+    bc6_bit_runs(7),
+    bc6_bit_runs(8),
+    bc6_bit_runs(9),
+    bc6_bit_runs(10),
+    bc6_bit_runs(11),
+    bc6_bit_runs(12),
+    bc6_bit_runs(13),
+];
+
 static BC7_WEIGHTS2: [u8; 4] = [0, 21, 43, 64];
 static BC7_WEIGHTS3: [u8; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
 static BC7_WEIGHTS4: [u8; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
@@ -685,14 +2168,16 @@ fn bc7_get_weights(n: u8) -> &'static [u8] {
     if n == 3 {
         return &BC7_WEIGHTS3;
     }
-    return &BC7_WEIGHTS4;
+    &BC7_WEIGHTS4
 }
 
-static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
+// Every entry maps one bit of the block, in order, to bit `entry & 15` of
+// endpoint value `entry >> 4`.
+const BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
     [
         116,
         132,
-        176,
+        180,
         0,
         1,
         2,
@@ -738,7 +2223,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         66,
         67,
         68,
-        172,
+        176,
         160,
         161,
         162,
@@ -748,7 +2233,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         82,
         83,
         84,
-        173,
+        177,
         128,
         129,
         130,
@@ -758,13 +2243,13 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         98,
         99,
         100,
-        174,
+        178,
         144,
         145,
         146,
         147,
         148,
-        175,
+        179,
     ],
     [
         117,
@@ -777,8 +2262,8 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         4,
         5,
         6,
-        172,
-        173,
+        176,
+        177,
         132,
         16,
         17,
@@ -788,7 +2273,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         21,
         22,
         133,
-        174,
+        178,
         116,
         32,
         33,
@@ -797,9 +2282,9 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         36,
         37,
         38,
-        175,
-        177,
-        176,
+        179,
+        181,
+        180,
         48,
         49,
         50,
@@ -889,7 +2374,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         66,
         67,
         26,
-        172,
+        176,
         160,
         161,
         162,
@@ -899,7 +2384,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         82,
         83,
         42,
-        173,
+        177,
         128,
         129,
         130,
@@ -909,13 +2394,13 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         98,
         99,
         100,
-        174,
+        178,
         144,
         145,
         146,
         147,
         148,
-        175,
+        179,
         0,
         0,
         0,
@@ -976,7 +2461,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         82,
         83,
         42,
-        173,
+        177,
         128,
         129,
         130,
@@ -985,14 +2470,14 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         97,
         98,
         99,
-        172,
-        174,
+        176,
+        178,
         144,
         145,
         146,
         147,
         116,
-        175,
+        179,
         0,
         0,
         0,
@@ -1043,7 +2528,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         66,
         67,
         26,
-        172,
+        176,
         160,
         161,
         162,
@@ -1062,14 +2547,14 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         97,
         98,
         99,
-        173,
-        174,
+        177,
+        178,
         144,
         145,
         146,
         147,
-        176,
-        175,
+        180,
+        179,
         0,
         0,
         0,
@@ -1104,7 +2589,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         38,
         39,
         40,
-        176,
+        180,
         48,
         49,
         50,
@@ -1120,7 +2605,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         66,
         67,
         68,
-        172,
+        176,
         160,
         161,
         162,
@@ -1130,7 +2615,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         82,
         83,
         84,
-        173,
+        177,
         128,
         129,
         130,
@@ -1140,13 +2625,13 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         98,
         99,
         100,
-        174,
+        178,
         144,
         145,
         146,
         147,
         148,
-        175,
+        179,
         0,
         0,
         0,
@@ -1170,7 +2655,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         21,
         22,
         23,
-        174,
+        178,
         116,
         32,
         33,
@@ -1180,8 +2665,8 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         37,
         38,
         39,
-        175,
-        176,
+        179,
+        180,
         48,
         49,
         50,
@@ -1197,7 +2682,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         66,
         67,
         68,
-        172,
+        176,
         160,
         161,
         162,
@@ -1207,7 +2692,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         82,
         83,
         84,
-        173,
+        177,
         128,
         129,
         130,
@@ -1237,7 +2722,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         5,
         6,
         7,
-        172,
+        176,
         132,
         16,
         17,
@@ -1258,7 +2743,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         38,
         39,
         165,
-        176,
+        180,
         48,
         49,
         50,
@@ -1284,7 +2769,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         82,
         83,
         84,
-        173,
+        177,
         128,
         129,
         130,
@@ -1294,13 +2779,13 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         98,
         99,
         100,
-        174,
+        178,
         144,
         145,
         146,
         147,
         148,
-        175,
+        179,
         0,
         0,
         0,
@@ -1314,7 +2799,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         5,
         6,
         7,
-        173,
+        177,
         132,
         16,
         17,
@@ -1334,8 +2819,8 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         37,
         38,
         39,
-        177,
-        176,
+        181,
+        180,
         48,
         49,
         50,
@@ -1351,7 +2836,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         66,
         67,
         68,
-        172,
+        176,
         160,
         161,
         162,
@@ -1371,13 +2856,13 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         98,
         99,
         100,
-        174,
+        178,
         144,
         145,
         146,
         147,
         148,
-        175,
+        179,
         0,
         0,
         0,
@@ -1390,8 +2875,8 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         4,
         5,
         164,
-        172,
-        173,
+        176,
+        177,
         132,
         16,
         17,
@@ -1401,7 +2886,7 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         21,
         117,
         133,
-        174,
+        178,
         116,
         32,
         33,
@@ -1410,9 +2895,9 @@ static BC6_BIT_PACKINGS: [[u8; 75]; 14] = [
         36,
         37,
         165,
-        175,
-        177,
-        176,
+        179,
+        181,
+        180,
         48,
         49,
         50,
@@ -2111,30 +3596,56 @@ static BC7_AI2: [u8; 64] = [
     8,
 ];
 
-fn get_bit(src: &[u8], bit: usize) -> u8 {
-    let by = bit >> 3;
-    let bit_m = bit & 7;
-    return (src[by] >> bit_m) & 1;
+/// Reads the bits of a 16-byte block, least significant bit first.
+///
+/// Reads are served from a 64-bit window over the two halves of the
+/// block, which are loaded once, so no read needs a bounds check.
+#[derive(Clone, Copy)]
+struct BitReader {
+    lo: u64,
+    hi: u64,
+    // Position of the next bit to read
+    bit: u32,
 }
 
-fn get_bits(src: &[u8], bit: usize, count: usize) -> u8 {
-    let v: u8;
-    let by = bit >> 3;
-    let _bit = bit & 7;
-    if count == 0 {
-        return 0;
+impl BitReader {
+    fn new(source: &[u8]) -> Self {
+        let mut lo = [0; 8];
+        let mut hi = [0; 8];
+        lo.copy_from_slice(&source[..8]);
+        hi.copy_from_slice(&source[8..16]);
+        BitReader {
+            lo: u64::from_le_bytes(lo),
+            hi: u64::from_le_bytes(hi),
+            bit: 0,
+        }
     }
-    if _bit + count <= 8 {
-        v = (src[by] >> _bit) & ((1 << count) - 1);
-    } else {
-        let x = src[by] as usize | ((src[by + 1] as usize) << 8);
-        v = ((x >> _bit) & ((1 << count) - 1)) as u8;
+
+    /// Returns `count` bits, at most 32, starting at `bit`.
+    fn peek_at(&self, bit: u32, count: u8) -> u32 {
+        let window = if bit >= 64 {
+            self.hi >> (bit - 64)
+        } else if bit == 0 {
+            self.lo
+        } else {
+            (self.lo >> bit) | (self.hi << (64 - bit))
+        };
+        (window & ((1 << count) - 1)) as u32
+    }
+
+    fn read(&mut self, count: u8) -> u32 {
+        let value = self.peek_at(self.bit, count);
+        self.bit += count as u32;
+        value
+    }
+
+    fn skip(&mut self, count: u32) {
+        self.bit += count;
     }
-    return v;
 }
 
 fn bc6_sign_extend(v: &mut u16, prec: isize) {
-    let mut x = v.clone() as isize;
+    let mut x = *v as isize;
     if x & (1 << (prec - 1)) > 0 {
         x |= -1 << prec;
     }
@@ -2155,9 +3666,10 @@ fn bc6_unquantize(v: u16, prec: isize, sign: bool) -> isize {
         if x == ((1 << prec) - 1) {
             return 0xffff;
         }
-        return ((x << 15) + 0x4000) >> (prec - 1);
+        ((x << 15) + 0x4000) >> (prec - 1)
     } else {
-        let mut x = v as isize;
+        // signed endpoints are stored sign-extended to 16 bits
+        let mut x = v as i16 as isize;
         if prec >= 16 {
             return x;
         }
@@ -2178,7 +3690,7 @@ fn bc6_unquantize(v: u16, prec: isize, sign: bool) -> isize {
         if s != 0 {
             return -x;
         }
-        return x;
+        x
     }
 }
 
@@ -2189,10 +3701,17 @@ fn bc7_get_subset(ns: u8, partition: usize, n: usize) -> usize {
     if ns == 3 {
         return 3 & (BC7_SI3[partition] as usize >> (2 * n));
     }
-    return 0;
+    0
 }
 
-fn decode_565(x: u16) -> RGBA {
+/// Expands a 565 color, red in the top bits, to 8-bit RGBA the way BC1
+/// endpoints are expanded.
+pub fn rgba_565(x: u16) -> [u8; 4] {
+    let c = decode_565(x);
+    [c.r, c.g, c.b, c.a]
+}
+
+fn decode_565(x: u16) -> Rgba {
     let mut r: isize = ((x & 0xf800) >> 8) as isize;
     r |= r >> 5;
 
@@ -2202,17 +3721,19 @@ fn decode_565(x: u16) -> RGBA {
     let mut b: isize = ((x & 0x1f) << 3) as isize;
     b |= b >> 5;
 
-    return RGBA {
+    Rgba {
         r: r as u8,
         g: g as u8,
         b: b as u8,
         a: 0xff,
-    };
+    }
 }
 
-fn decode_bc1_color(dst: &mut [RGBA], source: &[u8]) {
+/// Decodes the color of a BC1 block. The black of the 3-color mode is
+/// transparent unless `opaque` is set.
+fn decode_bc1_color(dst: &mut [Rgba], source: &[u8], opaque: bool) {
     let mut col = Bc1Color::default();
-    let mut p = [RGBA::default(); 4];
+    let mut p = [Rgba::default(); 4];
 
     col.load(source);
 
@@ -2227,13 +3748,13 @@ fn decode_bc1_color(dst: &mut [RGBA], source: &[u8]) {
     let b1: u16 = p[1].b as u16;
 
     if col.c0 > col.c1 {
-        p[2].r = ((2 * r0 + 1 * r1) / 3) as u8;
-        p[2].g = ((2 * g0 + 1 * g1) / 3) as u8;
-        p[2].b = ((2 * b0 + 1 * b1) / 3) as u8;
+        p[2].r = ((2 * r0 + r1) / 3) as u8;
+        p[2].g = ((2 * g0 + g1) / 3) as u8;
+        p[2].b = ((2 * b0 + b1) / 3) as u8;
         p[2].a = 0xff;
-        p[3].r = ((1 * r0 + 2 * r1) / 3) as u8;
-        p[3].g = ((1 * g0 + 2 * g1) / 3) as u8;
-        p[3].b = ((1 * b0 + 2 * b1) / 3) as u8;
+        p[3].r = ((r0 + 2 * r1) / 3) as u8;
+        p[3].g = ((g0 + 2 * g1) / 3) as u8;
+        p[3].b = ((b0 + 2 * b1) / 3) as u8;
         p[3].a = 0xff;
     } else {
         p[2].r = ((r0 + r1) / 2) as u8;
@@ -2243,16 +3764,52 @@ fn decode_bc1_color(dst: &mut [RGBA], source: &[u8]) {
         p[3].r = 0;
         p[3].g = 0;
         p[3].b = 0;
-        p[3].a = 0;
+        p[3].a = if opaque { 0xff } else { 0 };
     }
 
-    for n in 0..16 {
-        let cw: usize = (3 & (col.lut >> (2 * n))) as usize;
-        dst[n] = p[cw];
+    let palette = p.map(|c| [c.r, c.g, c.b, c.a]);
+    let pixels = simd::select_pixels_16(&palette, col.lut);
+    for (d, pixel) in dst.iter_mut().zip(pixels.iter()) {
+        *d = Rgba {
+            r: pixel[0],
+            g: pixel[1],
+            b: pixel[2],
+            a: pixel[3],
+        };
     }
 }
 
-fn decode_bc3_alpha(dst: &mut [u8], source: &[u8], stride: usize, o: usize) {
+/// Decodes an ATC color block into 16 rgba pixels, row by row.
+///
+/// The block is laid out like a BC1 block, but the first color is RGB555
+/// and its top bit selects the palette: either the two colors and two
+/// blends like opaque BC1, or black, the first color minus a quarter of the
+/// second, and the two colors.
+pub fn decode_atc_color(source: &[u8]) -> [[u8; 4]; 16] {
+    let c0 = load_16(source);
+    let c1 = load_16(&source[2..]);
+    let lut = load_32(&source[4..]);
+
+    let expand_5 = |x: u16| ((x << 3) | (x >> 2)) as u8;
+    let p0 = [expand_5((c0 >> 10) & 0x1f), expand_5((c0 >> 5) & 0x1f), expand_5(c0 & 0x1f), 0xff];
+    let p1 = rgba_565(c1);
+
+    let palette = if c0 & 0x8000 == 0 {
+        let blend = |a: u8, b: u8| ((2 * a as u16 + b as u16) / 3) as u8;
+        [
+            p0,
+            [blend(p0[0], p1[0]), blend(p0[1], p1[1]), blend(p0[2], p1[2]), 0xff],
+            [blend(p1[0], p0[0]), blend(p1[1], p0[1]), blend(p1[2], p0[2]), 0xff],
+            p1,
+        ]
+    } else {
+        let sub = |a: u8, b: u8| a.saturating_sub(b / 4);
+        [[0, 0, 0, 0xff], [sub(p0[0], p1[0]), sub(p0[1], p1[1]), sub(p0[2], p1[2]), 0xff], p0, p1]
+    };
+    simd::select_pixels_16(&palette, lut)
+}
+
+pub fn decode_bc3_alpha(dst: &mut [u8], source: &[u8], stride: usize, o: usize) {
     let mut b = Bc3Alpha::default();
     b.load(source);
 
@@ -2262,28 +3819,70 @@ fn decode_bc3_alpha(dst: &mut [u8], source: &[u8], stride: usize, o: usize) {
     a[0] = a0 as u8;
     a[1] = a1 as u8;
     if a0 > a1 {
-        a[2] = ((6 * a0 + 1 * a1) / 7) as u8;
+        a[2] = ((6 * a0 + a1) / 7) as u8;
         a[3] = ((5 * a0 + 2 * a1) / 7) as u8;
         a[4] = ((4 * a0 + 3 * a1) / 7) as u8;
         a[5] = ((3 * a0 + 4 * a1) / 7) as u8;
         a[6] = ((2 * a0 + 5 * a1) / 7) as u8;
-        a[7] = ((1 * a0 + 6 * a1) / 7) as u8;
+        a[7] = ((a0 + 6 * a1) / 7) as u8;
     } else {
-        a[2] = ((4 * a0 + 1 * a1) / 5) as u8;
+        a[2] = ((4 * a0 + a1) / 5) as u8;
         a[3] = ((3 * a0 + 2 * a1) / 5) as u8;
         a[4] = ((2 * a0 + 3 * a1) / 5) as u8;
-        a[5] = ((1 * a0 + 4 * a1) / 5) as u8;
+        a[5] = ((a0 + 4 * a1) / 5) as u8;
         a[6] = 0;
         a[7] = 0xff;
     }
-    let lut: usize = (b.lut[0] as usize) | ((b.lut[1] as usize) << 8) | ((b.lut[2] as usize) << 16);
-    for n in 0..8 {
-        let aw: usize = 7 & (lut >> (3 * n));
-        dst[stride * n + o] = a[aw];
+    let mut lut = 0u64;
+    for (i, &byte) in b.lut.iter().enumerate() {
+        lut |= (byte as u64) << (8 * i);
+    }
+    let mut indices = [0u8; 16];
+    for (n, index) in indices.iter_mut().enumerate() {
+        *index = (7 & (lut >> (3 * n))) as u8;
+    }
+    for (n, &value) in simd::lookup_16(&a, &indices).iter().enumerate() {
+        dst[stride * n + o] = value;
+    }
+}
+
+/// Decodes a BC3 alpha block with signed endpoints, as used by the SNORM
+/// formats. The values are written as `mapping` stores them.
+fn decode_signed_alpha(dst: &mut [u8], source: &[u8], stride: usize, o: usize, mapping: SignedMapping) {
+    let (a, lut) = signed_alpha_values(source);
+    let a = a.map(|v| match mapping {
+        SignedMapping::Unorm => ((v + 127.0) * 255.0 / 254.0).round() as u8,
+        SignedMapping::Snorm => v.round() as i8 as u8,
+    });
+    for n in 0..16 {
+        dst[stride * n + o] = a[(7 & (lut >> (3 * n))) as usize];
     }
-    let lut: usize = (b.lut[3] as usize) | ((b.lut[4] as usize) << 8) | ((b.lut[5] as usize) << 16);
-    for n in 0..8 {
-        let aw: usize = 7 & (lut >> (3 * n));
-        dst[stride * (8 + n) + o] = a[aw];
+}
+
+/// Returns the eight values from -127 to 127 of a BC3 alpha block with
+/// signed endpoints, and its 3-bit indices.
+fn signed_alpha_values(source: &[u8]) -> ([f32; 8], u64) {
+    let mut b = Bc3Alpha::default();
+    b.load(source);
+
+    // -128 and -127 both stand for -1
+    let a0 = (b.a0 as i8).max(-127) as f32;
+    let a1 = (b.a1 as i8).max(-127) as f32;
+    let mut a = [a0, a1, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+    if b.a0 as i8 > b.a1 as i8 {
+        for (i, v) in a[2..].iter_mut().enumerate() {
+            let w = (i + 1) as f32;
+            *v = ((7.0 - w) * a0 + w * a1) / 7.0;
+        }
+    } else {
+        for (i, v) in a[2..6].iter_mut().enumerate() {
+            let w = (i + 1) as f32;
+            *v = ((5.0 - w) * a0 + w * a1) / 5.0;
+        }
+        a[6] = -127.0;
+        a[7] = 127.0;
     }
+
+    let lut = b.lut.iter().enumerate().fold(0u64, |lut, (i, &byte)| lut | (byte as u64) << (8 * i));
+    (a, lut)
 }
@@ -0,0 +1,197 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Builder for decode calls.
+
+use super::decode::{decode_into, decode_with_options};
+use super::{
+    BcnDecoderFormat, BcnEncoding, DecodeOptions, DecodeStrategy, Error, Lut3d, NormalMap, OutputLayout,
+    SignedMapping, SourceLayout, ValueTransform,
+};
+
+/// Decoder for images of one encoding, configured step by step.
+///
+/// Starts out with the RGBA format and the default `DecodeOptions`; every
+/// option of `decode_with_options` has a method of the same name, so new
+/// options do not need new function signatures.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::{BcnDecoder, BcnDecoderFormat, BcnEncoding, DecodeStrategy};
+///
+/// let block = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+/// let pixels = BcnDecoder::new(BcnEncoding::Bc1)
+///     .format(BcnDecoderFormat::BGRA)
+///     .strategy(DecodeStrategy::Serial)
+///     .decode(&block, 4, 4)
+///     .unwrap();
+/// assert_eq!(pixels, vec![0xff; 64]);
+/// ```
+#[derive(Copy, Clone)]
+pub struct BcnDecoder<'a> {
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: DecodeOptions<'a>,
+}
+
+impl<'a> BcnDecoder<'a> {
+    /// Creates a decoder for images of the given encoding.
+    pub fn new(encoding: BcnEncoding) -> BcnDecoder<'a> {
+        BcnDecoder {
+            encoding,
+            format: BcnDecoderFormat::RGBA,
+            options: DecodeOptions::default(),
+        }
+    }
+
+    /// Sets the pixel format of the output.
+    pub fn format(mut self, format: BcnDecoderFormat) -> BcnDecoder<'a> {
+        self.format = format;
+        self
+    }
+
+    /// Replaces all options at once.
+    pub fn options(mut self, options: DecodeOptions<'a>) -> BcnDecoder<'a> {
+        self.options = options;
+        self
+    }
+
+    /// Sets `DecodeOptions::strategy`.
+    pub fn strategy(mut self, strategy: DecodeStrategy) -> BcnDecoder<'a> {
+        self.options.strategy = strategy;
+        self
+    }
+
+    /// Sets `DecodeOptions::sanitize_floats`.
+    pub fn sanitize_floats(mut self, sanitize_floats: bool) -> BcnDecoder<'a> {
+        self.options.sanitize_floats = sanitize_floats;
+        self
+    }
+
+    /// Sets `DecodeOptions::layout`.
+    pub fn layout(mut self, layout: OutputLayout) -> BcnDecoder<'a> {
+        self.options.layout = layout;
+        self
+    }
+
+    /// Sets `DecodeOptions::lut`.
+    pub fn lut(mut self, lut: &'a Lut3d) -> BcnDecoder<'a> {
+        self.options.lut = Some(lut);
+        self
+    }
+
+    /// Sets `DecodeOptions::value_transform`.
+    pub fn value_transform(mut self, value_transform: ValueTransform<'a>) -> BcnDecoder<'a> {
+        self.options.value_transform = value_transform;
+        self
+    }
+
+    /// Sets `DecodeOptions::normal_map`.
+    pub fn normal_map(mut self, normal_map: NormalMap) -> BcnDecoder<'a> {
+        self.options.normal_map = normal_map;
+        self
+    }
+
+    /// Sets `DecodeOptions::swap_bc5_channels`.
+    pub fn swap_bc5_channels(mut self, swap_bc5_channels: bool) -> BcnDecoder<'a> {
+        self.options.swap_bc5_channels = swap_bc5_channels;
+        self
+    }
+
+    /// Sets `DecodeOptions::signed`.
+    pub fn signed(mut self, signed: bool) -> BcnDecoder<'a> {
+        self.options.signed = signed;
+        self
+    }
+
+    /// Sets `DecodeOptions::signed_mapping`.
+    pub fn signed_mapping(mut self, signed_mapping: SignedMapping) -> BcnDecoder<'a> {
+        self.options.signed_mapping = signed_mapping;
+        self
+    }
+
+    /// Sets `DecodeOptions::srgb`.
+    pub fn srgb(mut self, srgb: bool) -> BcnDecoder<'a> {
+        self.options.srgb = srgb;
+        self
+    }
+
+    /// Sets `DecodeOptions::row_pitch`.
+    pub fn row_pitch(mut self, row_pitch: usize) -> BcnDecoder<'a> {
+        self.options.row_pitch = row_pitch;
+        self
+    }
+
+    /// Sets `DecodeOptions::unpremultiply`.
+    pub fn unpremultiply(mut self, unpremultiply: bool) -> BcnDecoder<'a> {
+        self.options.unpremultiply = unpremultiply;
+        self
+    }
+
+    /// Sets `DecodeOptions::bc1_opaque`.
+    pub fn bc1_opaque(mut self, bc1_opaque: bool) -> BcnDecoder<'a> {
+        self.options.bc1_opaque = bc1_opaque;
+        self
+    }
+
+    /// Sets `DecodeOptions::flip_y`.
+    pub fn flip_y(mut self, flip_y: bool) -> BcnDecoder<'a> {
+        self.options.flip_y = Some(flip_y);
+        self
+    }
+
+    /// Sets `DecodeOptions::source_layout`.
+    pub fn source_layout(mut self, source_layout: SourceLayout) -> BcnDecoder<'a> {
+        self.options.source_layout = source_layout;
+        self
+    }
+
+    /// Sets `DecodeOptions::strict`.
+    pub fn strict(mut self, strict: bool) -> BcnDecoder<'a> {
+        self.options.strict = strict;
+        self
+    }
+
+    /// Decodes an image of the given size.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the data cannot be decoded with the configured
+    /// parameters.
+    pub fn decode(&self, source: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+        decode_with_options(source, width, height, self.encoding, self.format, &self.options)
+    }
+
+    /// Decodes an image of the given size into the start of `dst`, e.g. an
+    /// existing framebuffer.
+    ///
+    /// Only the pixels of the image are written: with a `row_pitch`, the
+    /// bytes between the rows are left as they are, so an image can be
+    /// decoded into a part of a larger one.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the data cannot be decoded with the configured
+    /// parameters or `dst` is too small for the decoded image.
+    pub fn decode_into(&self, source: &[u8], width: usize, height: usize, dst: &mut [u8]) -> Result<(), Error> {
+        decode_into(source, width, height, self.encoding, self.format, &self.options, dst)
+    }
+}
@@ -0,0 +1,89 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Block by block comparison of compressed images.
+
+use super::decode::{block_size, compressed_size, output_params};
+use super::{BcnDecoderFormat, BcnEncoding, Error, Rect};
+
+/// Compares two compressed images of the same size and encoding block by
+/// block and returns the pixels of the blocks that differ.
+///
+/// The blocks are compared as bytes, without decoding them, and returned
+/// row by row, top row first, cut to the image. A block missing from one
+/// image differs from any block of the other. Pass the result to
+/// `decode_rects` to decode just the differing blocks of either image.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the
+/// given parameters.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::{BcnDecoderFormat, BcnEncoding};
+///
+/// // two 8x4 BC1 images that differ in their second block
+/// let a = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0].repeat(2);
+/// let mut b = a.clone();
+/// b[8] = 0;
+///
+/// let rects = bcndecode::diff_blocks(&a, &b, 8, 4, BcnEncoding::Bc1).unwrap();
+/// assert_eq!(rects.len(), 1);
+/// assert_eq!((rects[0].x, rects[0].y), (4, 0));
+///
+/// let before = bcndecode::decode_rects(&a, 8, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, &rects).unwrap();
+/// let after = bcndecode::decode_rects(&b, 8, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, &rects).unwrap();
+/// assert_ne!(before, after);
+/// ```
+pub fn diff_blocks(
+    a: &[u8],
+    b: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+) -> Result<Vec<Rect>, Error> {
+    let format = match encoding {
+        BcnEncoding::Bc4 => BcnDecoderFormat::LUM,
+        _ => BcnDecoderFormat::RGBA,
+    };
+    output_params(width, height, encoding, format)?;
+
+    let block_size = block_size(encoding);
+    let blocks_per_row = width.div_ceil(4);
+    let size = compressed_size(width, height, encoding);
+    let (a, b) = (&a[..a.len().min(size)], &b[..b.len().min(size)]);
+
+    let mut rects = Vec::new();
+    for index in 0..size / block_size {
+        let range = index * block_size..(index + 1) * block_size;
+        if a.get(range.clone()) != b.get(range) {
+            let (x, y) = (4 * (index % blocks_per_row), 4 * (index / blocks_per_row));
+            rects.push(Rect {
+                x,
+                y,
+                width: (width - x).min(4),
+                height: (height - y).min(4),
+            });
+        }
+    }
+    Ok(rects)
+}
@@ -0,0 +1,95 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Analysis of repeated blocks in compressed images.
+
+use std::collections::HashMap;
+
+use super::decode::{block_size, compressed_size, output_params};
+use super::{BcnDecoderFormat, BcnEncoding, Error};
+
+/// Blocks of an image that occur more than once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateBlocks {
+    /// Number of blocks in the image
+    pub total: usize,
+    /// Number of distinct blocks; a container storing every distinct block
+    /// once saves `total - unique` blocks
+    pub unique: usize,
+    /// Every block that occurs more than once, the most frequent first
+    pub groups: Vec<DuplicateGroup>,
+}
+
+/// The occurrences of a repeated block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// Offset of the first occurrence in the compressed data
+    pub offset: usize,
+    /// Top left pixel of every occurrence, row by row, top row first
+    pub positions: Vec<(usize, usize)>,
+}
+
+/// Finds the blocks that occur more than once in a compressed image.
+///
+/// Blocks are compared as bytes, so blocks that decode to the same pixels
+/// but are encoded differently count as distinct. Blocks missing from
+/// `source` are left out.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the
+/// given parameters.
+pub fn find_duplicate_blocks(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+) -> Result<DuplicateBlocks, Error> {
+    let format = match encoding {
+        BcnEncoding::Bc4 => BcnDecoderFormat::LUM,
+        _ => BcnDecoderFormat::RGBA,
+    };
+    output_params(width, height, encoding, format)?;
+
+    let size = compressed_size(width, height, encoding).min(source.len());
+    let blocks_per_row = width.div_ceil(4);
+    let mut occurrences: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    let mut total = 0;
+    for (index, block) in source[..size].chunks_exact(block_size(encoding)).enumerate() {
+        occurrences.entry(block).or_default().push(index);
+        total += 1;
+    }
+
+    let unique = occurrences.len();
+    let mut groups: Vec<DuplicateGroup> = occurrences
+        .into_values()
+        .filter(|indices| indices.len() > 1)
+        .map(|indices| DuplicateGroup {
+            offset: indices[0] * block_size(encoding),
+            positions: indices
+                .iter()
+                .map(|index| (4 * (index % blocks_per_row), 4 * (index / blocks_per_row)))
+                .collect(),
+        })
+        .collect();
+    groups.sort_by(|a, b| b.positions.len().cmp(&a.positions.len()).then(a.offset.cmp(&b.offset)));
+
+    Ok(DuplicateBlocks { total, unique, groups })
+}
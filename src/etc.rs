@@ -0,0 +1,513 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Decoding of the ETC2 and EAC formats of OpenGL ES 3 and Vulkan.
+//!
+//! ETC2 and EAC store 4x4 blocks of 8 bytes like BC1 and BC4, but are
+//! otherwise unrelated to the BCn formats, so they are decoded by functions
+//! of their own. ETC1 data is valid ETC2 RGB8 data.
+
+use std::array;
+
+use super::decode::pixel_size;
+use super::raw::channel_positions;
+use super::{BcnDecoderFormat, BcnEncoding, Error};
+
+/// Intensity modifiers of the individual and differential modes, by table
+/// codeword. Pixel indices 2 and 3 negate the two values.
+const MODIFIER_TABLES: [[i16; 2]; 8] = [[2, 8], [5, 17], [9, 29], [13, 42], [18, 60], [24, 80], [33, 106], [47, 183]];
+
+/// Distances of the paint colors of the T and H modes.
+const DISTANCE_TABLE: [i16; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+/// Modifiers of the EAC formats, by table index and pixel index.
+const EAC_MODIFIER_TABLES: [[i8; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+/// ETC format of the source data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EtcFormat {
+    /// ETC2 RGB, opaque (`GL_COMPRESSED_RGB8_ETC2`,
+    /// `VK_FORMAT_ETC2_R8G8B8_UNORM_BLOCK`), which includes ETC1
+    Etc2Rgb8,
+    /// ETC2 RGB with punch-through alpha, where the bit that selects the
+    /// differential mode in `Etc2Rgb8` tells whether the block is opaque
+    /// instead (`GL_COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2`,
+    /// `VK_FORMAT_ETC2_R8G8B8A1_UNORM_BLOCK`)
+    Etc2Rgb8A1,
+    /// ETC2 RGB with an 8-bit EAC alpha block in front of every color block
+    /// (`GL_COMPRESSED_RGBA8_ETC2_EAC`, `VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK`)
+    Etc2Rgba8,
+    /// EAC single channel of 11 bits (`GL_COMPRESSED_R11_EAC`,
+    /// `VK_FORMAT_EAC_R11_UNORM_BLOCK`)
+    EacR11,
+    /// EAC single channel of 11 bits, signed (`GL_COMPRESSED_SIGNED_R11_EAC`,
+    /// `VK_FORMAT_EAC_R11_SNORM_BLOCK`)
+    EacR11Signed,
+    /// Two EAC channels of 11 bits, red and green (`GL_COMPRESSED_RG11_EAC`,
+    /// `VK_FORMAT_EAC_R11G11_UNORM_BLOCK`)
+    EacRg11,
+    /// Two EAC channels of 11 bits, signed
+    /// (`GL_COMPRESSED_SIGNED_RG11_EAC`, `VK_FORMAT_EAC_R11G11_SNORM_BLOCK`)
+    EacRg11Signed,
+}
+
+impl EtcFormat {
+    /// Returns the number of bytes of a 4x4 block.
+    pub fn block_size(self) -> usize {
+        match self {
+            EtcFormat::Etc2Rgb8 | EtcFormat::Etc2Rgb8A1 => 8,
+            EtcFormat::Etc2Rgba8 => 16,
+            EtcFormat::EacR11 | EtcFormat::EacR11Signed => 8,
+            EtcFormat::EacRg11 | EtcFormat::EacRg11Signed => 16,
+        }
+    }
+
+    /// Returns the number of EAC channels, or 0 for the ETC2 formats.
+    fn eac_channels(self) -> usize {
+        match self {
+            EtcFormat::EacR11 | EtcFormat::EacR11Signed => 1,
+            EtcFormat::EacRg11 | EtcFormat::EacRg11Signed => 2,
+            _ => 0,
+        }
+    }
+
+    fn is_signed(self) -> bool {
+        matches!(self, EtcFormat::EacR11Signed | EtcFormat::EacRg11Signed)
+    }
+}
+
+/// Decodes ETC data into the given output format.
+///
+/// Transparent pixels of `Etc2Rgb8A1` are black with an alpha of 0, the
+/// other pixels are opaque; `Etc2Rgba8` pixels take the alpha of their own
+/// block. R11 values are rounded to 8 bits and, like BC4,
+/// stored as they are in the LUM format or repeated in red, green and blue
+/// in the color formats. RG11 values are stored in red and green like BC5,
+/// with blue 0 and an opaque alpha; use `decode_eac_16` for the two
+/// channels alone. Signed values from -1 to 1 are mapped to 0 to 255. The
+/// LUM format is only accepted for R11. Rows are tightly packed,
+/// top row first, and are never flipped.
+///
+/// # Errors
+///
+/// This function will return an error if the size of the image is 0,
+/// `source` is too short for the image or the output format is LUM for
+/// other formats than R11.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::{BcnDecoderFormat, EtcFormat};
+///
+/// // a differential mode block of red with the pixel index 2 everywhere,
+/// // which is transparent in punch-through blocks without the opaque bit
+/// let block = [0xf8, 0, 0, 0x02, 0xff, 0xff, 0, 0];
+/// let pixels = bcndecode::decode_etc(&block, 4, 4, EtcFormat::Etc2Rgb8A1, BcnDecoderFormat::RGBA).unwrap();
+/// assert_eq!(pixels[..4], [0xfd, 0, 0, 0xff]);
+///
+/// let block = [0xf8, 0, 0, 0, 0xff, 0xff, 0, 0];
+/// let pixels = bcndecode::decode_etc(&block, 4, 4, EtcFormat::Etc2Rgb8A1, BcnDecoderFormat::RGBA).unwrap();
+/// assert_eq!(pixels[..4], [0, 0, 0, 0]);
+/// ```
+pub fn decode_etc(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    etc_format: EtcFormat,
+    format: BcnDecoderFormat,
+) -> Result<Vec<u8>, Error> {
+    if format == BcnDecoderFormat::LUM && etc_format.eac_channels() != 1 {
+        return Err(Error::InvalidPixelFormat);
+    }
+    let blocks = image_blocks(source, width, height, etc_format.block_size())?;
+
+    // the size of the 8-bit formats does not depend on the encoding
+    let size = pixel_size(BcnEncoding::Bc1, format);
+    let positions = channel_positions(format);
+    let mut pixels = vec![0; size * width * height];
+    for (index, block) in blocks.enumerate() {
+        let decoded = match etc_format {
+            EtcFormat::Etc2Rgb8 => decode_etc2_block(block, false),
+            EtcFormat::Etc2Rgb8A1 => decode_etc2_block(block, true),
+            EtcFormat::Etc2Rgba8 => {
+                let mut pixels = decode_etc2_block(&block[8..], false);
+                for (pixel, alpha) in pixels.iter_mut().zip(decode_eac_alpha(&block[..8])) {
+                    pixel[3] = alpha;
+                }
+                pixels
+            }
+            EtcFormat::EacR11 | EtcFormat::EacR11Signed => {
+                let values = decode_eac_block(block, etc_format.is_signed());
+                values.map(|v| {
+                    let l = to_8_bits(v, etc_format.is_signed());
+                    [l, l, l, 0xff]
+                })
+            }
+            EtcFormat::EacRg11 | EtcFormat::EacRg11Signed => {
+                let red = decode_eac_block(&block[..8], etc_format.is_signed());
+                let green = decode_eac_block(&block[8..], etc_format.is_signed());
+                array::from_fn(|n| {
+                    let to_8_bits = |v| to_8_bits(v, etc_format.is_signed());
+                    [to_8_bits(red[n]), to_8_bits(green[n]), 0, 0xff]
+                })
+            }
+        };
+        write_block(&mut pixels, index, width, height, size, |dst, n| {
+            store_pixel(dst, decoded[n], format, positions)
+        });
+    }
+    Ok(pixels)
+}
+
+/// Decodes EAC data into 16-bit values, one per channel and pixel: red,
+/// or red and green.
+///
+/// Unsigned values are extended from 11 to 16 bits, signed values from -1
+/// to 1 are mapped to 0 to 65535. Rows are tightly packed, top row first,
+/// and are never flipped.
+///
+/// # Errors
+///
+/// This function will return an error if the size of the image is 0,
+/// `source` is too short for the image or the format is not an EAC format.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::EtcFormat;
+///
+/// // the largest base value plus the largest modifier of table 13
+/// let block = [0xff, 0x0d, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+/// let values = bcndecode::decode_eac_16(&block, 4, 4, EtcFormat::EacR11).unwrap();
+/// assert_eq!(values[0], 0xffff);
+/// ```
+pub fn decode_eac_16(source: &[u8], width: usize, height: usize, etc_format: EtcFormat) -> Result<Vec<u16>, Error> {
+    let channels = etc_format.eac_channels();
+    if channels == 0 {
+        return Err(Error::InvalidPixelFormat);
+    }
+    let blocks = image_blocks(source, width, height, etc_format.block_size())?;
+
+    let mut values = vec![0; channels * width * height];
+    for (index, block) in blocks.enumerate() {
+        let mut decoded = [[0; 16]; 2];
+        for (channel, b) in decoded.iter_mut().zip(block.chunks_exact(8)) {
+            *channel = decode_eac_block(b, etc_format.is_signed());
+        }
+        write_block(&mut values, index, width, height, channels, |dst, n| {
+            for (value, channel) in dst.iter_mut().zip(&decoded) {
+                *value = to_16_bits(channel[n], etc_format.is_signed());
+            }
+        });
+    }
+    Ok(values)
+}
+
+/// Checks that the image fits in `source` and returns its blocks.
+pub fn image_blocks(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    block_size: usize,
+) -> Result<impl Iterator<Item = &[u8]>, Error> {
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidImageSize);
+    }
+    let blocks = width
+        .div_ceil(4)
+        .checked_mul(height.div_ceil(4))
+        .filter(|&blocks| blocks <= source.len() / block_size)
+        .ok_or(Error::InvalidImageSize)?;
+    Ok(source.chunks_exact(block_size).take(blocks))
+}
+
+/// Calls `write` with the output of every pixel of the block with the
+/// given index that lies inside the image, and the index of the pixel in
+/// the block.
+pub fn write_block<T, F: FnMut(&mut [T], usize)>(
+    output: &mut [T],
+    index: usize,
+    width: usize,
+    height: usize,
+    size: usize,
+    mut write: F,
+) {
+    let blocks_per_row = width.div_ceil(4);
+    let (bx, by) = (4 * (index % blocks_per_row), 4 * (index / blocks_per_row));
+    for y in 0..4.min(height - by) {
+        for x in 0..4.min(width - bx) {
+            let offset = size * ((by + y) * width + bx + x);
+            write(&mut output[offset..offset + size], 4 * y + x);
+        }
+    }
+}
+
+/// Stores an rgba pixel in the given 8-bit output format; LUM takes red.
+pub fn store_pixel(dst: &mut [u8], rgba: [u8; 4], format: BcnDecoderFormat, positions: [usize; 4]) {
+    match format {
+        BcnDecoderFormat::LUM => dst[0] = rgba[0],
+        BcnDecoderFormat::Custom(swizzle) => dst.copy_from_slice(&swizzle.apply(rgba)),
+        _ => {
+            for (c, &position) in positions.iter().enumerate().take(dst.len()) {
+                dst[position] = rgba[c];
+            }
+        }
+    }
+}
+
+/// Decodes an ETC2 RGB block into 16 rgba pixels, row by row.
+fn decode_etc2_block(block: &[u8], punchthrough: bool) -> [[u8; 4]; 16] {
+    let indices = u32::from_be_bytes([block[4], block[5], block[6], block[7]]);
+    // with punch-through alpha, the differential bit tells whether the
+    // block is opaque, and the individual mode does not exist
+    let differential = punchthrough || block[3] & 0x02 != 0;
+    let opaque = !punchthrough || block[3] & 0x02 != 0;
+
+    let r = (block[0] >> 3) as i16 + delta(block[0]);
+    let g = (block[1] >> 3) as i16 + delta(block[1]);
+    let b = (block[2] >> 3) as i16 + delta(block[2]);
+    let mode = if !differential {
+        Mode::Individual
+    } else if !(0..32).contains(&r) {
+        Mode::T
+    } else if !(0..32).contains(&g) {
+        Mode::H
+    } else if !(0..32).contains(&b) {
+        Mode::Planar
+    } else {
+        Mode::Differential
+    };
+
+    let mut pixels = [[0; 4]; 16];
+    if mode == Mode::Planar {
+        // planar blocks are always opaque
+        let o = [
+            extend_6(block[0] >> 1),
+            extend_7((block[0] & 0x01) << 6 | (block[1] >> 1) & 0x3f),
+            extend_6((block[1] & 0x01) << 5 | (block[2] & 0x18) | (block[2] & 0x03) << 1 | block[3] >> 7),
+        ];
+        let h = [
+            extend_6((block[3] >> 1) & 0x3e | (block[3] & 0x01)),
+            extend_7(block[4] >> 1),
+            extend_6((block[4] & 0x01) << 5 | block[5] >> 3),
+        ];
+        let v = [
+            extend_6((block[5] & 0x07) << 3 | block[6] >> 5),
+            extend_7((block[6] & 0x1f) << 2 | block[7] >> 6),
+            extend_6(block[7] & 0x3f),
+        ];
+        for (n, pixel) in pixels.iter_mut().enumerate() {
+            let (x, y) = ((n % 4) as i32, (n / 4) as i32);
+            for c in 0..3 {
+                let (o, h, v) = (o[c] as i32, h[c] as i32, v[c] as i32);
+                pixel[c] = ((x * (h - o) + y * (v - o) + 4 * o + 2) >> 2).clamp(0, 255) as u8;
+            }
+            pixel[3] = 0xff;
+        }
+        return pixels;
+    }
+
+    let paint_colors = match mode {
+        Mode::T => {
+            let c0 = [
+                extend_4((block[0] >> 1) & 0x0c | (block[0] & 0x03)),
+                extend_4(block[1] >> 4),
+                extend_4(block[1] & 0x0f),
+            ];
+            let c1 = [extend_4(block[2] >> 4), extend_4(block[2] & 0x0f), extend_4(block[3] >> 4)];
+            let d = DISTANCE_TABLE[((block[3] >> 1) & 0x06 | (block[3] & 0x01)) as usize];
+            Some([c0, offset_color(c1, d), c1, offset_color(c1, -d)])
+        }
+        Mode::H => {
+            let c0 = [
+                (block[0] >> 3) & 0x0f,
+                (block[0] & 0x07) << 1 | (block[1] >> 4) & 0x01,
+                (block[1] & 0x08) | (block[1] & 0x03) << 1 | block[2] >> 7,
+            ];
+            let c1 = [(block[2] >> 3) & 0x0f, (block[2] & 0x07) << 1 | block[3] >> 7, (block[3] >> 3) & 0x0f];
+            // the order of the two colors holds the lowest bit of the
+            // distance index
+            let index = (block[3] & 0x04) | (block[3] & 0x01) << 1 | (c0 >= c1) as u8;
+            let d = DISTANCE_TABLE[index as usize];
+            let (c0, c1) = (c0.map(extend_4), c1.map(extend_4));
+            Some([offset_color(c0, d), offset_color(c0, -d), offset_color(c1, d), offset_color(c1, -d)])
+        }
+        _ => None,
+    };
+
+    let base_colors = if mode == Mode::Individual {
+        [
+            [extend_4(block[0] >> 4), extend_4(block[1] >> 4), extend_4(block[2] >> 4)],
+            [extend_4(block[0] & 0x0f), extend_4(block[1] & 0x0f), extend_4(block[2] & 0x0f)],
+        ]
+    } else {
+        [
+            [extend_5(block[0] >> 3), extend_5(block[1] >> 3), extend_5(block[2] >> 3)],
+            [extend_5(r as u8), extend_5(g as u8), extend_5(b as u8)],
+        ]
+    };
+    let tables = [MODIFIER_TABLES[(block[3] >> 5) as usize], MODIFIER_TABLES[((block[3] >> 2) & 0x07) as usize]];
+    let flip = block[3] & 0x01 != 0;
+
+    for (n, pixel) in pixels.iter_mut().enumerate() {
+        let (x, y) = (n % 4, n / 4);
+        // pixels are numbered column by column
+        let i = 4 * x + y;
+        let index = ((indices >> (15 + i)) & 0x02 | (indices >> i) & 0x01) as usize;
+        if !opaque && index == 2 {
+            // transparent black
+            continue;
+        }
+
+        let color = match paint_colors {
+            Some(paint_colors) => paint_colors[index],
+            None => {
+                let subblock = if flip { y / 2 } else { x / 2 };
+                let modifier = match index {
+                    // without an opaque bit, the smaller modifier is 0
+                    0 if !opaque => 0,
+                    0 | 2 => tables[subblock][0],
+                    _ => tables[subblock][1],
+                };
+                let modifier = if index >= 2 { -modifier } else { modifier };
+                offset_color(base_colors[subblock], modifier)
+            }
+        };
+        *pixel = [color[0], color[1], color[2], 0xff];
+    }
+    pixels
+}
+
+/// Decodes an EAC block into 16 values, row by row: from 0 to 2047, or
+/// from -1023 to 1023 if signed.
+fn decode_eac_block(block: &[u8], signed: bool) -> [i16; 16] {
+    let bits = u64::from_be_bytes([block[0], block[1], block[2], block[3], block[4], block[5], block[6], block[7]]);
+    let multiplier = (block[1] >> 4) as i16;
+    let table = EAC_MODIFIER_TABLES[(block[1] & 0x0f) as usize];
+    let (base, min, max) = if signed {
+        // -128 and -127 both stand for -1
+        ((block[0] as i8).max(-127) as i16 * 8, -1023, 1023)
+    } else {
+        (block[0] as i16 * 8 + 4, 0, 2047)
+    };
+
+    let mut values = [0; 16];
+    for (n, value) in values.iter_mut().enumerate() {
+        // pixels are numbered column by column, from the highest bits down
+        let i = 4 * (n % 4) + n / 4;
+        let modifier = table[((bits >> (45 - 3 * i)) & 0x07) as usize] as i16;
+        // a multiplier of 0 leaves the modifier as it is, at 1/8 of the
+        // scale of the others
+        let modifier = if multiplier == 0 { modifier } else { modifier * multiplier * 8 };
+        *value = (base + modifier).clamp(min, max);
+    }
+    values
+}
+
+/// Decodes the 8-bit alpha block of `Etc2Rgba8` into 16 values, row by
+/// row. It is laid out like an 11-bit EAC block, but its values are not
+/// scaled.
+fn decode_eac_alpha(block: &[u8]) -> [u8; 16] {
+    let bits = u64::from_be_bytes([block[0], block[1], block[2], block[3], block[4], block[5], block[6], block[7]]);
+    let multiplier = (block[1] >> 4) as i16;
+    let table = EAC_MODIFIER_TABLES[(block[1] & 0x0f) as usize];
+    array::from_fn(|n| {
+        // pixels are numbered column by column, from the highest bits down
+        let i = 4 * (n % 4) + n / 4;
+        let modifier = table[((bits >> (45 - 3 * i)) & 0x07) as usize] as i16;
+        (block[0] as i16 + modifier * multiplier).clamp(0, 255) as u8
+    })
+}
+
+/// Rounds an 11-bit EAC value to 8 bits.
+fn to_8_bits(value: i16, signed: bool) -> u8 {
+    let value = value as i32;
+    if signed {
+        (((value + 1023) * 255 + 1023) / 2046) as u8
+    } else {
+        ((value * 255 + 1023) / 2047) as u8
+    }
+}
+
+/// Extends an 11-bit EAC value to 16 bits.
+fn to_16_bits(value: i16, signed: bool) -> u16 {
+    let value = value as i32;
+    if signed {
+        (((value + 1023) * 65535 + 1023) / 2046) as u16
+    } else {
+        ((value << 5) | (value >> 6)) as u16
+    }
+}
+
+#[derive(PartialEq, Eq)]
+enum Mode {
+    Individual,
+    Differential,
+    T,
+    H,
+    Planar,
+}
+
+/// Returns the signed 3-bit difference of the differential mode from the
+/// lowest bits of a byte.
+fn delta(x: u8) -> i16 {
+    (((x & 0x07) << 5) as i8 >> 5) as i16
+}
+
+fn offset_color(color: [u8; 3], offset: i16) -> [u8; 3] {
+    color.map(|c| (c as i16 + offset).clamp(0, 255) as u8)
+}
+
+fn extend_4(x: u8) -> u8 {
+    (x & 0x0f) * 0x11
+}
+
+fn extend_5(x: u8) -> u8 {
+    let x = x & 0x1f;
+    (x << 3) | (x >> 2)
+}
+
+fn extend_6(x: u8) -> u8 {
+    let x = x & 0x3f;
+    (x << 2) | (x >> 4)
+}
+
+fn extend_7(x: u8) -> u8 {
+    let x = x & 0x7f;
+    (x << 1) | (x >> 6)
+}
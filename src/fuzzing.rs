@@ -0,0 +1,213 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! `Arbitrary` implementations of the decode parameters, for fuzzing
+//! decoders and the loaders built on them.
+//!
+//! Sizes and thread counts are kept small, so that fuzz inputs do not spend
+//! their time allocating huge buffers or spawning threads. Parameters that
+//! `decode_with_options` rejects are generated as well, to exercise the
+//! error paths.
+//!
+//! The module is only compiled when the `arbitrary` feature is enabled.
+
+use std::convert::TryInto;
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use super::{
+    BcnDecoderFormat, BcnEncoding, Channel, DecodeOptions, DecodeStrategy, GnfTileMode, Lut3d, NormalMap, OutputLayout,
+    SignedMapping, SourceLayout, Swizzle, ValueTransform,
+};
+
+/// Largest number of threads of a generated `DecodeStrategy::Threaded`.
+const MAX_THREADS: usize = 8;
+
+/// Largest size of a generated `OutputLayout::Tiled`.
+const MAX_TILE_SIZE: usize = 256;
+
+/// Largest number of entries per axis of a generated `Lut3d`.
+const MAX_LUT_SIZE: usize = 8;
+
+impl<'a> Arbitrary<'a> for BcnEncoding {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(*u.choose(&[
+            BcnEncoding::Bc1,
+            BcnEncoding::Bc2,
+            BcnEncoding::Bc3,
+            BcnEncoding::Bc4,
+            BcnEncoding::Bc5,
+            BcnEncoding::Bc6H,
+            BcnEncoding::Bc7,
+        ])?)
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, Some(4))
+    }
+}
+
+impl<'a> Arbitrary<'a> for BcnDecoderFormat {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        if u.ratio(1, 8)? {
+            return Ok(BcnDecoderFormat::Custom(Swizzle(u.arbitrary()?)));
+        }
+        Ok(*u.choose(&[
+            BcnDecoderFormat::RGBA,
+            BcnDecoderFormat::BGRA,
+            BcnDecoderFormat::ARGB,
+            BcnDecoderFormat::ABGR,
+            BcnDecoderFormat::LUM,
+            BcnDecoderFormat::RGB,
+            BcnDecoderFormat::BGR,
+        ])?)
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, None)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Channel {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(*u.choose(&[
+            Channel::Red,
+            Channel::Green,
+            Channel::Blue,
+            Channel::Alpha,
+            Channel::Zero,
+            Channel::One,
+        ])?)
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, Some(4))
+    }
+}
+
+impl<'a> Arbitrary<'a> for DecodeStrategy {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=2)? {
+            0 => DecodeStrategy::Auto,
+            1 => DecodeStrategy::Serial,
+            _ => DecodeStrategy::Threaded(u.int_in_range(0..=MAX_THREADS)?),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for OutputLayout {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(if u.arbitrary()? {
+            OutputLayout::Linear
+        } else {
+            // includes sizes that are not a multiple of 4
+            OutputLayout::Tiled(u.int_in_range(0..=MAX_TILE_SIZE)?)
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SourceLayout {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=3)? {
+            0 => SourceLayout::Linear,
+            // includes unsupported block heights
+            1 => SourceLayout::TegraBlockLinear(u.int_in_range(0..=64)?),
+            2 => SourceLayout::GnfTiled(u.arbitrary()?),
+            _ => SourceLayout::Morton,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for GnfTileMode {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(*u.choose(&[GnfTileMode::LinearAligned, GnfTileMode::Display1dThin, GnfTileMode::Thin1dThin])?)
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, Some(4))
+    }
+}
+
+impl<'a> Arbitrary<'a> for ValueTransform<'a> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=2)? {
+            0 => ValueTransform::Identity,
+            1 => ValueTransform::Invert,
+            _ => ValueTransform::Curve(u.bytes(256)?.try_into().unwrap()),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for NormalMap {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(*u.choose(&[NormalMap::Raw, NormalMap::Reconstructed, NormalMap::Octahedral])?)
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, Some(4))
+    }
+}
+
+impl<'a> Arbitrary<'a> for SignedMapping {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(*u.choose(&[SignedMapping::Unorm, SignedMapping::Snorm])?)
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, Some(4))
+    }
+}
+
+/// Generates tables over the domain 0 to 1 with any values, including
+/// non-finite ones.
+impl<'a> Arbitrary<'a> for Lut3d {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let size = u.int_in_range(2..=MAX_LUT_SIZE)?;
+        let table = (0..size * size * size).map(|_| u.arbitrary()).collect::<Result<_>>()?;
+        Ok(Lut3d::new(size, table).unwrap())
+    }
+}
+
+/// Generates options without a `lut`, as a reference to a table cannot be
+/// made up from the input; set one from an arbitrary `Lut3d` if needed.
+impl<'a> Arbitrary<'a> for DecodeOptions<'a> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(DecodeOptions {
+            strategy: u.arbitrary()?,
+            sanitize_floats: u.arbitrary()?,
+            layout: u.arbitrary()?,
+            source_layout: u.arbitrary()?,
+            lut: None,
+            value_transform: u.arbitrary()?,
+            normal_map: u.arbitrary()?,
+            swap_bc5_channels: u.arbitrary()?,
+            signed: u.arbitrary()?,
+            signed_mapping: u.arbitrary()?,
+            srgb: u.arbitrary()?,
+            unpremultiply: u.arbitrary()?,
+            bc1_opaque: u.arbitrary()?,
+            flip_y: u.arbitrary()?,
+            // large enough for padded rows of the images decoded while
+            // fuzzing, without huge allocations
+            row_pitch: u.int_in_range(0..=4096)?,
+            strict: u.arbitrary()?,
+        })
+    }
+}
@@ -0,0 +1,152 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Detiling of the tile modes of PS4 (GNF) textures.
+//!
+//! The GPU of the PS4 stores every level in one of the GCN tile modes. The
+//! modes supported here arrange the blocks of an image, padded to a multiple
+//! of 8 blocks horizontally, either in rows or in micro tiles of 8x8 blocks.
+//! The 2D modes, which also spread the micro tiles over banks and pipes, are
+//! not supported.
+
+use super::decode::block_size;
+use super::{BcnEncoding, Error};
+
+/// Width and height of a micro tile in blocks
+const MICRO_TILE_SIZE: usize = 8;
+
+/// Tile mode of a GNF texture, as stored in its `sce::Gnm::TileMode`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum GnfTileMode {
+    /// `kTileModeDisplay_LinearAligned`: rows of blocks, each one padded to
+    /// a multiple of 8 blocks.
+    LinearAligned,
+    /// `kTileModeDisplay_1dThin`: micro tiles in the display order, which
+    /// depends on the size of a block.
+    Display1dThin,
+    /// `kTileModeThin_1dThin`: micro tiles in Z-order. Most compressed
+    /// textures use this mode.
+    Thin1dThin,
+}
+
+impl GnfTileMode {
+    /// Looks up the tile mode from its value in the texture header.
+    /// Returns `None` for modes that are not supported.
+    pub fn from_raw(tile_mode: u32) -> Option<GnfTileMode> {
+        match tile_mode {
+            8 => Some(GnfTileMode::LinearAligned),
+            9 => Some(GnfTileMode::Display1dThin),
+            13 => Some(GnfTileMode::Thin1dThin),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the tile mode in the texture header.
+    pub fn raw(&self) -> u32 {
+        match *self {
+            GnfTileMode::LinearAligned => 8,
+            GnfTileMode::Display1dThin => 9,
+            GnfTileMode::Thin1dThin => 13,
+        }
+    }
+}
+
+/// Reorders a level of a PS4 texture with the given tile mode into rows of
+/// blocks.
+///
+/// The level is padded to a multiple of 8 blocks horizontally, and for the
+/// tiled modes vertically; the result holds exactly the blocks of the image.
+///
+/// # Errors
+///
+/// Returns `Error::InvalidImageSize` if the size of the image is 0 or
+/// `source` is shorter than the padded level.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::{BcnEncoding, GnfTileMode};
+///
+/// // a 16x8 BC1 image takes a single micro tile, in which the third block
+/// // of the first row comes fifth
+/// let mut source = vec![0; 512];
+/// source[32] = 1;
+/// let linear = bcndecode::detile_gnf(&source, 16, 8, BcnEncoding::Bc1, GnfTileMode::Thin1dThin).unwrap();
+/// assert_eq!(linear.len(), 64);
+/// assert_eq!(linear[16], 1);
+/// ```
+pub fn detile_gnf(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    tile_mode: GnfTileMode,
+) -> Result<Vec<u8>, Error> {
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidImageSize);
+    }
+    let bytes_per_block = block_size(encoding);
+    let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+    let pitch = blocks_x.div_ceil(MICRO_TILE_SIZE) * MICRO_TILE_SIZE;
+    let rows = match tile_mode {
+        GnfTileMode::LinearAligned => blocks_y,
+        GnfTileMode::Display1dThin | GnfTileMode::Thin1dThin => {
+            blocks_y.div_ceil(MICRO_TILE_SIZE) * MICRO_TILE_SIZE
+        }
+    };
+    let size = pitch
+        .checked_mul(rows)
+        .and_then(|blocks| blocks.checked_mul(bytes_per_block))
+        .ok_or(Error::InvalidImageSize)?;
+    if source.len() < size {
+        return Err(Error::InvalidImageSize);
+    }
+
+    let mut linear = Vec::with_capacity(blocks_x * blocks_y * bytes_per_block);
+    for y in 0..blocks_y {
+        for x in 0..blocks_x {
+            let index = match tile_mode {
+                GnfTileMode::LinearAligned => y * pitch + x,
+                GnfTileMode::Display1dThin | GnfTileMode::Thin1dThin => {
+                    let tile = (y / MICRO_TILE_SIZE) * (pitch / MICRO_TILE_SIZE) + x / MICRO_TILE_SIZE;
+                    let element = element_index(x % MICRO_TILE_SIZE, y % MICRO_TILE_SIZE, tile_mode, bytes_per_block);
+                    tile * MICRO_TILE_SIZE * MICRO_TILE_SIZE + element
+                }
+            };
+            let offset = index * bytes_per_block;
+            linear.extend_from_slice(&source[offset..offset + bytes_per_block]);
+        }
+    }
+    Ok(linear)
+}
+
+/// Returns the index of the block at `x`, `y` within a micro tile.
+fn element_index(x: usize, y: usize, tile_mode: GnfTileMode, bytes_per_block: usize) -> usize {
+    let bit = |v: usize, n: usize| (v >> n) & 1;
+    // the bits of the index from the lowest one
+    let bits = match (tile_mode, bytes_per_block) {
+        (GnfTileMode::Display1dThin, 8) => [bit(x, 0), bit(y, 0), bit(x, 1), bit(x, 2), bit(y, 1), bit(y, 2)],
+        (GnfTileMode::Display1dThin, _) => [bit(y, 0), bit(x, 0), bit(x, 1), bit(x, 2), bit(y, 1), bit(y, 2)],
+        _ => [bit(x, 0), bit(y, 0), bit(x, 1), bit(y, 1), bit(x, 2), bit(y, 2)],
+    };
+    bits.iter().enumerate().fold(0, |index, (n, &b)| index | b << n)
+}
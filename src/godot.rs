@@ -0,0 +1,302 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Parser for Godot compressed texture resources.
+//!
+//! Both the Godot 3 StreamTexture format (.stex, magic `GDST`) and the
+//! Godot 4 CompressedTexture2D format (.ctex, magic `GST2`) are supported.
+//! Only textures stored as raw VRAM-compressed images can be decoded;
+//! textures stored as PNG, WebP or Basis Universal payloads are rejected
+//! with `Error::FeatureNotImplemented`.
+
+use std::ops::Range;
+
+use super::decode::compressed_size;
+use super::range::mip_byte_range;
+use super::reader::{read_u16, read_u32};
+use super::{decode_with_options, BcnDecoderFormat, BcnEncoding, DecodeOptions, Error, MipLevel};
+
+// Godot 3 StreamTexture data format bits
+const V3_FORMAT_MASK_IMAGE_FORMAT: u32 = (1 << 20) - 1;
+const V3_FORMAT_BIT_LOSSLESS: u32 = 1 << 20;
+const V3_FORMAT_BIT_LOSSY: u32 = 1 << 21;
+const V3_FORMAT_BIT_STREAM: u32 = 1 << 22;
+const V3_FORMAT_BIT_HAS_MIPMAPS: u32 = 1 << 23;
+
+// Godot 4 CompressedTexture2D data formats
+const V4_DATA_FORMAT_IMAGE: u32 = 0;
+
+// Image::Format values shared by Godot 3 and 4
+const IMAGE_FORMAT_DXT1: u32 = 17;
+const IMAGE_FORMAT_DXT3: u32 = 18;
+const IMAGE_FORMAT_DXT5: u32 = 19;
+const IMAGE_FORMAT_RGTC_R: u32 = 20;
+const IMAGE_FORMAT_RGTC_RG: u32 = 21;
+const IMAGE_FORMAT_BPTC_RGBA: u32 = 22;
+const IMAGE_FORMAT_BPTC_RGBF: u32 = 23;
+const IMAGE_FORMAT_BPTC_RGBFU: u32 = 24;
+
+/// Version of the Godot texture container.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum GodotTextureVersion {
+    /// Godot 3 StreamTexture (.stex)
+    Stex,
+    /// Godot 4 CompressedTexture2D (.ctex)
+    Ctex,
+}
+
+/// A parsed Godot compressed texture.
+#[derive(Debug)]
+pub struct GodotTexture<'a> {
+    /// Container version the texture was read from.
+    pub version: GodotTextureVersion,
+    /// Width of the base level in pixels
+    pub width: usize,
+    /// Height of the base level in pixels
+    pub height: usize,
+    /// The raw Godot `Image::Format` value of the stored data
+    pub image_format: u32,
+    /// Number of mip levels the header declares, including the base level
+    pub mip_count: usize,
+    /// The mip levels present in the file, largest first. Files that are
+    /// cut short hold fewer than `mip_count`.
+    pub mipmaps: Vec<MipLevel<'a>>,
+}
+
+impl<'a> GodotTexture<'a> {
+    /// Returns the encoding of the stored image data, if it can be decoded.
+    pub fn encoding(&self) -> Option<BcnEncoding> {
+        image_format_encoding(self.image_format)
+    }
+
+    /// Returns whether the stored data holds signed values, as the
+    /// `BPTC_RGBF` format does.
+    pub fn signed(&self) -> bool {
+        self.image_format == IMAGE_FORMAT_BPTC_RGBF
+    }
+
+    /// Decodes the given mip level of the texture. Signed data is decoded
+    /// with `DecodeOptions::signed`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::FeatureNotImplemented` if the stored format cannot be
+    /// decoded, `Error::InvalidImageSize` if the level does not exist, or any
+    /// error returned by `decode_with_options`.
+    pub fn decode(&self, level: usize, format: BcnDecoderFormat) -> Result<Vec<u8>, Error> {
+        let encoding = self.encoding().ok_or(Error::FeatureNotImplemented)?;
+        let mip = self.mipmaps.get(level).ok_or(Error::InvalidImageSize)?;
+        let options = DecodeOptions {
+            signed: self.signed(),
+            ..Default::default()
+        };
+        decode_with_options(mip.data, mip.width, mip.height, encoding, format, &options)
+    }
+}
+
+/// Returns the encoding of a Godot `Image::Format` value, if it is supported.
+pub fn image_format_encoding(image_format: u32) -> Option<BcnEncoding> {
+    match image_format {
+        IMAGE_FORMAT_DXT1 => Some(BcnEncoding::Bc1),
+        IMAGE_FORMAT_DXT3 => Some(BcnEncoding::Bc2),
+        IMAGE_FORMAT_DXT5 => Some(BcnEncoding::Bc3),
+        IMAGE_FORMAT_RGTC_R => Some(BcnEncoding::Bc4),
+        IMAGE_FORMAT_RGTC_RG => Some(BcnEncoding::Bc5),
+        IMAGE_FORMAT_BPTC_RGBA => Some(BcnEncoding::Bc7),
+        IMAGE_FORMAT_BPTC_RGBF | IMAGE_FORMAT_BPTC_RGBFU => Some(BcnEncoding::Bc6H),
+        _ => None,
+    }
+}
+
+/// Parses a Godot .stex or .ctex texture resource.
+///
+/// # Errors
+///
+/// Returns `Error::ImageDecodingError` if the header is malformed, or
+/// `Error::FeatureNotImplemented` if the texture is not stored as a raw
+/// VRAM-compressed image in one of the supported formats.
+///
+/// # Examples
+///
+/// ```no_run
+/// use bcndecode::{godot, BcnDecoderFormat};
+/// use std::fs::File;
+/// use std::io::Read;
+///
+/// # use std::io;
+/// # fn foo() -> io::Result<()> {
+/// let mut data = Vec::new();
+/// File::open("icon.ctex")?.read_to_end(&mut data)?;
+///
+/// let texture = godot::parse(&data)?;
+/// let pixels = texture.decode(0, BcnDecoderFormat::RGBA)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse<'a>(data: &'a [u8]) -> Result<GodotTexture<'a>, Error> {
+    let header = read_header(data)?;
+    Ok(GodotTexture {
+        version: header.version,
+        width: header.width,
+        height: header.height,
+        image_format: header.image_format,
+        mip_count: header.mip_count,
+        mipmaps: mip_levels(
+            &data[header.data_offset..],
+            header.width,
+            header.height,
+            header.image_format,
+            header.mip_count,
+        )?,
+    })
+}
+
+/// Returns the bytes of a Godot texture file that hold the given mip level.
+///
+/// Only the header is read, so `header` can be the start of a file that is
+/// fetched in parts: 20 bytes are enough for a `.stex` file and 52 for a
+/// `.ctex` file.
+///
+/// # Errors
+///
+/// Returns `Error::FeatureNotImplemented` if the stored format cannot be
+/// decoded, `Error::InvalidImageSize` if the level does not exist, or
+/// `Error::ImageDecodingError` if the header is invalid.
+pub fn level_byte_range(header: &[u8], level: usize) -> Result<Range<usize>, Error> {
+    let header = read_header(header)?;
+    let encoding = image_format_encoding(header.image_format).ok_or(Error::FeatureNotImplemented)?;
+    if level >= header.mip_count {
+        return Err(Error::InvalidImageSize);
+    }
+    let range = mip_byte_range(header.width, header.height, encoding, level)?;
+    Ok(header.data_offset + range.start..header.data_offset + range.end)
+}
+
+/// Container header fields needed to locate the image data.
+struct Header {
+    version: GodotTextureVersion,
+    width: usize,
+    height: usize,
+    image_format: u32,
+    // Number of mip levels, including the base level
+    mip_count: usize,
+    // Offset of the first mip level in the file
+    data_offset: usize,
+}
+
+fn read_header(data: &[u8]) -> Result<Header, Error> {
+    match data.get(0..4) {
+        Some(b"GDST") => read_stex_header(data),
+        Some(b"GST2") => read_ctex_header(data),
+        _ => Err(Error::ImageDecodingError),
+    }
+}
+
+fn read_stex_header(data: &[u8]) -> Result<Header, Error> {
+    let width = read_u16(data, 4)? as usize;
+    let height = read_u16(data, 8)? as usize;
+    let data_format = read_u32(data, 16)?;
+
+    if data_format & (V3_FORMAT_BIT_LOSSLESS | V3_FORMAT_BIT_LOSSY | V3_FORMAT_BIT_STREAM) != 0 {
+        return Err(Error::FeatureNotImplemented);
+    }
+
+    Ok(Header {
+        version: GodotTextureVersion::Stex,
+        width,
+        height,
+        image_format: data_format & V3_FORMAT_MASK_IMAGE_FORMAT,
+        // Godot 3 stores the whole chain down to 1x1 pixels
+        mip_count: if data_format & V3_FORMAT_BIT_HAS_MIPMAPS != 0 {
+            (usize::BITS - width.max(height).leading_zeros()) as usize
+        } else {
+            1
+        },
+        data_offset: 20,
+    })
+}
+
+fn read_ctex_header(data: &[u8]) -> Result<Header, Error> {
+    // the texture header (version, size, flags, mipmap limit and three
+    // reserved fields) is followed by the header of the stored image
+    let data_format = read_u32(data, 36)?;
+    let width = read_u16(data, 40)? as usize;
+    let height = read_u16(data, 42)? as usize;
+    let mipmap_count = read_u32(data, 44)?;
+    let image_format = read_u32(data, 48)?;
+
+    if data_format != V4_DATA_FORMAT_IMAGE {
+        return Err(Error::FeatureNotImplemented);
+    }
+
+    Ok(Header {
+        version: GodotTextureVersion::Ctex,
+        width,
+        height,
+        image_format,
+        // the count leaves out the base level
+        mip_count: mipmap_count as usize + 1,
+        data_offset: 52,
+    })
+}
+
+/// Splits the image data into its first `mip_count` mip levels, each
+/// padded to whole 4x4 blocks. The levels stop at 1x1 pixels or at the end
+/// of the data.
+fn mip_levels<'a>(
+    data: &'a [u8],
+    width: usize,
+    height: usize,
+    image_format: u32,
+    mip_count: usize,
+) -> Result<Vec<MipLevel<'a>>, Error> {
+    let encoding = image_format_encoding(image_format).ok_or(Error::FeatureNotImplemented)?;
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidImageSize);
+    }
+
+    let mut mipmaps = Vec::new();
+    let (mut w, mut h) = (width, height);
+    let mut offset = 0;
+    loop {
+        let size = compressed_size(w, h, encoding);
+        if offset + size > data.len() {
+            break;
+        }
+        mipmaps.push(MipLevel {
+            width: w,
+            height: h,
+            data: &data[offset..offset + size],
+        });
+        offset += size;
+
+        if mipmaps.len() == mip_count || (w == 1 && h == 1) {
+            break;
+        }
+        w = (w >> 1).max(1);
+        h = (h >> 1).max(1);
+    }
+
+    if mipmaps.is_empty() {
+        return Err(Error::ImageDecodingError);
+    }
+    Ok(mipmaps)
+}
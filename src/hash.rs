@@ -0,0 +1,148 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Perceptual hashes of decoded textures.
+//!
+//! Both hashes work on the luminance of the decoded image, downscaled to a
+//! fixed size, so the same texture hashes alike regardless of its encoding
+//! and resolution. Compare hashes by the number of differing bits, e.g.
+//! `(a ^ b).count_ones()`; a distance of up to about 10 usually means the
+//! same image.
+
+use std::f32::consts::PI;
+
+use super::decode::decode_rgba_f32;
+use super::{BcnEncoding, Error};
+
+/// Computes the difference hash of a compressed image.
+///
+/// The image is downscaled to 9x8 pixels and every bit of the hash tells
+/// whether a pixel is darker than its right neighbor.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters.
+pub fn dhash(source: &[u8], width: usize, height: usize, encoding: BcnEncoding) -> Result<u64, Error> {
+    let luminance = decode_luminance(source, width, height, encoding)?;
+    let small = downscale(&luminance, width, height, 9, 8);
+
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small[y * 9 + x] < small[y * 9 + x + 1] {
+                hash |= 1;
+            }
+        }
+    }
+    Ok(hash)
+}
+
+/// Computes the DCT-based perceptual hash of a compressed image.
+///
+/// The image is downscaled to 32x32 pixels and every bit of the hash tells
+/// whether one of the 8x8 lowest frequency DCT coefficients is above their
+/// median. This is more robust against blurring and compression artifacts
+/// than `dhash`, at a higher cost.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters.
+pub fn phash(source: &[u8], width: usize, height: usize, encoding: BcnEncoding) -> Result<u64, Error> {
+    let luminance = decode_luminance(source, width, height, encoding)?;
+    let small = downscale(&luminance, width, height, 32, 32);
+
+    // separable DCT-II, only the coefficients that end up in the hash
+    let mut rows = [[0.0f32; 8]; 32];
+    for (y, row) in rows.iter_mut().enumerate() {
+        for (u, coefficient) in row.iter_mut().enumerate() {
+            *coefficient = (0..32)
+                .map(|x| small[y * 32 + x] * dct_basis(u, x))
+                .sum();
+        }
+    }
+    let mut coefficients = [0.0f32; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            coefficients[v * 8 + u] = (0..32).map(|y| rows[y][u] * dct_basis(v, y)).sum();
+        }
+    }
+
+    // the DC coefficient only holds the average brightness, leave it out
+    // of the median
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let median = (sorted[31] + sorted[32]) / 2.0;
+
+    let mut hash = 0;
+    for &coefficient in coefficients.iter() {
+        hash <<= 1;
+        if coefficient > median {
+            hash |= 1;
+        }
+    }
+    Ok(hash)
+}
+
+fn dct_basis(frequency: usize, position: usize) -> f32 {
+    ((2 * position + 1) as f32 * frequency as f32 * PI / 64.0).cos()
+}
+
+fn decode_luminance(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+) -> Result<Vec<f32>, Error> {
+    let pixels = decode_rgba_f32(source, width, height, encoding)?;
+    Ok(pixels
+        .iter()
+        .map(|p| {
+            let l = 0.299 * p[0] + 0.587 * p[1] + 0.114 * p[2];
+            // keep non-finite BC6H values from spreading over the whole hash
+            if l.is_finite() {
+                l
+            } else {
+                0.0
+            }
+        })
+        .collect())
+}
+
+/// Downscales by averaging the source pixels that fall into each target
+/// pixel. Images smaller than the target are upscaled by repeating pixels.
+fn downscale(values: &[f32], width: usize, height: usize, target_width: usize, target_height: usize) -> Vec<f32> {
+    let mut result = Vec::with_capacity(target_width * target_height);
+    for ty in 0..target_height {
+        let y0 = ty * height / target_height;
+        let y1 = ((ty + 1) * height / target_height).max(y0 + 1);
+        for tx in 0..target_width {
+            let x0 = tx * width / target_width;
+            let x1 = ((tx + 1) * width / target_width).max(x0 + 1);
+
+            let mut sum = 0.0;
+            for y in y0..y1 {
+                sum += values[y * width + x0..y * width + x1].iter().sum::<f32>();
+            }
+            result.push(sum / ((y1 - y0) * (x1 - x0)) as f32);
+        }
+    }
+    result
+}
@@ -0,0 +1,123 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Helpers for displaying HDR (BC6H) textures.
+
+use super::decode::decode_rgba_f32;
+use super::{BcnEncoding, Error};
+
+/// Mapping of HDR values to the displayable range in `decode_tone_mapped`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ToneMapping {
+    /// Values above 1 are clipped.
+    Clamp,
+    /// Every channel is mapped to `x / (1 + x)`, which compresses highlights
+    /// instead of clipping them.
+    #[default]
+    Reinhard,
+}
+
+/// Middle gray that the log-average luminance is mapped to.
+const KEY_VALUE: f32 = 0.18;
+
+/// Keeps black pixels from pulling the log-average to zero.
+const LUMINANCE_EPSILON: f32 = 1e-4;
+
+/// Estimates the exposure for previewing a compressed HDR image.
+///
+/// Returns the exposure in stops that maps the log-average luminance of the
+/// image to middle gray; scale the decoded values by `2^ev` before tone
+/// mapping. Non-finite and negative values are ignored. Although meant for
+/// BC6H, any encoding is accepted.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters.
+pub fn auto_exposure(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+) -> Result<f32, Error> {
+    let pixels = decode_rgba_f32(source, width, height, encoding)?;
+
+    let mut log_sum = 0.0f64;
+    let mut count = 0;
+    for p in &pixels {
+        let l = 0.2126 * p[0] + 0.7152 * p[1] + 0.0722 * p[2];
+        if l.is_finite() {
+            log_sum += (LUMINANCE_EPSILON + l.max(0.0)).ln() as f64;
+            count += 1;
+        }
+    }
+    if count == 0 {
+        return Ok(0.0);
+    }
+
+    let log_average = (log_sum / count as f64).exp() as f32;
+    Ok((KEY_VALUE / log_average).log2())
+}
+
+/// Decodes a BC6H image into 8-bit RGBA for previewing.
+///
+/// The values are scaled by `2^exposure`, e.g. the result of
+/// `auto_exposure`, mapped to [0, 1] by `tone_mapping` and sRGB encoded.
+/// Alpha is opaque and rows are stored top row first. NaN becomes black
+/// and infinities white.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters.
+pub fn decode_tone_mapped(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    exposure: f32,
+    tone_mapping: ToneMapping,
+) -> Result<Vec<u8>, Error> {
+    let pixels = decode_rgba_f32(source, width, height, BcnEncoding::Bc6H)?;
+
+    let scale = exposure.exp2();
+    let mut result = Vec::with_capacity(4 * pixels.len());
+    for p in &pixels {
+        for &c in &p[..3] {
+            // max turns NaN into 0
+            let c = (c * scale).max(0.0);
+            let mapped = match tone_mapping {
+                ToneMapping::Clamp => c.min(1.0),
+                ToneMapping::Reinhard if c.is_infinite() => 1.0,
+                ToneMapping::Reinhard => c / (1.0 + c),
+            };
+            result.push((linear_to_srgb(mapped) * 255.0).round() as u8);
+        }
+        result.push(0xff);
+    }
+    Ok(result)
+}
+
+/// Applies the sRGB OETF to a value from 0 to 1.
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! False-color images of the encoder choices in BC6H and BC7 data.
+
+use super::decode::{block_mode, block_size, output_params};
+use super::{BcnDecoderFormat, BcnEncoding, Error};
+
+/// Block property shown by `block_heatmap`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Heatmap {
+    /// The mode of every block
+    Mode,
+    /// The partition of every block, 0 for blocks with a single subset
+    Partition,
+}
+
+/// Renders an image showing the mode or partition of every block.
+///
+/// Every block is filled with a color of its own value: of `n` possible
+/// values, value `v` gets the fully saturated hue `360° * v / n`, with 14
+/// BC6H and 8 BC7 modes, and 32 BC6H and 64 BC7 partitions. Reserved and
+/// missing blocks are black. The result is in the layout of `decode` with
+/// the RGBA format, so it can be blended over the decoded image.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the
+/// given parameters or the encoding is not BC6H or BC7.
+pub fn block_heatmap(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    heatmap: Heatmap,
+) -> Result<Vec<u8>, Error> {
+    let count = match (encoding, heatmap) {
+        (BcnEncoding::Bc6H, Heatmap::Mode) => 14,
+        (BcnEncoding::Bc6H, Heatmap::Partition) => 32,
+        (BcnEncoding::Bc7, Heatmap::Mode) => 8,
+        (BcnEncoding::Bc7, Heatmap::Partition) => 64,
+        _ => return Err(Error::InvalidPixelFormat),
+    };
+    output_params(width, height, encoding, BcnDecoderFormat::RGBA)?;
+
+    // the decoder flips images that are not a multiple of the block size
+    let flipped = ((width & 3) | (height & 3)) != 0;
+    let blocks_per_row = width.div_ceil(4);
+    let mut pixels = vec![0; 4 * width * height];
+    for (index, block) in source.chunks_exact(block_size(encoding)).enumerate() {
+        let (block_x, block_y) = (4 * (index % blocks_per_row), 4 * (index / blocks_per_row));
+        if block_y >= height {
+            break;
+        }
+        let color = match block_mode(encoding, block) {
+            Some((mode, partition)) => hue(if heatmap == Heatmap::Mode { mode } else { partition }, count),
+            None => [0, 0, 0, 0xff],
+        };
+        for y in block_y..(block_y + 4).min(height) {
+            let row = if flipped { height - 1 - y } else { y };
+            let start = 4 * (row * width + block_x);
+            let end = 4 * (row * width + (block_x + 4).min(width));
+            for pixel in pixels[start..end].chunks_exact_mut(4) {
+                pixel.copy_from_slice(&color);
+            }
+        }
+    }
+
+    // opaque black for the missing blocks
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel[3] = 0xff;
+    }
+    Ok(pixels)
+}
+
+/// Returns the fully saturated color with the hue `360° * value / count`.
+fn hue(value: u8, count: u8) -> [u8; 4] {
+    let h = 6.0 * value as f32 / count as f32;
+    let x = ((1.0 - (h % 2.0 - 1.0).abs()) * 255.0).round() as u8;
+    match h as u8 {
+        0 => [0xff, x, 0, 0xff],
+        1 => [x, 0xff, 0, 0xff],
+        2 => [0, 0xff, x, 0xff],
+        3 => [0, x, 0xff, 0xff],
+        4 => [x, 0, 0xff, 0xff],
+        _ => [0xff, 0, x, 0xff],
+    }
+}
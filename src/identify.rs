@@ -0,0 +1,203 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Identification of the encoding of unlabeled block-compressed data.
+//!
+//! A number of block rows, spread over the image, are decoded under every candidate encoding and the
+//! output is scored on how much it looks like an image: smooth gradients,
+//! a histogram that is not uniform noise and a well-behaved alpha channel.
+
+use super::decode::{compressed_size, decode_rgba_f32};
+use super::BcnEncoding;
+use std::ops::Range;
+
+/// Number of block rows decoded per candidate encoding.
+const SAMPLE_ROWS: usize = 16;
+
+const CANDIDATES: [BcnEncoding; 7] = [
+    BcnEncoding::Bc1,
+    BcnEncoding::Bc2,
+    BcnEncoding::Bc3,
+    BcnEncoding::Bc4,
+    BcnEncoding::Bc5,
+    BcnEncoding::Bc6H,
+    BcnEncoding::Bc7,
+];
+
+/// Guesses the encoding of block-compressed data of unknown format.
+///
+/// Returns the candidate encodings with a plausibility score between 0 and
+/// 1, most likely first. Encodings that need more data than `source` holds
+/// for a `width` x `height` image are not listed, so the result is empty if
+/// the data is too short for any of them. Trailing data, such as a mip
+/// chain, is ignored.
+///
+/// # Examples
+///
+/// ```
+/// // a single BC1 block of solid white
+/// let block = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+/// let scores = bcndecode::identify(&block, 4, 4);
+/// assert!(scores.iter().all(|&(_, score)| score >= 0.0 && score <= 1.0));
+/// ```
+pub fn identify(source: &[u8], width: usize, height: usize) -> Vec<(BcnEncoding, f32)> {
+    let mut scores = Vec::new();
+    if width == 0 || height == 0 {
+        return scores;
+    }
+
+    for &encoding in CANDIDATES.iter() {
+        if compressed_size(width, height, encoding) > source.len() {
+            continue;
+        }
+        if let Some(score) = score_encoding(source, width, height, encoding) {
+            scores.push((encoding, score));
+        }
+    }
+
+    scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    scores
+}
+
+/// Pixels of a decoded sample, normalized to [0, 1].
+struct Sample {
+    width: usize,
+    height: usize,
+    // rgba values, row by row
+    pixels: Vec<[f32; 4]>,
+    // number of pixels that did not decode to a finite, in-range value
+    invalid: usize,
+}
+
+fn score_encoding(source: &[u8], width: usize, height: usize, encoding: BcnEncoding) -> Option<f32> {
+    let block_rows = height.div_ceil(4);
+    let row_size = compressed_size(width, 4, encoding);
+    let rows = block_rows.min(SAMPLE_ROWS);
+
+    let mut samples = Vec::with_capacity(rows);
+    for i in 0..rows {
+        let start = (i * block_rows / rows) * row_size;
+        samples.push(decode_sample(&source[start..start + row_size], width, encoding)?);
+    }
+
+    let (inner, seams) = gradients(&samples, 0..3);
+    let (alpha_inner, alpha_seams) = gradients(&samples, 3..4);
+    let invalid: usize = samples.iter().map(|s| s.invalid).sum();
+    let total: usize = samples.iter().map(|s| s.pixels.len()).sum();
+
+    // misread data decodes each block on its own, so the seams between
+    // blocks stand out against the gradients within them
+    let continuity = if inner + seams > 0.0 {
+        1.0 - (seams - inner).max(0.0) / (inner + seams)
+    } else {
+        1.0
+    };
+    let smoothness = 1.0 - (1.5 * (inner + seams)).min(1.0);
+    let alpha = 1.0 - (1.5 * (alpha_inner + alpha_seams)).min(1.0);
+    let entropy = 1.0 - luminance_entropy(&samples);
+    let validity = 1.0 - invalid as f32 / total as f32;
+
+    let score = 0.35 * continuity + 0.35 * smoothness + 0.15 * entropy + 0.15 * alpha;
+    Some((validity * score).clamp(0.0, 1.0))
+}
+
+/// Decodes a single row of blocks into normalized pixels.
+fn decode_sample(data: &[u8], width: usize, encoding: BcnEncoding) -> Option<Sample> {
+    let mut pixels = decode_rgba_f32(data, width, 4, encoding).ok()?;
+
+    let mut invalid = 0;
+    for pixel in pixels.iter_mut() {
+        // values far outside the displayable range are more likely to come
+        // from misinterpreted data than from real HDR content
+        if pixel.iter().any(|v| !v.is_finite() || !(0.0..=256.0).contains(v)) {
+            invalid += 1;
+            *pixel = [0.0, 0.0, 0.0, 1.0];
+        }
+        for value in pixel.iter_mut() {
+            *value = value.min(1.0);
+        }
+    }
+
+    Some(Sample {
+        width,
+        height: 4,
+        pixels,
+        invalid,
+    })
+}
+
+/// Mean absolute difference between adjacent pixels over the given
+/// channels, separately for pairs within a block and for pairs across the
+/// vertical seams between blocks.
+fn gradients(samples: &[Sample], channels: Range<usize>) -> (f32, f32) {
+    let (mut inner, mut inner_count) = (0.0, 0);
+    let (mut seams, mut seam_count) = (0.0, 0);
+    for sample in samples {
+        for y in 0..sample.height {
+            let row = &sample.pixels[y * sample.width..(y + 1) * sample.width];
+            for (x, pair) in row.windows(2).enumerate() {
+                let diff: f32 = channels.clone().map(|c| (pair[0][c] - pair[1][c]).abs()).sum();
+                if x % 4 == 3 {
+                    seams += diff;
+                    seam_count += channels.len();
+                } else {
+                    inner += diff;
+                    inner_count += channels.len();
+                }
+            }
+            if y + 1 < sample.height {
+                let next = &sample.pixels[(y + 1) * sample.width..(y + 2) * sample.width];
+                for (p, n) in row.iter().zip(next) {
+                    inner += channels.clone().map(|c| (p[c] - n[c]).abs()).sum::<f32>();
+                    inner_count += channels.len();
+                }
+            }
+        }
+    }
+    (
+        if inner_count > 0 { inner / inner_count as f32 } else { 0.0 },
+        if seam_count > 0 { seams / seam_count as f32 } else { 0.0 },
+    )
+}
+
+/// Shannon entropy of the 8-bit luminance histogram, normalized to [0, 1].
+fn luminance_entropy(samples: &[Sample]) -> f32 {
+    let mut histogram = [0usize; 256];
+    let mut total = 0;
+    for p in samples.iter().flat_map(|s| s.pixels.iter()) {
+        let l = 0.299 * p[0] + 0.587 * p[1] + 0.114 * p[2];
+        histogram[(l * 255.0).round() as usize] += 1;
+        total += 1;
+    }
+
+    let total = total as f32;
+    let mut entropy = 0.0;
+    for &count in histogram.iter().filter(|&&c| c > 0) {
+        let p = count as f32 / total;
+        entropy -= p * p.log2();
+    }
+    // a sample cannot have more distinct values than pixels
+    let max = total.min(256.0).log2();
+    if max > 0.0 {
+        entropy / max
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,239 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Writer for Khronos KTX2 (.ktx2) containers.
+//!
+//! `from_dds` moves the block-compressed payload of a DDS file into a KTX2
+//! file without decoding it. The header is followed by the level index,
+//! which lists the base level first, and the data format descriptor; the
+//! levels themselves are stored smallest first, each holding all array
+//! elements, cube faces or depth slices of that size. Supercompression and
+//! key/value data are not written.
+
+use super::dds::{self, DdsTexture};
+use super::decode::block_size;
+use super::{BcnEncoding, Error};
+
+const IDENTIFIER: [u8; 12] = [0xab, b'K', b'T', b'X', b' ', b'2', b'0', 0xbb, b'\r', b'\n', 0x1a, b'\n'];
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+// khr_df_model_e
+const KHR_DF_MODEL_BC1A: u32 = 128;
+const KHR_DF_MODEL_BC2: u32 = 129;
+const KHR_DF_MODEL_BC3: u32 = 130;
+const KHR_DF_MODEL_BC4: u32 = 131;
+const KHR_DF_MODEL_BC5: u32 = 132;
+const KHR_DF_MODEL_BC6H: u32 = 133;
+const KHR_DF_MODEL_BC7: u32 = 134;
+
+// khr_df_versionnumber_e
+const KHR_DF_VERSIONNUMBER_1_3: u32 = 2;
+
+// khr_df_primaries_e
+const KHR_DF_PRIMARIES_BT709: u32 = 1;
+
+// khr_df_transfer_e
+const KHR_DF_TRANSFER_LINEAR: u32 = 1;
+const KHR_DF_TRANSFER_SRGB: u32 = 2;
+
+// khr_df_flags_e
+const KHR_DF_FLAG_ALPHA_PREMULTIPLIED: u32 = 1;
+
+// Channel types of the block-compressed models
+const CHANNEL_COLOR: u32 = 0;
+const CHANNEL_BC1A_ALPHA_PRESENT: u32 = 1;
+const CHANNEL_GREEN: u32 = 1;
+const CHANNEL_ALPHA: u32 = 15;
+
+// khr_df_sample_datatype_qualifiers_e, above the channel type
+const QUALIFIER_LINEAR: u32 = 0x10;
+const QUALIFIER_SIGNED: u32 = 0x40;
+const QUALIFIER_FLOAT: u32 = 0x80;
+
+// Sample ranges: the full range of the integers, or 0.0 or -1.0 to 1.0
+const UNSIGNED_LOWER: u32 = 0;
+const UNSIGNED_UPPER: u32 = 0xffff_ffff;
+const SIGNED_LOWER: u32 = 0x8000_0000;
+const SIGNED_UPPER: u32 = 0x7fff_ffff;
+const FLOAT_MINUS_ONE: u32 = 0xbf80_0000;
+const FLOAT_ZERO: u32 = 0;
+const FLOAT_ONE: u32 = 0x3f80_0000;
+
+/// Returns the `VkFormat` value of an encoding that holds signed or sRGB
+/// encoded data, as `DdsTexture` describes it, if Vulkan has one.
+///
+/// BC1 maps to the RGBA formats, as its blocks may hold transparent
+/// pixels.
+pub fn vk_format(encoding: BcnEncoding, signed: bool, srgb: bool) -> Option<u32> {
+    match (encoding, signed, srgb) {
+        (BcnEncoding::Bc1, false, false) => Some(133),
+        (BcnEncoding::Bc1, false, true) => Some(134),
+        (BcnEncoding::Bc2, false, false) => Some(135),
+        (BcnEncoding::Bc2, false, true) => Some(136),
+        (BcnEncoding::Bc3, false, false) => Some(137),
+        (BcnEncoding::Bc3, false, true) => Some(138),
+        (BcnEncoding::Bc4, false, false) => Some(139),
+        (BcnEncoding::Bc4, true, false) => Some(140),
+        (BcnEncoding::Bc5, false, false) => Some(141),
+        (BcnEncoding::Bc5, true, false) => Some(142),
+        (BcnEncoding::Bc6H, false, false) => Some(143),
+        (BcnEncoding::Bc6H, true, false) => Some(144),
+        (BcnEncoding::Bc7, false, false) => Some(145),
+        (BcnEncoding::Bc7, false, true) => Some(146),
+        _ => None,
+    }
+}
+
+/// Converts a DDS file into a KTX2 file holding the same compressed
+/// payload.
+///
+/// Arrays, cubemaps and volume textures keep their shape and all of their
+/// mip levels. The data format descriptor records whether the texture is
+/// signed, sRGB encoded or premultiplied.
+///
+/// # Errors
+///
+/// Returns any error of `dds::parse`, or `Error::FeatureNotImplemented` if
+/// the texture is uncompressed or a cubemap missing some of its faces.
+///
+/// # Examples
+///
+/// ```no_run
+/// use bcndecode::ktx2;
+///
+/// # use std::io;
+/// # fn foo() -> io::Result<()> {
+/// let data = std::fs::read("albedo.dds")?;
+/// std::fs::write("albedo.ktx2", ktx2::from_dds(&data)?)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn from_dds(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let texture = dds::parse(data)?;
+    if texture.raw_format.is_some() || (texture.faces != 1 && texture.faces != 6) {
+        return Err(Error::FeatureNotImplemented);
+    }
+    let vk_format = vk_format(texture.encoding, texture.signed, texture.srgb).ok_or(Error::FeatureNotImplemented)?;
+    let dfd = data_format_descriptor(&texture);
+
+    // the levels follow the descriptor, smallest first and aligned to
+    // their block size
+    let dfd_offset = HEADER_SIZE + LEVEL_INDEX_ENTRY_SIZE * texture.mip_count;
+    let mut offset = dfd_offset + dfd.len();
+    let mut level_index = vec![(0, 0); texture.mip_count];
+    for level in (0..texture.mip_count).rev() {
+        offset = offset.next_multiple_of(block_size(texture.encoding));
+        let length: usize = (0..texture.surface_count(level))
+            .filter_map(|index| texture.surface(level, index))
+            .map(|surface| surface.data.len())
+            .sum();
+        level_index[level] = (offset, length);
+        offset += length;
+    }
+
+    let mut file = Vec::with_capacity(offset);
+    file.extend_from_slice(&IDENTIFIER);
+    let depth = if texture.depth > 1 { texture.depth } else { 0 };
+    let layers = if texture.array_size > 1 { texture.array_size } else { 0 };
+    for &value in &[
+        vk_format,
+        1, // typeSize
+        texture.width as u32,
+        texture.height as u32,
+        depth as u32,
+        layers as u32,
+        texture.faces as u32,
+        texture.mip_count as u32,
+        0, // supercompressionScheme
+        dfd_offset as u32,
+        dfd.len() as u32,
+        0, // kvdByteOffset
+        0, // kvdByteLength
+    ] {
+        file.extend_from_slice(&value.to_le_bytes());
+    }
+    // sgdByteOffset and sgdByteLength
+    file.extend_from_slice(&[0; 16]);
+    for &(offset, length) in &level_index {
+        // without supercompression, the uncompressed length is the length
+        for &value in &[offset, length, length] {
+            file.extend_from_slice(&(value as u64).to_le_bytes());
+        }
+    }
+    file.extend_from_slice(&dfd);
+    for level in (0..texture.mip_count).rev() {
+        file.resize(level_index[level].0, 0);
+        for surface in (0..texture.surface_count(level)).filter_map(|index| texture.surface(level, index)) {
+            file.extend_from_slice(surface.data);
+        }
+    }
+    Ok(file)
+}
+
+/// Returns the data format descriptor of a texture: its total size
+/// followed by a single basic descriptor block.
+fn data_format_descriptor(texture: &DdsTexture) -> Vec<u8> {
+    let model = match texture.encoding {
+        BcnEncoding::Bc1 => KHR_DF_MODEL_BC1A,
+        BcnEncoding::Bc2 => KHR_DF_MODEL_BC2,
+        BcnEncoding::Bc3 => KHR_DF_MODEL_BC3,
+        BcnEncoding::Bc4 => KHR_DF_MODEL_BC4,
+        BcnEncoding::Bc5 => KHR_DF_MODEL_BC5,
+        BcnEncoding::Bc6H => KHR_DF_MODEL_BC6H,
+        BcnEncoding::Bc7 => KHR_DF_MODEL_BC7,
+    };
+    // channel type, bit offset and bit length of every sample
+    let samples: &[(u32, u32, u32)] = match texture.encoding {
+        BcnEncoding::Bc1 => &[(CHANNEL_BC1A_ALPHA_PRESENT, 0, 64)],
+        BcnEncoding::Bc2 | BcnEncoding::Bc3 => &[(CHANNEL_ALPHA, 0, 64), (CHANNEL_COLOR, 64, 64)],
+        BcnEncoding::Bc4 => &[(CHANNEL_COLOR, 0, 64)],
+        BcnEncoding::Bc5 => &[(CHANNEL_COLOR, 0, 64), (CHANNEL_GREEN, 64, 64)],
+        BcnEncoding::Bc6H | BcnEncoding::Bc7 => &[(CHANNEL_COLOR, 0, 128)],
+    };
+    let (qualifiers, lower, upper) = match (texture.encoding, texture.signed) {
+        (BcnEncoding::Bc6H, false) => (QUALIFIER_FLOAT, FLOAT_ZERO, FLOAT_ONE),
+        (BcnEncoding::Bc6H, true) => (QUALIFIER_FLOAT | QUALIFIER_SIGNED, FLOAT_MINUS_ONE, FLOAT_ONE),
+        (_, false) => (0, UNSIGNED_LOWER, UNSIGNED_UPPER),
+        (_, true) => (QUALIFIER_SIGNED, SIGNED_LOWER, SIGNED_UPPER),
+    };
+    let transfer = if texture.srgb { KHR_DF_TRANSFER_SRGB } else { KHR_DF_TRANSFER_LINEAR };
+    let flags = if texture.premultiplied { KHR_DF_FLAG_ALPHA_PREMULTIPLIED } else { 0 };
+
+    let descriptor_size = 24 + 16 * samples.len() as u32;
+    let mut words = vec![
+        4 + descriptor_size,
+        // vendorId and descriptorType of the basic descriptor block
+        0,
+        KHR_DF_VERSIONNUMBER_1_3 | descriptor_size << 16,
+        model | KHR_DF_PRIMARIES_BT709 << 8 | transfer << 16 | flags << 24,
+        // texel block dimensions minus one
+        3 | 3 << 8,
+        block_size(texture.encoding) as u32,
+        0,
+    ];
+    for &(channel, offset, length) in samples {
+        // alpha is linear even in sRGB textures
+        let linear = if texture.srgb && channel == CHANNEL_ALPHA { QUALIFIER_LINEAR } else { 0 };
+        let channel_type = channel | qualifiers | linear;
+        words.extend_from_slice(&[offset | (length - 1) << 16 | channel_type << 24, 0, lower, upper]);
+    }
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
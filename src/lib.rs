@@ -30,9 +30,6 @@
 //! * Bc4: 1-channel 8-bit via 1 BC3 alpha block
 //! * Bc5: 2-channel 8-bit via 2 BC3 alpha blocks
 //! * Bc6: 3-channel 16-bit float
-//!
-//! The following formats are not implemented:
-//!
 //! * Bc7: 4-channel 8-bit via everything
 //!
 //! Format documentation for BC1-BC5
@@ -44,20 +41,122 @@
 //! The decompression code was based on the original C code used in the
 //! [Python Pillow Imaging package](https://python-pillow.org/)
 
-#[cfg(test)]
-extern crate libc;
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+#![cfg_attr(feature = "safe", forbid(unsafe_code))]
 
+#[cfg(feature = "arbitrary")]
+extern crate arbitrary;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+#[cfg(feature = "png")]
+extern crate png;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "rgb")]
+extern crate rgb;
+
+#[cfg(all(feature = "mmap", feature = "safe"))]
+compile_error!("memory-mapping a file needs unsafe code, the mmap and safe features cannot be combined");
+
+#[cfg(feature = "allocator_api")]
+use std::alloc::Allocator;
 use std::error;
 use std::fmt;
 use std::io;
+use std::mem::MaybeUninit;
+use std::str::FromStr;
 
+mod atc;
+mod atlas;
+mod block;
+pub mod const_decode;
+pub mod crn;
+mod cubemap;
+pub mod dds;
 mod decode;
+mod decoder;
+mod diff;
+mod duplicates;
+mod etc;
+#[cfg(feature = "arbitrary")]
+mod fuzzing;
+mod gnf;
+pub mod godot;
+mod hash;
+mod hdr;
+mod heatmap;
+mod identify;
+pub mod ktx2;
+mod lut;
+#[cfg(feature = "mmap")]
+mod mmap;
+pub mod morton;
+mod pack;
+mod range;
+mod raw;
+mod reader;
+#[cfg(any(test, feature = "reference"))]
+pub mod reference;
+mod report;
+mod resize;
+#[cfg(feature = "rgb")]
+mod rgb_pixels;
+mod rows;
+mod selftest;
+mod simd;
+mod staging;
+mod stats;
+mod stream;
+mod synth;
+mod tegra;
+mod tenbit;
+#[cfg(any(test, feature = "testutil"))]
+pub mod testutil;
+mod tiles;
+pub mod unreal;
+mod view;
+pub mod vtex;
 
 #[cfg(test)]
 mod tests;
 
+pub use atc::{decode_atc, AtcFormat};
+pub use atlas::{decode_rects, decode_region, Rect};
+pub use block::{decode_block, Block4x4};
+pub use cubemap::{decode_cubemap, CubeFace, Cubemap, MissingFaces};
+pub use decoder::BcnDecoder;
+pub use diff::diff_blocks;
+pub use duplicates::{find_duplicate_blocks, DuplicateBlocks, DuplicateGroup};
+pub use etc::{decode_eac_16, decode_etc, EtcFormat};
+pub use gnf::{detile_gnf, GnfTileMode};
+pub use hash::{dhash, phash};
+pub use hdr::{auto_exposure, decode_tone_mapped, ToneMapping};
+pub use heatmap::{block_heatmap, Heatmap};
+pub use identify::identify;
+pub use lut::Lut3d;
+#[cfg(feature = "mmap")]
+pub use mmap::decode_to_file;
+pub use pack::pack_bc4;
+pub use range::{mip_byte_range, required_byte_range};
+pub use raw::{decode_raw, decode_raw_float, decode_raw_with_pitch, FloatPrecision, RawFormat};
+pub use report::{decode_with_report, repair_blocks, CorruptBlock, Corruption};
+pub use resize::decode_resized;
+#[cfg(feature = "rgb")]
+pub use rgb_pixels::{decode_rgb_f32, decode_rgba8};
+pub use rows::{decode_to_writer, DecodedRows};
+pub use selftest::{self_test, SelfTestError};
+pub use staging::{decode_staging, StagingBuffer, StagingRegion};
+pub use stats::{decode_with_stats, DecodeStats};
+pub use stream::StreamedRows;
+pub use synth::{synthesize, EndpointOrder, Pattern, SynthOptions};
+pub use tegra::{deswizzle_tegra, tegra_block_height};
+pub use tenbit::{decode_10bit, TenBitLayout};
+pub use tiles::{decode_chunked, decode_resident, decode_tiles, ResidencyMask};
+pub use view::TextureView;
+
 /// The error type for all bcn decoding operations.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum Error {
     /// Decoding failed due to incorrect source data.
     ImageDecodingError,
@@ -67,33 +166,90 @@ pub enum Error {
     FeatureNotImplemented,
     /// Pixel format is invalid for the given decoding
     InvalidPixelFormat,
+    /// The source data is corrupt at a block, see `DecodeOptions::strict`.
+    InvalidBlock(BlockError),
 }
 
-impl error::Error for Error {
-    fn description(&self) -> &str {
-        match self {
-            &Error::ImageDecodingError => "Failed to decode image",
-            &Error::InvalidImageSize => "Size of the image is invalid",
-            &Error::FeatureNotImplemented => "Feature is not implemented",
-            &Error::InvalidPixelFormat => "Pixel format is invalid for the given decoding",
-        }
+impl error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match *self {
+            Error::ImageDecodingError => "Failed to decode image",
+            Error::InvalidImageSize => "Size of the image is invalid",
+            Error::FeatureNotImplemented => "Feature is not implemented",
+            Error::InvalidPixelFormat => "Pixel format is invalid for the given decoding",
+            Error::InvalidBlock(ref error) => return write!(f, "{}", error),
+        };
+        write!(f, "{}", description)
     }
 }
 
-impl fmt::Display for Error {
+/// Location and cause of an `Error::InvalidBlock`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockError {
+    /// Offset of the block in the source in bytes
+    pub offset: usize,
+    /// Left edge of the block in pixels
+    pub x: usize,
+    /// Top edge of the block in pixels, counted from the top row even if
+    /// the decoded image is flipped
+    pub y: usize,
+    /// Encoding of the source
+    pub encoding: BcnEncoding,
+    /// What is wrong with the block
+    pub reason: BlockErrorReason,
+}
+
+/// Cause of a `BlockError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BlockErrorReason {
+    /// The source ends before the block is complete.
+    Truncated {
+        /// Number of blocks of the image
+        expected: usize,
+        /// Number of whole blocks in the source
+        provided: usize,
+    },
+    /// The block uses a mode reserved by the format (BC6H, BC7).
+    InvalidMode,
+    /// The source holds data after the last block of the image; the block
+    /// is the one that would follow it, at x 0 and y the padded height.
+    SizeMismatch {
+        /// Number of blocks of the image
+        expected: usize,
+        /// Number of bytes after the last block
+        trailing_bytes: usize,
+    },
+}
+
+impl error::Error for BlockError {}
+
+impl fmt::Display for BlockError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", error::Error::description(self))
+        write!(f, "Invalid {:?} block at ({}, {}), offset {}: ", self.encoding, self.x, self.y, self.offset)?;
+        match self.reason {
+            BlockErrorReason::Truncated { expected, provided } => {
+                write!(f, "source is truncated, expected {} blocks, got {}", expected, provided)
+            }
+            BlockErrorReason::InvalidMode => write!(f, "reserved mode"),
+            BlockErrorReason::SizeMismatch { expected, trailing_bytes } => {
+                write!(f, "{} bytes after the expected {} blocks", trailing_bytes, expected)
+            }
+        }
     }
 }
 
 impl From<Error> for io::Error {
     fn from(error: Error) -> io::Error {
-        io::Error::new(io::ErrorKind::Other, error)
+        io::Error::other(error)
     }
 }
 
 /// Encoding type of the source data.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum BcnEncoding {
     /// BC1: 565 color, 1-bit alpha (dxt1)
     Bc1 = 1,
@@ -109,13 +265,130 @@ pub enum BcnEncoding {
     /// (16 bit value that consists of an optional sign bit, a 5 bit biased exponent,
     /// and a 10 or 11 bit mantissa.)
     Bc6H = 6,
-    // BC7: Three color channels (4 to 7 bits per channel) with 0 to 8 bits of alpha
-    // (not implemented)
-    //Bc7 = 7,
+    /// BC7: Three color channels (4 to 7 bits per channel) with 0 to 8 bits of alpha
+    Bc7 = 7,
+}
+
+impl BcnEncoding {
+    /// Returns the number of bytes of a 4x4 block.
+    pub fn block_size(self) -> usize {
+        decode::block_size(self)
+    }
+
+    /// Returns the number of bytes of an image of the given size, which is
+    /// rounded up to whole blocks.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidImageSize` if the size does not fit into a
+    /// `usize`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bcndecode::BcnEncoding;
+    ///
+    /// // 2x2 blocks of 8 bytes
+    /// assert_eq!(BcnEncoding::Bc1.compressed_size(5, 8).unwrap(), 32);
+    /// ```
+    pub fn compressed_size(self, width: usize, height: usize) -> Result<usize, Error> {
+        width
+            .div_ceil(4)
+            .checked_mul(height.div_ceil(4))
+            .and_then(|blocks| blocks.checked_mul(self.block_size()))
+            .ok_or(Error::InvalidImageSize)
+    }
+
+    /// Returns the length of the output of `decode` for an image of the
+    /// given size and pixel format.
+    ///
+    /// This is the size of a pixel of the format times the number of
+    /// pixels, except for BC6H, whose 12-byte pixels are returned in a
+    /// buffer of 16 bytes per pixel.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `decode` would reject the size
+    /// or the format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bcndecode::{BcnDecoderFormat, BcnEncoding};
+    ///
+    /// assert_eq!(BcnEncoding::Bc1.decoded_size(5, 8, BcnDecoderFormat::RGB).unwrap(), 120);
+    /// assert!(BcnEncoding::Bc1.decoded_size(5, 8, BcnDecoderFormat::LUM).is_err());
+    /// ```
+    pub fn decoded_size(self, width: usize, height: usize, format: BcnDecoderFormat) -> Result<usize, Error> {
+        decode::output_params(width, height, self, format)
+    }
+
+    /// Looks up the encoding of a DDS FourCC code, e.g. `*b"DXT5"`.
+    ///
+    /// Besides the DXTn codes, the legacy ATI1/ATI2 codes and their BC4U/BC5U
+    /// spellings are recognized for BC4 and BC5, as are the signed BC4S and
+    /// BC5S codes; `from_fourcc_signed` also tells whether the data is
+    /// signed. DXT2 and DXT4 are BC2 and BC3 with premultiplied alpha, see
+    /// `DecodeOptions::unpremultiply`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidPixelFormat` for codes that are not block
+    /// compressed formats.
+    pub fn from_fourcc(fourcc: [u8; 4]) -> Result<BcnEncoding, Error> {
+        BcnEncoding::from_fourcc_signed(fourcc).map(|(encoding, _)| encoding)
+    }
+
+    /// Looks up the encoding of a DDS FourCC code and whether it holds
+    /// signed values, which are decoded with `DecodeOptions::signed`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidPixelFormat` for codes that are not block
+    /// compressed formats.
+    pub fn from_fourcc_signed(fourcc: [u8; 4]) -> Result<(BcnEncoding, bool), Error> {
+        match &fourcc {
+            b"DXT1" => Ok((BcnEncoding::Bc1, false)),
+            b"DXT2" | b"DXT3" => Ok((BcnEncoding::Bc2, false)),
+            b"DXT4" | b"DXT5" => Ok((BcnEncoding::Bc3, false)),
+            b"ATI1" | b"BC4U" => Ok((BcnEncoding::Bc4, false)),
+            b"ATI2" | b"BC5U" => Ok((BcnEncoding::Bc5, false)),
+            b"BC4S" => Ok((BcnEncoding::Bc4, true)),
+            b"BC5S" => Ok((BcnEncoding::Bc5, true)),
+            _ => Err(Error::InvalidPixelFormat),
+        }
+    }
+}
+
+/// Parses an encoding from its name, ignoring case.
+///
+/// Accepts the BCn names (`BC1` to `BC7`), the DXTn names and the FourCC
+/// aliases of `BcnEncoding::from_fourcc`. BC4S and BC5S parse to BC4 and
+/// BC5, whose signed data is decoded with `DecodeOptions::signed`.
+impl FromStr for BcnEncoding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<BcnEncoding, Error> {
+        match s.to_ascii_uppercase().as_str() {
+            "BC1" | "DXT1" => Ok(BcnEncoding::Bc1),
+            "BC2" | "DXT2" | "DXT3" => Ok(BcnEncoding::Bc2),
+            "BC3" | "DXT4" | "DXT5" => Ok(BcnEncoding::Bc3),
+            "BC4" | "BC4U" | "BC4S" | "ATI1" => Ok(BcnEncoding::Bc4),
+            "BC5" | "BC5U" | "BC5S" | "ATI2" => Ok(BcnEncoding::Bc5),
+            "BC6H" | "BC6" => Ok(BcnEncoding::Bc6H),
+            "BC7" => Ok(BcnEncoding::Bc7),
+            _ => Err(Error::InvalidPixelFormat),
+        }
+    }
 }
 
 /// Specifies the pixel format of the output data
-#[derive(Copy, Clone)]
+///
+/// BC4 can also be decoded to the color formats, which repeat its value in
+/// red, green and blue with an opaque alpha.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+#[non_exhaustive]
 pub enum BcnDecoderFormat {
     RGBA = 1,
     BGRA = 2,
@@ -123,6 +396,235 @@ pub enum BcnDecoderFormat {
     ABGR = 4,
     /// Format only used for BC4 decompression
     LUM = 5,
+    /// Red, green and blue in 3 bytes, without alpha. Not for BC6H.
+    RGB = 6,
+    /// Blue, green and red in 3 bytes, without alpha. Not for BC6H.
+    BGR = 7,
+    /// Any order of the channels in 4 bytes, which may repeat or leave out
+    /// channels. Not for BC6H.
+    Custom(Swizzle) = 8,
+}
+
+/// Source of a byte of a `BcnDecoderFormat::Custom` pixel.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    /// Always 0
+    Zero,
+    /// Always 255
+    One,
+}
+
+/// The sources of the four bytes of an output pixel, in order.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::{BcnDecoderFormat, BcnEncoding, Channel, Swizzle};
+///
+/// // red in every channel but alpha
+/// let format = BcnDecoderFormat::Custom(Swizzle([Channel::Red, Channel::Red, Channel::Red, Channel::Alpha]));
+/// let block = [0x00, 0xf8, 0x00, 0xf8, 0, 0, 0, 0];
+/// let pixels = bcndecode::decode(&block, 4, 4, BcnEncoding::Bc1, format).unwrap();
+/// assert_eq!(pixels[..4], [0xff, 0xff, 0xff, 0xff]);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Swizzle(pub [Channel; 4]);
+
+impl Swizzle {
+    /// Returns the output pixel of an RGBA pixel.
+    pub fn apply(self, rgba: [u8; 4]) -> [u8; 4] {
+        self.0.map(|channel| match channel {
+            Channel::Red => rgba[0],
+            Channel::Green => rgba[1],
+            Channel::Blue => rgba[2],
+            Channel::Alpha => rgba[3],
+            Channel::Zero => 0,
+            Channel::One => 0xff,
+        })
+    }
+}
+
+/// How the decoding work is spread over threads.
+///
+/// The image is split into bands of whole block rows, one per thread, which
+/// are decoded into disjoint parts of the output. With the `rayon` feature,
+/// the bands are decoded on the current rayon thread pool, and "all
+/// available cores" means all threads of that pool; otherwise every band
+/// gets a thread of its own.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DecodeStrategy {
+    /// Decode small images on the calling thread and large ones on all
+    /// available cores.
+    #[default]
+    Auto,
+    /// Always decode on the calling thread.
+    Serial,
+    /// Decode bands of the image on the given number of threads, or on all
+    /// available cores if it is 0.
+    Threaded(usize),
+}
+
+/// Arrangement of the pixels in the decoded image.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum OutputLayout {
+    /// Rows of pixels, as described for `decode`.
+    #[default]
+    Linear,
+    /// Square tiles of the given number of pixels, which must be a multiple
+    /// of 4. Tiles are stored row by row, each one as rows of pixels, top
+    /// row first. Tiles on the right and bottom edge are padded to full
+    /// size with zeros.
+    Tiled(usize),
+}
+
+/// Arrangement of the blocks in the source of `decode_with_options`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SourceLayout {
+    /// Rows of blocks, top row first.
+    #[default]
+    Linear,
+    /// Tegra X1 block-linear layout of Nintendo Switch textures with the
+    /// given block height in GOBs, see `deswizzle_tegra`.
+    TegraBlockLinear(usize),
+    /// PS4 texture level with the given tile mode, see `detile_gnf`.
+    GnfTiled(GnfTileMode),
+    /// Blocks in Morton order, see `morton::deswizzle`.
+    Morton,
+}
+
+/// Mapping of the values of single-channel output.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ValueTransform<'a> {
+    /// Values are left as decoded.
+    #[default]
+    Identity,
+    /// Every value `x` becomes `255 - x`, e.g. to turn a gloss map into a
+    /// roughness map.
+    Invert,
+    /// Every value `x` becomes `curve[x]`.
+    Curve(&'a [u8; 256]),
+}
+
+/// How the channels of a BC5 normal map are turned into normals.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum NormalMap {
+    /// The two channels are left as decoded, with blue set to 0.
+    #[default]
+    Raw,
+    /// Red and green hold X and Y; Z is computed from them and the vector
+    /// is renormalized to unit length, then stored in red, green and blue
+    /// mapped from -1..1 to 0..255. Compression error otherwise leaves
+    /// normals that are not unit length.
+    Reconstructed,
+    /// Red and green hold the octahedral mapping of the normal; the normal
+    /// is unfolded from it, renormalized and stored like `Reconstructed`.
+    Octahedral,
+}
+
+/// How the signed values of BC4 and BC5 are stored in 8-bit output.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SignedMapping {
+    /// Values from -1 to 1 are mapped to 0 to 255, so a decoded byte `x`
+    /// stands for `x / 127.5 - 1`, as `normal_map` expects.
+    #[default]
+    Unorm,
+    /// Values are stored as `i8` from -127 to 127, as the SNORM formats
+    /// store their endpoints, so a decoded byte `x` stands for
+    /// `x as i8 / 127`. Use `decode_snorm16` for more precision, or
+    /// `decode_to_f32_with_options` for floats from -1 to 1.
+    Snorm,
+}
+
+/// Options of `decode_with_options`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DecodeOptions<'a> {
+    /// How the decoding work is spread over threads
+    pub strategy: DecodeStrategy,
+    /// Whether to replace non-finite BC6H values: NaN by 0 and infinities
+    /// by the largest finite half float of the same sign
+    pub sanitize_floats: bool,
+    /// Arrangement of the pixels in the output
+    pub layout: OutputLayout,
+    /// Arrangement of the blocks in the source. Swizzled sources are
+    /// reordered into rows of blocks before they are decoded, which
+    /// `strict` then checks.
+    pub source_layout: SourceLayout,
+    /// Color grading applied to the RGB channels of every pixel as it is
+    /// written. 8-bit channels are mapped from 0 to 1 and back, BC6H values
+    /// are looked up as they are. Cannot be used with BC4, which has no
+    /// color.
+    pub lut: Option<&'a Lut3d>,
+    /// Mapping applied to every value of BC4 output as it is written. Can
+    /// only be used with BC4.
+    pub value_transform: ValueTransform<'a>,
+    /// Reconstruction of the normals of BC5 output as it is written. Can
+    /// only be used with BC5.
+    pub normal_map: NormalMap,
+    /// Whether to swap the two channels of BC5 output as they are decoded,
+    /// for files written with Y in the first channel. The swap comes before
+    /// `normal_map`. Can only be used with BC5.
+    pub swap_bc5_channels: bool,
+    /// Whether the source holds signed values, as in the BC4_SNORM,
+    /// BC5_SNORM and BC6H_SF16 formats. BC4 and BC5 values from -1 to 1
+    /// are stored as `signed_mapping` says, by default mapped to 0 to 255;
+    /// BC6H values keep their sign. Cannot be used with the other encodings.
+    pub signed: bool,
+    /// How the signed values of BC4 and BC5 are stored; see `signed`. Only
+    /// the default can be used without `signed` or with `normal_map`.
+    pub signed_mapping: SignedMapping,
+    /// Whether the source holds sRGB encoded colors, as in the `*_SRGB`
+    /// formats. Red, green and blue are converted to linear values, rounded
+    /// to 8 bits, as they are decoded, the way GPUs sample such textures;
+    /// alpha is left alone. The conversion comes before `lut`. Can only be
+    /// used with BC1, BC2, BC3 and BC7.
+    pub srgb: bool,
+    /// Whether the source holds colors premultiplied by alpha, as in the
+    /// DXT2 and DXT4 formats. Red, green and blue are divided by alpha as
+    /// they are decoded, before the sRGB conversion; pixels with an alpha of
+    /// 0 are left as they are. Can only be used with BC1, BC2, BC3 and BC7.
+    pub unpremultiply: bool,
+    /// Whether BC1 blocks are opaque, like the DXGI BC1 formats without
+    /// alpha: the 3-color mode still decodes index 3 as black, but with an
+    /// alpha of 255 instead of 0. Can only be used with BC1.
+    pub bc1_opaque: bool,
+    /// Whether to store the rows bottom row first, e.g. for OpenGL, or top
+    /// row first. `None` keeps the behavior of `decode`, which flips images
+    /// whose width or height is not a multiple of 4. Only top row first can
+    /// be used with the tiled layout.
+    pub flip_y: Option<bool>,
+    /// Distance between the starts of two rows of the output in bytes, or
+    /// 0 for tightly packed rows. Must be at least the size of a row; the
+    /// output is then `row_pitch * height` bytes long, with zeros between
+    /// the rows. Can only be used with the linear layout.
+    pub row_pitch: usize,
+    /// Whether to reject corrupt sources with `Error::InvalidBlock`: data
+    /// that does not hold exactly the blocks of the image, or blocks with
+    /// reserved modes. The first problem in storage order is reported.
+    /// Otherwise missing blocks and reserved modes are decoded as zeros and
+    /// data after the last block is ignored.
+    pub strict: bool,
+}
+
+/// A single mip level of a texture container.
+#[derive(Debug, Clone, Copy)]
+pub struct MipLevel<'a> {
+    /// Width of the level in pixels
+    pub width: usize,
+    /// Height of the level in pixels
+    pub height: usize,
+    /// Compressed data of the level
+    pub data: &'a [u8],
 }
 
 /// Decodes the given BCN encoded image data.
@@ -174,3 +676,231 @@ pub fn decode(
 ) -> Result<Vec<u8>, Error> {
     decode::decode_rust(source, width, height, encoding, format)
 }
+
+/// Decodes the given BCN encoded image data with the given options.
+///
+/// Works like `decode`, which uses the default options.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::{DecodeOptions, DecodeStrategy};
+///
+/// let block = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+/// let options = DecodeOptions {
+///     strategy: DecodeStrategy::Serial,
+///     ..Default::default()
+/// };
+/// let pixels = bcndecode::decode_with_options(
+///     &block,
+///     4,
+///     4,
+///     bcndecode::BcnEncoding::Bc1,
+///     bcndecode::BcnDecoderFormat::RGBA,
+///     &options,
+/// ).unwrap();
+/// assert_eq!(pixels.len(), 4 * 4 * 4);
+/// ```
+pub fn decode_with_options(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: &DecodeOptions,
+) -> Result<Vec<u8>, Error> {
+    decode::decode_with_options(source, width, height, encoding, format, options)
+}
+
+/// Decodes the given BCN encoded image data into 32-bit float RGBA values.
+///
+/// Every encoding comes out in the same layout, four floats per pixel, top
+/// row first, so images of any encoding can be processed alike. 8-bit
+/// channels are mapped to [0, 1] and BC6H values are passed through with an
+/// alpha of 1. BC4 values are repeated in red, green and blue.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters.
+pub fn decode_to_f32(source: &[u8], width: usize, height: usize, encoding: BcnEncoding) -> Result<Vec<f32>, Error> {
+    Ok(decode::decode_rgba_f32(source, width, height, encoding)?.concat())
+}
+
+/// Decodes the given BCN encoded image data into 32-bit float RGBA values
+/// with the given options.
+///
+/// Works like `decode_to_f32`, which uses the default options. With
+/// `DecodeOptions::signed`, BC4 and BC5 channels are mapped to [-1, 1],
+/// so -127 becomes -1 and 127 becomes 1, and BC6H values keep their sign.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters.
+/// Returns `Error::InvalidImageSize` if the `layout` or `row_pitch` option is set, as the
+/// output is always linear.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::{BcnEncoding, DecodeOptions};
+///
+/// // a signed BC4 block of -127
+/// let block = [0x81, 0x7f, 0, 0, 0, 0, 0, 0];
+/// let options = DecodeOptions {
+///     signed: true,
+///     ..Default::default()
+/// };
+/// let pixels = bcndecode::decode_to_f32_with_options(&block, 4, 4, BcnEncoding::Bc4, &options).unwrap();
+/// assert_eq!(pixels[..4], [-1.0, -1.0, -1.0, 1.0]);
+/// ```
+pub fn decode_to_f32_with_options(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    options: &DecodeOptions,
+) -> Result<Vec<f32>, Error> {
+    Ok(decode::decode_rgba_f32_with_options(source, width, height, encoding, options)?.concat())
+}
+
+/// Decodes the given signed BC4 or BC5 encoded image data into 16-bit
+/// values.
+///
+/// Every value from -1 to 1 is stored as an `i16` from -32767 to 32767, as
+/// in the `R16_SNORM` and `R16G16_SNORM` formats: one per pixel for BC4,
+/// red and green for BC5. The values are interpolated without rounding to
+/// 8 bits first. Rows are tightly packed, top row first, and are never
+/// flipped.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters.
+/// Returns `Error::InvalidPixelFormat` for the encodings other than BC4 and BC5.
+///
+/// # Examples
+///
+/// ```
+/// // a signed BC4 block of -127
+/// let block = [0x81, 0x7f, 0, 0, 0, 0, 0, 0];
+/// let values = bcndecode::decode_snorm16(&block, 4, 4, bcndecode::BcnEncoding::Bc4).unwrap();
+/// assert_eq!(values, vec![-32767; 16]);
+/// ```
+pub fn decode_snorm16(source: &[u8], width: usize, height: usize, encoding: BcnEncoding) -> Result<Vec<i16>, Error> {
+    decode::decode_snorm16(source, width, height, encoding)
+}
+
+/// Decodes the given BC6H encoded image data into 32-bit floats.
+///
+/// Returns the values that `decode_with_options` stores as bytes: red,
+/// green and blue of every pixel in the same order, including flipping and
+/// the `layout` and `row_pitch` options, without the unused space at the
+/// end of its buffer.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters
+/// or the `row_pitch` is not a multiple of 4.
+///
+/// # Examples
+///
+/// ```
+/// // a mode 0 block with black endpoints
+/// let block = [0u8; 16];
+/// let pixels = bcndecode::decode_bc6h(&block, 4, 4, &Default::default()).unwrap();
+/// assert_eq!(pixels, vec![0.0; 3 * 4 * 4]);
+/// ```
+pub fn decode_bc6h(source: &[u8], width: usize, height: usize, options: &DecodeOptions) -> Result<Vec<f32>, Error> {
+    decode::decode_bc6h(source, width, height, options)
+}
+
+/// Decodes the given BC6H encoded image data into 16-bit half floats.
+///
+/// Every pixel is stored as four halves, red, green, blue and an alpha of
+/// 1, given by their bits, top row first, ready to be uploaded as
+/// `R16G16B16A16_FLOAT`. The output takes half the memory of `decode`, and
+/// the image is never held as floats as a whole. The values are the same
+/// as those of `decode_bc6h`.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters.
+/// Returns `Error::InvalidImageSize` if the `layout` or `row_pitch` option is set, as the
+/// output is always linear.
+///
+/// # Examples
+///
+/// ```
+/// let block = [0u8; 16];
+/// let pixels = bcndecode::decode_bc6h_f16(&block, 4, 4, &Default::default()).unwrap();
+/// assert_eq!(pixels[..4], [0, 0, 0, 0x3c00]);
+/// ```
+pub fn decode_bc6h_f16(source: &[u8], width: usize, height: usize, options: &DecodeOptions) -> Result<Vec<u16>, Error> {
+    decode::decode_bc6h_f16(source, width, height, options)
+}
+
+/// Decodes the given BCN encoded image data into uninitialized memory.
+///
+/// Works like `decode`, but writes into the start of `dst`, e.g. freshly
+/// mapped GPU staging memory, which does not have to be zeroed first. Every
+/// byte of the returned slice, which holds the decoded image, is written;
+/// the rest of `dst` is left untouched.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters
+/// or `dst` is too small for the decoded image.
+///
+/// # Examples
+///
+/// ```
+/// use std::mem::MaybeUninit;
+///
+/// let block = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+/// let mut staging = vec![MaybeUninit::uninit(); 256];
+/// let pixels = bcndecode::decode_into_uninit(
+///     &block,
+///     4,
+///     4,
+///     bcndecode::BcnEncoding::Bc1,
+///     bcndecode::BcnDecoderFormat::RGBA,
+///     &mut staging,
+/// ).unwrap();
+/// assert_eq!(pixels, &[0xff; 64][..]);
+/// ```
+pub fn decode_into_uninit<'a>(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    dst: &'a mut [MaybeUninit<u8>],
+) -> Result<&'a mut [u8], Error> {
+    decode::decode_into_uninit(source, width, height, encoding, format, &DecodeOptions::default(), dst)
+}
+
+/// Decodes the given BCN encoded image data into a buffer allocated from
+/// `alloc`.
+///
+/// Works like `decode_with_options`, for callers that need the output in a
+/// particular heap, e.g. an arena or memory shared with a GPU. Requires the
+/// `allocator_api` feature, which needs a nightly compiler.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters.
+#[cfg(feature = "allocator_api")]
+pub fn decode_in<A: Allocator>(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: &DecodeOptions,
+    alloc: A,
+) -> Result<Vec<u8, A>, Error> {
+    decode::decode_in(source, width, height, encoding, format, options, alloc)
+}
@@ -0,0 +1,141 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Color grading of the decoded pixels through a 3D lookup table.
+
+use super::Error;
+
+/// A 3D color lookup table, as used for color grading.
+///
+/// Maps RGB colors within a cube, the domain, to new colors; colors between
+/// the entries of the table are interpolated trilinearly. Set it as the
+/// `lut` of `DecodeOptions` to grade the pixels as they are decoded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut3d {
+    size: usize,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    // red changes fastest, then green, then blue
+    table: Vec<[f32; 3]>,
+}
+
+impl Lut3d {
+    /// Creates a table of `size` entries per axis over the domain 0 to 1.
+    ///
+    /// The entries are ordered with red changing fastest, then green, then
+    /// blue.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidImageSize` if `size` is less than 2 or the
+    /// table does not hold `size³` entries.
+    pub fn new(size: usize, table: Vec<[f32; 3]>) -> Result<Lut3d, Error> {
+        if size < 2 || size.checked_pow(3) != Some(table.len()) {
+            return Err(Error::InvalidImageSize);
+        }
+        Ok(Lut3d {
+            size,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            table,
+        })
+    }
+
+    /// Parses a table in the Adobe/Resolve `.cube` format.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::FeatureNotImplemented` for 1D tables and
+    /// `Error::ImageDecodingError` if the text is not a valid 3D table.
+    pub fn from_cube(text: &str) -> Result<Lut3d, Error> {
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut table = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("TITLE") => {}
+                Some("LUT_1D_SIZE") => return Err(Error::FeatureNotImplemented),
+                Some("LUT_3D_SIZE") => {
+                    size = Some(fields.next().and_then(|n| n.parse().ok()).ok_or(Error::ImageDecodingError)?);
+                }
+                Some("DOMAIN_MIN") => domain_min = parse_triple(fields)?,
+                Some("DOMAIN_MAX") => domain_max = parse_triple(fields)?,
+                _ => table.push(parse_triple(line.split_whitespace())?),
+            }
+        }
+
+        let size = size.ok_or(Error::ImageDecodingError)?;
+        if (0..3).any(|i| domain_min[i] >= domain_max[i]) {
+            return Err(Error::ImageDecodingError);
+        }
+        let mut lut = Lut3d::new(size, table).map_err(|_| Error::ImageDecodingError)?;
+        lut.domain_min = domain_min;
+        lut.domain_max = domain_max;
+        Ok(lut)
+    }
+
+    /// Looks up a color, interpolating between the nearest entries. Colors
+    /// outside the domain are clamped to it.
+    pub fn sample(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let n = self.size;
+        let mut base = [0; 3];
+        let mut fraction = [0.0; 3];
+        for i in 0..3 {
+            let t = (rgb[i] - self.domain_min[i]) / (self.domain_max[i] - self.domain_min[i]);
+            // NaN ends up at the bottom of the domain
+            let position = if t > 0.0 { t.min(1.0) * (n - 1) as f32 } else { 0.0 };
+            base[i] = (position as usize).min(n - 2);
+            fraction[i] = position - base[i] as f32;
+        }
+
+        let mut result = [0.0; 3];
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut index = 0;
+            for (axis, stride) in [1, n, n * n].iter().enumerate() {
+                let upper = (corner >> axis) & 1;
+                weight *= if upper == 1 { fraction[axis] } else { 1.0 - fraction[axis] };
+                index += (base[axis] + upper) * stride;
+            }
+            for (r, &value) in result.iter_mut().zip(&self.table[index]) {
+                *r += weight * value;
+            }
+        }
+        result
+    }
+}
+
+fn parse_triple<'a, I: Iterator<Item = &'a str>>(mut fields: I) -> Result<[f32; 3], Error> {
+    let mut triple = [0.0; 3];
+    for value in triple.iter_mut() {
+        *value = fields.next().and_then(|f| f.parse().ok()).ok_or(Error::ImageDecodingError)?;
+    }
+    if fields.next().is_some() {
+        return Err(Error::ImageDecodingError);
+    }
+    Ok(triple)
+}
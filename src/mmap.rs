@@ -0,0 +1,68 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Decoding straight into memory-mapped output files.
+
+use std::fs::OpenOptions;
+use std::io;
+use std::mem::MaybeUninit;
+use std::path::Path;
+
+use memmap2::MmapMut;
+
+use super::decode::{decode_into_uninit, layout_size, output_params};
+use super::{BcnDecoderFormat, BcnEncoding, DecodeOptions};
+
+/// Decodes the given BCN encoded image data into a new memory-mapped file.
+///
+/// Creates the file at `path`, or truncates it if it exists, sizes it for
+/// the decoded image and decodes into the mapping, so the decoded image
+/// never has to fit into memory as a whole. The file holds the same bytes
+/// as `decode_with_options` would return; call `flush` on the returned
+/// mapping to make sure they reach the disk before it is dropped. Requires
+/// the `mmap` feature.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters
+/// or the file cannot be created or mapped.
+pub fn decode_to_file<P: AsRef<Path>>(
+    path: P,
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: &DecodeOptions,
+) -> io::Result<MmapMut> {
+    // validate before touching the file system
+    let size = output_params(width, height, encoding, format)?;
+    let size = layout_size(size, width, height, encoding, format, options)?;
+
+    let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+    file.set_len(size as u64)?;
+    // the file is private to this call until the mapping is returned
+    let mut map = unsafe { MmapMut::map_mut(&file)? };
+
+    // every byte is initialized, so it can be written as uninitialized memory
+    let dst = unsafe { &mut *(&mut map[..] as *mut [u8] as *mut [MaybeUninit<u8>]) };
+    decode_into_uninit(source, width, height, encoding, format, options, dst)?;
+    Ok(map)
+}
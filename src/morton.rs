@@ -0,0 +1,160 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Reordering of blocks stored in Morton (Z-order).
+//!
+//! Consoles and PVRTC textures often store the blocks of an image in
+//! Morton order, interleaving the bits of the x and y coordinate so that
+//! neighboring blocks stay close in memory. The image is padded to a power
+//! of two in both directions; if it is not square, the Morton order covers
+//! squares of the smaller size, which follow each other along the longer
+//! side.
+//!
+//! The functions here work on blocks of any byte size, so they can also be
+//! applied to formats this crate does not decode.
+//! `SourceLayout::Morton` runs `deswizzle` before decoding:
+//!
+//! ```
+//! use bcndecode::{morton, BcnDecoderFormat, BcnEncoding, DecodeOptions, SourceLayout};
+//!
+//! // in a 16x8 image of 4x2 BC1 blocks, the first block of the second row
+//! // comes third
+//! assert_eq!(morton::block_index(0, 1, 4, 2), 2);
+//!
+//! let mut swizzled = [0; 64];
+//! swizzled[16..20].copy_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+//! let options = DecodeOptions {
+//!     source_layout: SourceLayout::Morton,
+//!     ..Default::default()
+//! };
+//! let pixels = bcndecode::decode_with_options(&swizzled, 16, 8, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, &options).unwrap();
+//! assert_eq!(pixels[4 * 16 * 4..][..4], [0xff, 0xff, 0xff, 0xff]);
+//! //! ```
+
+use super::Error;
+
+/// Interleaves the bits of `x` and `y`, starting with the lowest bit of `x`.
+///
+/// Coordinates of up to 32 bits are supported; higher bits are ignored.
+pub fn interleave(x: usize, y: usize) -> u64 {
+    spread(x as u32) | spread(y as u32) << 1
+}
+
+/// Splits a Morton index into its `x` and `y` coordinate, the inverse of
+/// `interleave`.
+pub fn deinterleave(index: u64) -> (usize, usize) {
+    (compact(index) as usize, compact(index >> 1) as usize)
+}
+
+/// Returns the position of the block at `x`, `y` in an image of the given
+/// size in blocks, stored in Morton order.
+///
+/// `x` and `y` must lie within the image.
+pub fn block_index(x: usize, y: usize, width: usize, height: usize) -> usize {
+    // the size of the Morton squares
+    let side = width.next_power_of_two().min(height.next_power_of_two());
+    let square = if width > height { x / side } else { y / side };
+    square * side * side + interleave(x % side, y % side) as usize
+}
+
+/// Returns the size in bytes of an image of the given size in blocks,
+/// stored in Morton order.
+///
+/// # Errors
+///
+/// Returns `Error::InvalidImageSize` if the size is 0 or too large.
+pub fn swizzled_size(width: usize, height: usize, block_size: usize) -> Result<usize, Error> {
+    if width == 0 || height == 0 || block_size == 0 {
+        return Err(Error::InvalidImageSize);
+    }
+    width
+        .checked_next_power_of_two()
+        .zip(height.checked_next_power_of_two())
+        .and_then(|(width, height)| width.checked_mul(height))
+        .and_then(|blocks| blocks.checked_mul(block_size))
+        .ok_or(Error::InvalidImageSize)
+}
+
+/// Reorders an image stored in Morton order into rows of blocks.
+///
+/// `width` and `height` are the size of the image in blocks and
+/// `block_size` the size of a block in bytes. The result holds exactly the
+/// blocks of the image, without the padding.
+///
+/// # Errors
+///
+/// Returns `Error::InvalidImageSize` if the size of the image or block is 0
+/// or `source` is shorter than `swizzled_size`.
+pub fn deswizzle(source: &[u8], width: usize, height: usize, block_size: usize) -> Result<Vec<u8>, Error> {
+    if source.len() < swizzled_size(width, height, block_size)? {
+        return Err(Error::InvalidImageSize);
+    }
+
+    let mut linear = Vec::with_capacity(width * height * block_size);
+    for y in 0..height {
+        for x in 0..width {
+            let offset = block_index(x, y, width, height) * block_size;
+            linear.extend_from_slice(&source[offset..offset + block_size]);
+        }
+    }
+    Ok(linear)
+}
+
+/// Reorders rows of blocks into Morton order, the inverse of `deswizzle`.
+///
+/// The padding of the result is filled with zeros.
+///
+/// # Errors
+///
+/// Returns `Error::InvalidImageSize` if the size of the image or block is 0
+/// or `source` is shorter than the blocks of the image.
+pub fn swizzle(source: &[u8], width: usize, height: usize, block_size: usize) -> Result<Vec<u8>, Error> {
+    let mut swizzled = vec![0; swizzled_size(width, height, block_size)?];
+    if source.len() < width * height * block_size {
+        return Err(Error::InvalidImageSize);
+    }
+
+    for (n, block) in source.chunks_exact(block_size).take(width * height).enumerate() {
+        let offset = block_index(n % width, n / width, width, height) * block_size;
+        swizzled[offset..offset + block_size].copy_from_slice(block);
+    }
+    Ok(swizzled)
+}
+
+/// Moves the bits of `v` to the even positions.
+fn spread(v: u32) -> u64 {
+    let mut v = v as u64;
+    v = (v | v << 16) & 0x0000_ffff_0000_ffff;
+    v = (v | v << 8) & 0x00ff_00ff_00ff_00ff;
+    v = (v | v << 4) & 0x0f0f_0f0f_0f0f_0f0f;
+    v = (v | v << 2) & 0x3333_3333_3333_3333;
+    (v | v << 1) & 0x5555_5555_5555_5555
+}
+
+/// Gathers the even bits of `v`, the inverse of `spread`.
+fn compact(v: u64) -> u32 {
+    let mut v = v & 0x5555_5555_5555_5555;
+    v = (v | v >> 1) & 0x3333_3333_3333_3333;
+    v = (v | v >> 2) & 0x0f0f_0f0f_0f0f_0f0f;
+    v = (v | v >> 4) & 0x00ff_00ff_00ff_00ff;
+    v = (v | v >> 8) & 0x0000_ffff_0000_ffff;
+    ((v | v >> 16) & 0xffff_ffff) as u32
+}
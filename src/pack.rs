@@ -0,0 +1,72 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Interleaving of single-channel textures into one image.
+
+use std::convert::TryInto;
+
+use super::const_decode::decode_bc4_block;
+use super::decode::output_params;
+use super::{BcnDecoderFormat, BcnEncoding, Error};
+
+/// Decodes two or three BC4 images of the same size and interleaves them
+/// into one image with a channel per source.
+///
+/// Meant for material textures stored as separate channels, e.g. roughness,
+/// metallic and ambient occlusion, that are wanted as a single RG or RGB
+/// image. The sources are decoded block by block straight into the output,
+/// which has the rows in the order of `decode`. Blocks missing from a
+/// source decode to zeros.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the
+/// given parameters or there are not two or three sources.
+pub fn pack_bc4(sources: &[&[u8]], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    output_params(width, height, BcnEncoding::Bc4, BcnDecoderFormat::LUM)?;
+    let channels = sources.len();
+    if channels != 2 && channels != 3 {
+        return Err(Error::InvalidPixelFormat);
+    }
+
+    // the decoder flips images that are not a multiple of the block size
+    let flipped = ((width & 3) | (height & 3)) != 0;
+    let blocks_per_row = width.div_ceil(4);
+    let mut pixels = vec![0; channels * width * height];
+    for block_y in 0..height.div_ceil(4) {
+        for block_x in 0..blocks_per_row {
+            let offset = 8 * (block_y * blocks_per_row + block_x);
+            for (channel, source) in sources.iter().enumerate() {
+                let values = match source.get(offset..offset + 8) {
+                    Some(block) => decode_bc4_block(block.try_into().unwrap()),
+                    None => [0; 16],
+                };
+                for (n, &value) in values.iter().enumerate() {
+                    let (x, y) = (4 * block_x + n % 4, 4 * block_y + n / 4);
+                    if x < width && y < height {
+                        let row = if flipped { height - 1 - y } else { y };
+                        pixels[channels * (row * width + x) + channel] = value;
+                    }
+                }
+            }
+        }
+    }
+    Ok(pixels)
+}
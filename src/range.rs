@@ -0,0 +1,82 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Byte ranges of compressed data, for fetching textures in parts, e.g.
+//! with HTTP range requests.
+
+use std::ops::Range;
+
+use super::decode::{block_size, compressed_size};
+use super::{BcnEncoding, Error, Rect};
+
+/// Returns the bytes of a compressed image needed to decode a rectangle.
+///
+/// Blocks are stored row by row, so the range runs from the first block of
+/// the rectangle to its last one and includes the blocks to the left and
+/// right of it in the rows between.
+///
+/// # Errors
+///
+/// Returns `Error::InvalidImageSize` if the rectangle is empty or does not
+/// fit into the image.
+pub fn required_byte_range(
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    rect: &Rect,
+) -> Result<Range<usize>, Error> {
+    if rect.width == 0 || rect.height == 0 || rect.x + rect.width > width || rect.y + rect.height > height {
+        return Err(Error::InvalidImageSize);
+    }
+
+    let blocks_per_row = width.div_ceil(4);
+    let first = blocks_per_row * (rect.y / 4) + rect.x / 4;
+    let last = blocks_per_row * ((rect.y + rect.height - 1) / 4) + (rect.x + rect.width - 1) / 4;
+    let block_size = block_size(encoding);
+    Ok(first * block_size..(last + 1) * block_size)
+}
+
+/// Returns the bytes of a mip level in a chain of levels stored largest
+/// first without any padding between them, as in DDS and Godot textures.
+///
+/// Level 0 is the base level of the given size; every further level halves
+/// the size, down to 1x1 pixels.
+///
+/// # Errors
+///
+/// Returns `Error::InvalidImageSize` if the size is 0 or the chain ends
+/// before the level.
+pub fn mip_byte_range(width: usize, height: usize, encoding: BcnEncoding, level: usize) -> Result<Range<usize>, Error> {
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidImageSize);
+    }
+
+    let (mut w, mut h) = (width, height);
+    let mut offset = 0;
+    for _ in 0..level {
+        if w == 1 && h == 1 {
+            return Err(Error::InvalidImageSize);
+        }
+        offset += compressed_size(w, h, encoding);
+        w = (w >> 1).max(1);
+        h = (h >> 1).max(1);
+    }
+    Ok(offset..offset + compressed_size(w, h, encoding))
+}
@@ -0,0 +1,276 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Decoding of uncompressed pixel formats found next to BCn data, e.g. in
+//! DDS files.
+
+use super::decode::{float_to_half, half_to_float, rgba_565};
+use super::{BcnDecoderFormat, Error};
+
+/// Uncompressed pixel format of the source data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RawFormat {
+    /// 8-bit luminance (`D3DFMT_L8`)
+    L8,
+    /// 8-bit alpha (`D3DFMT_A8`, `DXGI_FORMAT_A8_UNORM`)
+    A8,
+    /// 8-bit luminance followed by 8-bit alpha (`D3DFMT_A8L8`)
+    A8L8,
+    /// 8-bit red followed by 8-bit green (`DXGI_FORMAT_R8G8_UNORM`)
+    R8G8,
+    /// 8-bit blue, green and red, in this order in memory (`D3DFMT_R8G8B8`,
+    /// a DDS RGB format of 24 bits with a red mask of 0xff0000)
+    R8G8B8,
+    /// 8-bit red, green and blue, in this order in memory (a DDS RGB format
+    /// of 24 bits with a red mask of 0xff)
+    B8G8R8,
+    /// 16 bits of 5-bit red, 6-bit green and 5-bit blue, from the most
+    /// significant bit down (`D3DFMT_R5G6B5`, `DXGI_FORMAT_B5G6R5_UNORM`)
+    B5G6R5,
+    /// 16 bits of 1-bit alpha and 5-bit red, green and blue, from the most
+    /// significant bit down (`D3DFMT_A1R5G5B5`, `DXGI_FORMAT_B5G5R5A1_UNORM`)
+    B5G5R5A1,
+    /// Half float red (`D3DFMT_R16F`, `DXGI_FORMAT_R16_FLOAT`)
+    R16F,
+    /// Half float red and green (`D3DFMT_G16R16F`,
+    /// `DXGI_FORMAT_R16G16_FLOAT`)
+    RG16F,
+    /// Half float red, green, blue and alpha (`D3DFMT_A16B16G16R16F`,
+    /// `DXGI_FORMAT_R16G16B16A16_FLOAT`)
+    RGBA16F,
+}
+
+/// Size of the floats in the output of `decode_raw_float`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FloatPrecision {
+    /// 32-bit floats
+    F32,
+    /// 16-bit half floats
+    F16,
+}
+
+impl RawFormat {
+    /// Returns the number of bytes of a pixel.
+    pub fn pixel_size(self) -> usize {
+        match self {
+            RawFormat::L8 | RawFormat::A8 => 1,
+            RawFormat::A8L8 | RawFormat::R8G8 | RawFormat::R16F => 2,
+            RawFormat::B5G6R5 | RawFormat::B5G5R5A1 => 2,
+            RawFormat::R8G8B8 | RawFormat::B8G8R8 => 3,
+            RawFormat::RG16F => 4,
+            RawFormat::RGBA16F => 8,
+        }
+    }
+
+    /// Returns whether the pixels are made of half floats.
+    fn is_float(self) -> bool {
+        matches!(self, RawFormat::R16F | RawFormat::RG16F | RawFormat::RGBA16F)
+    }
+
+    /// Expands a pixel to 8-bit RGBA. Floats are clamped to [0, 1].
+    fn rgba(self, p: &[u8]) -> [u8; 4] {
+        match self {
+            RawFormat::L8 => [p[0], p[0], p[0], 0xff],
+            RawFormat::A8 => [0, 0, 0, p[0]],
+            RawFormat::A8L8 => [p[0], p[0], p[0], p[1]],
+            RawFormat::R8G8 => [p[0], p[1], 0, 0xff],
+            RawFormat::R8G8B8 => [p[2], p[1], p[0], 0xff],
+            RawFormat::B8G8R8 => [p[0], p[1], p[2], 0xff],
+            RawFormat::B5G6R5 => rgba_565(u16::from_le_bytes([p[0], p[1]])),
+            RawFormat::B5G5R5A1 => {
+                let x = u16::from_le_bytes([p[0], p[1]]);
+                let expand = |shift: u16| {
+                    let v = ((x >> shift) & 0x1f) as u8;
+                    (v << 3) | (v >> 2)
+                };
+                [expand(10), expand(5), expand(0), if x & 0x8000 != 0 { 0xff } else { 0 }]
+            }
+            _ => self.rgba_f16(p).map(|h| (half_to_float(h).clamp(0.0, 1.0) * 255.0).round() as u8),
+        }
+    }
+
+    /// Expands a pixel to half float RGBA. 8-bit channels are mapped to
+    /// [0, 1].
+    fn rgba_f16(self, p: &[u8]) -> [u16; 4] {
+        // 0 and 1
+        const ZERO: u16 = 0;
+        const ONE: u16 = 0x3c00;
+        let h = |c: usize| u16::from_le_bytes([p[2 * c], p[2 * c + 1]]);
+        match self {
+            RawFormat::R16F => [h(0), ZERO, ZERO, ONE],
+            RawFormat::RG16F => [h(0), h(1), ZERO, ONE],
+            RawFormat::RGBA16F => [h(0), h(1), h(2), h(3)],
+            _ => self.rgba(p).map(|v| float_to_half(v as f32 / 255.0)),
+        }
+    }
+}
+
+/// Decodes uncompressed pixels into the given output format.
+///
+/// Pixels are expanded to RGBA the way GPUs sample them: luminance is
+/// repeated in red, green and blue, missing color channels are 0 and
+/// missing alpha is opaque. Half floats are clamped to [0, 1]; use
+/// `decode_raw_float` to keep their range. The LUM format is only accepted
+/// for L8, which is copied as is. Rows are tightly packed, in the source
+/// and the output, and are never flipped.
+///
+/// # Errors
+///
+/// This function will return an error if the size of the image is 0,
+/// `source` is too short for the image or the output format is LUM for
+/// other source formats than L8.
+pub fn decode_raw(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    raw_format: RawFormat,
+    format: BcnDecoderFormat,
+) -> Result<Vec<u8>, Error> {
+    let pitch = width.checked_mul(raw_format.pixel_size()).ok_or(Error::InvalidImageSize)?;
+    decode_raw_with_pitch(source, width, height, raw_format, format, pitch)
+}
+
+/// Decodes uncompressed pixels whose rows are `pitch` bytes apart.
+///
+/// Works like `decode_raw`, for writers that pad the rows, e.g. of 24-bit
+/// formats to a multiple of 4 bytes. The output is tightly packed.
+///
+/// # Errors
+///
+/// This function will return an error if the size of the image is 0,
+/// `pitch` is smaller than a row, `source` is too short for the image or
+/// the output format is LUM for other source formats than L8.
+pub fn decode_raw_with_pitch(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    raw_format: RawFormat,
+    format: BcnDecoderFormat,
+    pitch: usize,
+) -> Result<Vec<u8>, Error> {
+    let rows = checked_rows(source, width, height, raw_format, pitch)?;
+
+    let positions = match (format, raw_format) {
+        (BcnDecoderFormat::LUM, RawFormat::L8) => return Ok(rows.collect::<Vec<_>>().concat()),
+        (BcnDecoderFormat::LUM, _) => return Err(Error::InvalidPixelFormat),
+        (format, _) => channel_positions(format),
+    };
+    // the 3-byte formats leave out alpha, which comes last
+    let size = match format {
+        BcnDecoderFormat::RGB | BcnDecoderFormat::BGR => 3,
+        _ => 4,
+    };
+    let mut pixels = vec![0; size * width * height];
+    let sources = rows.flat_map(|row| row.chunks_exact(raw_format.pixel_size()));
+    for (src, dst) in sources.zip(pixels.chunks_exact_mut(size)) {
+        let rgba = raw_format.rgba(src);
+        if let BcnDecoderFormat::Custom(swizzle) = format {
+            dst.copy_from_slice(&swizzle.apply(rgba));
+            continue;
+        }
+        for c in 0..size {
+            dst[positions[c]] = rgba[c];
+        }
+    }
+    Ok(pixels)
+}
+
+/// Decodes uncompressed pixels into float RGBA values.
+///
+/// Half floats are passed through as they are, or widened to 32 bits;
+/// 8-bit channels are mapped to [0, 1]. Channels are expanded to RGBA as by
+/// `decode_raw`. Every float is stored in native byte order; rows are
+/// tightly packed and never flipped.
+///
+/// # Errors
+///
+/// This function will return an error if the size of the image is 0 or
+/// `source` is too short for the image.
+pub fn decode_raw_float(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    raw_format: RawFormat,
+    precision: FloatPrecision,
+) -> Result<Vec<u8>, Error> {
+    let pitch = width.checked_mul(raw_format.pixel_size()).ok_or(Error::InvalidImageSize)?;
+    let rows = checked_rows(source, width, height, raw_format, pitch)?;
+
+    let float_size = match precision {
+        FloatPrecision::F32 => 4,
+        FloatPrecision::F16 => 2,
+    };
+    let mut pixels = Vec::with_capacity(4 * float_size * width * height);
+    for src in rows.flat_map(|row| row.chunks_exact(raw_format.pixel_size())) {
+        match precision {
+            FloatPrecision::F32 => {
+                // 8-bit channels are converted directly, without rounding
+                // them to half floats first
+                let rgba = if raw_format.is_float() {
+                    raw_format.rgba_f16(src).map(half_to_float)
+                } else {
+                    raw_format.rgba(src).map(|v| v as f32 / 255.0)
+                };
+                for f in rgba.iter() {
+                    pixels.extend_from_slice(&f.to_ne_bytes());
+                }
+            }
+            FloatPrecision::F16 => {
+                for h in raw_format.rgba_f16(src).iter() {
+                    pixels.extend_from_slice(&h.to_ne_bytes());
+                }
+            }
+        }
+    }
+    Ok(pixels)
+}
+
+/// Checks that the image fits in `source` and returns its rows.
+fn checked_rows(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    raw_format: RawFormat,
+    pitch: usize,
+) -> Result<impl Iterator<Item = &[u8]>, Error> {
+    let row_size = width.checked_mul(raw_format.pixel_size()).ok_or(Error::InvalidImageSize)?;
+    if height == 0 || row_size == 0 || pitch < row_size {
+        return Err(Error::InvalidImageSize);
+    }
+    pitch
+        .checked_mul(height - 1)
+        .and_then(|size| size.checked_add(row_size))
+        .filter(|&size| size <= source.len())
+        .ok_or(Error::InvalidImageSize)?;
+    Ok((0..height).map(move |y| &source[y * pitch..y * pitch + row_size]))
+}
+
+/// Returns the index of the red, green, blue and alpha channel in a pixel
+/// of a 3 or 4-channel format.
+pub fn channel_positions(format: BcnDecoderFormat) -> [usize; 4] {
+    match format {
+        BcnDecoderFormat::BGRA | BcnDecoderFormat::BGR => [2, 1, 0, 3],
+        BcnDecoderFormat::ARGB => [1, 2, 3, 0],
+        BcnDecoderFormat::ABGR => [3, 2, 1, 0],
+        _ => [0, 1, 2, 3],
+    }
+}
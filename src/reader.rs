@@ -1,4 +1,4 @@
-// Copyright (c) Robert Nix, Istvan Fehervari
+// Copyright (c) Istvan Fehervari
 
 // Permission is hereby granted, free of charge, to any person obtaining a copy
 // of this software and associated documentation files (the "Software"), to deal
@@ -18,18 +18,25 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-#ifndef bcndecode_h
-#define bcndecode_h
+//! Bounds-checked little-endian readers used by the container parsers.
 
-#include <stdio.h>
+use super::Error;
 
-typedef enum {
-    BcnDecoderFormatRGBA = 1,
-    BcnDecoderFormatBGRA = 2,
-    BcnDecoderFormatARGB = 3,
-    BcnDecoderFormatABGR = 4
-} BcnDecoderFormat;
+pub fn read_u8(data: &[u8], offset: usize) -> Result<u8, Error> {
+    data.get(offset).cloned().ok_or(Error::ImageDecodingError)
+}
 
-int BcnDecode(uint8_t *dst, int dst_size, const uint8_t *src, int src_size, int width, int height, int N, int dst_format, int flip);
+pub fn read_u16(data: &[u8], offset: usize) -> Result<u16, Error> {
+    match data.get(offset..offset + 2) {
+        Some(b) => Ok((b[0] as u16) | ((b[1] as u16) << 8)),
+        None => Err(Error::ImageDecodingError),
+    }
+}
 
-#endif /* bcndecode_h */
\ No newline at end of file
+pub fn read_u32(data: &[u8], offset: usize) -> Result<u32, Error> {
+    match data.get(offset..offset + 4) {
+        Some(b) => Ok((b[0] as u32) | ((b[1] as u32) << 8) | ((b[2] as u32) << 16) |
+            ((b[3] as u32) << 24)),
+        None => Err(Error::ImageDecodingError),
+    }
+}
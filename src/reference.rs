@@ -0,0 +1,745 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Reference implementation of the decoders.
+//!
+//! This module decodes every texel on its own, straight from the format
+//! specifications, without any of the optimizations of the main decoder.
+//! It is slow, but simple enough to be checked by eye, and produces output
+//! identical to `decode`, which makes it suitable for differential and
+//! property-based testing.
+//!
+//! The module is only compiled when the `reference` feature is enabled.
+
+use super::{BcnDecoderFormat, BcnEncoding, Error};
+
+/// Decodes the given BCN encoded image data, one texel at a time.
+///
+/// The arguments, errors and output layout are the same as for `decode`.
+pub fn decode(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+) -> Result<Vec<u8>, Error> {
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidImageSize);
+    }
+
+    // position of the red, green, blue and alpha channel in an output pixel
+    let channel_order = match format {
+        BcnDecoderFormat::RGBA => [0, 1, 2, 3],
+        BcnDecoderFormat::BGRA => [2, 1, 0, 3],
+        BcnDecoderFormat::ARGB => [1, 2, 3, 0],
+        BcnDecoderFormat::ABGR => [3, 2, 1, 0],
+        BcnDecoderFormat::LUM => match encoding {
+            BcnEncoding::Bc4 => [0, 1, 2, 3],
+            _ => return Err(Error::InvalidPixelFormat),
+        },
+        BcnDecoderFormat::RGB | BcnDecoderFormat::BGR => match encoding {
+            BcnEncoding::Bc6H => return Err(Error::InvalidPixelFormat),
+            _ if matches!(format, BcnDecoderFormat::RGB) => [0, 1, 2, 3],
+            _ => [2, 1, 0, 3],
+        },
+        // applied to the pixel before it is stored
+        BcnDecoderFormat::Custom(_) => match encoding {
+            BcnEncoding::Bc6H => return Err(Error::InvalidPixelFormat),
+            _ => [0, 1, 2, 3],
+        },
+    };
+    // the 3-byte formats leave out alpha, which comes last
+    let channels = match format {
+        BcnDecoderFormat::RGB | BcnDecoderFormat::BGR => 3,
+        _ => 4,
+    };
+
+    // BC4 is written as 1 byte per pixel, unless it is expanded to a color
+    // format, and BC6H as three 32-bit floats per pixel, in a buffer sized
+    // for four floats per pixel.
+    let luminance = matches!(format, BcnDecoderFormat::LUM);
+    let (block_size, pixel_size, buffer_size) = match encoding {
+        BcnEncoding::Bc1 => (8, channels, channels * width * height),
+        BcnEncoding::Bc4 if luminance => (8, 1, width * height),
+        BcnEncoding::Bc4 => (8, channels, channels * width * height),
+        BcnEncoding::Bc6H => (16, 12, 16 * width * height),
+        _ => (16, channels, channels * width * height),
+    };
+
+    // images that are not a multiple of the block size come out upside down
+    let flip = !width.is_multiple_of(4) || !height.is_multiple_of(4);
+    let blocks_per_row = width.div_ceil(4);
+
+    let mut buffer = vec![0; buffer_size];
+    for y in 0..height {
+        for x in 0..width {
+            let block_index = (y / 4) * blocks_per_row + x / 4;
+            let block = match source.get(block_index * block_size..(block_index + 1) * block_size) {
+                Some(block) => block,
+                None => continue,
+            };
+            let texel = (y % 4) * 4 + x % 4;
+            let mut pixel = decode_texel(block, texel, encoding);
+            if matches!(encoding, BcnEncoding::Bc4) && !luminance {
+                pixel = vec![pixel[0], pixel[0], pixel[0], 0xff];
+            }
+            if let BcnDecoderFormat::Custom(swizzle) = format {
+                pixel = swizzle.apply([pixel[0], pixel[1], pixel[2], pixel[3]]).to_vec();
+            }
+
+            let row = if flip { height - 1 - y } else { y };
+            let offset = (row * width + x) * pixel_size;
+            let channel_size = pixel_size / channels;
+            if channel_size == 0 {
+                buffer[offset] = pixel[0];
+                continue;
+            }
+            for (channel, &position) in channel_order.iter().enumerate().filter(|&(_, &p)| p < channels) {
+                let src = channel * channel_size;
+                let dst = offset + position * channel_size;
+                buffer[dst..dst + channel_size].copy_from_slice(&pixel[src..src + channel_size]);
+            }
+        }
+    }
+
+    Ok(buffer)
+}
+
+/// Decodes a single texel of a block into the bytes of its output pixel.
+fn decode_texel(block: &[u8], texel: usize, encoding: BcnEncoding) -> Vec<u8> {
+    match encoding {
+        BcnEncoding::Bc1 => bc1_color(block, texel).to_vec(),
+        BcnEncoding::Bc2 => {
+            let mut pixel = bc1_color(&block[8..], texel);
+            let alpha = (block[texel / 2] >> (4 * (texel % 2))) & 0xf;
+            pixel[3] = alpha * 17;
+            pixel.to_vec()
+        }
+        BcnEncoding::Bc3 => {
+            let mut pixel = bc1_color(&block[8..], texel);
+            pixel[3] = bc3_alpha(block, texel);
+            pixel.to_vec()
+        }
+        BcnEncoding::Bc4 => vec![bc3_alpha(block, texel)],
+        BcnEncoding::Bc5 => vec![bc3_alpha(block, texel), bc3_alpha(&block[8..], texel), 0, 0],
+        BcnEncoding::Bc6H => {
+            let mut pixel = Vec::with_capacity(12);
+            for value in bc6h_texel(block, texel).iter() {
+                let bits = value.to_bits();
+                pixel.extend_from_slice(&[bits as u8, (bits >> 8) as u8, (bits >> 16) as u8, (bits >> 24) as u8]);
+            }
+            pixel
+        }
+        BcnEncoding::Bc7 => bc7_texel(block, texel).to_vec(),
+    }
+}
+
+/// BC1 color: two RGB565 endpoints and 2-bit indices selecting one of four
+/// colors, or three colors and transparent black if `c0 <= c1`.
+fn bc1_color(block: &[u8], texel: usize) -> [u8; 4] {
+    let c0 = block[0] as u16 | (block[1] as u16) << 8;
+    let c1 = block[2] as u16 | (block[3] as u16) << 8;
+    let index = (block[4 + texel / 4] >> (2 * (texel % 4))) & 3;
+
+    let e0 = expand_565(c0);
+    let e1 = expand_565(c1);
+    let mix = |w0: u16, w1: u16, d: u16| {
+        [
+            ((w0 * e0[0] as u16 + w1 * e1[0] as u16) / d) as u8,
+            ((w0 * e0[1] as u16 + w1 * e1[1] as u16) / d) as u8,
+            ((w0 * e0[2] as u16 + w1 * e1[2] as u16) / d) as u8,
+            255,
+        ]
+    };
+
+    match (index, c0 > c1) {
+        (0, _) => e0,
+        (1, _) => e1,
+        (2, true) => mix(2, 1, 3),
+        (3, true) => mix(1, 2, 3),
+        (2, false) => mix(1, 1, 2),
+        _ => [0, 0, 0, 0],
+    }
+}
+
+fn expand_565(c: u16) -> [u8; 4] {
+    let r = ((c >> 11) & 0x1f) as u8;
+    let g = ((c >> 5) & 0x3f) as u8;
+    let b = (c & 0x1f) as u8;
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2), 255]
+}
+
+/// BC3 alpha: two 8-bit endpoints and 3-bit indices selecting one of eight
+/// values, or six values plus 0 and 255 if `a0 <= a1`.
+fn bc3_alpha(block: &[u8], texel: usize) -> u8 {
+    let a0 = block[0] as u32;
+    let a1 = block[1] as u32;
+    let mut bits = 0u64;
+    for i in 0..6 {
+        bits |= (block[2 + i] as u64) << (8 * i);
+    }
+    let index = ((bits >> (3 * texel)) & 7) as u32;
+
+    match index {
+        0 => a0 as u8,
+        1 => a1 as u8,
+        _ if a0 > a1 => (((8 - index) * a0 + (index - 1) * a1) / 7) as u8,
+        6 => 0,
+        7 => 255,
+        _ => (((6 - index) * a0 + (index - 1) * a1) / 5) as u8,
+    }
+}
+
+/// A BC6H mode: number of regions, whether the endpoints are stored as
+/// deltas, endpoint precision, delta precision per channel and the order of
+/// the endpoint bits after the mode bits.
+struct Bc6hMode {
+    regions: usize,
+    transformed: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    layout: &'static str,
+}
+
+/// The BC6H modes as listed in the Direct3D 11 documentation. Endpoints are
+/// named `w`, `x`, `y`, `z` there and `0`, `1`, `2`, `3` here. A range
+/// `[a:b]` lists the bits in the order they are read, last to first.
+static BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode {
+        regions: 2,
+        transformed: true,
+        endpoint_bits: 10,
+        delta_bits: [5, 5, 5],
+        layout: "g2[4],b2[4],b3[4],r0[9:0],g0[9:0],b0[9:0],r1[4:0],g3[4],g2[3:0],g1[4:0],\
+                 b3[0],g3[3:0],b1[4:0],b3[1],b2[3:0],r2[4:0],b3[2],r3[4:0],b3[3]",
+    },
+    Bc6hMode {
+        regions: 2,
+        transformed: true,
+        endpoint_bits: 7,
+        delta_bits: [6, 6, 6],
+        layout: "g2[5],g3[4],g3[5],r0[6:0],b3[0],b3[1],b2[4],g0[6:0],b2[5],b3[2],g2[4],\
+                 b0[6:0],b3[3],b3[5],b3[4],r1[5:0],g2[3:0],g1[5:0],g3[3:0],b1[5:0],\
+                 b2[3:0],r2[5:0],r3[5:0]",
+    },
+    Bc6hMode {
+        regions: 2,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [5, 4, 4],
+        layout: "r0[9:0],g0[9:0],b0[9:0],r1[4:0],r0[10],g2[3:0],g1[3:0],g0[10],b3[0],\
+                 g3[3:0],b1[3:0],b0[10],b3[1],b2[3:0],r2[4:0],b3[2],r3[4:0],b3[3]",
+    },
+    Bc6hMode {
+        regions: 2,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [4, 5, 4],
+        layout: "r0[9:0],g0[9:0],b0[9:0],r1[3:0],r0[10],g3[4],g2[3:0],g1[4:0],g0[10],\
+                 g3[3:0],b1[3:0],b0[10],b3[1],b2[3:0],r2[3:0],b3[0],b3[2],r3[3:0],\
+                 g2[4],b3[3]",
+    },
+    Bc6hMode {
+        regions: 2,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [4, 4, 5],
+        layout: "r0[9:0],g0[9:0],b0[9:0],r1[3:0],r0[10],b2[4],g2[3:0],g1[3:0],g0[10],\
+                 b3[0],g3[3:0],b1[4:0],b0[10],b2[3:0],r2[3:0],b3[1],b3[2],r3[3:0],\
+                 b3[4],b3[3]",
+    },
+    Bc6hMode {
+        regions: 2,
+        transformed: true,
+        endpoint_bits: 9,
+        delta_bits: [5, 5, 5],
+        layout: "r0[8:0],b2[4],g0[8:0],g2[4],b0[8:0],b3[4],r1[4:0],g3[4],g2[3:0],\
+                 g1[4:0],b3[0],g3[3:0],b1[4:0],b3[1],b2[3:0],r2[4:0],b3[2],r3[4:0],b3[3]",
+    },
+    Bc6hMode {
+        regions: 2,
+        transformed: true,
+        endpoint_bits: 8,
+        delta_bits: [6, 5, 5],
+        layout: "r0[7:0],g3[4],b2[4],g0[7:0],b3[2],g2[4],b0[7:0],b3[3],b3[4],r1[5:0],\
+                 g2[3:0],g1[4:0],b3[0],g3[3:0],b1[4:0],b3[1],b2[3:0],r2[5:0],r3[5:0]",
+    },
+    Bc6hMode {
+        regions: 2,
+        transformed: true,
+        endpoint_bits: 8,
+        delta_bits: [5, 6, 5],
+        layout: "r0[7:0],b3[0],b2[4],g0[7:0],g2[5],g2[4],b0[7:0],g3[5],b3[4],r1[4:0],\
+                 g3[4],g2[3:0],g1[5:0],g3[3:0],b1[4:0],b3[1],b2[3:0],r2[4:0],b3[2],\
+                 r3[4:0],b3[3]",
+    },
+    Bc6hMode {
+        regions: 2,
+        transformed: true,
+        endpoint_bits: 8,
+        delta_bits: [5, 5, 6],
+        layout: "r0[7:0],b3[1],b2[4],g0[7:0],b2[5],g2[4],b0[7:0],b3[5],b3[4],r1[4:0],\
+                 g3[4],g2[3:0],g1[4:0],b3[0],g3[3:0],b1[5:0],b2[3:0],r2[4:0],b3[2],\
+                 r3[4:0],b3[3]",
+    },
+    Bc6hMode {
+        regions: 2,
+        transformed: false,
+        endpoint_bits: 6,
+        delta_bits: [6, 6, 6],
+        layout: "r0[5:0],g3[4],b3[0],b3[1],b2[4],g0[5:0],g2[5],b2[5],b3[2],g2[4],\
+                 b0[5:0],g3[5],b3[3],b3[5],b3[4],r1[5:0],g2[3:0],g1[5:0],g3[3:0],\
+                 b1[5:0],b2[3:0],r2[5:0],r3[5:0]",
+    },
+    Bc6hMode {
+        regions: 1,
+        transformed: false,
+        endpoint_bits: 10,
+        delta_bits: [10, 10, 10],
+        layout: "r0[9:0],g0[9:0],b0[9:0],r1[9:0],g1[9:0],b1[9:0]",
+    },
+    Bc6hMode {
+        regions: 1,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [9, 9, 9],
+        layout: "r0[9:0],g0[9:0],b0[9:0],r1[8:0],r0[10],g1[8:0],g0[10],b1[8:0],b0[10]",
+    },
+    Bc6hMode {
+        regions: 1,
+        transformed: true,
+        endpoint_bits: 12,
+        delta_bits: [8, 8, 8],
+        layout: "r0[9:0],g0[9:0],b0[9:0],r1[7:0],r0[10:11],g1[7:0],g0[10:11],b1[7:0],\
+                 b0[10:11]",
+    },
+    Bc6hMode {
+        regions: 1,
+        transformed: true,
+        endpoint_bits: 16,
+        delta_bits: [4, 4, 4],
+        layout: "r0[9:0],g0[9:0],b0[9:0],r1[3:0],r0[10:15],g1[3:0],g0[10:15],b1[3:0],\
+                 b0[10:15]",
+    },
+];
+
+/// Reads `count` bits starting at `bit`, least significant bit first.
+fn read_bits(block: &[u8], bit: usize, count: usize) -> u32 {
+    let mut value = 0;
+    for i in 0..count {
+        let b = bit + i;
+        value |= (((block[b / 8] >> (b % 8)) & 1) as u32) << i;
+    }
+    value
+}
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+fn bc6h_texel(block: &[u8], texel: usize) -> [f32; 3] {
+    // 2-bit modes 0 and 1, 5-bit modes 2 to 9 (ending in 10) and 10 to 13
+    // (ending in 11), the remaining 5-bit values are reserved
+    let low = read_bits(block, 0, 2) as usize;
+    let (mode, mut bit) = if low < 2 {
+        (low, 2)
+    } else {
+        let high = read_bits(block, 2, 3) as usize;
+        (if low == 2 { 2 + high } else { 10 + high }, 5)
+    };
+    if mode >= BC6H_MODES.len() {
+        return [0.0; 3];
+    }
+    let mode = &BC6H_MODES[mode];
+
+    // endpoints[endpoint][channel]
+    let mut endpoints = [[0i32; 3]; 4];
+    for field in mode.layout.split(',') {
+        let field = field.trim();
+        let channel = match &field[0..1] {
+            "r" => 0,
+            "g" => 1,
+            _ => 2,
+        };
+        let endpoint = field[1..2].parse::<usize>().unwrap();
+        let range = &field[3..field.len() - 1];
+        let (last, first) = match range.find(':') {
+            Some(i) => (range[..i].parse::<i32>().unwrap(), range[i + 1..].parse::<i32>().unwrap()),
+            None => {
+                let b = range.parse::<i32>().unwrap();
+                (b, b)
+            }
+        };
+        let step = if last >= first { 1 } else { -1 };
+        let mut target = first;
+        loop {
+            endpoints[endpoint][channel] |= (read_bits(block, bit, 1) as i32) << target;
+            bit += 1;
+            if target == last {
+                break;
+            }
+            target += step;
+        }
+    }
+
+    let partition = if mode.regions == 2 {
+        let partition = read_bits(block, bit, 5) as usize;
+        bit += 5;
+        partition
+    } else {
+        0
+    };
+
+    let endpoint_count = 2 * mode.regions;
+    let mask = (1i32 << mode.endpoint_bits) - 1;
+    if mode.transformed {
+        let base = endpoints[0];
+        for endpoint in endpoints.iter_mut().take(endpoint_count).skip(1) {
+            for channel in 0..3 {
+                let delta = sign_extend(endpoint[channel], mode.delta_bits[channel]);
+                endpoint[channel] = (base[channel] + delta) & mask;
+            }
+        }
+    }
+
+    // unquantize the endpoints to 16 bits
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for value in endpoint.iter_mut() {
+            *value = if mode.endpoint_bits >= 15 || *value == 0 {
+                *value
+            } else if *value == mask {
+                0xffff
+            } else {
+                ((*value << 15) + 0x4000) >> (mode.endpoint_bits - 1)
+            };
+        }
+    }
+
+    // the subset of the texel and the anchor texels, whose index has one
+    // bit less than the others
+    let (subset, anchors) = if mode.regions == 2 {
+        let subset = (PARTITIONS_2[partition] >> texel) & 1;
+        (subset as usize, [0, ANCHORS_2[partition]])
+    } else {
+        (0, [0, 0])
+    };
+    let index_bits = if mode.regions == 2 { 3 } else { 4 };
+    for i in 0..texel {
+        bit += index_bits - if anchors.contains(&i) { 1 } else { 0 };
+    }
+    let bits = index_bits - if anchors.contains(&texel) { 1 } else { 0 };
+    let index = read_bits(block, bit, bits) as usize;
+    let weight = if mode.regions == 2 { WEIGHTS_3[index] } else { WEIGHTS_4[index] };
+
+    let e0 = endpoints[2 * subset];
+    let e1 = endpoints[2 * subset + 1];
+    let mut color = [0.0; 3];
+    for channel in 0..3 {
+        let value = (e0[channel] * (64 - weight) + e1[channel] * weight + 32) >> 6;
+        color[channel] = half_to_f32(((value * 31) / 64) as u16);
+    }
+    color
+}
+
+/// A BC7 mode: number of subsets, bits of the partition, rotation and index
+/// selection fields, bits per color and alpha endpoint value, whether each
+/// endpoint or each subset has a p-bit, and the bits of the primary and
+/// secondary indices.
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: usize,
+    rotation_bits: usize,
+    selection_bits: usize,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: usize,
+    index_bits_2: usize,
+}
+
+/// The BC7 modes as listed in the Direct3D 11 documentation.
+static BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode {
+        subsets: 3,
+        partition_bits: 4,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 4,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 3,
+        index_bits_2: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 6,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: true,
+        index_bits: 3,
+        index_bits_2: 0,
+    },
+    Bc7Mode {
+        subsets: 3,
+        partition_bits: 6,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        index_bits_2: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        index_bits_2: 0,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        selection_bits: 1,
+        color_bits: 5,
+        alpha_bits: 6,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        index_bits_2: 3,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 8,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        index_bits_2: 2,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 7,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 4,
+        index_bits_2: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 5,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        index_bits_2: 0,
+    },
+];
+
+fn bc7_texel(block: &[u8], texel: usize) -> [u8; 4] {
+    // the mode is given by the position of the lowest set bit, a block
+    // without any set bit in its first byte is reserved
+    let mode_index = match (0..8).find(|&m| read_bits(block, m, 1) == 1) {
+        Some(m) => m,
+        None => return [0; 4],
+    };
+    let mode = &BC7_MODES[mode_index];
+    let mut bit = mode_index + 1;
+
+    let partition = read_bits(block, bit, mode.partition_bits) as usize;
+    bit += mode.partition_bits;
+    let rotation = read_bits(block, bit, mode.rotation_bits) as usize;
+    bit += mode.rotation_bits;
+    let selection = read_bits(block, bit, mode.selection_bits);
+    bit += mode.selection_bits;
+
+    // endpoints[endpoint][channel], stored channel by channel
+    let endpoint_count = 2 * mode.subsets;
+    let mut precision = [mode.color_bits, mode.color_bits, mode.color_bits, mode.alpha_bits];
+    let mut endpoints = [[0u32; 4]; 6];
+    for channel in 0..4 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = read_bits(block, bit, precision[channel] as usize);
+            bit += precision[channel] as usize;
+        }
+    }
+
+    // p-bits are the least significant bit of every value of an endpoint
+    if mode.endpoint_pbits || mode.shared_pbits {
+        let endpoints_per_pbit = if mode.endpoint_pbits { 1 } else { 2 };
+        for group in endpoints[..endpoint_count].chunks_mut(endpoints_per_pbit) {
+            let pbit = read_bits(block, bit, 1);
+            bit += 1;
+            for endpoint in group {
+                for value in endpoint.iter_mut() {
+                    *value = (*value << 1) | pbit;
+                }
+            }
+        }
+        for p in precision.iter_mut() {
+            *p += 1;
+        }
+    }
+
+    // expand to 8 bits by repeating the most significant bits
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for channel in 0..4 {
+            let p = precision[channel];
+            endpoint[channel] = if channel == 3 && mode.alpha_bits == 0 {
+                255
+            } else {
+                let v = endpoint[channel] << (8 - p);
+                v | (v >> p)
+            };
+        }
+    }
+
+    let subset = match mode.subsets {
+        1 => 0,
+        2 => ((PARTITIONS_2[partition] >> texel) & 1) as usize,
+        _ => ((PARTITIONS_3[partition] >> (2 * texel)) & 3) as usize,
+    };
+    let is_anchor = |i: usize| match mode.subsets {
+        1 => i == 0,
+        2 => i == 0 || i == ANCHORS_2[partition],
+        _ => i == 0 || ANCHORS_3[partition].contains(&i),
+    };
+
+    // anchor texels store their indices with one bit less
+    let read_index = |start: usize, bits: usize| {
+        let offset: usize = (0..texel).map(|i| bits - if is_anchor(i) { 1 } else { 0 }).sum();
+        let count = bits - if is_anchor(texel) { 1 } else { 0 };
+        bc7_weight(bits, read_bits(block, start + offset, count) as usize)
+    };
+    let mut color_weight = read_index(bit, mode.index_bits);
+    let mut alpha_weight = if mode.index_bits_2 > 0 {
+        read_index(bit + 16 * mode.index_bits - mode.subsets, mode.index_bits_2)
+    } else {
+        color_weight
+    };
+    if selection == 1 {
+        std::mem::swap(&mut color_weight, &mut alpha_weight);
+    }
+
+    let e0 = endpoints[2 * subset];
+    let e1 = endpoints[2 * subset + 1];
+    let mut color = [0u8; 4];
+    for channel in 0..4 {
+        let weight = if channel == 3 { alpha_weight } else { color_weight };
+        color[channel] = ((e0[channel] * (64 - weight) + e1[channel] * weight + 32) >> 6) as u8;
+    }
+    if rotation > 0 {
+        color.swap(rotation - 1, 3);
+    }
+    color
+}
+
+fn bc7_weight(bits: usize, index: usize) -> u32 {
+    match bits {
+        2 => WEIGHTS_2[index] as u32,
+        3 => WEIGHTS_3[index] as u32,
+        _ => WEIGHTS_4[index] as u32,
+    }
+}
+
+/// Converts the bits of an IEEE 754 half-precision value to `f32`.
+fn half_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((h >> 10) & 0x1f) as i32;
+    let mantissa = (h & 0x3ff) as u32;
+    match exponent {
+        0 => sign * mantissa as f32 * 2f32.powi(-24),
+        31 => f32::from_bits(((h as u32 & 0x8000) << 16) | 0x7f80_0000 | (mantissa << 13)),
+        _ => sign * (1024 + mantissa) as f32 * 2f32.powi(exponent - 25),
+    }
+}
+
+static WEIGHTS_2: [i32; 4] = [0, 21, 43, 64];
+static WEIGHTS_3: [i32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+static WEIGHTS_4: [i32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Two-region partitions, one bit per texel selecting the region. BC6H
+/// only uses the first 32.
+static PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80,
+    0xc800, 0xffec, 0xfe80, 0xe800, 0xffe8, 0xff00, 0xfff0, 0xf000,
+    0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c,
+    0xaaaa, 0xf0f0, 0x5a5a, 0x33cc, 0x3c3c, 0x55aa, 0x9696, 0xa55a,
+    0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c,
+    0x9336, 0x9cc6, 0x817e, 0xe718, 0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// Anchor texel of the second region of each two-region partition.
+static ANCHORS_2: [usize; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Three-region partitions, two bits per texel selecting the region.
+static PARTITIONS_3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+/// Anchor texels of the second and third region of each three-region
+/// partition.
+static ANCHORS_3: [[usize; 2]; 64] = [
+    [3, 15], [3, 8], [15, 8], [15, 3], [8, 15], [3, 15], [15, 3], [15, 8],
+    [8, 15], [8, 15], [6, 15], [6, 15], [6, 15], [5, 15], [3, 15], [3, 8],
+    [3, 15], [3, 8], [8, 15], [15, 3], [3, 15], [3, 8], [6, 15], [10, 8],
+    [5, 3], [8, 15], [8, 6], [6, 10], [8, 15], [5, 15], [15, 10], [15, 8],
+    [8, 15], [15, 3], [3, 15], [5, 10], [6, 10], [10, 8], [8, 9], [15, 10],
+    [15, 6], [3, 15], [15, 8], [5, 15], [15, 3], [15, 6], [15, 6], [15, 8],
+    [3, 15], [15, 3], [5, 15], [5, 15], [5, 15], [8, 15], [5, 15], [10, 15],
+    [5, 15], [10, 15], [8, 15], [13, 15], [15, 3], [12, 15], [3, 15], [3, 8],
+];
@@ -0,0 +1,208 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Decoding with a report of corrupt blocks.
+
+use super::decode::{block_size, decode_rust, is_reserved_block};
+use super::{BcnDecoderFormat, BcnEncoding, Error};
+
+/// What is wrong with a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Corruption {
+    /// The block uses a mode reserved by the format (BC6H, BC7)
+    ReservedMode,
+    /// The source ends before the block
+    MissingData,
+}
+
+/// A block that did not decode cleanly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CorruptBlock {
+    /// Left edge of the block in pixels
+    pub x: usize,
+    /// Top edge of the block in pixels, counted from the top row even if
+    /// the decoded image is flipped
+    pub y: usize,
+    /// Offset of the block in the source in bytes
+    pub offset: usize,
+    /// What is wrong with the block
+    pub corruption: Corruption,
+}
+
+/// Decodes the given BCN encoded image data and lists the blocks that did
+/// not decode cleanly.
+///
+/// The image is the same as returned by `decode`, which decodes corrupt
+/// blocks to zeros. The blocks are listed in the order they are stored.
+/// Indices cannot be out of range in any of the formats, so only reserved
+/// modes and missing data are reported.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters.
+pub fn decode_with_report(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+) -> Result<(Vec<u8>, Vec<CorruptBlock>), Error> {
+    let image = decode_rust(source, width, height, encoding, format)?;
+
+    let block_size = block_size(encoding);
+    let blocks_per_row = width.div_ceil(4);
+    let mut report = Vec::new();
+    for index in 0..blocks_per_row * height.div_ceil(4) {
+        let offset = index * block_size;
+        let corruption = match source.get(offset..offset + block_size) {
+            None => Corruption::MissingData,
+            Some(block) if is_reserved_block(encoding, block) => Corruption::ReservedMode,
+            Some(_) => continue,
+        };
+        report.push(CorruptBlock {
+            x: 4 * (index % blocks_per_row),
+            y: 4 * (index / blocks_per_row),
+            offset,
+            corruption,
+        });
+    }
+
+    Ok((image, report))
+}
+
+/// Replaces the pixels of corrupt blocks by interpolating the pixels around
+/// them.
+///
+/// Every pixel of a listed block becomes the inverse distance weighted
+/// average of the nearest pixels of clean blocks to its left, right, top
+/// and bottom, which is a linear interpolation across the block. Pixels
+/// without any clean neighbor are left alone. `image` has the layout of
+/// `decode` with a format of 4 bytes per pixel, or LUM for BC4, and
+/// `corrupt` is typically the list of `decode_with_report`.
+///
+/// # Errors
+///
+/// This function will return an error if `image` is too small for the
+/// given size and encoding.
+pub fn repair_blocks(
+    image: &mut [u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    corrupt: &[CorruptBlock],
+) -> Result<(), Error> {
+    let pixel_size = match encoding {
+        BcnEncoding::Bc4 => 1,
+        BcnEncoding::Bc6H => 12,
+        _ => 4,
+    };
+    if width == 0 || height == 0 || image.len() < pixel_size * width * height {
+        return Err(Error::InvalidImageSize);
+    }
+
+    let blocks_per_row = width.div_ceil(4);
+    let mut is_corrupt = vec![false; blocks_per_row * height.div_ceil(4)];
+    for block in corrupt {
+        if block.x < width && block.y < height {
+            is_corrupt[(block.y / 4) * blocks_per_row + block.x / 4] = true;
+        }
+    }
+
+    // the decoder flips images that are not a multiple of the block size
+    let flipped = ((width & 3) | (height & 3)) != 0;
+    let offset = |x: usize, y: usize| {
+        let row = if flipped { height - 1 - y } else { y };
+        pixel_size * (row * width + x)
+    };
+    let clean = |x: isize, y: isize| {
+        if x < 0 || y < 0 || x as usize >= width || y as usize >= height {
+            return None;
+        }
+        let (x, y) = (x as usize, y as usize);
+        if is_corrupt[(y / 4) * blocks_per_row + x / 4] {
+            None
+        } else {
+            Some(offset(x, y))
+        }
+    };
+
+    for (index, _) in is_corrupt.iter().enumerate().filter(|&(_, &c)| c) {
+        let x0 = 4 * (index % blocks_per_row) as isize;
+        let y0 = 4 * (index / blocks_per_row) as isize;
+        for y in y0..(y0 + 4).min(height as isize) {
+            for x in x0..(x0 + 4).min(width as isize) {
+                // the nearest clean pixels on the row and the column, with
+                // their distance
+                let neighbors = [
+                    (x0 - 1, y, x - x0 + 1),
+                    (x0 + 4, y, x0 + 4 - x),
+                    (x, y0 - 1, y - y0 + 1),
+                    (x, y0 + 4, y0 + 4 - y),
+                ];
+                let mut sum = [0.0f32; 4];
+                let mut total = 0.0;
+                for &(nx, ny, distance) in &neighbors {
+                    if let Some(o) = clean(nx, ny) {
+                        let weight = 1.0 / distance as f32;
+                        let pixel = read_pixel(&image[o..o + pixel_size]);
+                        for (s, p) in sum.iter_mut().zip(pixel.iter()) {
+                            *s += weight * p;
+                        }
+                        total += weight;
+                    }
+                }
+                if total > 0.0 {
+                    let o = offset(x as usize, y as usize);
+                    write_pixel(&mut image[o..o + pixel_size], sum.iter().map(|s| s / total));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads the channels of a pixel, 8-bit values or three 32-bit floats.
+fn read_pixel(pixel: &[u8]) -> [f32; 4] {
+    let mut channels = [0.0; 4];
+    if pixel.len() == 12 {
+        for (c, bytes) in channels.iter_mut().zip(pixel.chunks(4)) {
+            *c = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+    } else {
+        for (c, &byte) in channels.iter_mut().zip(pixel.iter()) {
+            *c = byte as f32;
+        }
+    }
+    channels
+}
+
+fn write_pixel<I: Iterator<Item = f32>>(pixel: &mut [u8], channels: I) {
+    if pixel.len() == 12 {
+        for (bytes, c) in pixel.chunks_mut(4).zip(channels) {
+            bytes.copy_from_slice(&c.to_le_bytes());
+        }
+    } else {
+        for (byte, c) in pixel.iter_mut().zip(channels) {
+            *byte = c.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Decoding straight into a downscaled image, for thumbnails.
+
+use super::decode::{block_size, decode_rust, output_params, pixel_size};
+use super::{BcnDecoderFormat, BcnEncoding, Error};
+
+/// Number of block rows decoded at a time.
+const BAND_BLOCK_ROWS: usize = 16;
+
+/// Decodes an image and downscales it to the given size with a box filter.
+///
+/// Decoding, conversion to `format` and filtering happen in one pass: the
+/// image is decoded a band of block rows at a time and every band is added
+/// into the target pixels it covers right away, so the full size image is
+/// never held in memory. Every target pixel is the average of the source
+/// pixels falling into it. The result is tightly packed, top row first, in
+/// the pixel layout of `decode`. Blocks missing from `source` decode to
+/// zeros.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the
+/// given parameters or the target size is 0 or larger than the image.
+pub fn decode_resized(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    target_width: usize,
+    target_height: usize,
+) -> Result<Vec<u8>, Error> {
+    output_params(width, height, encoding, format)?;
+    if target_width == 0 || target_height == 0 || target_width > width || target_height > height {
+        return Err(Error::InvalidImageSize);
+    }
+
+    let pixel_size = pixel_size(encoding, format);
+    let channels = match encoding {
+        BcnEncoding::Bc6H => 3,
+        _ => pixel_size,
+    };
+    let columns = bins(width, target_width);
+    let rows = bins(height, target_height);
+
+    // bands are decoded a whole number of blocks wide, so they are not flipped
+    let blocks_per_row = width.div_ceil(4);
+    let band_width = 4 * blocks_per_row;
+    let row_size = blocks_per_row * block_size(encoding);
+    let mut sums = vec![0.0f64; channels * target_width * target_height];
+    for band_y in (0..height).step_by(4 * BAND_BLOCK_ROWS) {
+        let band_rows = (4 * BAND_BLOCK_ROWS).min(height - band_y);
+        let band_height = band_rows.div_ceil(4) * 4;
+        let start = (band_y / 4 * row_size).min(source.len());
+        let end = (start + band_height / 4 * row_size).min(source.len());
+        let band = decode_rust(&source[start..end], band_width, band_height, encoding, format)?;
+
+        for y in 0..band_rows {
+            let target_row = &mut sums[channels * target_width * rows[band_y + y]..][..channels * target_width];
+            let band_row = &band[pixel_size * band_width * y..][..pixel_size * width];
+            for (pixel, &column) in band_row.chunks_exact(pixel_size).zip(&columns) {
+                let sum = &mut target_row[channels * column..][..channels];
+                if let BcnEncoding::Bc6H = encoding {
+                    for (s, value) in sum.iter_mut().zip(pixel.chunks_exact(4)) {
+                        *s += f32::from_ne_bytes([value[0], value[1], value[2], value[3]]) as f64;
+                    }
+                } else {
+                    for (s, &value) in sum.iter_mut().zip(pixel) {
+                        *s += value as f64;
+                    }
+                }
+            }
+        }
+    }
+
+    let column_counts = bin_sizes(&columns, target_width);
+    let row_counts = bin_sizes(&rows, target_height);
+    let mut result = Vec::with_capacity(pixel_size * target_width * target_height);
+    for (target_row, &row_count) in sums.chunks_exact(channels * target_width).zip(&row_counts) {
+        for (sum, &column_count) in target_row.chunks_exact(channels).zip(&column_counts) {
+            let count = (row_count * column_count) as f64;
+            for &s in sum {
+                if let BcnEncoding::Bc6H = encoding {
+                    result.extend_from_slice(&((s / count) as f32).to_ne_bytes());
+                } else {
+                    result.push((s / count).round() as u8);
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Maps every source row or column to the target one it falls into.
+fn bins(size: usize, target_size: usize) -> Vec<usize> {
+    let mut bins = Vec::with_capacity(size);
+    for target in 0..target_size {
+        let end = ((target as u128 + 1) * size as u128 / target_size as u128) as usize;
+        bins.resize(end, target);
+    }
+    bins
+}
+
+fn bin_sizes(bins: &[usize], target_size: usize) -> Vec<usize> {
+    let mut sizes = vec![0; target_size];
+    for &bin in bins {
+        sizes[bin] += 1;
+    }
+    sizes
+}
@@ -0,0 +1,69 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Decoding into the pixel types of the `rgb` crate.
+//!
+//! The module is only compiled when the `rgb` feature is enabled.
+
+use rgb::{RGB, RGBA8};
+
+use super::decode::{decode_rgba_f32, decode_rust};
+use super::{BcnDecoderFormat, BcnEncoding, Error, TextureView};
+
+/// Decodes the given BCN encoded image data into 8-bit RGBA pixels, top row
+/// first.
+///
+/// BC4 values are repeated in red, green and blue with an opaque alpha.
+///
+/// # Errors
+///
+/// Returns `Error::InvalidPixelFormat` for BC6H, which does not fit into 8
+/// bits; use `decode_rgb_f32` instead. Otherwise, this function will return
+/// an error if the data cannot be decoded with the given parameters.
+pub fn decode_rgba8(source: &[u8], width: usize, height: usize, encoding: BcnEncoding) -> Result<Vec<RGBA8>, Error> {
+    let format = match encoding {
+        BcnEncoding::Bc4 => BcnDecoderFormat::LUM,
+        BcnEncoding::Bc6H => return Err(Error::InvalidPixelFormat),
+        _ => BcnDecoderFormat::RGBA,
+    };
+    let decoded = decode_rust(source, width, height, encoding, format)?;
+    let view = TextureView::from_decoded(&decoded, width, height, format)?;
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        pixels.extend((0..width).map(|x| view.rgba8(x, y)));
+    }
+    Ok(pixels)
+}
+
+/// Decodes the given BCN encoded image data into float RGB pixels, top row
+/// first.
+///
+/// Values are the same as those of `decode_to_f32`, without the alpha.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters.
+pub fn decode_rgb_f32(source: &[u8], width: usize, height: usize, encoding: BcnEncoding) -> Result<Vec<RGB<f32>>, Error> {
+    Ok(decode_rgba_f32(source, width, height, encoding)?
+        .iter()
+        .map(|p| RGB::new(p[0], p[1], p[2]))
+        .collect())
+}
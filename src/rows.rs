@@ -0,0 +1,186 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Row by row decoding.
+
+use std::io::{self, Write};
+
+use super::decode::{compressed_size, decode_rust, output_params, pixel_size};
+use super::{BcnDecoderFormat, BcnEncoding, Error};
+
+/// Decodes an image lazily, one row of pixels at a time.
+///
+/// Rows come in the same order as in the output of `decode`, so images that
+/// are not a multiple of the block size start with their bottom row. Only
+/// the block row holding the current row is kept decoded.
+///
+/// `next_row` borrows the rows from an internal buffer; the `Iterator`
+/// implementation copies every row into a `Vec` of its own.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::{BcnDecoderFormat, BcnEncoding, DecodedRows};
+///
+/// let block = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+/// let mut rows = DecodedRows::new(&block, 4, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA).unwrap();
+/// while let Some(row) = rows.next_row() {
+///     assert_eq!(row.unwrap(), &[0xff; 16][..]);
+/// }
+/// ```
+pub struct DecodedRows<'a> {
+    source: &'a [u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    // next row of the output
+    row: usize,
+    // block row held in `band`
+    band_index: Option<usize>,
+    band: Vec<u8>,
+}
+
+impl<'a> DecodedRows<'a> {
+    /// Prepares the decoding of an image, with the same arguments as `decode`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the data cannot be decoded with the given parameters.
+    pub fn new(
+        source: &'a [u8],
+        width: usize,
+        height: usize,
+        encoding: BcnEncoding,
+        format: BcnDecoderFormat,
+    ) -> Result<DecodedRows<'a>, Error> {
+        output_params(width, height, encoding, format)?;
+        Ok(DecodedRows {
+            source,
+            width,
+            height,
+            encoding,
+            format,
+            row: 0,
+            band_index: None,
+            band: Vec::new(),
+        })
+    }
+
+    /// Decodes the next row, or returns `None` after the last one.
+    pub fn next_row(&mut self) -> Option<Result<&[u8], Error>> {
+        if self.row >= self.height {
+            return None;
+        }
+
+        // the image row of the output row
+        let flipped = ((self.width & 3) | (self.height & 3)) != 0;
+        let y = if flipped {
+            self.height - 1 - self.row
+        } else {
+            self.row
+        };
+        self.row += 1;
+
+        let band_index = y / 4;
+        let band_height = (self.height - 4 * band_index).min(4);
+        if self.band_index != Some(band_index) {
+            let band_source_size = compressed_size(self.width, 4, self.encoding);
+            let start = (band_index * band_source_size).min(self.source.len());
+            let end = (start + band_source_size).min(self.source.len());
+            self.band = match decode_rust(&self.source[start..end], self.width, band_height, self.encoding, self.format) {
+                Ok(band) => band,
+                Err(err) => return Some(Err(err)),
+            };
+            self.band_index = Some(band_index);
+        }
+
+        // the band is flipped on its own if it is not a multiple of the
+        // block size
+        let j = y % 4;
+        let band_flipped = ((self.width & 3) | (band_height & 3)) != 0;
+        let band_row = if band_flipped { band_height - 1 - j } else { j };
+
+        let row_size = self.row_size();
+        Some(Ok(&self.band[band_row * row_size..(band_row + 1) * row_size]))
+    }
+
+    /// Returns the size of a row in bytes.
+    pub fn row_size(&self) -> usize {
+        let pixel_size = pixel_size(self.encoding, self.format);
+        pixel_size * self.width
+    }
+}
+
+impl<'a> Iterator for DecodedRows<'a> {
+    type Item = Result<Vec<u8>, Error>;
+
+    fn next(&mut self) -> Option<Result<Vec<u8>, Error>> {
+        self.next_row().map(|row| row.map(|row| row.to_vec()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.height - self.row;
+        (remaining, Some(remaining))
+    }
+}
+
+/// Decodes the given BCN encoded image data into `writer`, one row at a time.
+///
+/// Writes the pixels that `decode` returns, in the same row order, but only
+/// one block row is decoded at a time, so large images never have to fit
+/// into memory as a whole. For BC6H, the unused space `decode` leaves after
+/// the pixels is not written, so the output is 12 bytes per pixel. Wrap
+/// unbuffered writers in a `BufWriter`, as every row is a separate write.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters
+/// or writing fails.
+///
+/// # Examples
+///
+/// ```
+/// let block = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+/// let mut output = Vec::new();
+/// bcndecode::decode_to_writer(
+///     &block,
+///     4,
+///     4,
+///     bcndecode::BcnEncoding::Bc1,
+///     bcndecode::BcnDecoderFormat::RGBA,
+///     &mut output,
+/// ).unwrap();
+/// assert_eq!(output, vec![0xff; 64]);
+/// ```
+pub fn decode_to_writer<W: Write>(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    writer: &mut W,
+) -> io::Result<()> {
+    let mut rows = DecodedRows::new(source, width, height, encoding, format)?;
+    while let Some(row) = rows.next_row() {
+        writer.write_all(row?)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,229 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Startup check of the decoder against known-good blocks.
+
+use std::error;
+use std::fmt;
+
+use super::decode::decode_rust;
+use super::{BcnDecoderFormat, BcnEncoding};
+
+/// Failure of `self_test`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestError {
+    /// Name of the test vector that decoded incorrectly
+    pub vector: &'static str,
+    /// Index of the first wrong value in the decoded output, or `None` if the
+    /// block failed to decode at all
+    pub index: Option<usize>,
+}
+
+impl error::Error for SelfTestError {}
+
+impl fmt::Display for SelfTestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "Self-test vector {} decoded incorrectly at value {}", self.vector, index),
+            None => write!(f, "Self-test vector {} failed to decode", self.vector),
+        }
+    }
+}
+
+enum Expected {
+    /// Output of the 8-bit formats, RGBA or luminance
+    Bytes(&'static [u8]),
+    /// Output of BC6H, RGB
+    Floats(&'static [f32]),
+}
+
+struct TestVector {
+    name: &'static str,
+    encoding: BcnEncoding,
+    block: &'static [u8],
+    expected: Expected,
+}
+
+/// Single blocks covering every encoding and the block modes with their own
+/// code paths, with their decoded pixels.
+static TEST_VECTORS: [TestVector; 11] = [
+    TestVector {
+        name: "BC1 four colors",
+        encoding: BcnEncoding::Bc1,
+        block: &[0x1f, 0xf8, 0xe0, 0x07, 0xe4, 0x1b, 0x4e, 0xb1],
+        expected: Expected::Bytes(&[
+            255, 0, 255, 255, 0, 255, 0, 255, 170, 85, 170, 255, 85, 170, 85, 255, 85, 170, 85, 255,
+            170, 85, 170, 255, 0, 255, 0, 255, 255, 0, 255, 255, 170, 85, 170, 255, 85, 170, 85,
+            255, 255, 0, 255, 255, 0, 255, 0, 255, 0, 255, 0, 255, 255, 0, 255, 255, 85, 170, 85,
+            255, 170, 85, 170, 255,
+        ]),
+    },
+    TestVector {
+        name: "BC1 with alpha",
+        encoding: BcnEncoding::Bc1,
+        block: &[0xe0, 0x07, 0x1f, 0xf8, 0x39, 0xc6, 0x93, 0x6c],
+        expected: Expected::Bytes(&[
+            255, 0, 255, 255, 127, 127, 127, 255, 0, 0, 0, 0, 0, 255, 0, 255, 127, 127, 127, 255,
+            255, 0, 255, 255, 0, 255, 0, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 255, 0, 255, 255, 0, 255,
+            255, 127, 127, 127, 255, 0, 255, 0, 255, 0, 0, 0, 0, 127, 127, 127, 255, 255, 0, 255,
+            255,
+        ]),
+    },
+    TestVector {
+        name: "BC2",
+        encoding: BcnEncoding::Bc2,
+        block: &[0x10, 0x32, 0x54, 0x76, 0x98, 0xba, 0xdc, 0xfe, 0x00, 0xf8, 0x1f, 0x00, 0x1b, 0xe4, 0xb1, 0x4e],
+        expected: Expected::Bytes(&[
+            85, 0, 170, 0, 170, 0, 85, 17, 0, 0, 255, 34, 255, 0, 0, 51, 255, 0, 0, 68, 0, 0, 255,
+            85, 170, 0, 85, 102, 85, 0, 170, 119, 0, 0, 255, 136, 255, 0, 0, 153, 85, 0, 170, 170,
+            170, 0, 85, 187, 170, 0, 85, 204, 85, 0, 170, 221, 255, 0, 0, 238, 0, 0, 255, 255,
+        ]),
+    },
+    TestVector {
+        name: "BC3 eight alpha values",
+        encoding: BcnEncoding::Bc3,
+        block: &[0xf0, 0x10, 0x88, 0xc6, 0xfa, 0x05, 0x53, 0x7e, 0xef, 0x7b, 0x21, 0x08, 0xd8, 0x72, 0x27, 0x8d],
+        expected: Expected::Bytes(&[
+            123, 125, 123, 240, 84, 84, 84, 16, 8, 4, 8, 208, 46, 44, 46, 176, 84, 84, 84, 144, 123,
+            125, 123, 112, 46, 44, 46, 80, 8, 4, 8, 48, 46, 44, 46, 112, 8, 4, 8, 240, 84, 84, 84,
+            144, 123, 125, 123, 16, 8, 4, 8, 112, 46, 44, 46, 144, 123, 125, 123, 48, 84, 84, 84,
+            176,
+        ]),
+    },
+    TestVector {
+        name: "BC3 six alpha values",
+        encoding: BcnEncoding::Bc3,
+        block: &[0x20, 0xd0, 0x88, 0xc6, 0xfa, 0x05, 0x53, 0x7e, 0xef, 0x7b, 0x21, 0x08, 0xd8, 0x72, 0x27, 0x8d],
+        expected: Expected::Bytes(&[
+            123, 125, 123, 32, 84, 84, 84, 208, 8, 4, 8, 67, 46, 44, 46, 102, 84, 84, 84, 137, 123,
+            125, 123, 172, 46, 44, 46, 0, 8, 4, 8, 255, 46, 44, 46, 172, 8, 4, 8, 32, 84, 84, 84,
+            137, 123, 125, 123, 208, 8, 4, 8, 172, 46, 44, 46, 137, 123, 125, 123, 255, 84, 84, 84,
+            102,
+        ]),
+    },
+    TestVector {
+        name: "BC4",
+        encoding: BcnEncoding::Bc4,
+        block: &[0xe0, 0x18, 0x88, 0xc6, 0xfa, 0x05, 0x53, 0x7e],
+        expected: Expected::Bytes(&[
+            224, 24, 195, 166, 138, 109, 81, 52, 109, 224, 138, 24, 109, 138, 52, 166,
+        ]),
+    },
+    TestVector {
+        name: "BC5",
+        encoding: BcnEncoding::Bc5,
+        block: &[0x30, 0xc8, 0x88, 0xc6, 0xfa, 0x05, 0x53, 0x7e, 0xff, 0x00, 0x1b, 0xe4, 0xb1, 0x4e, 0x72, 0x27],
+        expected: Expected::Bytes(&[
+            48, 182, 0, 0, 200, 182, 0, 0, 78, 255, 0, 0, 108, 218, 0, 0, 139, 72, 0, 0, 169, 182,
+            0, 0, 0, 145, 0, 0, 255, 109, 0, 0, 169, 72, 0, 0, 48, 0, 0, 0, 139, 0, 0, 0, 200, 0, 0,
+            0, 169, 36, 0, 0, 139, 72, 0, 0, 255, 0, 0, 0, 108, 0, 0, 0,
+        ]),
+    },
+    TestVector {
+        name: "BC6H one region",
+        encoding: BcnEncoding::Bc6H,
+        block: &[0x03, 0x48, 0x3a, 0x91, 0x7c, 0x2e, 0x88, 0x15, 0xd2, 0x6f, 0x04, 0xb9, 0x5e, 0xa1, 0x37, 0xc0],
+        expected: Expected::Floats(&[
+            4.953125, 7.84375, 3.3417969, 0.75634766, 0.00067329407, 0.000395298, 0.515625,
+            0.00012099743, 8.034706e-05, 2.125, 0.13781738, 0.06506348, 3.0878906, 0.7495117,
+            0.3359375, 5.8085938, 16.421875, 6.7773438, 1.4208984, 0.014892578, 0.0076942444,
+            0.96972656, 0.002948761, 0.001627922, 0.6225586, 0.00025582314, 0.00016355515,
+            2.6601563, 0.35839844, 0.16503906, 4.953125, 7.84375, 3.3417969, 1.1533203,
+            0.0061531067, 0.0033016205, 1.8486328, 0.064819336, 0.031799316, 3.5136719, 1.5644531,
+            0.68359375, 5.8085938, 16.421875, 6.7773438, 0.86328125, 0.0014104843, 0.00080251694,
+        ]),
+    },
+    TestVector {
+        name: "BC6H two regions",
+        encoding: BcnEncoding::Bc6H,
+        block: &[0x4e, 0x28, 0x3a, 0x91, 0x7c, 0x2e, 0x88, 0x15, 0xd2, 0x6f, 0x04, 0xb9, 0x5e, 0xa1, 0x37, 0xc0],
+        expected: Expected::Floats(&[
+            26.453125, 0.004146576, 0.0007238388, 24.421875, 0.0041160583, 0.0006785393, 28.5,
+            0.004180908, 0.0007696152, 24.8125, 0.004016876, 0.00045633316, 37.71875, 0.004283905,
+            0.0009121895, 41.8125, 0.0043182373, 0.00095796585, 29.046875, 0.0034446716,
+            0.00055360794, 26.171875, 0.0037956238, 0.00047707558, 24.421875, 0.0041160583,
+            0.0006785393, 28.5, 0.004180908, 0.0007696152, 23.453125, 0.0043525696, 0.00043559074,
+            26.171875, 0.0037956238, 0.00047707558, 26.453125, 0.004146576, 0.0007238388, 34.28125,
+            0.0029468536, 0.0006785393, 34.28125, 0.0029468536, 0.0006785393, 29.046875,
+            0.0034446716, 0.00055360794,
+        ]),
+    },
+    TestVector {
+        name: "BC7 mode 1",
+        encoding: BcnEncoding::Bc7,
+        block: &[0x02, 0x8d, 0x3c, 0x71, 0x9e, 0x24, 0xe6, 0x5b, 0x13, 0xa8, 0x4f, 0xd0, 0x67, 0x2b, 0x95, 0xce],
+        expected: Expected::Bytes(&[
+            117, 102, 86, 255, 140, 94, 78, 255, 78, 139, 6, 255, 99, 192, 101, 255, 182, 81, 62,
+            255, 203, 74, 54, 255, 99, 192, 101, 255, 104, 205, 125, 255, 161, 88, 70, 255, 96, 109,
+            94, 255, 88, 165, 52, 255, 104, 205, 125, 255, 140, 94, 78, 255, 182, 81, 62, 255, 83,
+            152, 29, 255, 93, 178, 76, 255,
+        ]),
+    },
+    TestVector {
+        name: "BC7 mode 6",
+        encoding: BcnEncoding::Bc7,
+        block: &[0x40, 0x8d, 0x3c, 0x71, 0x9e, 0x24, 0xe6, 0x5b, 0x13, 0xa8, 0x4f, 0xd0, 0x67, 0x2b, 0x95, 0xce],
+        expected: Expected::Bytes(&[
+            63, 30, 37, 227, 63, 30, 37, 227, 146, 118, 28, 205, 171, 145, 25, 198, 229, 207, 19,
+            183, 99, 68, 33, 218, 52, 18, 38, 230, 204, 180, 22, 190, 135, 107, 29, 208, 124, 95,
+            30, 211, 182, 157, 24, 195, 77, 45, 35, 223, 110, 80, 32, 215, 157, 130, 27, 202, 218,
+            195, 20, 186, 193, 169, 23, 193,
+        ]),
+    },
+];
+
+/// Decodes a set of built-in blocks and compares them to their known-good
+/// output.
+///
+/// Meant to be run once at startup, to verify that the platform specific
+/// code paths, e.g. SIMD and byte order handling, produce correct results on
+/// the running machine.
+///
+/// # Errors
+///
+/// This function will return an error naming the first block that did not
+/// decode to the expected pixels.
+pub fn self_test() -> Result<(), SelfTestError> {
+    for vector in TEST_VECTORS.iter() {
+        let format = match vector.encoding {
+            BcnEncoding::Bc4 => BcnDecoderFormat::LUM,
+            _ => BcnDecoderFormat::RGBA,
+        };
+        let decoded = decode_rust(vector.block, 4, 4, vector.encoding, format).map_err(|_| SelfTestError {
+            vector: vector.name,
+            index: None,
+        })?;
+
+        let mismatch = match vector.expected {
+            Expected::Bytes(expected) => expected.iter().zip(&decoded).position(|(a, b)| a != b),
+            Expected::Floats(expected) => expected
+                .iter()
+                .zip(decoded.chunks_exact(4))
+                .position(|(&a, b)| a.to_bits() != u32::from_ne_bytes([b[0], b[1], b[2], b[3]])),
+        };
+        if let Some(index) = mismatch {
+            return Err(SelfTestError {
+                vector: vector.name,
+                index: Some(index),
+            });
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,307 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Vector kernels shared by the block decoders.
+//!
+//! The vector paths are built with the `simd` feature, which is enabled by
+//! default. SSE2 and NEON are part of the x86_64 and aarch64 baselines, so
+//! they are selected at compile time; the BC3 lookup additionally has an
+//! SSSE3 version and the BC1 lookup an AVX2 version, which are picked at
+//! runtime when the CPU supports them. Without the feature, and with the `safe` feature, as the
+//! intrinsics are unsafe, the scalar versions are used.
+
+/// Interpolates 64 channel values between two endpoints with 6-bit weights,
+/// `(e0 * (64 - w) + e1 * w + 32) >> 6` as BPTC defines it.
+#[cfg(all(target_arch = "x86_64", feature = "simd", not(feature = "safe")))]
+pub fn lerp_64(e0: &[u8; 64], e1: &[u8; 64], weights: &[u8; 64]) -> [u8; 64] {
+    use std::arch::x86_64::*;
+
+    let mut out = [0u8; 64];
+    unsafe {
+        let zero = _mm_setzero_si128();
+        let max = _mm_set1_epi16(64);
+        let round = _mm_set1_epi16(32);
+        for i in (0..64).step_by(16) {
+            let a = _mm_loadu_si128(e0[i..].as_ptr() as *const __m128i);
+            let b = _mm_loadu_si128(e1[i..].as_ptr() as *const __m128i);
+            let w = _mm_loadu_si128(weights[i..].as_ptr() as *const __m128i);
+
+            // widen to 16 bits, the products fit as the weights sum to 64
+            let lerp = |a: __m128i, b: __m128i, w: __m128i| {
+                let sum = _mm_add_epi16(
+                    _mm_mullo_epi16(a, _mm_sub_epi16(max, w)),
+                    _mm_mullo_epi16(b, w),
+                );
+                _mm_srli_epi16(_mm_add_epi16(sum, round), 6)
+            };
+            let lo = lerp(
+                _mm_unpacklo_epi8(a, zero),
+                _mm_unpacklo_epi8(b, zero),
+                _mm_unpacklo_epi8(w, zero),
+            );
+            let hi = lerp(
+                _mm_unpackhi_epi8(a, zero),
+                _mm_unpackhi_epi8(b, zero),
+                _mm_unpackhi_epi8(w, zero),
+            );
+            _mm_storeu_si128(out[i..].as_mut_ptr() as *mut __m128i, _mm_packus_epi16(lo, hi));
+        }
+    }
+    out
+}
+
+/// Interpolates 64 channel values between two endpoints with 6-bit weights,
+/// `(e0 * (64 - w) + e1 * w + 32) >> 6` as BPTC defines it.
+#[cfg(all(target_arch = "aarch64", feature = "simd", not(feature = "safe")))]
+pub fn lerp_64(e0: &[u8; 64], e1: &[u8; 64], weights: &[u8; 64]) -> [u8; 64] {
+    use std::arch::aarch64::*;
+
+    let mut out = [0u8; 64];
+    unsafe {
+        let max = vdupq_n_u8(64);
+        for i in (0..64).step_by(16) {
+            let a = vld1q_u8(e0[i..].as_ptr());
+            let b = vld1q_u8(e1[i..].as_ptr());
+            let w = vld1q_u8(weights[i..].as_ptr());
+            let t = vsubq_u8(max, w);
+
+            let lo = vmlal_u8(vmull_u8(vget_low_u8(a), vget_low_u8(t)), vget_low_u8(b), vget_low_u8(w));
+            let hi = vmlal_high_u8(vmull_high_u8(a, t), b, w);
+            // the rounding shift adds the 32
+            let result = vcombine_u8(vrshrn_n_u16::<6>(lo), vrshrn_n_u16::<6>(hi));
+            vst1q_u8(out[i..].as_mut_ptr(), result);
+        }
+    }
+    out
+}
+
+/// Interpolates 64 channel values between two endpoints with 6-bit weights,
+/// `(e0 * (64 - w) + e1 * w + 32) >> 6` as BPTC defines it.
+#[cfg(not(all(any(target_arch = "x86_64", target_arch = "aarch64"), feature = "simd", not(feature = "safe"))))]
+pub fn lerp_64(e0: &[u8; 64], e1: &[u8; 64], weights: &[u8; 64]) -> [u8; 64] {
+    lerp_64_scalar(e0, e1, weights)
+}
+
+/// Portable version of `lerp_64`, also used to check the vector paths.
+#[cfg_attr(
+    all(any(target_arch = "x86_64", target_arch = "aarch64"), feature = "simd", not(feature = "safe")),
+    allow(dead_code)
+)]
+pub fn lerp_64_scalar(e0: &[u8; 64], e1: &[u8; 64], weights: &[u8; 64]) -> [u8; 64] {
+    let mut out = [0u8; 64];
+    for (i, value) in out.iter_mut().enumerate() {
+        let w = weights[i] as u16;
+        *value = ((e0[i] as u16 * (64 - w) + e1[i] as u16 * w + 32) >> 6) as u8;
+    }
+    out
+}
+
+/// Looks up 16 values in a table of 8, e.g. the alpha palette of a BC3
+/// block. Every index must be below 8.
+#[cfg(all(target_arch = "x86_64", feature = "simd", not(feature = "safe")))]
+pub fn lookup_16(table: &[u8; 8], indices: &[u8; 16]) -> [u8; 16] {
+    if is_x86_feature_detected!("ssse3") {
+        unsafe { lookup_16_ssse3(table, indices) }
+    } else {
+        lookup_16_sse2(table, indices)
+    }
+}
+
+/// SSE2 version of `lookup_16`.
+#[cfg(all(target_arch = "x86_64", feature = "simd", not(feature = "safe")))]
+pub fn lookup_16_sse2(table: &[u8; 8], indices: &[u8; 16]) -> [u8; 16] {
+    use std::arch::x86_64::*;
+
+    let mut out = [0u8; 16];
+    unsafe {
+        // SSE2 has no byte shuffle, select every entry by comparison
+        let idx = _mm_loadu_si128(indices.as_ptr() as *const __m128i);
+        let mut result = _mm_setzero_si128();
+        for (k, &value) in table.iter().enumerate() {
+            let mask = _mm_cmpeq_epi8(idx, _mm_set1_epi8(k as i8));
+            result = _mm_or_si128(result, _mm_and_si128(mask, _mm_set1_epi8(value as i8)));
+        }
+        _mm_storeu_si128(out.as_mut_ptr() as *mut __m128i, result);
+    }
+    out
+}
+
+/// SSSE3 version of `lookup_16`, a single byte shuffle.
+///
+/// # Safety
+///
+/// The CPU must support SSSE3.
+#[cfg(all(target_arch = "x86_64", feature = "simd", not(feature = "safe")))]
+#[target_feature(enable = "ssse3")]
+pub unsafe fn lookup_16_ssse3(table: &[u8; 8], indices: &[u8; 16]) -> [u8; 16] {
+    use std::arch::x86_64::*;
+
+    let mut out = [0u8; 16];
+    let table = _mm_loadl_epi64(table.as_ptr() as *const __m128i);
+    let result = _mm_shuffle_epi8(table, _mm_loadu_si128(indices.as_ptr() as *const __m128i));
+    _mm_storeu_si128(out.as_mut_ptr() as *mut __m128i, result);
+    out
+}
+
+/// Looks up 16 values in a table of 8, e.g. the alpha palette of a BC3
+/// block. Every index must be below 8.
+#[cfg(all(target_arch = "aarch64", feature = "simd", not(feature = "safe")))]
+pub fn lookup_16(table: &[u8; 8], indices: &[u8; 16]) -> [u8; 16] {
+    use std::arch::aarch64::*;
+
+    let mut out = [0u8; 16];
+    unsafe {
+        let result = vqtbl1q_u8(vcombine_u8(vld1_u8(table.as_ptr()), vdup_n_u8(0)), vld1q_u8(indices.as_ptr()));
+        vst1q_u8(out.as_mut_ptr(), result);
+    }
+    out
+}
+
+/// Looks up 16 values in a table of 8, e.g. the alpha palette of a BC3
+/// block. Every index must be below 8.
+#[cfg(not(all(any(target_arch = "x86_64", target_arch = "aarch64"), feature = "simd", not(feature = "safe"))))]
+pub fn lookup_16(table: &[u8; 8], indices: &[u8; 16]) -> [u8; 16] {
+    lookup_16_scalar(table, indices)
+}
+
+/// Portable version of `lookup_16`, also used to check the vector paths.
+#[cfg_attr(
+    all(any(target_arch = "x86_64", target_arch = "aarch64"), feature = "simd", not(feature = "safe")),
+    allow(dead_code)
+)]
+pub fn lookup_16_scalar(table: &[u8; 8], indices: &[u8; 16]) -> [u8; 16] {
+    let mut out = [0u8; 16];
+    for (value, &index) in out.iter_mut().zip(indices.iter()) {
+        *value = table[index as usize];
+    }
+    out
+}
+
+/// Selects the 16 pixels of a BC1 block from its palette, using the 2-bit
+/// indices packed into `lut` from the lowest bits up.
+#[cfg(all(target_arch = "x86_64", feature = "simd", not(feature = "safe")))]
+pub fn select_pixels_16(palette: &[[u8; 4]; 4], lut: u32) -> [[u8; 4]; 16] {
+    if is_x86_feature_detected!("avx2") {
+        unsafe { select_pixels_16_avx2(palette, lut) }
+    } else {
+        select_pixels_16_sse2(palette, lut)
+    }
+}
+
+/// SSE2 version of `select_pixels_16`.
+#[cfg(all(target_arch = "x86_64", feature = "simd", not(feature = "safe")))]
+pub fn select_pixels_16_sse2(palette: &[[u8; 4]; 4], lut: u32) -> [[u8; 4]; 16] {
+    use std::arch::x86_64::*;
+
+    let mut out = [[0u8; 4]; 16];
+    unsafe {
+        let entries = palette.map(|p| _mm_set1_epi32(i32::from_ne_bytes(p)));
+        let select = |mask, a, b| _mm_or_si128(_mm_and_si128(mask, a), _mm_andnot_si128(mask, b));
+        let bits = _mm_set1_epi32(lut as i32);
+        for i in 0..4 {
+            // mask the low and the high bit of the index of every pixel
+            let shift = 8 * i;
+            let low = _mm_set_epi32(0x40 << shift, 0x10 << shift, 0x4 << shift, 0x1 << shift);
+            let high = _mm_slli_epi32::<1>(low);
+            let low = _mm_cmpeq_epi32(_mm_and_si128(bits, low), low);
+            let high = _mm_cmpeq_epi32(_mm_and_si128(bits, high), high);
+            let result = select(
+                high,
+                select(low, entries[3], entries[2]),
+                select(low, entries[1], entries[0]),
+            );
+            _mm_storeu_si128(out[4 * i..].as_mut_ptr() as *mut __m128i, result);
+        }
+    }
+    out
+}
+
+/// AVX2 version of `select_pixels_16`, which shifts out the index of every
+/// pixel and permutes the palette by it, 8 pixels at a time.
+///
+/// # Safety
+///
+/// The CPU must support AVX2.
+#[cfg(all(target_arch = "x86_64", feature = "simd", not(feature = "safe")))]
+#[target_feature(enable = "avx2")]
+pub unsafe fn select_pixels_16_avx2(palette: &[[u8; 4]; 4], lut: u32) -> [[u8; 4]; 16] {
+    use std::arch::x86_64::*;
+
+    let mut out = [[0u8; 4]; 16];
+    let table = _mm256_broadcastsi128_si256(_mm_loadu_si128(palette.as_ptr() as *const __m128i));
+    let bits = _mm256_set1_epi32(lut as i32);
+    let mask = _mm256_set1_epi32(3);
+    for half in 0..2 {
+        let shifts = _mm256_add_epi32(
+            _mm256_setr_epi32(0, 2, 4, 6, 8, 10, 12, 14),
+            _mm256_set1_epi32(16 * half as i32),
+        );
+        let indices = _mm256_and_si256(_mm256_srlv_epi32(bits, shifts), mask);
+        let result = _mm256_permutevar8x32_epi32(table, indices);
+        _mm256_storeu_si256(out[8 * half..].as_mut_ptr() as *mut __m256i, result);
+    }
+    out
+}
+
+/// Selects the 16 pixels of a BC1 block from its palette, using the 2-bit
+/// indices packed into `lut` from the lowest bits up.
+#[cfg(all(target_arch = "aarch64", feature = "simd", not(feature = "safe")))]
+pub fn select_pixels_16(palette: &[[u8; 4]; 4], lut: u32) -> [[u8; 4]; 16] {
+    use std::arch::aarch64::*;
+
+    const SHIFTS: [i8; 16] = [0, -2, -4, -6, 0, -2, -4, -6, 0, -2, -4, -6, 0, -2, -4, -6];
+    const CHANNELS: [u8; 16] = [0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3];
+
+    let mut out = [[0u8; 4]; 16];
+    unsafe {
+        let table = vld1q_u8(palette.as_ptr() as *const u8);
+        let channels = vld1q_u8(CHANNELS.as_ptr());
+        for (i, &byte) in lut.to_le_bytes().iter().enumerate() {
+            // every byte of the lut holds the indices of a row
+            let indices = vandq_u8(vshlq_u8(vdupq_n_u8(byte), vld1q_s8(SHIFTS.as_ptr())), vdupq_n_u8(3));
+            let indices = vzip1q_u8(vzip1q_u8(indices, indices), vzip1q_u8(indices, indices));
+            let bytes = vaddq_u8(vshlq_n_u8::<2>(indices), channels);
+            vst1q_u8(out[4 * i..].as_mut_ptr() as *mut u8, vqtbl1q_u8(table, bytes));
+        }
+    }
+    out
+}
+
+/// Selects the 16 pixels of a BC1 block from its palette, using the 2-bit
+/// indices packed into `lut` from the lowest bits up.
+#[cfg(not(all(any(target_arch = "x86_64", target_arch = "aarch64"), feature = "simd", not(feature = "safe"))))]
+pub fn select_pixels_16(palette: &[[u8; 4]; 4], lut: u32) -> [[u8; 4]; 16] {
+    select_pixels_16_scalar(palette, lut)
+}
+
+/// Portable version of `select_pixels_16`, also used to check the vector
+/// paths.
+#[cfg_attr(
+    all(any(target_arch = "x86_64", target_arch = "aarch64"), feature = "simd", not(feature = "safe")),
+    allow(dead_code)
+)]
+pub fn select_pixels_16_scalar(palette: &[[u8; 4]; 4], lut: u32) -> [[u8; 4]; 16] {
+    let mut out = [[0u8; 4]; 16];
+    for (n, pixel) in out.iter_mut().enumerate() {
+        *pixel = palette[(3 & (lut >> (2 * n))) as usize];
+    }
+    out
+}
@@ -0,0 +1,106 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Decoding of whole mip chains into a single upload buffer.
+
+use super::decode::{decode_rust, pixel_size};
+use super::{BcnDecoderFormat, BcnEncoding, Error, MipLevel};
+
+/// Placement of a mip level in a `StagingBuffer`.
+///
+/// The fields map to the `bufferOffset` and `imageExtent` of a
+/// `VkBufferImageCopy`; rows are tightly packed, so `bufferRowLength` and
+/// `bufferImageHeight` are 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StagingRegion {
+    /// Offset of the level in the buffer in bytes
+    pub offset: usize,
+    /// Width of the level in pixels
+    pub width: usize,
+    /// Height of the level in pixels
+    pub height: usize,
+}
+
+/// Decoded mip chain laid out for a single buffer-to-image copy.
+#[derive(Debug, Clone)]
+pub struct StagingBuffer {
+    /// Pixels of all levels, top row first
+    pub data: Vec<u8>,
+    /// Placement of every level, in the order they were given
+    pub regions: Vec<StagingRegion>,
+}
+
+/// Decodes a mip chain into one buffer as `vkCmdCopyBufferToImage` expects it.
+///
+/// Every level starts at a multiple of `alignment`, which should be the
+/// `optimalBufferCopyOffsetAlignment` of the device, and of the pixel size.
+/// Unlike `decode`, rows are always stored top row first.
+///
+/// # Errors
+///
+/// This function will return an error if a level cannot be decoded with the
+/// given parameters or `alignment` is 0.
+pub fn decode_staging(
+    levels: &[MipLevel],
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    alignment: usize,
+) -> Result<StagingBuffer, Error> {
+    if alignment == 0 {
+        return Err(Error::InvalidImageSize);
+    }
+    let pixel_size = pixel_size(encoding, format);
+    let alignment = lcm(alignment, pixel_size);
+
+    let mut data = Vec::new();
+    let mut regions = Vec::with_capacity(levels.len());
+    for level in levels {
+        let decoded = decode_rust(level.data, level.width, level.height, encoding, format)?;
+
+        let offset = data.len().div_ceil(alignment) * alignment;
+        data.resize(offset, 0);
+
+        // the decoder flips images that are not a multiple of the block size
+        let flipped = ((level.width & 3) | (level.height & 3)) != 0;
+        let row_size = pixel_size * level.width;
+        for y in 0..level.height {
+            let row = if flipped { level.height - 1 - y } else { y };
+            data.extend_from_slice(&decoded[row * row_size..(row + 1) * row_size]);
+        }
+
+        regions.push(StagingRegion {
+            offset,
+            width: level.width,
+            height: level.height,
+        });
+    }
+
+    Ok(StagingBuffer { data, regions })
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        let t = x % y;
+        x = y;
+        y = t;
+    }
+    a / x * b
+}
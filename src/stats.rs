@@ -0,0 +1,83 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Throughput counters for decoding telemetry.
+
+use std::time::{Duration, Instant};
+
+use super::decode::{block_size, compressed_size, decode_with_options};
+use super::{BcnDecoderFormat, BcnEncoding, DecodeOptions, Error};
+
+/// Counters of a single decode call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DecodeStats {
+    /// Number of blocks decoded from the source; blocks missing from the
+    /// end of the source are written as zeros and not counted
+    pub blocks: usize,
+    /// Number of source bytes read
+    pub source_bytes: usize,
+    /// Number of bytes written to the output
+    pub output_bytes: usize,
+    /// Wall time of the call, including allocation of the output
+    pub duration: Duration,
+}
+
+impl DecodeStats {
+    /// Returns the number of source bytes read per second.
+    pub fn source_throughput(&self) -> f64 {
+        self.source_bytes as f64 / self.duration.as_secs_f64()
+    }
+
+    /// Returns the number of output bytes written per second.
+    pub fn output_throughput(&self) -> f64 {
+        self.output_bytes as f64 / self.duration.as_secs_f64()
+    }
+}
+
+/// Decodes the given BCN encoded image data with the given options and
+/// measures the work done.
+///
+/// The image is the same as returned by `decode_with_options`.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the given parameters.
+pub fn decode_with_stats(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    options: &DecodeOptions,
+) -> Result<(Vec<u8>, DecodeStats), Error> {
+    let start = Instant::now();
+    let image = decode_with_options(source, width, height, encoding, format, options)?;
+    let duration = start.elapsed();
+
+    let source_bytes = compressed_size(width, height, encoding).min(source.len());
+    let source_bytes = source_bytes - source_bytes % block_size(encoding);
+    let stats = DecodeStats {
+        blocks: source_bytes / block_size(encoding),
+        source_bytes,
+        output_bytes: image.len(),
+        duration,
+    };
+    Ok((image, stats))
+}
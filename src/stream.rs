@@ -0,0 +1,147 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Row by row decoding of compressed data from a reader.
+
+use std::io::{self, Read};
+
+use super::decode::{compressed_size, decode_rust, output_params, pixel_size};
+use super::{BcnDecoderFormat, BcnEncoding};
+
+/// Decodes an image row by row while reading the compressed data from an
+/// `io::Read`, e.g. a file positioned at the start of the texture.
+///
+/// Only one block row of compressed data is read at a time, as the rows
+/// are decoded. Unlike `decode`, rows always come top row first, as the
+/// data is read in order. A reader that ends early leaves the missing
+/// blocks zeroed, like a short source does for `decode`. A read error is
+/// returned once, after which no more rows are returned.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::{BcnDecoderFormat, BcnEncoding, StreamedRows};
+///
+/// let data: &[u8] = &[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+/// let mut rows = StreamedRows::new(data, 4, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA).unwrap();
+/// while let Some(row) = rows.next_row() {
+///     assert_eq!(row.unwrap(), &[0xff; 16][..]);
+/// }
+/// ```
+pub struct StreamedRows<R> {
+    reader: R,
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    // next row of the output
+    row: usize,
+    // compressed and decoded data of the current block row
+    compressed: Vec<u8>,
+    band: Vec<u8>,
+}
+
+impl<R: Read> StreamedRows<R> {
+    /// Prepares the decoding of an image read from `reader`, with the same
+    /// arguments as `decode` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the data cannot be decoded with the given parameters.
+    pub fn new(
+        reader: R,
+        width: usize,
+        height: usize,
+        encoding: BcnEncoding,
+        format: BcnDecoderFormat,
+    ) -> io::Result<StreamedRows<R>> {
+        output_params(width, height, encoding, format)?;
+        Ok(StreamedRows {
+            reader,
+            width,
+            height,
+            encoding,
+            format,
+            row: 0,
+            compressed: Vec::new(),
+            band: Vec::new(),
+        })
+    }
+
+    /// Decodes the next row, or returns `None` after the last one or after
+    /// an error.
+    pub fn next_row(&mut self) -> Option<io::Result<&[u8]>> {
+        if self.row >= self.height {
+            return None;
+        }
+        let y = self.row;
+        self.row += 1;
+
+        let band_height = (self.height - (y & !3)).min(4);
+        if y.is_multiple_of(4) {
+            if let Err(err) = self.read_band(band_height) {
+                // the band is gone, so the remaining rows cannot be returned
+                self.row = self.height;
+                return Some(Err(err));
+            }
+        }
+
+        // the band is flipped on its own if it is not a multiple of the
+        // block size
+        let j = y % 4;
+        let band_flipped = ((self.width & 3) | (band_height & 3)) != 0;
+        let band_row = if band_flipped { band_height - 1 - j } else { j };
+
+        let row_size = self.row_size();
+        Some(Ok(&self.band[band_row * row_size..(band_row + 1) * row_size]))
+    }
+
+    /// Returns the size of a row in bytes.
+    pub fn row_size(&self) -> usize {
+        let pixel_size = pixel_size(self.encoding, self.format);
+        pixel_size * self.width
+    }
+
+    /// Returns the reader, positioned after the data read so far.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_band(&mut self, band_height: usize) -> io::Result<()> {
+        let band_source_size = compressed_size(self.width, 4, self.encoding);
+        self.compressed.clear();
+        (&mut self.reader).take(band_source_size as u64).read_to_end(&mut self.compressed)?;
+        self.band = decode_rust(&self.compressed, self.width, band_height, self.encoding, self.format)?;
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for StreamedRows<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<u8>>> {
+        self.next_row().map(|row| row.map(|row| row.to_vec()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.height - self.row;
+        (remaining, Some(remaining))
+    }
+}
@@ -0,0 +1,384 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Generation of synthetic test textures.
+//!
+//! `synthesize` encodes simple patterns with known pixels into any of the
+//! encodings, so loaders and decoders can be tested without binary
+//! fixtures. Colors that an encoding can represent exactly, e.g. black and
+//! white, decode to exactly the pattern; others come out as close as the
+//! encoding allows.
+
+use super::const_decode::{decode_bc1_block, decode_bc4_block};
+use super::decode::{compressed_size, encode_bc6h_block, float_to_half};
+use super::{BcnEncoding, Error};
+
+/// Content of a synthetic texture. Colors are RGBA, with 8-bit channels
+/// mapped to 0..1; BC6H takes RGB values as they are and ignores alpha.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Pattern {
+    /// A single color.
+    Solid([f32; 4]),
+    /// A gradient from the first color at the left edge to the second at
+    /// the right edge.
+    HorizontalGradient([f32; 4], [f32; 4]),
+    /// A gradient from the first color at the top edge to the second at
+    /// the bottom edge.
+    VerticalGradient([f32; 4], [f32; 4]),
+    /// Squares of `size` pixels in alternating colors, the first one in the
+    /// top left corner.
+    Checkerboard {
+        /// Width and height of a square in pixels
+        size: usize,
+        /// Colors of the squares
+        colors: [[f32; 4]; 2],
+    },
+}
+
+impl Pattern {
+    /// Returns the color of the pattern at `(x, y)` in an image of the
+    /// given size, counted from the top left pixel.
+    pub fn color(&self, x: usize, y: usize, width: usize, height: usize) -> [f32; 4] {
+        let lerp = |from: &[f32; 4], to: &[f32; 4], position: usize, size: usize| {
+            let t = if size > 1 { position as f32 / (size - 1) as f32 } else { 0.0 };
+            let mut color = [0.0; 4];
+            for c in 0..4 {
+                color[c] = from[c] + (to[c] - from[c]) * t;
+            }
+            color
+        };
+        match *self {
+            Pattern::Solid(color) => color,
+            Pattern::HorizontalGradient(from, to) => lerp(&from, &to, x, width),
+            Pattern::VerticalGradient(from, to) => lerp(&from, &to, y, height),
+            Pattern::Checkerboard { size, colors } => colors[(x / size + y / size) % 2],
+        }
+    }
+}
+
+/// Order of the endpoints of BC1 color blocks and of BC3, BC4 and BC5
+/// channel blocks, which selects the interpolation of the block.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EndpointOrder {
+    /// The first endpoint is larger: four BC1 colors and eight channel
+    /// values. BC1 blocks with transparent pixels use the ascending order
+    /// regardless.
+    #[default]
+    Descending,
+    /// The first endpoint is not larger: three BC1 colors and transparent
+    /// black, six channel values and 0 and 255.
+    Ascending,
+}
+
+/// Options of `synthesize`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SynthOptions {
+    /// Endpoint order of the blocks with interpolation modes
+    pub endpoint_order: EndpointOrder,
+    /// Mode of all BC6H blocks, numbered 0 to 13 as in the format
+    /// specification. Two-region modes use partition 0.
+    pub bc6h_mode: u8,
+}
+
+impl Default for SynthOptions {
+    fn default() -> SynthOptions {
+        SynthOptions {
+            endpoint_order: EndpointOrder::Descending,
+            // 10-bit endpoints without deltas, so any pair of colors fits
+            bc6h_mode: 10,
+        }
+    }
+}
+
+/// Encodes a pattern into an image of the given size and encoding.
+///
+/// BC7 images use mode 5 in every block. Pixels with an alpha below 0.5
+/// turn BC1 blocks transparent.
+///
+/// # Errors
+///
+/// Returns `Error::InvalidImageSize` if the size or the checkerboard size
+/// is 0, and `Error::InvalidPixelFormat` if the BC6H mode does not exist.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::{synthesize, BcnDecoderFormat, BcnEncoding, Pattern, SynthOptions};
+///
+/// let pattern = Pattern::Checkerboard {
+///     size: 2,
+///     colors: [[0.0, 0.0, 0.0, 1.0], [1.0, 1.0, 1.0, 1.0]],
+/// };
+/// let data = synthesize(8, 8, BcnEncoding::Bc7, &pattern, &SynthOptions::default()).unwrap();
+/// let pixels = bcndecode::decode(&data, 8, 8, BcnEncoding::Bc7, BcnDecoderFormat::RGBA).unwrap();
+/// assert_eq!(&pixels[..12], &[0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255, 255]);
+/// ```
+pub fn synthesize(
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    pattern: &Pattern,
+    options: &SynthOptions,
+) -> Result<Vec<u8>, Error> {
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidImageSize);
+    }
+    if let Pattern::Checkerboard { size: 0, .. } = *pattern {
+        return Err(Error::InvalidImageSize);
+    }
+    if options.bc6h_mode > 13 {
+        return Err(Error::InvalidPixelFormat);
+    }
+
+    let mut data = Vec::with_capacity(compressed_size(width, height, encoding));
+    for by in (0..height).step_by(4) {
+        for bx in (0..width).step_by(4) {
+            // pixels outside the image repeat the edge
+            let mut pixels = [[0.0; 4]; 16];
+            for (n, pixel) in pixels.iter_mut().enumerate() {
+                let x = (bx + n % 4).min(width - 1);
+                let y = (by + n / 4).min(height - 1);
+                *pixel = pattern.color(x, y, width, height);
+            }
+            encode_block(&mut data, encoding, &pixels, options);
+        }
+    }
+    Ok(data)
+}
+
+fn encode_block(data: &mut Vec<u8>, encoding: BcnEncoding, pixels: &[[f32; 4]; 16], options: &SynthOptions) {
+    let mut rgba = [[0u8; 4]; 16];
+    for (dst, src) in rgba.iter_mut().zip(pixels.iter()) {
+        for c in 0..4 {
+            dst[c] = (src[c].clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+    }
+    let channel = |c: usize| {
+        let mut values = [0; 16];
+        for (value, pixel) in values.iter_mut().zip(rgba.iter()) {
+            *value = pixel[c];
+        }
+        values
+    };
+    let order = options.endpoint_order;
+
+    match encoding {
+        BcnEncoding::Bc1 => data.extend_from_slice(&encode_color(&rgba, order, true)),
+        BcnEncoding::Bc2 => {
+            let mut alpha = [0; 8];
+            for (n, pixel) in rgba.iter().enumerate() {
+                alpha[n / 2] |= (((pixel[3] as u16 * 15 + 127) / 255) << (4 * (n % 2))) as u8;
+            }
+            data.extend_from_slice(&alpha);
+            data.extend_from_slice(&encode_color(&rgba, order, false));
+        }
+        BcnEncoding::Bc3 => {
+            data.extend_from_slice(&encode_channel(&channel(3), order));
+            data.extend_from_slice(&encode_color(&rgba, order, false));
+        }
+        BcnEncoding::Bc4 => data.extend_from_slice(&encode_channel(&channel(0), order)),
+        BcnEncoding::Bc5 => {
+            data.extend_from_slice(&encode_channel(&channel(0), order));
+            data.extend_from_slice(&encode_channel(&channel(1), order));
+        }
+        BcnEncoding::Bc6H => data.extend_from_slice(&encode_bc6h(pixels, options.bc6h_mode)),
+        BcnEncoding::Bc7 => data.extend_from_slice(&encode_bc7_mode5(&rgba)),
+    }
+}
+
+/// Returns the indices of the two pixels that are farthest apart.
+fn farthest_pair<T: Copy, F: Fn(T, T) -> f32>(pixels: &[T], distance: F) -> (usize, usize) {
+    let mut pair = (0, 0);
+    let mut largest = -1.0;
+    for i in 0..pixels.len() {
+        for j in i..pixels.len() {
+            let d = distance(pixels[i], pixels[j]);
+            if d > largest {
+                largest = d;
+                pair = (i, j);
+            }
+        }
+    }
+    pair
+}
+
+/// Returns the index of the palette entry closest to `value`.
+fn nearest<T: Copy, F: Fn(T, T) -> f32>(palette: &[T], value: T, distance: F) -> usize {
+    (0..palette.len())
+        .min_by(|&a, &b| distance(palette[a], value).total_cmp(&distance(palette[b], value)))
+        .unwrap()
+}
+
+fn rgb_distance(a: [u8; 4], b: [u8; 4]) -> f32 {
+    (0..3).map(|c| (a[c] as f32 - b[c] as f32).powi(2)).sum()
+}
+
+/// Encodes a BC1 color block. Pixels with an alpha below 128 are made
+/// transparent if `transparency` is set; otherwise alpha is ignored.
+fn encode_color(pixels: &[[u8; 4]; 16], order: EndpointOrder, transparency: bool) -> [u8; 8] {
+    let transparent = |p: [u8; 4]| transparency && p[3] < 128;
+    let opaque: Vec<[u8; 4]> = pixels.iter().cloned().filter(|&p| !transparent(p)).collect();
+    let (c0, c1) = if opaque.is_empty() {
+        (0, 0)
+    } else {
+        let (i, j) = farthest_pair(&opaque, rgb_distance);
+        (to_565(opaque[i]), to_565(opaque[j]))
+    };
+
+    // three colors and transparent black unless the first endpoint is larger
+    let three_colors = order == EndpointOrder::Ascending || opaque.len() < 16;
+    let (c0, c1) = if (c0 > c1) == three_colors { (c1, c0) } else { (c0, c1) };
+
+    let mut block = [c0 as u8, (c0 >> 8) as u8, c1 as u8, (c1 >> 8) as u8, 0xe4, 0, 0, 0];
+    let palette = &decode_bc1_block(&block)[..4];
+    let mut lut = 0u32;
+    for (n, &pixel) in pixels.iter().enumerate() {
+        let index = if transparent(pixel) {
+            3
+        } else if transparency {
+            // keep opaque pixels away from transparent black
+            nearest(&palette[..3 + !three_colors as usize], pixel, rgb_distance)
+        } else {
+            nearest(palette, pixel, rgb_distance)
+        };
+        lut |= (index as u32) << (2 * n);
+    }
+    block[4..].copy_from_slice(&lut.to_le_bytes());
+    block
+}
+
+fn to_565(p: [u8; 4]) -> u16 {
+    let quantize = |v: u8, max: u16| (v as u16 * max + 127) / 255;
+    quantize(p[0], 31) << 11 | quantize(p[1], 63) << 5 | quantize(p[2], 31)
+}
+
+/// Encodes a BC3 alpha block, the channel blocks of BC4 and BC5.
+fn encode_channel(values: &[u8; 16], order: EndpointOrder) -> [u8; 8] {
+    let (min, max) = (*values.iter().min().unwrap(), *values.iter().max().unwrap());
+    let (a0, a1) = match order {
+        EndpointOrder::Descending => (max, min),
+        EndpointOrder::Ascending => (min, max),
+    };
+
+    // indices 0 to 7 in the first eight pixels list the palette
+    let mut block = [a0, a1, 0x88, 0xc6, 0xfa, 0, 0, 0];
+    let palette = &decode_bc4_block(&block)[..8];
+    let mut lut = 0u64;
+    for (n, &value) in values.iter().enumerate() {
+        let index = nearest(palette, value, |a, b| (a as f32 - b as f32).abs());
+        lut |= (index as u64) << (3 * n);
+    }
+    block[2..].copy_from_slice(&lut.to_le_bytes()[..6]);
+    block
+}
+
+fn encode_bc6h(pixels: &[[f32; 4]; 16], mode: u8) -> [u8; 16] {
+    // unsigned half floats, without infinities and NaN
+    let half = |v: f32| if v > 0.0 { float_to_half(v).min(0x7bff) } else { 0 };
+    let rgb: Vec<[f32; 3]> = pixels.iter().map(|p| [p[0].max(0.0), p[1].max(0.0), p[2].max(0.0)]).collect();
+    let (i, j) = farthest_pair(&rgb, |a, b| (0..3).map(|c| (a[c] - b[c]).powi(2)).sum());
+    let (e0, e1) = (rgb[i], rgb[j]);
+
+    let axis = [e1[0] - e0[0], e1[1] - e0[1], e1[2] - e0[2]];
+    let length = axis.iter().map(|a| a * a).sum::<f32>();
+    let mut t = [0.0; 16];
+    if length > 0.0 {
+        for (t, p) in t.iter_mut().zip(rgb.iter()) {
+            *t = (0..3).map(|c| (p[c] - e0[c]) * axis[c]).sum::<f32>() / length;
+        }
+    }
+    encode_bc6h_block(
+        mode as usize,
+        [half(e0[0]), half(e0[1]), half(e0[2])],
+        [half(e1[0]), half(e1[1]), half(e1[2])],
+        &t,
+    )
+}
+
+/// Encodes a BC7 block in mode 5: one subset of 7-bit RGB and 8-bit alpha
+/// endpoints, with separate 2-bit indices for color and alpha.
+fn encode_bc7_mode5(pixels: &[[u8; 4]; 16]) -> [u8; 16] {
+    const WEIGHTS: [u16; 4] = [0, 21, 43, 64];
+    let lerp = |e0: u8, e1: u8, w: u16| ((e0 as u16 * (64 - w) + e1 as u16 * w + 32) >> 6) as u8;
+
+    let (i, j) = farthest_pair(pixels, rgb_distance);
+    let quantize = |v: u8| ((v as u16 * 127 + 127) / 255) as u8;
+    let expand = |v: u8| v << 1 | v >> 6;
+    let mut colors = [[0u8; 3]; 2];
+    for c in 0..3 {
+        colors[0][c] = quantize(pixels[i][c]);
+        colors[1][c] = quantize(pixels[j][c]);
+    }
+    let mut color_indices = [0u8; 16];
+    for (index, &pixel) in color_indices.iter_mut().zip(pixels.iter()) {
+        let mut palette = [[0u8; 4]; 4];
+        for (entry, &w) in palette.iter_mut().zip(WEIGHTS.iter()) {
+            for c in 0..3 {
+                entry[c] = lerp(expand(colors[0][c]), expand(colors[1][c]), w);
+            }
+        }
+        *index = nearest(&palette, pixel, rgb_distance) as u8;
+    }
+
+    let alpha = pixels.iter().map(|p| p[3]);
+    let mut alphas = [alpha.clone().max().unwrap(), alpha.min().unwrap()];
+    let mut alpha_indices = [0u8; 16];
+    for (index, &pixel) in alpha_indices.iter_mut().zip(pixels.iter()) {
+        let palette: Vec<u8> = WEIGHTS.iter().map(|&w| lerp(alphas[0], alphas[1], w)).collect();
+        *index = nearest(&palette, pixel[3], |a, b| (a as f32 - b as f32).abs()) as u8;
+    }
+
+    // the top index bit of the first pixel is implied 0
+    if color_indices[0] >= 2 {
+        colors.swap(0, 1);
+        for index in color_indices.iter_mut() {
+            *index = 3 - *index;
+        }
+    }
+    if alpha_indices[0] >= 2 {
+        alphas.swap(0, 1);
+        for index in alpha_indices.iter_mut() {
+            *index = 3 - *index;
+        }
+    }
+
+    // mode bit, then a rotation of 0
+    let mut bits: u128 = 1 << 5;
+    let mut offset = 8;
+    for c in 0..3 {
+        for color in colors.iter() {
+            bits |= (color[c] as u128) << offset;
+            offset += 7;
+        }
+    }
+    for &alpha in alphas.iter() {
+        bits |= (alpha as u128) << offset;
+        offset += 8;
+    }
+    for indices in [color_indices, alpha_indices].iter() {
+        for (n, &index) in indices.iter().enumerate() {
+            bits |= (index as u128) << offset;
+            offset += if n == 0 { 1 } else { 2 };
+        }
+    }
+    bits.to_le_bytes()
+}
@@ -0,0 +1,116 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Deswizzling of the block-linear layout of Tegra X1 GPUs, in which
+//! Nintendo Switch textures are stored.
+//!
+//! The blocks of the image are grouped into GOBs of 512 bytes, 64 bytes by
+//! 8 rows of blocks, which are themselves stacked `block_height` GOBs high.
+
+use super::decode::block_size;
+use super::{BcnEncoding, Error};
+
+/// Width of a GOB in bytes
+const GOB_WIDTH: usize = 64;
+
+/// Height of a GOB in rows of blocks
+const GOB_HEIGHT: usize = 8;
+
+const GOB_SIZE: usize = GOB_WIDTH * GOB_HEIGHT;
+
+/// Returns the block height, in GOBs, that Switch textures use for the base
+/// level of an image of the given height in pixels.
+pub fn tegra_block_height(height: usize) -> usize {
+    let rows = height.div_ceil(4);
+    match rows + rows / 2 {
+        128.. => 16,
+        64..=127 => 8,
+        32..=63 => 4,
+        16..=31 => 2,
+        _ => 1,
+    }
+}
+
+/// Reorders a surface in Tegra block-linear layout into rows of blocks.
+///
+/// `block_height` is the number of GOBs stacked in a block: 1, 2, 4, 8, 16
+/// or 32, usually the value of `tegra_block_height` for the base level.
+/// The surface is padded to whole GOBs horizontally and whole blocks of
+/// GOBs vertically; the result holds exactly the blocks of the image.
+///
+/// # Errors
+///
+/// Returns `Error::InvalidImageSize` if the size of the image is 0, the
+/// block height is not supported or `source` is shorter than the padded
+/// surface.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::BcnEncoding;
+///
+/// // two BC1 blocks side by side take a whole GOB
+/// let mut source = vec![0; 512];
+/// source[8] = 1;
+/// let linear = bcndecode::deswizzle_tegra(&source, 8, 4, BcnEncoding::Bc1, 1).unwrap();
+/// assert_eq!(linear.len(), 16);
+/// assert_eq!(linear[8], 1);
+/// ```
+pub fn deswizzle_tegra(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    block_height: usize,
+) -> Result<Vec<u8>, Error> {
+    if width == 0 || height == 0 || !block_height.is_power_of_two() || block_height > 32 {
+        return Err(Error::InvalidImageSize);
+    }
+    let bytes_per_block = block_size(encoding);
+    let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+    let gobs_x = blocks_x.checked_mul(bytes_per_block).ok_or(Error::InvalidImageSize)?.div_ceil(GOB_WIDTH);
+    let gob_rows = blocks_y.div_ceil(GOB_HEIGHT * block_height) * block_height;
+    let size = gobs_x
+        .checked_mul(gob_rows)
+        .and_then(|gobs| gobs.checked_mul(GOB_SIZE))
+        .ok_or(Error::InvalidImageSize)?;
+    if source.len() < size {
+        return Err(Error::InvalidImageSize);
+    }
+
+    let mut linear = Vec::with_capacity(blocks_x * blocks_y * bytes_per_block);
+    for y in 0..blocks_y {
+        for x in 0..blocks_x {
+            let offset = block_linear_offset(x * bytes_per_block, y, gobs_x, block_height);
+            linear.extend_from_slice(&source[offset..offset + bytes_per_block]);
+        }
+    }
+    Ok(linear)
+}
+
+/// Returns the offset of the byte at `x` in row `y` of blocks.
+fn block_linear_offset(x: usize, y: usize, gobs_x: usize, block_height: usize) -> usize {
+    let gob = (y / (GOB_HEIGHT * block_height)) * gobs_x * block_height
+        + (x / GOB_WIDTH) * block_height
+        + (y % (GOB_HEIGHT * block_height)) / GOB_HEIGHT;
+    // within a GOB, the bytes are arranged in sectors of 16 by 2
+    let (x, y) = (x % GOB_WIDTH, y % GOB_HEIGHT);
+    GOB_SIZE * gob + (x / 32) * 256 + (y / 2) * 64 + ((x % 32) / 16) * 32 + (y % 2) * 16 + x % 16
+}
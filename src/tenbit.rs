@@ -0,0 +1,114 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Output in the 10-bit layouts of video pipelines.
+
+use super::decode::decode_rust;
+use super::{BcnDecoderFormat, BcnEncoding, Error};
+
+/// Arrangement of 10-bit channels in the output of `decode_10bit`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TenBitLayout {
+    /// Every channel in a 16-bit word, in the upper 10 bits with the lower
+    /// 6 bits zero, as in P010
+    Msb16,
+    /// Every pixel in a 32-bit word, the first three channels in 10 bits
+    /// each from the least significant bit up and alpha in the top 2 bits,
+    /// as in Y410. Only for the RGBA and BGRA formats.
+    Packed32,
+}
+
+/// Decodes the given BCN encoded image data into 10-bit channels packed as
+/// video pipelines expect them.
+///
+/// 8-bit channels are widened by repeating their upper bits, BC6H values
+/// are clamped to [0, 1] and get an opaque alpha. Words are stored little
+/// endian, with the channels in the order of `format` and the rows in the
+/// order of `decode`.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the
+/// given parameters, or the format is not RGBA or BGRA with
+/// `TenBitLayout::Packed32`.
+pub fn decode_10bit(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    layout: TenBitLayout,
+) -> Result<Vec<u8>, Error> {
+    if layout == TenBitLayout::Packed32 {
+        match format {
+            BcnDecoderFormat::RGBA | BcnDecoderFormat::BGRA => {}
+            _ => return Err(Error::InvalidPixelFormat),
+        }
+    }
+
+    let channels: Vec<u16> = match encoding {
+        BcnEncoding::Bc6H => {
+            // the float channels come in RGB order, without alpha
+            let decoded = decode_rust(source, width, height, encoding, BcnDecoderFormat::RGBA)?;
+            let order: &[usize] = match format {
+                BcnDecoderFormat::BGRA => &[2, 1, 0, 3],
+                BcnDecoderFormat::ARGB => &[3, 0, 1, 2],
+                BcnDecoderFormat::ABGR => &[3, 2, 1, 0],
+                BcnDecoderFormat::RGB => &[0, 1, 2],
+                BcnDecoderFormat::BGR => &[2, 1, 0],
+                BcnDecoderFormat::Custom(_) => return Err(Error::InvalidPixelFormat),
+                _ => &[0, 1, 2, 3],
+            };
+            let mut channels = Vec::with_capacity(4 * width * height);
+            for p in decoded[..12 * width * height].chunks_exact(12) {
+                let rgba = [
+                    f32::from_ne_bytes([p[0], p[1], p[2], p[3]]),
+                    f32::from_ne_bytes([p[4], p[5], p[6], p[7]]),
+                    f32::from_ne_bytes([p[8], p[9], p[10], p[11]]),
+                    1.0,
+                ];
+                // NaN becomes 0
+                channels.extend(order.iter().map(|&c| (rgba[c].clamp(0.0, 1.0) * 1023.0).round() as u16));
+            }
+            channels
+        }
+        _ => decode_rust(source, width, height, encoding, format)?
+            .iter()
+            .map(|&v| ((v as u16) << 2) | ((v as u16) >> 6))
+            .collect(),
+    };
+
+    let mut result = Vec::with_capacity(2 * channels.len());
+    match layout {
+        TenBitLayout::Msb16 => {
+            for c in channels {
+                result.extend_from_slice(&(c << 6).to_le_bytes());
+            }
+        }
+        TenBitLayout::Packed32 => {
+            for p in channels.chunks_exact(4) {
+                let word = p[0] as u32 | (p[1] as u32) << 10 | (p[2] as u32) << 20 | (p[3] as u32 >> 8) << 30;
+                result.extend_from_slice(&word.to_le_bytes());
+            }
+        }
+    }
+    Ok(result)
+}
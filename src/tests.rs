@@ -18,91 +18,75 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-use libc::{c_int, uint8_t};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::Read;
-use std::error;
+use std::io::{self, Read};
+use std::mem::MaybeUninit;
 use super::decode::*;
 
+use const_decode;
+use crn;
+use dds;
+use {decode_cubemap, CubeFace, MissingFaces};
+use {decode_eac_16, decode_etc, EtcFormat};
+use {decode_atc, AtcFormat};
+use {deswizzle_tegra, tegra_block_height, SourceLayout};
+use {detile_gnf, GnfTileMode};
+use atlas::{self, Rect};
+use decode_region;
+use godot;
+use hash;
+use hdr;
+use identify;
+use ktx2;
+use morton;
+use reference;
+use simd;
+use staging;
+use unreal;
+use vtex;
 use BcnDecoderFormat;
+use {Channel, Swizzle};
 use BcnEncoding;
-use super::Error;
-
-static TEST_DATA_PATH: &'static str = "testdata/images/";
-
-static COMPRESSED_BC1: &'static str = "copyright_2048_compressed_bc1.dat";
-static COMPRESSED_BC3: &'static str = "copyright_2048_compressed_bc3.dat";
-static COMPRESSED_BC4: &'static str = "copyright_2048_compressed_bc4.dat";
-static COMPRESSED_BC5: &'static str = "copyright_2048_compressed_bc5.dat";
-static COMPRESSED_BC6H: &'static str = "copyright_2048_compressed_bc6h.dat";
-
-static DECOMPRESSED_BC1: &'static str = "copyright_2048_decompressed_bc1.dat";
-static DECOMPRESSED_BC3: &'static str = "copyright_2048_decompressed_bc3.dat";
-static DECOMPRESSED_BC4: &'static str = "copyright_2048_decompressed_bc4.dat";
-static DECOMPRESSED_BC5: &'static str = "copyright_2048_decompressed_bc5.dat";
-
-extern "C" {
-    fn BcnDecode(
-        dst: *mut uint8_t,
-        dst_size: c_int,
-        src: *const uint8_t,
-        src_size: c_int,
-        width: c_int,
-        height: c_int,
-        N: c_int,
-        dst_format: c_int,
-        flip: c_int,
-    ) -> c_int;
-}
-
-fn decode_c(
-    source: &[u8],
-    width: usize,
-    height: usize,
-    encoding: BcnEncoding,
-    format: BcnDecoderFormat,
-) -> Result<Vec<u8>, Error> {
-    let mut dst_size = (4 * width * height) as usize;
-
-    match encoding {
-        BcnEncoding::Bc4 => {
-            dst_size >>= 2;
-        }
-        BcnEncoding::Bc6H => {
-            dst_size <<= 2;
-        }
-        _ => {}
-    };
+use {decode_to_writer, DecodedRows};
+use TextureView;
+use {decode_chunked, decode_resident, decode_tiles, ResidencyMask};
+use {self_test, SelfTestError};
+use {decode_with_report, repair_blocks, CorruptBlock, Corruption};
+use decode_resized;
+use {decode_bc6h, decode_bc6h_f16, decode_snorm16, decode_to_f32, decode_to_f32_with_options};
+use decode_into_uninit;
+use {decode_with_stats, DecodeStats};
+use {mip_byte_range, required_byte_range};
+use {synthesize, EndpointOrder, Pattern, SynthOptions};
+use {decode_10bit, TenBitLayout};
+use diff_blocks;
+use {find_duplicate_blocks, DuplicateGroup};
+use {block_heatmap, Heatmap};
+use pack_bc4;
+use {decode_raw, decode_raw_float, decode_raw_with_pitch, FloatPrecision, RawFormat};
+use BcnDecoder;
+use {decode_block, Block4x4};
+use StreamedRows;
+use {BlockErrorReason, Error};
+use MipLevel;
+use {DecodeOptions, DecodeStrategy, Lut3d, NormalMap, OutputLayout, SignedMapping, ValueTransform};
+use testutil;
 
-    let mut dst: Vec<u8> = vec![0; dst_size];
+static TEST_DATA_PATH: &str = "testdata/images/";
 
-    let mut flip: c_int = 0;
+static COMPRESSED_BC1: &str = "copyright_2048_compressed_bc1.dat";
+static COMPRESSED_BC3: &str = "copyright_2048_compressed_bc3.dat";
+static COMPRESSED_BC4: &str = "copyright_2048_compressed_bc4.dat";
+static COMPRESSED_BC5: &str = "copyright_2048_compressed_bc5.dat";
+static COMPRESSED_BC6H: &str = "copyright_2048_compressed_bc6h.dat";
 
-    if ((width & 3) | (height & 3)) != 0 {
-        flip = 1;
-    }
-
-    unsafe {
-        let data_read = BcnDecode(
-            dst.as_mut_ptr(),
-            dst.len() as c_int,
-            source.as_ptr(),
-            source.len() as c_int,
-            width as c_int,
-            height as c_int,
-            encoding as c_int,
-            format as c_int,
-            flip,
-        );
-        if data_read < 0 {
-            return Err(Error::ImageDecodingError);
-        }
-    }
+static DECOMPRESSED_BC1: &str = "copyright_2048_decompressed_bc1.dat";
+static DECOMPRESSED_BC3: &str = "copyright_2048_decompressed_bc3.dat";
+static DECOMPRESSED_BC4: &str = "copyright_2048_decompressed_bc4.dat";
+static DECOMPRESSED_BC5: &str = "copyright_2048_decompressed_bc5.dat";
 
-    Ok(dst)
-}
-
-/// Compares the decoding output of the C and rust implementation
+/// Compares the decoding output of the reference and rust implementation
 fn compare_decode(
     compressed_file_path: &str,
     width: usize,
@@ -116,7 +100,7 @@ fn compare_decode(
         Err(err) => panic!(
             "Failed to open test data file at {}: {}",
             file_path,
-            error::Error::description(&err)
+            err
         ),
     };
 
@@ -126,17 +110,18 @@ fn compare_decode(
         Err(err) => panic!(
             "Failed to read test data at {}: {}",
             file_path,
-            error::Error::description(&err)
+            err
         ),
     };
 
-    let decompressed_data_c = match decode_c(&compressed_data, width, height, encoding, format) {
+    let decompressed_data_reference =
+        match reference::decode(&compressed_data, width, height, encoding, format) {
         Ok(result) => result,
         Err(err) => {
             panic!(
-                "Failed to decompress test data with c decoder at {}: {}",
+                "Failed to decompress test data with reference decoder at {}: {}",
                 file_path,
-                error::Error::description(&err)
+                err
             );
         }
     };
@@ -148,13 +133,13 @@ fn compare_decode(
                 panic!(
                     "Failed to decompress test data with rust decoder at {}: {}",
                     file_path,
-                    error::Error::description(&err)
+                    err
                 );
             }
         };
 
-    assert_eq!(decompressed_data_c.len(), decompressed_data_rust.len());
-    assert_eq!(decompressed_data_c, decompressed_data_rust);
+    assert_eq!(decompressed_data_reference.len(), decompressed_data_rust.len());
+    assert_eq!(decompressed_data_reference, decompressed_data_rust);
 }
 
 fn test_decode_rust(
@@ -172,7 +157,7 @@ fn test_decode_rust(
         Err(err) => panic!(
             "Failed to open test data file at {}: {}",
             compressed_file_path,
-            error::Error::description(&err)
+            err
         ),
     };
 
@@ -184,7 +169,7 @@ fn test_decode_rust(
         Err(err) => panic!(
             "Failed to read test data at {}: {}",
             compressed_file_path,
-            error::Error::description(&err)
+            err
         ),
     };
 
@@ -194,7 +179,7 @@ fn test_decode_rust(
         Err(err) => panic!(
             "Failed to open test data file at {}: {}",
             decompressed_file_path,
-            error::Error::description(&err)
+            err
         ),
     };
 
@@ -204,7 +189,7 @@ fn test_decode_rust(
         Err(err) => panic!(
             "Failed to read test data at {}: {}",
             decompressed_file_path,
-            error::Error::description(&err)
+            err
         ),
     };
 
@@ -214,7 +199,7 @@ fn test_decode_rust(
             panic!(
                 "Failed to decompress test data at {}: {}",
                 compressed_file_path,
-                error::Error::description(&err)
+                err
             );
         }
     };
@@ -223,6 +208,53 @@ fn test_decode_rust(
     assert_eq!(decompressed_data, correct_decompressed_data);
 }
 
+#[test]
+fn argb_channel_order() {
+    // opaque red
+    let block = [0x00, 0xf8, 0, 0, 0, 0, 0, 0];
+    let rgba = decode_rust(&block, 4, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA).unwrap();
+    let argb = decode_rust(&block, 4, 4, BcnEncoding::Bc1, BcnDecoderFormat::ARGB).unwrap();
+    assert_eq!(&rgba[..4], &[255, 0, 0, 255]);
+    assert_eq!(&argb[..4], &[255, 255, 0, 0]);
+}
+
+#[test]
+fn bc6h_second_region_blue() {
+    // mode 9 with all bits of the blue endpoint 3 set and everything else
+    // 0; in partition 0 the fourth pixel is in the second region and index
+    // 7 puts it at endpoint 3
+    let bits: u128 = 0x1e | 1 << 12 | 1 << 13 | 1 << 23 | 1 << 32 | 1 << 33 | 1 << 34 | 7 << 90;
+    let decoded = decode_rust(&bits.to_le_bytes(), 4, 4, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA).unwrap();
+    let pixels: Vec<f32> = decoded.chunks(4).map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]])).collect();
+    assert_eq!(&pixels[..3], &[0.0, 0.0, 0.0]);
+    assert_eq!(&pixels[9..12], &[0.0, 0.0, 65504.0]);
+}
+
+#[test]
+fn bc6h_delta_base_channel() {
+    // mode 11 with an 11-bit base of green 200 and zero deltas, second pixel
+    // at the other endpoint: green unquantizes to 6416, the half 3107
+    let bits: u128 = 0x07 | 200 << 15 | 15 << 68;
+    let decoded = decode_rust(&bits.to_le_bytes(), 4, 4, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA).unwrap();
+    let pixels: Vec<f32> = decoded.chunks(4).map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]])).collect();
+    let green = 1059.0 / 4194304.0;
+    assert_eq!(&pixels[..6], &[0.0, green, 0.0, 0.0, green, 0.0]);
+}
+
+#[test]
+fn bc6h_interpolation_rounding() {
+    // mode 10 with endpoints 0 and 100, which unquantize to 0 and 6432, and
+    // index 2 (weight 9) everywhere: (9 * 6432 + 32) >> 6 is 905, which
+    // becomes the half 905 * 31 / 64 = 438
+    let mut bits: u128 = 0x03 | 100 << 35 | 100 << 45 | 100 << 55 | 2 << 65;
+    for texel in 1..16 {
+        bits |= 2 << (68 + 4 * (texel - 1));
+    }
+    let decoded = decode_rust(&bits.to_le_bytes(), 4, 4, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA).unwrap();
+    let pixels: Vec<f32> = decoded.chunks(4).map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]])).collect();
+    assert!(pixels[..48].iter().all(|&c| c == 438.0 / 16777216.0));
+}
+
 #[test]
 fn decode_rust_bc1() {
     test_decode_rust(
@@ -275,6 +307,39 @@ fn decode_rust_bc5() {
     );
 }
 
+#[test]
+fn compare_decode_bc1_bgra() {
+    compare_decode(
+        COMPRESSED_BC1,
+        2048,
+        2048,
+        BcnEncoding::Bc1,
+        BcnDecoderFormat::BGRA,
+    );
+}
+
+#[test]
+fn compare_decode_bc3_argb() {
+    compare_decode(
+        COMPRESSED_BC3,
+        2048,
+        2048,
+        BcnEncoding::Bc3,
+        BcnDecoderFormat::ARGB,
+    );
+}
+
+#[test]
+fn compare_decode_bc3_unaligned() {
+    compare_decode(
+        COMPRESSED_BC3,
+        1021,
+        1023,
+        BcnEncoding::Bc3,
+        BcnDecoderFormat::ABGR,
+    );
+}
+
 #[test]
 fn compare_decode_bc5() {
     compare_decode(
@@ -296,3 +361,2802 @@ fn compare_decode_bc6h() {
         BcnDecoderFormat::RGBA,
     );
 }
+
+#[test]
+fn bc6h_float_output() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC6H))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // flipped, and with padded rows
+    let (width, height) = (29, 18);
+    for &row_pitch in &[0, 12 * width + 8] {
+        let options = DecodeOptions {
+            row_pitch,
+            ..Default::default()
+        };
+        let decoded = decode_with_options(&compressed_data, width, height, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA, &options).unwrap();
+        let floats = decode_bc6h(&compressed_data, width, height, &options).unwrap();
+        assert_eq!(floats.len(), if row_pitch > 0 { row_pitch * height / 4 } else { 3 * width * height });
+        for (value, bytes) in floats.iter().zip(decoded.chunks_exact(4)) {
+            assert_eq!(value.to_bits(), u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+        }
+    }
+    let options = DecodeOptions {
+        row_pitch: 12 * width + 2,
+        ..Default::default()
+    };
+    assert!(decode_bc6h(&compressed_data, width, height, &options).is_err());
+}
+
+#[test]
+fn bc6h_half_float_output() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC6H))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    for &(width, height) in &[(32, 16), (29, 18)] {
+        let floats = decode_to_f32(&compressed_data, width, height, BcnEncoding::Bc6H).unwrap();
+        let halves = decode_bc6h_f16(&compressed_data, width, height, &DecodeOptions::default()).unwrap();
+        assert_eq!(halves.len(), floats.len());
+        for (&h, &f) in halves.iter().zip(floats.iter()) {
+            assert_eq!(half_to_float(h).to_bits(), f.to_bits());
+        }
+    }
+    let options = DecodeOptions {
+        layout: OutputLayout::Tiled(8),
+        ..Default::default()
+    };
+    assert!(decode_bc6h_f16(&compressed_data, 32, 16, &options).is_err());
+}
+
+#[test]
+fn unreal_pixel_format_names() {
+    match unreal::decode_params("PF_DXT1") {
+        Ok((BcnEncoding::Bc1, BcnDecoderFormat::RGBA)) => {}
+        _ => panic!("PF_DXT1 should map to Bc1/RGBA"),
+    }
+    match unreal::decode_params("PF_BC4") {
+        Ok((BcnEncoding::Bc4, BcnDecoderFormat::LUM)) => {}
+        _ => panic!("PF_BC4 should map to Bc4/LUM"),
+    }
+    match unreal::decode_params("PF_BC7") {
+        Ok((BcnEncoding::Bc7, BcnDecoderFormat::RGBA)) => {}
+        _ => panic!("PF_BC7 should map to Bc7/RGBA"),
+    }
+    assert!(unreal::decode_params("PF_B8G8R8A8").is_err());
+    let dxt3 = unreal::UnrealPixelFormat::from_name("PF_DXT3").unwrap();
+    assert_eq!(dxt3.encoding(), BcnEncoding::Bc2);
+}
+
+#[test]
+fn godot_ctex_mip_layout() {
+    let mut data = Vec::new();
+    data.extend_from_slice(b"GST2");
+    for v in &[1u32, 8, 8, 1 << 23, 0, 0, 0, 0, 0] {
+        data.extend_from_slice(&[*v as u8, (*v >> 8) as u8, (*v >> 16) as u8, (*v >> 24) as u8]);
+    }
+    // image header: 8x8, 3 mipmaps, DXT1
+    data.extend_from_slice(&[8, 0, 8, 0, 3, 0, 0, 0, 17, 0, 0, 0]);
+    // 8x8, 4x4, 2x2 and 1x1 levels of white BC1 blocks
+    for _ in 0..7 {
+        data.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0]);
+    }
+
+    let texture = godot::parse(&data).unwrap();
+    assert_eq!(texture.version, godot::GodotTextureVersion::Ctex);
+    assert_eq!((texture.width, texture.height), (8, 8));
+    assert_eq!(texture.mip_count, 4);
+    assert_eq!(texture.mipmaps.len(), 4);
+    assert_eq!(texture.mipmaps[3].width, 1);
+
+    let pixels = texture.decode(0, BcnDecoderFormat::RGBA).unwrap();
+    assert_eq!(pixels.len(), 8 * 8 * 4);
+    assert!(pixels.iter().all(|&p| p == 0xff));
+
+    // a single mipmap stops the chain at 4x4, whatever follows
+    data[44] = 1;
+    let texture = godot::parse(&data).unwrap();
+    assert_eq!((texture.mip_count, texture.mipmaps.len()), (2, 2));
+    assert_eq!(texture.mipmaps[1].width, 4);
+    assert!(texture.decode(2, BcnDecoderFormat::RGBA).is_err());
+    assert!(godot::level_byte_range(&data, 2).is_err());
+
+    // BPTC_RGBF is signed BC6H
+    let block = [0x0c; 16];
+    data.truncate(48);
+    data.extend_from_slice(&[23, 0, 0, 0]);
+    data.extend_from_slice(&block);
+    data[40..48].copy_from_slice(&[4, 0, 4, 0, 0, 0, 0, 0]);
+    let texture = godot::parse(&data).unwrap();
+    assert_eq!(texture.encoding(), Some(BcnEncoding::Bc6H));
+    assert!(texture.signed());
+    let options = DecodeOptions {
+        signed: true,
+        ..Default::default()
+    };
+    let expected = decode_with_options(&block, 4, 4, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA, &options).unwrap();
+    assert!(texture.decode(0, BcnDecoderFormat::RGBA).unwrap() == expected);
+    assert!(expected != decode_rust(&block, 4, 4, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA).unwrap());
+}
+
+#[test]
+fn vtex_mip_order() {
+    let mut data = Vec::new();
+    // resource header with a single DATA block of 40 bytes at offset 28
+    data.extend_from_slice(&[108, 0, 0, 0, 12, 0, 0, 0, 8, 0, 0, 0, 1, 0, 0, 0]);
+    data.extend_from_slice(b"DATA");
+    data.extend_from_slice(&[8, 0, 0, 0, 40, 0, 0, 0]);
+    // VTEX header: 8x8 DXT1 with two mip levels and no extra data
+    data.extend_from_slice(&[1, 0, 0, 0]);
+    data.extend_from_slice(&[0; 16]);
+    data.extend_from_slice(&[8, 0, 8, 0, 1, 0, 1, 2]);
+    data.extend_from_slice(&[0; 12]);
+    // 4x4 level first (black), then the 8x8 level (white)
+    data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
+    for _ in 0..4 {
+        data.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0]);
+    }
+
+    let texture = vtex::parse(&data).unwrap();
+    assert_eq!(texture.mipmaps.len(), 2);
+    assert_eq!((texture.mipmaps[0].width, texture.mipmaps[1].width), (8, 4));
+
+    let pixels = texture.decode(0, BcnDecoderFormat::RGBA).unwrap();
+    assert!(pixels.iter().all(|&p| p == 0xff));
+
+    // more levels than the size allows
+    let mut corrupt = data.clone();
+    corrupt[55] = 100;
+    assert_eq!(vtex::parse(&corrupt).unwrap_err(), Error::ImageDecodingError);
+    // a block table running past the end of the data
+    let mut corrupt = data.clone();
+    corrupt[12..16].copy_from_slice(&[0xff; 4]);
+    corrupt[16..20].copy_from_slice(b"INFO");
+    assert_eq!(vtex::parse(&corrupt).unwrap_err(), Error::ImageDecodingError);
+}
+
+#[test]
+fn vtex_etc_formats() {
+    // the color and the alpha block of `etc2_modes`, and a flat EAC green
+    let color = [0xf0, 0x0f, 0, 0, 0, 0, 0, 0];
+    let alpha = [0x80, 0x20, 0x7c, 0, 0, 0, 0, 0];
+    let green = [0xff, 0x0d, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+    for (format, etc_format, block) in [
+        (23, EtcFormat::Etc2Rgb8, color.to_vec()),
+        (24, EtcFormat::Etc2Rgba8, [alpha, color].concat()),
+        (25, EtcFormat::EacR11, alpha.to_vec()),
+        (26, EtcFormat::EacRg11, [alpha, green].concat()),
+    ] {
+        let mut data = Vec::new();
+        data.extend_from_slice(&[108, 0, 0, 0, 12, 0, 0, 0, 8, 0, 0, 0, 1, 0, 0, 0]);
+        data.extend_from_slice(b"DATA");
+        data.extend_from_slice(&[8, 0, 0, 0, 40, 0, 0, 0]);
+        // VTEX header: 4x4 with a single level
+        data.extend_from_slice(&[1, 0, 0, 0]);
+        data.extend_from_slice(&[0; 16]);
+        data.extend_from_slice(&[4, 0, 4, 0, 1, 0, format, 1]);
+        data.extend_from_slice(&[0; 12]);
+        data.extend_from_slice(&block);
+
+        let texture = vtex::parse(&data).unwrap();
+        assert_eq!(texture.encoding(), Some(vtex::VtexEncoding::Etc(etc_format)));
+        assert_eq!(texture.mipmaps[0].data.len(), block.len());
+        let expected = decode_etc(&block, 4, 4, etc_format, BcnDecoderFormat::RGBA).unwrap();
+        assert_eq!(texture.decode(0, BcnDecoderFormat::RGBA).unwrap(), expected);
+    }
+}
+
+/// Builds the headers of a DDS file, with a DX10 header if `dx10` holds
+/// its format, resource dimension, misc flag and array size.
+fn dds_header(fourcc: &[u8; 4], width: u32, height: u32, mip_count: u32, caps2: u32, dx10: Option<[u32; 4]>) -> Vec<u8> {
+    let mut words = vec![124, 0x1007, height, width, 0, 1, mip_count];
+    words.extend_from_slice(&[0; 11]);
+    words.extend_from_slice(&[32, 0x4]);
+    let mut data = b"DDS ".to_vec();
+    for v in &words {
+        data.extend_from_slice(&v.to_le_bytes());
+    }
+    data.extend_from_slice(fourcc);
+    let mut words = vec![0, 0, 0, 0, 0, 0x1000, caps2, 0, 0, 0];
+    if let Some(dx10) = dx10 {
+        words.extend_from_slice(&dx10);
+        words.push(0);
+    }
+    for v in &words {
+        data.extend_from_slice(&v.to_le_bytes());
+    }
+    data
+}
+
+/// Packs values of the given sizes in bits, most significant bit first.
+fn crn_bits(fields: &[(u32, u32)]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut count = 0;
+    for &(value, bits) in fields {
+        for bit in (0..bits).rev() {
+            if count % 8 == 0 {
+                data.push(0);
+            }
+            *data.last_mut().unwrap() |= (((value >> bit) & 1) as u8) << (7 - count % 8);
+            count += 1;
+        }
+    }
+    // a byte of zeros, which decode to `symbol` for the models below
+    data.push(0);
+    data
+}
+
+/// Builds a Huffman model of crunch in which `symbol` is the only symbol.
+fn crn_model(symbol: u32) -> Vec<(u32, u32)> {
+    // code size 0 is sent fifth and code size 1 nineteenth
+    let mut fields = vec![(symbol + 1, 14), (19, 5)];
+    for i in 0..19 {
+        fields.push((if i == 4 || i == 18 { 1 } else { 0 }, 3));
+    }
+    fields.extend((0..symbol).map(|_| (0, 1)));
+    fields.push((1, 1));
+    fields
+}
+
+#[test]
+fn crn_blocks() {
+    let tables = crn_bits(&[crn_model(0), crn_model(0), crn_model(0)].concat());
+    // every channel decreases by 1 from 0, except green
+    let endpoints = crn_bits(&[crn_model(31), crn_model(1)].concat());
+    // the first pixel of every pair moves to the next palette entry
+    let selectors = crn_bits(&crn_model(25));
+
+    let mut data = vec![0; 74];
+    data[..2].copy_from_slice(b"Hx");
+    data[2..4].copy_from_slice(&74u16.to_be_bytes());
+    data[12..14].copy_from_slice(&12u16.to_be_bytes());
+    data[14..16].copy_from_slice(&8u16.to_be_bytes());
+    data[16] = 1;
+    data[17] = 1;
+    let palette = |offset: usize, data: &mut Vec<u8>, section: &[u8]| {
+        let start = data.len() as u32;
+        data[offset..offset + 3].copy_from_slice(&start.to_be_bytes()[1..]);
+        data[offset + 3..offset + 6].copy_from_slice(&(section.len() as u32).to_be_bytes()[1..]);
+        data[offset + 6..offset + 8].copy_from_slice(&1u16.to_be_bytes());
+        data.extend_from_slice(section);
+    };
+    palette(33, &mut data, &endpoints);
+    palette(41, &mut data, &selectors);
+    let start = data.len() as u32;
+    data[65..67].copy_from_slice(&(tables.len() as u16).to_be_bytes());
+    data[67..70].copy_from_slice(&start.to_be_bytes()[1..]);
+    data.extend_from_slice(&tables);
+    let start = data.len() as u32;
+    data[70..74].copy_from_slice(&start.to_be_bytes());
+    data.extend_from_slice(&[0; 2]);
+
+    let texture = crn::parse(&data).unwrap();
+    assert_eq!((texture.width, texture.height, texture.format), (12, 8, crn::CrnFormat::Dxt1));
+    let blocks = texture.blocks(0, 0).unwrap();
+    assert_eq!(blocks, [0x3f, 0xf8, 0x3f, 0xf8, 0x22, 0x22, 0x22, 0x22].repeat(6));
+    assert_eq!(
+        texture.decode(0, 0, BcnDecoderFormat::RGBA).unwrap(),
+        decode_rust(&blocks, 12, 8, BcnEncoding::Bc1, BcnDecoderFormat::RGBA).unwrap()
+    );
+    assert!(texture.blocks(1, 0).is_err());
+
+    data[18] = 1;
+    assert_eq!(crn::parse(&data).err(), Some(Error::FeatureNotImplemented));
+    data[0] = b'D';
+    assert_eq!(crn::parse(&data).err(), Some(Error::ImageDecodingError));
+}
+
+#[test]
+fn dds_surfaces() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // 8x8 DXT5 with its 4x4 level
+    let mut data = dds_header(b"DXT5", 8, 8, 2, 0, None);
+    data.extend_from_slice(&compressed_data[..80]);
+    let texture = dds::parse(&data).unwrap();
+    assert_eq!((texture.width, texture.height, texture.mip_count), (8, 8, 2));
+    assert_eq!(texture.surface_count(1), 1);
+    let level = texture.decode(1, 0, BcnDecoderFormat::RGBA).unwrap();
+    assert!(level == decode_rust(&compressed_data[64..80], 4, 4, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap());
+    assert!(dds::parse(&data[..data.len() - 1]).is_err());
+
+    // an array of two 6x6 BC3 cubemaps, whose surfaces are flipped by
+    // `decode` but not by `decode_file`
+    let mut data = dds_header(b"DX10", 6, 6, 1, 0, Some([77, 3, 0x4, 2]));
+    data.extend_from_slice(&compressed_data[..12 * 64]);
+    let texture = dds::parse(&data).unwrap();
+    assert_eq!((texture.array_size, texture.faces, texture.dxgi_format), (2, 6, Some(77)));
+    assert_eq!(texture.surface(0, 7).unwrap().data, &compressed_data[7 * 64..8 * 64]);
+    assert!(texture.surface(0, 12).is_none());
+
+    let image = dds::decode_file(&data[..], BcnDecoderFormat::RGB).unwrap();
+    assert_eq!(image.surfaces.len(), 12);
+    let flipped = texture.decode(0, 7, BcnDecoderFormat::RGB).unwrap();
+    let surface = image.surface(0, 7).unwrap();
+    assert!(surface.pixels[..18] == flipped[5 * 18..]);
+
+    // signed formats and volume textures
+    let mut data = dds_header(b"DX10", 4, 4, 3, 0, Some([84, 4, 0, 1]));
+    data[24..28].copy_from_slice(&4u32.to_le_bytes());
+    data.extend_from_slice(&compressed_data[..16 * (4 + 2 + 1)]);
+    let texture = dds::parse(&data).unwrap();
+    assert!(texture.signed);
+    assert_eq!((texture.depth, texture.surface_count(0), texture.surface_count(1), texture.surface_count(2)), (4, 4, 2, 1));
+    assert_eq!(texture.surface(2, 0).unwrap().data, &compressed_data[16 * 6..16 * 7]);
+
+    // sizes that overflow are rejected instead of panicking
+    let data = dds_header(b"DXT5", u32::MAX, u32::MAX, 0, 0, None);
+    assert_eq!(dds::parse(&data).unwrap_err(), Error::ImageDecodingError);
+
+    // uncompressed data described by masks: 3x2 RGB 565 with its 1x1 level
+    let pixel_format = |data: &mut Vec<u8>, flags: u32, bit_count: u32, masks: [u32; 4]| {
+        data[80..84].copy_from_slice(&flags.to_le_bytes());
+        data[88..92].copy_from_slice(&bit_count.to_le_bytes());
+        for (i, mask) in masks.iter().enumerate() {
+            data[92 + 4 * i..96 + 4 * i].copy_from_slice(&mask.to_le_bytes());
+        }
+    };
+    let mut data = dds_header(b"\0\0\0\0", 3, 2, 2, 0, None);
+    pixel_format(&mut data, 0x40, 16, [0xf800, 0x7e0, 0x1f, 0]);
+    let pixels: [u16; 7] = [0xf800, 0, 0, 0x1f, 0, 0x7e0, 0xffff];
+    for p in &pixels {
+        data.extend_from_slice(&p.to_le_bytes());
+    }
+    let texture = dds::parse(&data).unwrap();
+    assert_eq!(texture.raw_format, Some(RawFormat::B5G6R5));
+    let image = dds::decode_file(&data[..], BcnDecoderFormat::RGBA).unwrap();
+    let surface = image.surface(0, 0).unwrap();
+    assert_eq!((surface.width, surface.height, surface.pixels.len()), (3, 2, 24));
+    assert_eq!((&surface.pixels[..4], &surface.pixels[12..16]), (&[0xff, 0, 0, 0xff][..], &[0, 0, 0xff, 0xff][..]));
+    assert_eq!(image.surface(1, 0).unwrap().pixels, [0xff; 4]);
+    assert!(dds::parse(&data[..data.len() - 1]).is_err());
+
+    // luminance, and float formats of the DX10 and legacy headers
+    let mut data = dds_header(b"\0\0\0\0", 2, 1, 1, 0, None);
+    pixel_format(&mut data, 0x20000, 8, [0xff, 0, 0, 0]);
+    data.extend_from_slice(&[7, 9]);
+    assert_eq!(dds::parse(&data).unwrap().decode(0, 0, BcnDecoderFormat::LUM).unwrap(), [7, 9]);
+    let mut data = dds_header(b"DX10", 1, 1, 1, 0, Some([54, 3, 0, 1]));
+    data.extend_from_slice(&[0x00, 0x3c]);
+    assert_eq!(dds::parse(&data).unwrap().decode(0, 0, BcnDecoderFormat::RGB).unwrap(), [0xff, 0, 0]);
+    let mut data = dds_header(&113u32.to_le_bytes(), 1, 1, 1, 0, None);
+    data.extend_from_slice(&[0, 0x3c, 0, 0, 0, 0x3c, 0, 0x38]);
+    assert_eq!(dds::parse(&data).unwrap().decode(0, 0, BcnDecoderFormat::RGBA).unwrap(), [0xff, 0, 0xff, 0x80]);
+
+    // other uncompressed formats are not supported
+    let mut data = dds_header(b"\0\0\0\0", 4, 4, 1, 0, None);
+    pixel_format(&mut data, 0x41, 32, [0xff0000, 0xff00, 0xff, 0xff000000]);
+    assert_eq!(dds::parse(&data).unwrap_err(), Error::FeatureNotImplemented);
+}
+
+#[test]
+fn dds_lenient() {
+    let block = [0xff, 0xff, 0, 0, 0, 0, 0, 0];
+    // 4x4 DXT1 claiming 5 levels, with a zero header size, a wrong linear
+    // size and no FourCC flag, holding only its base level
+    let mut data = dds_header(b"DXT1", 4, 4, 5, 0, None);
+    data[4..8].copy_from_slice(&0u32.to_le_bytes());
+    data[8..12].copy_from_slice(&0x81007u32.to_le_bytes());
+    data[20..24].copy_from_slice(&12345u32.to_le_bytes());
+    data[80..84].copy_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&block);
+    assert!(dds::parse(&data).is_err());
+
+    let (texture, warnings) = dds::parse_lenient(&data).unwrap();
+    assert_eq!(
+        warnings,
+        [
+            dds::DdsWarning::HeaderSize(0),
+            dds::DdsWarning::MissingFourccFlag,
+            dds::DdsWarning::Pitch {
+                stored: 12345,
+                expected: 8
+            },
+            dds::DdsWarning::MipCount { stored: 5, used: 3 },
+            dds::DdsWarning::TruncatedMipChain { stored: 3, used: 1 },
+        ]
+    );
+    assert_eq!((texture.encoding, texture.mip_count), (BcnEncoding::Bc1, 1));
+    assert!(texture.decode(0, 0, BcnDecoderFormat::RGBA).unwrap() == [0xff; 64]);
+
+    // valid files parse without warnings, and truncated arrays stay errors
+    let mut data = dds_header(b"DXT1", 4, 4, 1, 0, None);
+    data.extend_from_slice(&block);
+    assert!(dds::parse_lenient(&data).unwrap().1.is_empty());
+    let mut data = dds_header(b"DX10", 4, 4, 2, 0, Some([71, 3, 0, 2]));
+    data.extend_from_slice(&block);
+    assert!(dds::parse_lenient(&data).is_err());
+}
+
+#[test]
+fn dds_thumbnail() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // 8x8 DXT5 with its 4x4 level
+    let mut data = dds_header(b"DXT5", 8, 8, 2, 0, None);
+    data.extend_from_slice(&compressed_data[..80]);
+    let image = dds::decode_file(&data[..], BcnDecoderFormat::RGBA).unwrap();
+    for &(max_size, level) in &[(1, 1), (4, 1), (5, 0), (100, 0)] {
+        let thumbnail = dds::decode_thumbnail(&data, max_size, BcnDecoderFormat::RGBA).unwrap();
+        let expected = image.surface(level, 0).unwrap();
+        assert_eq!((thumbnail.level, thumbnail.width, thumbnail.height), (level, expected.width, expected.height));
+        assert!(thumbnail.pixels == expected.pixels);
+    }
+    assert!(dds::decode_thumbnail(&data[..data.len() - 1], 4, BcnDecoderFormat::RGBA).is_err());
+}
+
+#[test]
+fn ktx2_from_dds() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+    let word = |data: &[u8], offset: usize| u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+
+    // 8x8 DXT5 with its 4x4 level
+    let mut data = dds_header(b"DXT5", 8, 8, 2, 0, None);
+    data.extend_from_slice(&compressed_data[..80]);
+    let file = ktx2::from_dds(&data).unwrap();
+    assert_eq!(&file[..12], b"\xabKTX 20\xbb\r\n\x1a\n");
+    let header: Vec<u32> = (12..64).step_by(4).map(|offset| word(&file, offset)).collect();
+    // BC3_UNORM, 2 levels, with the descriptor after the level index
+    assert_eq!(header, [137, 1, 8, 8, 0, 0, 1, 2, 0, 128, 60, 0, 0]);
+    let (base, small) = (word(&file, 80) as usize, word(&file, 104) as usize);
+    assert_eq!((word(&file, 88), word(&file, 112)), (64, 16));
+    assert!(small < base && base % 16 == 0 && small % 16 == 0);
+    assert!(file[base..base + 64] == compressed_data[..64] && file[small..small + 16] == compressed_data[64..80]);
+    assert_eq!(file.len(), base + 64);
+    // BC3 model, BT.709 primaries, linear transfer, 4x4 blocks of 16 bytes
+    assert_eq!(word(&file, 140), 130 | 1 << 8 | 1 << 16);
+    assert_eq!((word(&file, 144), word(&file, 148)), (0x303, 16));
+
+    // an array of two sRGB BC1 cubemaps, element by element in every level
+    let mut data = dds_header(b"DX10", 4, 4, 1, 0, Some([72, 3, 0x4, 2]));
+    let faces: Vec<u8> = (0..96).collect();
+    data.extend_from_slice(&faces);
+    let file = ktx2::from_dds(&data).unwrap();
+    assert_eq!((word(&file, 12), word(&file, 32), word(&file, 36)), (134, 2, 6));
+    let offset = word(&file, 80) as usize;
+    assert!(file[offset..] == faces[..]);
+
+    assert_eq!(ktx2::vk_format(BcnEncoding::Bc6H, true, false), Some(144));
+    assert_eq!(ktx2::vk_format(BcnEncoding::Bc4, false, true), None);
+    let mut data = dds_header(b"DX10", 4, 4, 1, 0, Some([65, 3, 0, 1]));
+    data.extend_from_slice(&[0; 16]);
+    assert!(matches!(ktx2::from_dds(&data), Err(Error::FeatureNotImplemented)));
+}
+
+#[test]
+fn cubemap_faces() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC6H))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // 6x6 faces with their 3x3 and 1x1 levels, 4 + 1 + 1 blocks each
+    let source = &compressed_data[..6 * 6 * 16];
+    let options = DecodeOptions::default();
+    let cubemap = decode_cubemap(source, 6, 3, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA, &options).unwrap();
+    assert_eq!((cubemap.level_size(1), cubemap.level_size(2)), (3, 1));
+    for (i, &face) in CubeFace::ALL.iter().enumerate() {
+        let face_source = &source[6 * 16 * i..6 * 16 * (i + 1)];
+        let decoded = decode_rust(&face_source[..64], 6, 6, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA).unwrap();
+        // top row first, without the unused space after the pixels
+        assert!(cubemap.has_face(face));
+        assert_eq!(cubemap.face(face).unwrap().len(), 12 * 36);
+        assert!(cubemap.face(face).unwrap()[..72] == decoded[5 * 72..6 * 72]);
+        let decoded = decode_rust(&face_source[80..], 1, 1, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA).unwrap();
+        assert!(cubemap.level(face, 2).unwrap() == &decoded[..12]);
+        assert!(cubemap.level(face, 3).is_none());
+    }
+    assert!(decode_cubemap(source, 6, 4, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA, &options).is_err());
+
+    let mut data = dds_header(b"DX10", 6, 6, 3, 0, Some([95, 3, 0x4, 1]));
+    data.extend_from_slice(source);
+    let from_dds = dds::parse(&data).unwrap().decode_cubemap(0, BcnDecoderFormat::RGBA, MissingFaces::Skip).unwrap();
+    assert!(from_dds.level(CubeFace::NegativeZ, 1) == cubemap.level(CubeFace::NegativeZ, 1));
+
+    // five 4x4 faces without -Y, the faces after it moved up
+    let blocks: Vec<[u8; 8]> = (0..5).map(|i| [0x1f - 6 * i, 0, 0, 0, 0, 0, 0, 0]).collect();
+    let mut data = dds_header(b"DXT1", 4, 4, 1, 0x200 | 0xdc00, None);
+    data.extend_from_slice(&blocks.concat());
+    let texture = dds::parse(&data).unwrap();
+    assert_eq!(texture.faces, 5);
+    assert_eq!(
+        texture.cube_faces(),
+        [CubeFace::PositiveX, CubeFace::NegativeX, CubeFace::PositiveY, CubeFace::PositiveZ, CubeFace::NegativeZ]
+    );
+    let block = |i: usize| decode_rust(&blocks[i], 4, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA).unwrap();
+    let skipped = texture.decode_cubemap(0, BcnDecoderFormat::RGBA, MissingFaces::Skip).unwrap();
+    assert!(!skipped.has_face(CubeFace::NegativeY));
+    assert!(skipped.face(CubeFace::NegativeY).is_none());
+    assert!(skipped.face(CubeFace::PositiveY).unwrap() == &block(2)[..]);
+    assert!(skipped.face(CubeFace::PositiveZ).unwrap() == &block(3)[..]);
+    assert!(skipped.face(CubeFace::NegativeZ).unwrap() == &block(4)[..]);
+    let filled = texture.decode_cubemap(0, BcnDecoderFormat::RGBA, MissingFaces::Fill).unwrap();
+    assert!(!filled.has_face(CubeFace::NegativeY));
+    assert_eq!(filled.face(CubeFace::NegativeY).unwrap(), &[0; 64][..]);
+    assert!(filled.face(CubeFace::PositiveZ) == skipped.face(CubeFace::PositiveZ));
+}
+
+#[test]
+fn etc2_modes() {
+    let rgba = |block: &[u8], etc_format| decode_etc(block, 4, 4, etc_format, BcnDecoderFormat::RGBA).unwrap();
+    let pixel = |pixels: &[u8], x: usize, y: usize| [pixels[16 * y + 4 * x], pixels[16 * y + 4 * x + 1], pixels[16 * y + 4 * x + 2], pixels[16 * y + 4 * x + 3]];
+
+    // individual mode, side by side and stacked subblocks
+    let pixels = rgba(&[0xf0, 0x0f, 0, 0, 0, 0, 0, 0], EtcFormat::Etc2Rgb8);
+    assert_eq!((pixel(&pixels, 1, 3), pixel(&pixels, 2, 0)), ([0xff, 2, 2, 0xff], [2, 0xff, 2, 0xff]));
+    let pixels = rgba(&[0xf0, 0x0f, 0, 0x01, 0, 0, 0, 0], EtcFormat::Etc2Rgb8);
+    assert_eq!((pixel(&pixels, 3, 1), pixel(&pixels, 0, 2)), ([0xff, 2, 2, 0xff], [2, 0xff, 2, 0xff]));
+
+    // T mode, with the pixel index 1 everywhere, and 2 or 3 by columns
+    let pixels = rgba(&[0xfb, 0, 0x0f, 0x02, 0, 0, 0xff, 0xff], EtcFormat::Etc2Rgb8);
+    assert_eq!(pixel(&pixels, 2, 1), [3, 0xff, 3, 0xff]);
+    let block = [0xfb, 0, 0x0f, 0, 0xff, 0xff, 0xff, 0];
+    let pixels = rgba(&block, EtcFormat::Etc2Rgb8A1);
+    assert_eq!((pixel(&pixels, 0, 0), pixel(&pixels, 3, 0)), ([0, 0, 0, 0], [0, 0xfc, 0, 0xff]));
+
+    // H mode, where the order of the colors selects the distance
+    let pixels = rgba(&[0, 0xfb, 0, 0x02, 0, 0, 0, 0], EtcFormat::Etc2Rgb8);
+    assert_eq!(pixel(&pixels, 0, 0), [6, 23, 244, 0xff]);
+
+    // planar mode, opaque even without the opaque bit
+    let pixels = rgba(&[0, 0, 0xf9, 0x02, 0, 0, 0, 0], EtcFormat::Etc2Rgb8A1);
+    assert_eq!((pixel(&pixels, 0, 0), pixel(&pixels, 1, 0)), ([0, 0, 105, 0xff], [0, 0, 79, 0xff]));
+    assert_eq!(pixel(&pixels, 3, 3), [0, 0, 0, 0xff]);
+    // bit 55, between the green and blue origin, is not part of the colors
+    let pixels = rgba(&[0, 0x80, 0xfb, 0x02, 0, 0, 0, 0], EtcFormat::Etc2Rgb8);
+    assert_eq!(pixel(&pixels, 0, 0), [0, 0, 121, 0xff]);
+
+    // an alpha block in front of the color block, holding the values of the
+    // block of `eac_r11_values` without the scaling to 11 bits
+    let block = [[0x80, 0x20, 0x7c, 0, 0, 0, 0, 0], [0xf0, 0x0f, 0, 0, 0, 0, 0, 0]].concat();
+    let pixels = rgba(&block, EtcFormat::Etc2Rgba8);
+    assert_eq!(pixel(&pixels, 0, 0), [0xff, 2, 2, 98]);
+    assert_eq!((pixel(&pixels, 0, 1), pixel(&pixels, 2, 0)), ([0xff, 2, 2, 156], [2, 0xff, 2, 122]));
+
+    // partial blocks and the other formats
+    let mut source = vec![0; 8 * 6];
+    source[8 * 5..8 * 6].copy_from_slice(&[0xf0, 0x0f, 0, 0, 0, 0, 0, 0]);
+    let pixels = decode_etc(&source, 9, 6, EtcFormat::Etc2Rgb8, BcnDecoderFormat::BGR).unwrap();
+    assert_eq!(pixels.len(), 3 * 9 * 6);
+    assert_eq!(pixels[3 * (9 * 5 + 8)..], [2, 2, 0xff]);
+    assert!(decode_etc(&source[..40], 9, 6, EtcFormat::Etc2Rgb8, BcnDecoderFormat::RGBA).is_err());
+    assert!(decode_etc(&source, 9, 6, EtcFormat::Etc2Rgb8, BcnDecoderFormat::LUM).is_err());
+}
+
+#[test]
+fn eac_r11_values() {
+    // base 128 with a multiplier of 2 and the modifiers -15, +14 and -3
+    // of table 0 for the first two pixels of the first column and the rest
+    let block = [0x80, 0x20, 0x7c, 0, 0, 0, 0, 0];
+    let values = decode_eac_16(&block, 4, 4, EtcFormat::EacR11).unwrap();
+    assert_eq!((values[0], values[4], values[1]), (788 << 5 | 12, 1252 << 5 | 19, 980 << 5 | 15));
+    let lum = decode_etc(&block, 4, 4, EtcFormat::EacR11, BcnDecoderFormat::LUM).unwrap();
+    assert_eq!((lum[0], lum[4]), (98, 156));
+    let rgba = decode_etc(&block, 4, 4, EtcFormat::EacR11, BcnDecoderFormat::RGBA).unwrap();
+    assert_eq!(rgba[16..20], [156, 156, 156, 0xff]);
+
+    // the signed base is -1, clamped like the values
+    let values = decode_eac_16(&block, 4, 4, EtcFormat::EacR11Signed).unwrap();
+    assert_eq!((values[0], values[4]), (0, 7399));
+    let lum = decode_etc(&block, 4, 5, EtcFormat::EacR11Signed, BcnDecoderFormat::LUM);
+    assert!(lum.is_err());
+    let lum = decode_etc(&block, 3, 2, EtcFormat::EacR11Signed, BcnDecoderFormat::LUM).unwrap();
+    assert_eq!(lum, [0, 0, 0, 29, 0, 0]);
+
+    assert!(decode_eac_16(&block, 4, 4, EtcFormat::Etc2Rgb8).is_err());
+}
+
+#[test]
+fn eac_rg11_values() {
+    // the R11 block of `eac_r11_values` in red, and a flat green
+    let block = [0x80, 0x20, 0x7c, 0, 0, 0, 0, 0, 0xff, 0x0d, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+    let values = decode_eac_16(&block, 4, 4, EtcFormat::EacRg11).unwrap();
+    assert_eq!(values.len(), 32);
+    assert_eq!(values[8..10], [1252 << 5 | 19, 0xffff]);
+    let rgba = decode_etc(&block, 4, 4, EtcFormat::EacRg11, BcnDecoderFormat::BGRA).unwrap();
+    assert_eq!(rgba[16..20], [0, 0xff, 156, 0xff]);
+
+    let values = decode_eac_16(&block, 2, 2, EtcFormat::EacRg11Signed).unwrap();
+    // the signed green is -8 + 9, just above 0
+    assert_eq!(values, [0, 32800, 0, 32800, 7399, 32800, 0, 32800]);
+    assert!(decode_etc(&block, 4, 4, EtcFormat::EacRg11, BcnDecoderFormat::LUM).is_err());
+    assert!(decode_etc(&block[..8], 4, 4, EtcFormat::EacRg11Signed, BcnDecoderFormat::RGBA).is_err());
+}
+
+#[test]
+fn atc_blocks() {
+    // red and blue with the blend of two thirds red everywhere
+    let color = [0x00, 0x7c, 0x1f, 0x00, 0x55, 0x55, 0x55, 0x55];
+    let rgba = decode_atc(&color, 4, 4, AtcFormat::Rgb, BcnDecoderFormat::RGBA).unwrap();
+    assert_eq!(rgba[..4], [170, 0, 85, 0xff]);
+
+    // with the top bit set, index 1 is red minus a quarter of red and
+    // index 0 black
+    let color = [0x00, 0xfc, 0x00, 0xf8, 0x55, 0x55, 0x55, 0x54];
+    let bgra = decode_atc(&color, 4, 4, AtcFormat::Rgb, BcnDecoderFormat::BGRA).unwrap();
+    assert_eq!(bgra[..4], [0, 0, 192, 0xff]);
+    assert_eq!(bgra[48..52], [0, 0, 0, 0xff]);
+
+    let mut block = [0; 16];
+    block[0] = 0xf0;
+    block[8..].copy_from_slice(&color);
+    let rgba = decode_atc(&block, 2, 2, AtcFormat::RgbaExplicitAlpha, BcnDecoderFormat::RGBA).unwrap();
+    assert_eq!(rgba, [192, 0, 0, 0, 192, 0, 0, 0xff, 192, 0, 0, 0, 192, 0, 0, 0]);
+
+    block[..2].copy_from_slice(&[200, 100]);
+    let rgba = decode_atc(&block, 4, 4, AtcFormat::RgbaInterpolatedAlpha, BcnDecoderFormat::RGBA).unwrap();
+    assert_eq!(rgba[..8], [192, 0, 0, 200, 192, 0, 0, 200]);
+
+    assert!(decode_atc(&block, 4, 4, AtcFormat::RgbaExplicitAlpha, BcnDecoderFormat::LUM).is_err());
+    assert!(decode_atc(&color, 4, 4, AtcFormat::RgbaInterpolatedAlpha, BcnDecoderFormat::RGBA).is_err());
+}
+
+#[test]
+fn identify_encoding() {
+    for &(path, expected) in &[(COMPRESSED_BC1, 1), (COMPRESSED_BC3, 3), (COMPRESSED_BC6H, 6)] {
+        let mut compressed_data = Vec::new();
+        File::open(format!("{}{}", TEST_DATA_PATH, path))
+            .and_then(|mut f| f.read_to_end(&mut compressed_data))
+            .unwrap();
+
+        let scores = identify::identify(&compressed_data, 2048, 2048);
+        assert_eq!(scores[0].0 as u8, expected, "misidentified {}", path);
+        assert!(scores.windows(2).all(|w| w[0].1 >= w[1].1));
+    }
+
+    // too little data for any encoding
+    assert!(identify::identify(&[0; 8], 8, 8).is_empty());
+}
+
+#[test]
+fn perceptual_hash_across_encodings() {
+    let read = |path: &str| {
+        let mut data = Vec::new();
+        File::open(format!("{}{}", TEST_DATA_PATH, path))
+            .and_then(|mut f| f.read_to_end(&mut data))
+            .unwrap();
+        data
+    };
+    let bc1 = read(COMPRESSED_BC1);
+    let bc3 = read(COMPRESSED_BC3);
+    // the second mip level follows the 2048x2048 level
+    let bc1_mip = &bc1[compressed_size(2048, 2048, BcnEncoding::Bc1)..];
+
+    let phash = hash::phash(&bc1, 2048, 2048, BcnEncoding::Bc1).unwrap();
+    assert!((phash ^ hash::phash(&bc3, 2048, 2048, BcnEncoding::Bc3).unwrap()).count_ones() <= 4);
+    assert!((phash ^ hash::phash(bc1_mip, 1024, 1024, BcnEncoding::Bc1).unwrap()).count_ones() <= 4);
+
+    let dhash = hash::dhash(&bc1, 2048, 2048, BcnEncoding::Bc1).unwrap();
+    assert!((dhash ^ hash::dhash(&bc3, 2048, 2048, BcnEncoding::Bc3).unwrap()).count_ones() <= 4);
+    assert!((dhash ^ hash::dhash(bc1_mip, 1024, 1024, BcnEncoding::Bc1).unwrap()).count_ones() <= 4);
+}
+
+#[test]
+fn auto_exposure_of_constant_bc6h() {
+    // BC6H mode 10 block with all 10-bit endpoints at 462, which decodes
+    // to a gray of 0.5005 everywhere
+    let mut block = [0u8; 16];
+    block[0] = 0x03;
+    for i in 0..6 {
+        for bit in 0..10 {
+            if 462 & (1 << bit) != 0 {
+                let position = 5 + i * 10 + bit;
+                block[position / 8] |= 1 << (position % 8);
+            }
+        }
+    }
+
+    let ev = hdr::auto_exposure(&block, 4, 4, BcnEncoding::Bc6H).unwrap();
+    assert!((ev - (0.18f32 / 0.5005).log2()).abs() < 0.01);
+}
+
+#[test]
+fn tone_mapped_bc6h() {
+    // the gray block of auto_exposure_of_constant_bc6h
+    let mut block = [0u8; 16];
+    block[0] = 0x03;
+    for i in 0..6 {
+        for bit in 0..10 {
+            if 462 & (1 << bit) != 0 {
+                let position = 5 + i * 10 + bit;
+                block[position / 8] |= 1 << (position % 8);
+            }
+        }
+    }
+
+    // 0.5005 is sRGB encoded to 188, and at +1 EV Reinhard maps it to 0.5
+    let clamped = hdr::decode_tone_mapped(&block, 4, 4, 0.0, hdr::ToneMapping::Clamp).unwrap();
+    assert_eq!(clamped.len(), 64);
+    assert_eq!(clamped[..4], [188, 188, 188, 255]);
+    let reinhard = hdr::decode_tone_mapped(&block, 4, 4, 1.0, hdr::ToneMapping::Reinhard).unwrap();
+    assert_eq!(reinhard[..4], [188, 188, 188, 255]);
+    let bright = hdr::decode_tone_mapped(&block, 4, 4, 2.0, hdr::ToneMapping::Clamp).unwrap();
+    assert_eq!(bright[..4], [255, 255, 255, 255]);
+}
+
+#[test]
+fn const_decode_matches_decode() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC1))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+    let bc1 = &compressed_data[..compressed_size(32, 32, BcnEncoding::Bc1)];
+    let rgba = decode_rust(bc1, 32, 32, BcnEncoding::Bc1, BcnDecoderFormat::RGBA).unwrap();
+    let pixels = const_decode::decode_bc1::<1024>(bc1, 32);
+    assert_eq!(pixels.iter().flat_map(|p| p.iter().cloned()).collect::<Vec<_>>(), rgba);
+
+    compressed_data.clear();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC4))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+    let bc4 = &compressed_data[..compressed_size(32, 32, BcnEncoding::Bc4)];
+    let lum = decode_rust(bc4, 32, 32, BcnEncoding::Bc4, BcnDecoderFormat::LUM).unwrap();
+    assert_eq!(const_decode::decode_bc4::<1024>(bc4, 32).to_vec(), lum);
+}
+
+#[test]
+fn encoding_aliases() {
+    for &(name, expected) in &[("dxt1", 1), ("BC3", 3), ("ATI1", 4), ("bc4u", 4), ("ATI2", 5), ("BC5U", 5), ("bc5s", 5), ("bc6h", 6)] {
+        assert_eq!(name.parse::<BcnEncoding>().unwrap() as u8, expected, "{}", name);
+    }
+    assert!("RGBA8".parse::<BcnEncoding>().is_err());
+
+    assert_eq!(BcnEncoding::from_fourcc(*b"ATI2").unwrap() as u8, 5);
+    assert_eq!(BcnEncoding::from_fourcc(*b"DXT3").unwrap() as u8, 2);
+    assert!(BcnEncoding::from_fourcc(*b"DX10").is_err());
+    assert_eq!(BcnEncoding::from_fourcc_signed(*b"BC4S").unwrap(), (BcnEncoding::Bc4, true));
+    assert_eq!(BcnEncoding::from_fourcc_signed(*b"ATI1").unwrap(), (BcnEncoding::Bc4, false));
+}
+
+#[test]
+fn threaded_decode_matches_serial() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let serial = DecodeOptions {
+        strategy: DecodeStrategy::Serial,
+        ..Default::default()
+    };
+    let threaded = DecodeOptions {
+        strategy: DecodeStrategy::Threaded(3),
+        ..Default::default()
+    };
+    // aligned, flipped, and running out of data halfway
+    for &(width, height, len) in &[(256, 256, 65536), (253, 198, 65536), (256, 256, 30000)] {
+        let data = &compressed_data[..len];
+        let expected = decode_with_options(data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, &serial).unwrap();
+        let actual = decode_with_options(data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, &threaded).unwrap();
+        assert!(expected == actual, "{}x{} with {} bytes", width, height, len);
+    }
+}
+
+#[test]
+fn explicit_flip_y() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let reversed = |pixels: &[u8], width: usize| -> Vec<u8> { pixels.chunks_exact(4 * width).rev().flatten().cloned().collect() };
+    for &(width, height) in &[(256, 256), (253, 198)] {
+        let legacy = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap();
+        let top_down = if width % 4 == 0 { legacy.clone() } else { reversed(&legacy, width) };
+        for &strategy in &[DecodeStrategy::Serial, DecodeStrategy::Threaded(3)] {
+            for &flip_y in &[false, true] {
+                let options = DecodeOptions {
+                    strategy,
+                    flip_y: Some(flip_y),
+                    ..Default::default()
+                };
+                let decoded = decode_with_options(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA, &options).unwrap();
+                let expected = if flip_y { reversed(&top_down, width) } else { top_down.clone() };
+                assert!(decoded == expected, "{}x{}, flip_y {}", width, height, flip_y);
+            }
+        }
+    }
+
+    let tiled = DecodeOptions {
+        layout: OutputLayout::Tiled(4),
+        flip_y: Some(true),
+        ..Default::default()
+    };
+    assert!(decode_with_options(&compressed_data, 8, 8, BcnEncoding::Bc3, BcnDecoderFormat::RGBA, &tiled).is_err());
+}
+
+#[test]
+fn tegra_deswizzle() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // GOBs of 64x8 bytes, sectors of 16x2 bytes in 2x4 blocks of 2x1
+    let gob_offset = |x: usize, y: usize| -> usize {
+        ((x & 0x20) << 3) | ((y & 0x6) << 5) | ((x & 0x10) << 1) | ((y & 0x1) << 4) | (x & 0xf)
+    };
+    for &(width, height) in &[(256usize, 256usize), (253, 198), (20, 12)] {
+        let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+        let block_height = tegra_block_height(height);
+        let gobs_x = (16 * blocks_x).div_ceil(64);
+        let gob_rows = blocks_y.div_ceil(8 * block_height) * block_height;
+        let linear = &compressed_data[..16 * blocks_x * blocks_y];
+
+        let mut swizzled = vec![0; 512 * gobs_x * gob_rows];
+        for y in 0..blocks_y {
+            for x in 0..16 * blocks_x {
+                let gob_y = y / 8;
+                let gob = (gob_y / block_height) * gobs_x * block_height + (x / 64) * block_height + gob_y % block_height;
+                swizzled[512 * gob + gob_offset(x % 64, y % 8)] = linear[y * 16 * blocks_x + x];
+            }
+        }
+
+        assert_eq!(deswizzle_tegra(&swizzled, width, height, BcnEncoding::Bc3, block_height).unwrap(), linear);
+        let options = DecodeOptions {
+            source_layout: SourceLayout::TegraBlockLinear(block_height),
+            ..Default::default()
+        };
+        let decoded = decode_with_options(&swizzled, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA, &options).unwrap();
+        let expected = decode_rust(linear, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap();
+        assert!(decoded == expected, "{}x{}", width, height);
+
+        assert!(deswizzle_tegra(&swizzled[1..], width, height, BcnEncoding::Bc3, block_height).is_err());
+        assert!(deswizzle_tegra(&swizzled, width, height, BcnEncoding::Bc3, 3).is_err());
+    }
+    assert_eq!(tegra_block_height(1024), 16);
+    assert_eq!(tegra_block_height(4), 1);
+}
+
+#[test]
+fn gnf_detile() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC1))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // order of the blocks in a micro tile, as (x, y) bit for every index bit
+    let thin = [(0, 0), (0, 1), (1, 0), (1, 1), (2, 0), (2, 1)];
+    let display_64 = [(0, 0), (0, 1), (1, 0), (2, 0), (1, 1), (2, 1)];
+    let display_128 = [(0, 1), (0, 0), (1, 0), (2, 0), (1, 1), (2, 1)];
+    for &encoding in &[BcnEncoding::Bc1, BcnEncoding::Bc3] {
+        let bytes = if encoding == BcnEncoding::Bc1 { 8 } else { 16 };
+        for &(width, height) in &[(256usize, 256usize), (253, 198), (20, 12)] {
+            let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+            let pitch = blocks_x.div_ceil(8) * 8;
+            let linear = &compressed_data[..bytes * blocks_x * blocks_y];
+            for &tile_mode in &[GnfTileMode::LinearAligned, GnfTileMode::Display1dThin, GnfTileMode::Thin1dThin] {
+                let order = match tile_mode {
+                    GnfTileMode::Display1dThin if bytes == 8 => Some(display_64),
+                    GnfTileMode::Display1dThin => Some(display_128),
+                    GnfTileMode::Thin1dThin => Some(thin),
+                    _ => None,
+                };
+                let rows = if order.is_some() { blocks_y.div_ceil(8) * 8 } else { blocks_y };
+
+                let mut tiled = vec![0; bytes * pitch * rows];
+                for y in 0..blocks_y {
+                    for x in 0..blocks_x {
+                        let index = match order {
+                            None => y * pitch + x,
+                            Some(order) => {
+                                let element = order.iter().enumerate().fold(0, |index, (n, &(bit, is_y))| {
+                                    let v = if is_y == 1 { y } else { x };
+                                    index | ((v >> bit) & 1) << n
+                                });
+                                64 * ((y / 8) * (pitch / 8) + x / 8) + element
+                            }
+                        };
+                        let block = &linear[bytes * (y * blocks_x + x)..bytes * (y * blocks_x + x + 1)];
+                        tiled[bytes * index..bytes * (index + 1)].copy_from_slice(block);
+                    }
+                }
+
+                assert_eq!(GnfTileMode::from_raw(tile_mode.raw()), Some(tile_mode));
+                assert_eq!(detile_gnf(&tiled, width, height, encoding, tile_mode).unwrap(), linear);
+                let options = DecodeOptions {
+                    source_layout: SourceLayout::GnfTiled(tile_mode),
+                    ..Default::default()
+                };
+                let decoded = decode_with_options(&tiled, width, height, encoding, BcnDecoderFormat::RGBA, &options).unwrap();
+                let expected = decode_rust(linear, width, height, encoding, BcnDecoderFormat::RGBA).unwrap();
+                assert!(decoded == expected, "{:?} {}x{}", tile_mode, width, height);
+                assert!(detile_gnf(&tiled[1..], width, height, encoding, tile_mode).is_err());
+            }
+        }
+    }
+    assert_eq!(GnfTileMode::from_raw(14), None);
+}
+
+#[test]
+fn morton_order() {
+    assert_eq!(morton::interleave(0b101, 0b011), 0b011011);
+    assert_eq!(morton::deinterleave(0b011011), (0b101, 0b011));
+    for &(x, y) in &[(0, 0), (1, 0), (12345, 678), (u32::MAX as usize, 7)] {
+        assert_eq!(morton::deinterleave(morton::interleave(x, y)), (x, y));
+    }
+
+    // squares of the smaller side follow each other along the longer one
+    let order = |width: usize, height: usize| -> Vec<usize> {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| morton::block_index(x, y, width, height)))
+            .collect()
+    };
+    assert_eq!(order(4, 2), [0, 1, 4, 5, 2, 3, 6, 7]);
+    assert_eq!(order(2, 4), [0, 1, 2, 3, 4, 5, 6, 7]);
+    assert_eq!(order(4, 4), [0, 1, 4, 5, 2, 3, 6, 7, 8, 9, 12, 13, 10, 11, 14, 15]);
+    assert_eq!(order(3, 3), [0, 1, 4, 2, 3, 6, 8, 9, 12]);
+
+    for &block_size in &[1, 3, 8, 16] {
+        for &(width, height) in &[(1usize, 1usize), (4, 4), (8, 2), (3, 17), (13, 5)] {
+            let linear: Vec<u8> = (0..width * height * block_size).map(|i| (i * 7 % 251) as u8).collect();
+            let swizzled = morton::swizzle(&linear, width, height, block_size).unwrap();
+            assert_eq!(swizzled.len(), morton::swizzled_size(width, height, block_size).unwrap());
+            assert_eq!(morton::deswizzle(&swizzled, width, height, block_size).unwrap(), linear);
+            assert!(morton::deswizzle(&swizzled[1..], width, height, block_size).is_err());
+        }
+    }
+    assert!(morton::swizzled_size(0, 4, 8).is_err());
+    assert!(morton::swizzled_size(usize::MAX, 4, 8).is_err());
+
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+    for &(width, height) in &[(256usize, 256usize), (253, 126), (20, 12)] {
+        let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+        let linear = &compressed_data[..16 * blocks_x * blocks_y];
+        let swizzled = morton::swizzle(linear, blocks_x, blocks_y, 16).unwrap();
+        let options = DecodeOptions {
+            source_layout: SourceLayout::Morton,
+            ..Default::default()
+        };
+        let decoded = decode_with_options(&swizzled, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA, &options).unwrap();
+        let expected = decode_rust(linear, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap();
+        assert!(decoded == expected, "{}x{}", width, height);
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn rayon_pool_decode_matches_serial() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (253, 198);
+    let expected = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA).unwrap();
+    // all threads of the pool, and more bands than threads
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    for &strategy in &[DecodeStrategy::Threaded(0), DecodeStrategy::Threaded(7)] {
+        let options = DecodeOptions {
+            strategy,
+            ..Default::default()
+        };
+        let actual = pool
+            .install(|| decode_with_options(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, &options))
+            .unwrap();
+        assert!(expected == actual, "{:?}", strategy);
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+#[test]
+fn decode_in_allocator() {
+    use std::alloc::Global;
+
+    let block = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+    let pixels = ::decode_in(&block, 4, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, &DecodeOptions::default(), Global).unwrap();
+    assert_eq!(&pixels[..], &decode_rust(&block, 4, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA).unwrap()[..]);
+}
+
+#[test]
+fn staging_mip_chain_layout() {
+    let white = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+    // white top row, black bottom row on a 2x2 level
+    let split = [0xff, 0xff, 0, 0, 0, 0x05, 0, 0];
+    let levels = [
+        MipLevel { width: 8, height: 8, data: &[0xff; 32] },
+        MipLevel { width: 4, height: 4, data: &white },
+        MipLevel { width: 2, height: 2, data: &split },
+        MipLevel { width: 1, height: 1, data: &white },
+    ];
+
+    let staging = staging::decode_staging(&levels, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, 16).unwrap();
+    let offsets: Vec<usize> = staging.regions.iter().map(|r| r.offset).collect();
+    assert_eq!(offsets, vec![0, 256, 320, 336]);
+    assert_eq!(staging.data.len(), 340);
+    assert_eq!(&staging.data[320..336], &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0xff, 0, 0, 0, 0xff]);
+}
+
+#[test]
+fn decoded_rows_match_decode() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // aligned, flipped, flipped by height only, and short data
+    for &(width, height, len) in &[(64, 64, 4096), (61, 37, 4096), (64, 38, 4096), (64, 64, 1000)] {
+        let data = &compressed_data[..len];
+        let expected = decode_rust(data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap();
+        let rows = DecodedRows::new(data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap();
+        assert_eq!(rows.size_hint(), (height, Some(height)));
+        let actual: Vec<u8> = rows.flat_map(|row| row.unwrap()).collect();
+        assert!(expected == actual, "{}x{} with {} bytes", width, height, len);
+    }
+
+    assert!(DecodedRows::new(&[], 4, 4, BcnEncoding::Bc1, BcnDecoderFormat::LUM).is_err());
+}
+
+#[test]
+fn decode_to_writer_matches_decode() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    for &(width, height) in &[(64, 64), (61, 37)] {
+        let expected = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA).unwrap();
+        let mut output = Vec::new();
+        decode_to_writer(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, &mut output).unwrap();
+        assert!(expected == output, "{}x{}", width, height);
+    }
+
+    // BC6H without the unused space after the pixels
+    let mut bc6h_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC6H))
+        .and_then(|mut f| f.read_to_end(&mut bc6h_data))
+        .unwrap();
+    let expected = decode_rust(&bc6h_data, 61, 37, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA).unwrap();
+    let mut output = Vec::new();
+    decode_to_writer(&bc6h_data, 61, 37, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA, &mut output).unwrap();
+    assert!(output[..] == expected[..12 * 61 * 37]);
+
+    // a full writer stops the decoding
+    let mut small = [0; 100];
+    let mut writer = &mut small[..];
+    assert!(decode_to_writer(&compressed_data, 64, 64, BcnEncoding::Bc3, BcnDecoderFormat::RGBA, &mut writer).is_err());
+}
+
+#[test]
+fn streamed_rows_match_decode() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // aligned, flipped, flipped by height only, and short data
+    for &(width, height, len) in &[(64, 64, 4096), (61, 37, 4096), (64, 38, 4096), (64, 64, 1000)] {
+        let data = &compressed_data[..len];
+        let rows = DecodedRows::new(data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap();
+        let mut expected: Vec<Vec<u8>> = rows.map(|row| row.unwrap()).collect();
+        if ((width & 3) | (height & 3)) != 0 {
+            expected.reverse();
+        }
+
+        let streamed = StreamedRows::new(data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap();
+        assert_eq!(streamed.size_hint(), (height, Some(height)));
+        let actual: Vec<Vec<u8>> = streamed.map(|row| row.unwrap()).collect();
+        assert!(expected == actual, "{}x{} with {} bytes", width, height, len);
+    }
+
+    // only the blocks of the image are read
+    let mut reader = &compressed_data[..];
+    let mut rows = StreamedRows::new(&mut reader, 8, 8, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap();
+    while rows.next_row().is_some() {}
+    assert_eq!(reader.len(), compressed_data.len() - 64);
+}
+
+#[test]
+fn streamed_rows_stop_at_read_error() {
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("unreadable"))
+        }
+    }
+
+    // the first block row is read, the second one fails
+    let reader = (&[0xff; 32][..]).chain(FailingReader);
+    let mut rows = StreamedRows::new(reader, 8, 8, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap();
+    for _ in 0..4 {
+        assert!(rows.next_row().unwrap().is_ok());
+    }
+    assert!(rows.next_row().unwrap().is_err());
+    assert!(rows.next_row().is_none());
+    assert_eq!(rows.size_hint(), (0, Some(0)));
+}
+
+#[test]
+fn atlas_rects_match_decode() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC1))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+    let data = &compressed_data[..compressed_size(64, 64, BcnEncoding::Bc1)];
+    let full = decode_rust(data, 64, 64, BcnEncoding::Bc1, BcnDecoderFormat::RGBA).unwrap();
+
+    let rects = [
+        Rect { x: 3, y: 5, width: 17, height: 9 },
+        Rect { x: 10, y: 10, width: 20, height: 30 },
+        Rect { x: 0, y: 0, width: 0, height: 0 },
+    ];
+    let sprites = atlas::decode_rects(data, 64, 64, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, &rects).unwrap();
+    for (rect, sprite) in rects.iter().zip(&sprites) {
+        let mut expected = Vec::new();
+        for y in rect.y..rect.y + rect.height {
+            expected.extend_from_slice(&full[4 * (y * 64 + rect.x)..4 * (y * 64 + rect.x + rect.width)]);
+        }
+        assert!(sprite == &expected, "{:?}", rect);
+    }
+
+    let outside = [Rect { x: 60, y: 0, width: 8, height: 8 }];
+    assert!(atlas::decode_rects(data, 64, 64, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, &outside).is_err());
+}
+
+#[test]
+fn region_matches_rects() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // an image that is not a multiple of the block size, and short data
+    let rects = [
+        Rect { x: 3, y: 5, width: 17, height: 9 },
+        Rect { x: 40, y: 20, width: 21, height: 17 },
+        Rect { x: 8, y: 4, width: 4, height: 4 },
+        Rect { x: 0, y: 0, width: 0, height: 0 },
+    ];
+    for &len in &[4096, 1000] {
+        let data = &compressed_data[..len];
+        let sprites = atlas::decode_rects(data, 61, 37, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, &rects).unwrap();
+        for (rect, sprite) in rects.iter().zip(&sprites) {
+            let region = decode_region(data, 61, 37, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, rect).unwrap();
+            assert!(&region == sprite, "{:?} with {} bytes", rect, len);
+        }
+    }
+
+    let outside = Rect { x: 60, y: 0, width: 2, height: 8 };
+    assert!(decode_region(&compressed_data, 61, 37, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, &outside).is_err());
+}
+
+/// Simple xorshift generator for synthetic test data.
+fn xorshift(state: &mut u32) -> u32 {
+    *state ^= *state << 13;
+    *state ^= *state >> 17;
+    *state ^= *state << 5;
+    *state
+}
+
+#[test]
+fn compare_decode_bc7_all_modes() {
+    // random blocks, cycling through the eight modes and the reserved one
+    let mut state = 0x2545_f491;
+    let mut compressed_data = Vec::new();
+    for block in 0..16 * 15 {
+        let mut bytes = [0u8; 16];
+        for byte in bytes.iter_mut() {
+            *byte = xorshift(&mut state) as u8;
+        }
+        let mode = block % 9;
+        bytes[0] = if mode == 8 { 0 } else { (bytes[0] | 1) << mode };
+        compressed_data.extend_from_slice(&bytes);
+    }
+
+    for &(width, height) in &[(64, 60), (61, 58)] {
+        let expected = reference::decode(&compressed_data, width, height, BcnEncoding::Bc7, BcnDecoderFormat::BGRA).unwrap();
+        let actual = decode_rust(&compressed_data, width, height, BcnEncoding::Bc7, BcnDecoderFormat::BGRA).unwrap();
+        assert!(expected == actual, "{}x{}", width, height);
+    }
+
+    // mode 6 with all endpoint values and p-bits set is opaque white
+    let mut block = [0u8; 16];
+    block[0] = 0xc0;
+    for byte in block[1..8].iter_mut() {
+        *byte = 0xff;
+    }
+    block[8] = 0x01;
+    let decoded = decode_rust(&block, 4, 4, BcnEncoding::Bc7, BcnDecoderFormat::RGBA).unwrap();
+    assert!(decoded.iter().all(|&v| v == 0xff));
+}
+
+#[test]
+fn simd_lerp_matches_scalar() {
+    let mut state = 0x9e37_79b9;
+    for _ in 0..64 {
+        let mut e0 = [0u8; 64];
+        let mut e1 = [0u8; 64];
+        let mut weights = [0u8; 64];
+        for i in 0..64 {
+            e0[i] = xorshift(&mut state) as u8;
+            e1[i] = xorshift(&mut state) as u8;
+            weights[i] = (xorshift(&mut state) % 65) as u8;
+        }
+        assert_eq!(simd::lerp_64(&e0, &e1, &weights)[..], simd::lerp_64_scalar(&e0, &e1, &weights)[..]);
+    }
+}
+
+#[test]
+fn simd_lookup_matches_scalar() {
+    let mut state = 0x2545_f491;
+    for _ in 0..64 {
+        let mut table = [0u8; 8];
+        let mut palette = [[0u8; 4]; 4];
+        let mut indices = [0u8; 16];
+        for value in table.iter_mut().chain(palette.iter_mut().flatten()) {
+            *value = xorshift(&mut state) as u8;
+        }
+        for index in indices.iter_mut() {
+            *index = (xorshift(&mut state) % 8) as u8;
+        }
+        assert_eq!(simd::lookup_16(&table, &indices), simd::lookup_16_scalar(&table, &indices));
+        let lut = xorshift(&mut state);
+        assert_eq!(simd::select_pixels_16(&palette, lut), simd::select_pixels_16_scalar(&palette, lut));
+
+        // both x86_64 paths, whichever one the CPU picks
+        #[cfg(all(target_arch = "x86_64", feature = "simd", not(feature = "safe")))]
+        {
+            assert_eq!(simd::lookup_16_sse2(&table, &indices), simd::lookup_16_scalar(&table, &indices));
+            assert_eq!(simd::select_pixels_16_sse2(&palette, lut), simd::select_pixels_16_scalar(&palette, lut));
+            if is_x86_feature_detected!("ssse3") {
+                unsafe {
+                    assert_eq!(simd::lookup_16_ssse3(&table, &indices), simd::lookup_16_scalar(&table, &indices));
+                }
+            }
+            if is_x86_feature_detected!("avx2") {
+                unsafe {
+                    assert_eq!(simd::select_pixels_16_avx2(&palette, lut), simd::select_pixels_16_scalar(&palette, lut));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn sanitize_non_finite_floats() {
+    assert_eq!(sanitize_float(f32::NAN), 0.0);
+    assert_eq!(sanitize_float(f32::INFINITY), 65504.0);
+    assert_eq!(sanitize_float(f32::NEG_INFINITY), -65504.0);
+    assert_eq!(sanitize_float(-1.5), -1.5);
+
+    // unsigned BC6H is always finite, so sanitizing leaves it unchanged
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC6H))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+    let options = DecodeOptions {
+        sanitize_floats: true,
+        ..Default::default()
+    };
+    let data = &compressed_data[..65536];
+    let expected = decode_rust(data, 256, 256, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA).unwrap();
+    let actual = decode_with_options(data, 256, 256, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA, &options).unwrap();
+    assert!(expected == actual);
+}
+
+#[test]
+fn tiled_layout_matches_linear() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    for &(width, height) in &[(256, 256), (253, 198), (6, 6)] {
+        let linear = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA).unwrap();
+        let flipped = ((width & 3) | (height & 3)) != 0;
+        for &tile_size in &[4, 16] {
+            for &strategy in &[DecodeStrategy::Serial, DecodeStrategy::Threaded(3)] {
+                let options = DecodeOptions {
+                    strategy,
+                    layout: OutputLayout::Tiled(tile_size),
+                    ..Default::default()
+                };
+                let tiled = decode_with_options(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, &options).unwrap();
+                let tiles_per_row = width.div_ceil(tile_size);
+                assert_eq!(tiled.len(), 4 * tiles_per_row * tile_size * height.div_ceil(tile_size) * tile_size);
+
+                for y in 0..height.div_ceil(tile_size) * tile_size {
+                    for x in 0..tiles_per_row * tile_size {
+                        let tile = (y / tile_size) * tiles_per_row + x / tile_size;
+                        let offset = 4 * (tile * tile_size * tile_size + (y % tile_size) * tile_size + x % tile_size);
+                        let pixel = &tiled[offset..offset + 4];
+                        if x >= width || y >= height {
+                            assert_eq!(pixel, &[0; 4][..]);
+                            continue;
+                        }
+                        let row = if flipped { height - 1 - y } else { y };
+                        assert_eq!(pixel, &linear[4 * (row * width + x)..4 * (row * width + x) + 4]);
+                    }
+                }
+            }
+        }
+    }
+
+    let options = DecodeOptions {
+        layout: OutputLayout::Tiled(6),
+        ..Default::default()
+    };
+    assert!(decode_with_options(&compressed_data, 8, 8, BcnEncoding::Bc3, BcnDecoderFormat::RGBA, &options).is_err());
+}
+
+#[test]
+fn report_corrupt_blocks() {
+    // 12x8 BC7 image: a reserved block at (4, 4), the last block missing
+    let mut data = vec![0x40; 16 * 5];
+    data[16 * 4] = 0;
+    let (image, report) = decode_with_report(&data, 12, 8, BcnEncoding::Bc7, BcnDecoderFormat::RGBA).unwrap();
+    assert!(image == decode_rust(&data, 12, 8, BcnEncoding::Bc7, BcnDecoderFormat::RGBA).unwrap());
+    assert_eq!(
+        report,
+        vec![
+            CorruptBlock {
+                x: 4,
+                y: 4,
+                offset: 64,
+                corruption: Corruption::ReservedMode,
+            },
+            CorruptBlock {
+                x: 8,
+                y: 4,
+                offset: 80,
+                corruption: Corruption::MissingData,
+            },
+        ]
+    );
+
+    // reserved BC6H mode 10011
+    let (_, report) = decode_with_report(&[0x13; 16], 4, 4, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA).unwrap();
+    assert_eq!(report.len(), 1);
+    let (_, report) = decode_with_report(&[0x03; 16], 4, 4, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA).unwrap();
+    assert!(report.is_empty());
+}
+
+#[test]
+fn repair_corrupt_block() {
+    // BC7 blocks: opaque white (mode 6, all bits set), reserved, and
+    // transparent black (mode 6, all bits clear)
+    let mut data = vec![0; 48];
+    data[0] = 0xc0;
+    for byte in data[1..8].iter_mut() {
+        *byte = 0xff;
+    }
+    data[8] = 0x01;
+    data[32] = 0x40;
+
+    let (mut image, report) = decode_with_report(&data, 12, 4, BcnEncoding::Bc7, BcnDecoderFormat::RGBA).unwrap();
+    assert_eq!(report.len(), 1);
+    repair_blocks(&mut image, 12, 4, BcnEncoding::Bc7, &report).unwrap();
+
+    // a linear ramp across the repaired block, in every row
+    for y in 0..4 {
+        let row = &image[4 * 12 * y..4 * 12 * (y + 1)];
+        for (i, &expected) in [204, 153, 102, 51].iter().enumerate() {
+            assert_eq!(&row[4 * (4 + i)..4 * (5 + i)], &[expected; 4][..]);
+        }
+    }
+}
+
+#[test]
+fn tiles_match_decode() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC1))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // unaligned tiles, and running out of data halfway
+    let (width, height) = (250, 190);
+    let data = &compressed_data[..10000];
+    let expected = reference::decode(data, width, height, BcnEncoding::Bc1, BcnDecoderFormat::RGBA).unwrap();
+    let mut covered = 0;
+    decode_tiles(data, width, height, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, 37, |rect, pixels| -> Result<(), Error> {
+        for y in 0..rect.height {
+            // the full image is flipped
+            let row = height - 1 - (rect.y + y);
+            let start = 4 * (row * width + rect.x);
+            assert!(pixels[4 * rect.width * y..4 * rect.width * (y + 1)] == expected[start..start + 4 * rect.width]);
+        }
+        covered += rect.width * rect.height;
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(covered, width * height);
+}
+
+#[test]
+fn chunked_bands_match_decode() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (250, 190);
+    let expected = reference::decode(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::ARGB).unwrap();
+    let bands = decode_chunked(&compressed_data, width as u64, height as u64, BcnEncoding::Bc3, BcnDecoderFormat::ARGB, 30).unwrap();
+    assert_eq!(bands.len(), 6);
+    assert!(bands.iter().all(|band| band.len() <= 4 * width * 32));
+
+    // the bands are top row first, the full image is flipped
+    let rows: Vec<&[u8]> = bands.iter().flat_map(|band| band.chunks(4 * width)).collect();
+    assert_eq!(rows.len(), height);
+    for (y, row) in rows.iter().enumerate() {
+        let start = 4 * width * (height - 1 - y);
+        assert!(*row == &expected[start..start + 4 * width]);
+    }
+
+    // sizes that overflow are rejected instead of wrapping around
+    assert!(decode_rust(&[], usize::MAX / 2, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA).is_err());
+    assert!(decode_chunked(&[], u64::MAX, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, 4).is_err());
+}
+
+#[test]
+fn resident_pages_only() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC1))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // 3x3 pages of 32 pixels, the last ones cut off; the diagonal is resident
+    let (width, height) = (90, 70);
+    let expected = decode_rust(&compressed_data, width, height, BcnEncoding::Bc1, BcnDecoderFormat::RGBA).unwrap();
+    let mask = ResidencyMask {
+        page_size: 32,
+        bits: &[0b0001_0001, 0b1],
+    };
+    let mut output = vec![0x55; 4 * width * height];
+    decode_resident(&compressed_data, width, height, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, &mask, &mut output).unwrap();
+
+    for y in 0..height {
+        // the image is flipped
+        let row = height - 1 - y;
+        for x in 0..width {
+            let offset = 4 * (row * width + x);
+            let pixel = &output[offset..offset + 4];
+            if x / 32 == y / 32 {
+                assert_eq!(pixel, &expected[offset..offset + 4]);
+            } else {
+                assert_eq!(pixel, &[0x55; 4][..]);
+            }
+        }
+    }
+}
+
+#[test]
+fn built_in_self_test() {
+    assert_eq!(self_test(), Ok::<(), SelfTestError>(()));
+}
+
+#[test]
+fn resized_matches_box_filter() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    for &(width, height) in &[(256, 256), (253, 198)] {
+        let full = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA).unwrap();
+        let flipped = ((width & 3) | (height & 3)) != 0;
+        let (target_width, target_height) = (100, 37);
+        let resized = decode_resized(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, target_width, target_height).unwrap();
+        assert_eq!(resized.len(), 4 * target_width * target_height);
+
+        for ty in 0..target_height {
+            for tx in 0..target_width {
+                let (x0, x1) = (tx * width / target_width, (tx + 1) * width / target_width);
+                let (y0, y1) = (ty * height / target_height, (ty + 1) * height / target_height);
+                for c in 0..4 {
+                    let mut sum = 0;
+                    for y in y0..y1 {
+                        let row = if flipped { height - 1 - y } else { y };
+                        for x in x0..x1 {
+                            sum += full[4 * (row * width + x) + c] as usize;
+                        }
+                    }
+                    let count = (x1 - x0) * (y1 - y0);
+                    let expected = (sum as f64 / count as f64).round() as u8;
+                    assert_eq!(resized[4 * (ty * target_width + tx) + c], expected);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn lut_grading() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // swaps red and blue and halves green
+    let mut cube = String::from("TITLE \"test\"\n# comment\nLUT_3D_SIZE 2\n");
+    for b in 0..2 {
+        for g in 0..2 {
+            for r in 0..2 {
+                cube += &format!("{} {} {}\n", b, g as f32 / 2.0, r);
+            }
+        }
+    }
+    let lut = Lut3d::from_cube(&cube).unwrap();
+    assert_eq!(lut.sample([0.25, 0.5, 1.0]), [1.0, 0.25, 0.25]);
+
+    let (width, height) = (253, 198);
+    let plain = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA).unwrap();
+    let options = DecodeOptions {
+        lut: Some(&lut),
+        ..Default::default()
+    };
+    let graded = decode_with_options(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, &options).unwrap();
+    for (p, g) in plain.chunks_exact(4).zip(graded.chunks_exact(4)) {
+        assert_eq!(g[0], p[2]);
+        assert!((g[1] as f32 - p[1] as f32 / 2.0).abs() <= 0.5);
+        assert_eq!(g[2], p[0]);
+        assert_eq!(g[3], p[3]);
+    }
+
+    assert!(decode_with_options(&compressed_data, 4, 4, BcnEncoding::Bc4, BcnDecoderFormat::LUM, &options).is_err());
+    assert!(Lut3d::from_cube("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+}
+
+#[test]
+fn pack_bc4_channels() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC4))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    for &(width, height) in &[(256usize, 256usize), (253, 198)] {
+        // three different images out of the same data
+        let size = 8 * width.div_ceil(4) * height.div_ceil(4);
+        let sources: Vec<&[u8]> = (0..3).map(|i| &compressed_data[i * size..(i + 1) * size]).collect();
+        let decoded: Vec<Vec<u8>> = sources
+            .iter()
+            .map(|s| decode_rust(s, width, height, BcnEncoding::Bc4, BcnDecoderFormat::LUM).unwrap())
+            .collect();
+
+        for channels in 2..4 {
+            let packed = pack_bc4(&sources[..channels], width, height).unwrap();
+            assert_eq!(packed.len(), channels * width * height);
+            for (i, pixel) in packed.chunks_exact(channels).enumerate() {
+                for (c, &value) in pixel.iter().enumerate() {
+                    assert_eq!(value, decoded[c][i]);
+                }
+            }
+        }
+    }
+    assert!(pack_bc4(&[&compressed_data], 4, 4).is_err());
+}
+
+#[test]
+fn bc4_value_transform() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC4))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (253, 198);
+    let plain = decode_rust(&compressed_data, width, height, BcnEncoding::Bc4, BcnDecoderFormat::LUM).unwrap();
+    let mut curve = [0; 256];
+    for (i, value) in curve.iter_mut().enumerate() {
+        *value = (i / 2) as u8;
+    }
+    for &(transform, f) in &[
+        (ValueTransform::Invert, &(|x: u8| 255 - x) as &dyn Fn(u8) -> u8),
+        (ValueTransform::Curve(&curve), &|x: u8| x / 2),
+    ] {
+        let options = DecodeOptions {
+            value_transform: transform,
+            ..Default::default()
+        };
+        let transformed = decode_with_options(&compressed_data, width, height, BcnEncoding::Bc4, BcnDecoderFormat::LUM, &options).unwrap();
+        for (&p, &t) in plain.iter().zip(&transformed) {
+            assert_eq!(t, f(p));
+        }
+
+        let result = decode_with_options(&compressed_data, 4, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, &options);
+        assert!(result.is_err());
+    }
+}
+
+#[test]
+fn bc5_reconstructed_normals() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC5))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (253, 198);
+    let raw = decode_rust(&compressed_data, width, height, BcnEncoding::Bc5, BcnDecoderFormat::RGBA).unwrap();
+    let options = DecodeOptions {
+        normal_map: NormalMap::Reconstructed,
+        ..Default::default()
+    };
+    let normals = decode_with_options(&compressed_data, width, height, BcnEncoding::Bc5, BcnDecoderFormat::RGBA, &options).unwrap();
+    for (r, n) in raw.chunks_exact(4).zip(normals.chunks_exact(4)) {
+        let v: Vec<f32> = n[..3].iter().map(|&c| c as f32 / 127.5 - 1.0).collect();
+        let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+        assert!((length - 1.0).abs() < 0.015);
+        assert!(v[2] >= -0.004);
+        // the direction in the XY plane is kept
+        let (x, y) = (r[0] as f32 / 127.5 - 1.0, r[1] as f32 / 127.5 - 1.0);
+        assert!((x * v[1] - y * v[0]).abs() < 0.02);
+        assert_eq!(n[3], r[3]);
+    }
+
+    let result = decode_with_options(&compressed_data, 4, 4, BcnEncoding::Bc3, BcnDecoderFormat::RGBA, &options);
+    assert!(result.is_err());
+}
+
+#[test]
+fn bc5_swapped_channels() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC5))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (253, 198);
+    let swap = DecodeOptions {
+        swap_bc5_channels: true,
+        ..Default::default()
+    };
+    let raw = decode_rust(&compressed_data, width, height, BcnEncoding::Bc5, BcnDecoderFormat::RGBA).unwrap();
+    let swapped = decode_with_options(&compressed_data, width, height, BcnEncoding::Bc5, BcnDecoderFormat::RGBA, &swap).unwrap();
+    for (r, s) in raw.chunks_exact(4).zip(swapped.chunks_exact(4)) {
+        assert_eq!(s, &[r[1], r[0], r[2], r[3]][..]);
+    }
+
+    // the normal is reconstructed from the swapped channels
+    let normals = DecodeOptions {
+        normal_map: NormalMap::Reconstructed,
+        ..Default::default()
+    };
+    let swapped_normals = DecodeOptions {
+        normal_map: NormalMap::Reconstructed,
+        swap_bc5_channels: true,
+        ..Default::default()
+    };
+    let block = [200, 200, 0, 0, 0, 0, 0, 0, 90, 90, 0, 0, 0, 0, 0, 0];
+    let swapped_block = [90, 90, 0, 0, 0, 0, 0, 0, 200, 200, 0, 0, 0, 0, 0, 0];
+    assert_eq!(
+        decode_with_options(&block, 4, 4, BcnEncoding::Bc5, BcnDecoderFormat::RGBA, &normals).unwrap(),
+        decode_with_options(&swapped_block, 4, 4, BcnEncoding::Bc5, BcnDecoderFormat::RGBA, &swapped_normals).unwrap()
+    );
+
+    assert!(decode_with_options(&compressed_data, 4, 4, BcnEncoding::Bc4, BcnDecoderFormat::LUM, &swap).is_err());
+}
+
+#[test]
+fn bc4_signed() {
+    let signed = DecodeOptions {
+        signed: true,
+        ..Default::default()
+    };
+    // indices 0 to 7 in the first two rows
+    let decode_signed = |a0: u8, a1: u8| {
+        let block = [a0, a1, 0x88, 0xc6, 0xfa, 0, 0, 0];
+        decode_with_options(&block, 4, 4, BcnEncoding::Bc4, BcnDecoderFormat::LUM, &signed).unwrap()
+    };
+
+    // 1 and -1 with six interpolated values
+    let decoded = decode_signed(0x7f, 0x81);
+    assert_eq!(&decoded[..8], &[255, 0, 219, 182, 146, 109, 73, 36]);
+    assert_eq!(&decoded[8..], &[255; 8]);
+
+    // -128 is -1 too; four interpolated values, then -1 and 1
+    assert_eq!(&decode_signed(0x80, 0x7f)[..8], &[0, 255, 51, 102, 153, 204, 0, 255]);
+    assert_eq!(decode_signed(0x00, 0x00)[0], 128);
+
+    assert!(decode_with_options(&[0; 8], 4, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, &signed).is_err());
+}
+
+#[test]
+fn bc5_signed() {
+    let signed = DecodeOptions {
+        signed: true,
+        ..Default::default()
+    };
+    // X of 0 and Y of -1 in every pixel
+    let block = [0, 0, 0, 0, 0, 0, 0, 0, 0x81, 0x81, 0, 0, 0, 0, 0, 0];
+    let decoded = decode_with_options(&block, 4, 4, BcnEncoding::Bc5, BcnDecoderFormat::RGBA, &signed).unwrap();
+    assert_eq!(&decoded[..2], &[128, 0]);
+
+    // a flat signed normal points straight up
+    let normals = DecodeOptions {
+        signed: true,
+        normal_map: NormalMap::Reconstructed,
+        ..Default::default()
+    };
+    let block = [0; 16];
+    let decoded = decode_with_options(&block, 4, 4, BcnEncoding::Bc5, BcnDecoderFormat::RGBA, &normals).unwrap();
+    assert_eq!(&decoded[..3], &[128, 128, 255]);
+
+    assert!(decode_with_options(&[0; 16], 4, 4, BcnEncoding::Bc7, BcnDecoderFormat::RGBA, &signed).is_err());
+}
+
+#[test]
+fn signed_mappings() {
+    // 1 and -1 with six interpolated values in the first two rows
+    let bc4 = [0x7f, 0x81, 0x88, 0xc6, 0xfa, 0, 0, 0];
+    // the same in red, -1 in green
+    let bc5 = [bc4, [0x81, 0x81, 0, 0, 0, 0, 0, 0]].concat();
+
+    let unorm = DecodeOptions {
+        signed: true,
+        ..Default::default()
+    };
+    let decoded = decode_with_options(&bc4, 4, 4, BcnEncoding::Bc4, BcnDecoderFormat::LUM, &unorm).unwrap();
+    assert_eq!(decoded[..8], [255, 0, 219, 182, 146, 109, 73, 36]);
+
+    let snorm = DecodeOptions {
+        signed_mapping: SignedMapping::Snorm,
+        ..unorm
+    };
+    let decoded = decode_with_options(&bc4, 4, 4, BcnEncoding::Bc4, BcnDecoderFormat::LUM, &snorm).unwrap();
+    let values: Vec<i8> = decoded[..8].iter().map(|&v| v as i8).collect();
+    assert_eq!(values, [127, -127, 91, 54, 18, -18, -54, -91]);
+    let decoded = decode_with_options(&bc5, 4, 4, BcnEncoding::Bc5, BcnDecoderFormat::RGBA, &snorm).unwrap();
+    assert_eq!(decoded[..8], [127, 0x81, 0, 0, 0x81, 0x81, 0, 0]);
+
+    let values = decode_snorm16(&bc4, 4, 4, BcnEncoding::Bc4).unwrap();
+    assert_eq!(values[..8], [32767, -32767, 23405, 14043, 4681, -4681, -14043, -23405]);
+    let values = decode_snorm16(&bc5, 4, 4, BcnEncoding::Bc5).unwrap();
+    assert_eq!(values[..4], [32767, -32767, -32767, -32767]);
+
+    let floats = decode_to_f32_with_options(&bc5, 4, 4, BcnEncoding::Bc5, &snorm).unwrap();
+    assert_eq!(floats[..2], [1.0, -1.0]);
+    assert_eq!(floats[4..6], [-1.0, -1.0]);
+
+    let unsigned = DecodeOptions {
+        signed_mapping: SignedMapping::Snorm,
+        ..Default::default()
+    };
+    assert!(decode_with_options(&bc4, 4, 4, BcnEncoding::Bc4, BcnDecoderFormat::LUM, &unsigned).is_err());
+    let normals = DecodeOptions {
+        normal_map: NormalMap::Reconstructed,
+        ..snorm
+    };
+    assert!(decode_with_options(&bc5, 4, 4, BcnEncoding::Bc5, BcnDecoderFormat::RGBA, &normals).is_err());
+    assert!(decode_snorm16(&bc5, 4, 4, BcnEncoding::Bc3).is_err());
+}
+
+#[test]
+fn bc6h_signed() {
+    let signed = DecodeOptions {
+        signed: true,
+        ..Default::default()
+    };
+    let decode_pixels = |bits: u128, options: &DecodeOptions| -> Vec<f32> {
+        let decoded = decode_with_options(&bits.to_le_bytes(), 4, 4, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA, options).unwrap();
+        decoded.chunks_exact(4).map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]])).collect()
+    };
+
+    // mode 10 with 10-bit endpoints: red -256, green 256, blue 0
+    let bits = 0x03 | 0x300 << 5 | 0x100 << 15;
+    assert_eq!(&decode_pixels(bits, &signed)[..3], &[-1.5302734, 1.5302734, 0.0]);
+    assert!(decode_pixels(bits, &DecodeOptions::default())[0] > 0.0);
+
+    // mode 11 with an 11-bit base of -512 and a red delta of -1, second
+    // pixel at the other endpoint
+    let bits = 0x07 | 0x200 << 5 | 1 << 44 | 0x1ff << 35 | 15 << 68;
+    let pixels = decode_pixels(bits, &signed);
+    assert_eq!(&pixels[..6], &[-1.5146484, 0.0, 0.0, -1.5449219, 0.0, 0.0]);
+}
+
+#[test]
+fn three_byte_formats() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // flipped, on one and on several threads
+    let (width, height) = (61, 37);
+    let rgba = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap();
+    for &strategy in &[DecodeStrategy::Serial, DecodeStrategy::Threaded(4)] {
+        let options = DecodeOptions {
+            strategy,
+            ..Default::default()
+        };
+        let rgb = decode_with_options(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGB, &options).unwrap();
+        let bgr = decode_with_options(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGR, &options).unwrap();
+        assert_eq!(rgb.len(), 3 * width * height);
+        for ((p, rgb), bgr) in rgba.chunks_exact(4).zip(rgb.chunks_exact(3)).zip(bgr.chunks_exact(3)) {
+            assert_eq!(rgb, &p[..3]);
+            assert_eq!(bgr, &[p[2], p[1], p[0]][..]);
+        }
+    }
+    let expected = reference::decode(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGR).unwrap();
+    assert!(expected == decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGR).unwrap());
+
+    let view = TextureView::from_decoded(&expected, width, height, BcnDecoderFormat::BGR).unwrap();
+    let p = &rgba[4 * width * (height - 1)..];
+    assert_eq!(view.rgba(0, 0), [p[0], p[1], p[2], 0xff]);
+    assert!(decode_rust(&compressed_data, 4, 4, BcnEncoding::Bc6H, BcnDecoderFormat::RGB).is_err());
+}
+
+#[test]
+fn bc4_color_output() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC4))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (61, 37);
+    let lum = decode_rust(&compressed_data, width, height, BcnEncoding::Bc4, BcnDecoderFormat::LUM).unwrap();
+    let rgba = decode_rust(&compressed_data, width, height, BcnEncoding::Bc4, BcnDecoderFormat::RGBA).unwrap();
+    let argb = decode_rust(&compressed_data, width, height, BcnEncoding::Bc4, BcnDecoderFormat::ARGB).unwrap();
+    let bgr = decode_rust(&compressed_data, width, height, BcnEncoding::Bc4, BcnDecoderFormat::BGR).unwrap();
+    for (i, &l) in lum.iter().enumerate() {
+        assert_eq!(rgba[4 * i..4 * i + 4], [l, l, l, 0xff]);
+        assert_eq!(argb[4 * i..4 * i + 4], [0xff, l, l, l]);
+        assert_eq!(bgr[3 * i..3 * i + 3], [l, l, l]);
+    }
+    let expected = reference::decode(&compressed_data, width, height, BcnEncoding::Bc4, BcnDecoderFormat::RGBA).unwrap();
+    assert!(expected == rgba);
+
+    // the value transform applies before the expansion
+    let options = DecodeOptions {
+        value_transform: ValueTransform::Invert,
+        strategy: DecodeStrategy::Threaded(3),
+        ..Default::default()
+    };
+    let inverted = decode_with_options(&compressed_data, width, height, BcnEncoding::Bc4, BcnDecoderFormat::BGRA, &options).unwrap();
+    for (i, &l) in lum.iter().enumerate() {
+        assert_eq!(inverted[4 * i..4 * i + 4], [255 - l, 255 - l, 255 - l, 0xff]);
+    }
+}
+
+#[test]
+fn public_enums_as_keys() {
+    let mut cache = HashMap::new();
+    cache.insert((BcnEncoding::Bc1, BcnDecoderFormat::RGBA), 1);
+    cache.insert((BcnEncoding::Bc1, BcnDecoderFormat::BGRA), 2);
+    let swizzle = Swizzle([Channel::Blue, Channel::Green, Channel::Red, Channel::Alpha]);
+    cache.insert((BcnEncoding::Bc7, BcnDecoderFormat::Custom(swizzle)), 3);
+    assert_eq!(cache[&(BcnEncoding::Bc1, BcnDecoderFormat::BGRA)], 2);
+    assert_eq!(cache[&(BcnEncoding::Bc7, BcnDecoderFormat::Custom(swizzle))], 3);
+    assert!(!cache.contains_key(&(BcnEncoding::Bc7, BcnDecoderFormat::RGBA)));
+
+    let error = decode_rust(&[], 4, 4, BcnEncoding::Bc1, BcnDecoderFormat::LUM).unwrap_err();
+    assert_eq!(error, Error::InvalidPixelFormat);
+    assert_eq!(format!("{:?}", BcnEncoding::Bc6H), "Bc6H");
+}
+
+#[test]
+fn size_helpers() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (61, 37);
+    assert_eq!(BcnEncoding::Bc3.compressed_size(width, height).unwrap(), 16 * 16 * 10);
+    assert_eq!(BcnEncoding::Bc4.compressed_size(width, height).unwrap(), 8 * 16 * 10);
+    assert!(BcnEncoding::Bc7.compressed_size(usize::MAX, usize::MAX).is_err());
+    for &format in &[BcnDecoderFormat::RGBA, BcnDecoderFormat::BGR] {
+        let decoded = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, format).unwrap();
+        assert_eq!(BcnEncoding::Bc3.decoded_size(width, height, format).unwrap(), decoded.len());
+    }
+    assert_eq!(BcnEncoding::Bc6H.decoded_size(width, height, BcnDecoderFormat::RGBA).unwrap(), 16 * width * height);
+    assert!(BcnEncoding::Bc3.decoded_size(0, height, BcnDecoderFormat::RGBA).is_err());
+}
+
+#[test]
+fn custom_swizzle_output() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (61, 37);
+    let rgba = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap();
+    let swizzle = Swizzle([Channel::Green, Channel::Alpha, Channel::One, Channel::Green]);
+    let format = BcnDecoderFormat::Custom(swizzle);
+    for &strategy in &[DecodeStrategy::Serial, DecodeStrategy::Threaded(4)] {
+        let options = DecodeOptions {
+            strategy,
+            ..Default::default()
+        };
+        let custom = decode_with_options(&compressed_data, width, height, BcnEncoding::Bc3, format, &options).unwrap();
+        assert_eq!(custom.len(), rgba.len());
+        for (p, c) in rgba.chunks_exact(4).zip(custom.chunks_exact(4)) {
+            assert_eq!(c, &[p[1], p[3], 0xff, p[1]][..]);
+        }
+    }
+    let expected = reference::decode(&compressed_data, width, height, BcnEncoding::Bc3, format).unwrap();
+    assert!(expected == decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, format).unwrap());
+    let view = TextureView::from_decoded(&expected, width, height, format).unwrap();
+    let p = &rgba[4 * width * (height - 1)..];
+    assert_eq!(view.rgba(0, 0), [0, p[1], 0, p[3]]);
+    assert!(decode_rust(&compressed_data, 4, 4, BcnEncoding::Bc6H, format).is_err());
+}
+
+#[test]
+fn srgb_to_linear_output() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (61, 37);
+    let encoded = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA).unwrap();
+    let decoder = BcnDecoder::new(BcnEncoding::Bc3).format(BcnDecoderFormat::BGRA).srgb(true);
+    let linear = decoder.decode(&compressed_data, width, height).unwrap();
+    for (e, l) in encoded.chunks_exact(4).zip(linear.chunks_exact(4)) {
+        for c in 0..3 {
+            let expected = (srgb_to_linear(e[c] as f32 / 255.0) * 255.0).round() as u8;
+            assert_eq!(l[c], expected);
+        }
+        assert_eq!(l[3], e[3]);
+    }
+    assert_eq!(srgb_to_linear(0.0), 0.0);
+    assert!((srgb_to_linear(0.5) - 0.214).abs() < 0.001);
+    assert_eq!(srgb_to_linear(1.0), 1.0);
+    assert!(BcnDecoder::new(BcnEncoding::Bc5).srgb(true).decode(&compressed_data, 4, 4).is_err());
+}
+
+#[test]
+fn unpremultiplied_output() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (61, 37);
+    let stored = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap();
+    let decoder = BcnDecoder::new(BcnEncoding::Bc3).unpremultiply(true);
+    let straight = decoder.decode(&compressed_data, width, height).unwrap();
+    for (p, s) in stored.chunks_exact(4).zip(straight.chunks_exact(4)) {
+        for c in 0..3 {
+            let expected = match p[3] as u16 {
+                0 => p[c],
+                a => ((p[c] as u16 * 255 + a / 2) / a).min(255) as u8,
+            };
+            assert_eq!(s[c], expected);
+        }
+        assert_eq!(s[3], p[3]);
+    }
+    assert!(BcnDecoder::new(BcnEncoding::Bc4).unpremultiply(true).decode(&compressed_data, 4, 4).is_err());
+
+    assert_eq!(BcnEncoding::from_fourcc(*b"DXT4").unwrap(), BcnEncoding::Bc3);
+    let mut data = dds_header(b"DXT4", 4, 4, 1, 0, None);
+    data.extend_from_slice(&compressed_data[..16]);
+    assert!(dds::parse(&data).unwrap().premultiplied);
+    let mut data = dds_header(b"DX10", 4, 4, 1, 0, Some([77, 3, 0, 1]));
+    data.extend_from_slice(&compressed_data[..16]);
+    assert!(!dds::parse(&data).unwrap().premultiplied);
+    let end = data.len() - 16;
+    data[end - 4] = 2;
+    assert!(dds::parse(&data).unwrap().premultiplied);
+}
+
+#[test]
+fn bc1_opaque_output() {
+    // 3-color mode with index 3 in the first row and index 2 in the others
+    let block = [0x00, 0x00, 0xff, 0xff, 0xff, 0xaa, 0xaa, 0xaa];
+    let transparent = decode_rust(&block, 4, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA).unwrap();
+    assert_eq!(transparent[..4], [0, 0, 0, 0]);
+    let opaque = BcnDecoder::new(BcnEncoding::Bc1).bc1_opaque(true).decode(&block, 4, 4).unwrap();
+    assert_eq!(opaque[..4], [0, 0, 0, 0xff]);
+    assert_eq!(opaque[16..], transparent[16..]);
+    assert!(BcnDecoder::new(BcnEncoding::Bc3).bc1_opaque(true).decode(&[0; 16], 4, 4).is_err());
+}
+
+#[test]
+fn strict_source_errors() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (61, 37);
+    let source = &compressed_data[..16 * 16 * 10];
+    let decoder = BcnDecoder::new(BcnEncoding::Bc3).strict(true);
+    assert!(decoder.decode(source, width, height).unwrap() == decode_rust(source, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap());
+
+    let error = decoder.decode(&source[..source.len() - 24], width, height).unwrap_err();
+    match error {
+        Error::InvalidBlock(ref e) => {
+            assert_eq!((e.offset, e.x, e.y), (16 * 158, 56, 36));
+            assert_eq!(e.reason, BlockErrorReason::Truncated { expected: 160, provided: 158 });
+        }
+        _ => panic!("unexpected error {}", error),
+    }
+    assert_eq!(error.to_string(), "Invalid Bc3 block at (56, 36), offset 2528: source is truncated, expected 160 blocks, got 158");
+    let error = decoder.decode(&compressed_data[..16 * 161], width, height).unwrap_err();
+    assert_eq!(error.to_string(), "Invalid Bc3 block at (0, 40), offset 2560: 16 bytes after the expected 160 blocks");
+
+    // a reserved BC7 mode comes before the missing data
+    let mut bc7 = vec![0x40; 16 * 12];
+    bc7[16 * 5] = 0;
+    let decoder = BcnDecoder::new(BcnEncoding::Bc7).strict(true);
+    match decoder.decode(&bc7[..16 * 11], 16, 12).unwrap_err() {
+        Error::InvalidBlock(e) => {
+            assert_eq!((e.offset, e.x, e.y), (16 * 5, 4, 4));
+            assert_eq!(e.reason, BlockErrorReason::InvalidMode);
+        }
+        error => panic!("unexpected error {}", error),
+    }
+    bc7[16 * 5] = 0x40;
+    assert!(decoder.decode(&bc7, 16, 12).is_ok());
+
+    // without strict mode both are decoded
+    assert!(decode_rust(&source[..source.len() - 24], width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).is_ok());
+    assert!(decode_bc6h_f16(&compressed_data[..16 * 161], width, height, &DecodeOptions { strict: true, ..Default::default() }).is_err());
+}
+
+#[test]
+fn row_pitch_output() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // flipped, on one and on several threads
+    let (width, height) = (61, 37);
+    let expected = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap();
+    let row_size = 4 * width;
+    let row_pitch = row_size + 13;
+    for &strategy in &[DecodeStrategy::Serial, DecodeStrategy::Threaded(4)] {
+        let options = DecodeOptions {
+            strategy,
+            row_pitch,
+            ..Default::default()
+        };
+        let decoded = decode_with_options(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA, &options).unwrap();
+        assert_eq!(decoded.len(), row_pitch * height);
+        for y in 0..height {
+            let row = &decoded[y * row_pitch..];
+            assert!(row[..row_size] == expected[y * row_size..(y + 1) * row_size], "{:?} row {}", strategy, y);
+            assert!(row[row_size..row_pitch].iter().all(|&b| b == 0));
+        }
+    }
+
+    // into part of a larger image, leaving the rest alone
+    let mut framebuffer = vec![0xaa; 4 * 64 * 40];
+    let decoder = BcnDecoder::new(BcnEncoding::Bc3).row_pitch(4 * 64);
+    decoder.decode_into(&compressed_data, width, height, &mut framebuffer[4 * 64 * 2..]).unwrap();
+    for y in 0..40 {
+        let row = &framebuffer[y * 4 * 64..(y + 1) * 4 * 64];
+        if (2..2 + height).contains(&y) {
+            assert!(row[..row_size] == expected[(y - 2) * row_size..(y - 1) * row_size]);
+            assert!(row[row_size..].iter().all(|&b| b == 0xaa));
+        } else {
+            assert!(row.iter().all(|&b| b == 0xaa));
+        }
+    }
+    assert!(decoder.decode_into(&compressed_data, width, height, &mut framebuffer[4 * 64 * 4..]).is_err());
+
+    // BC6H rows hold 12 bytes per pixel
+    let options = DecodeOptions {
+        row_pitch: 12 * 8 + 4,
+        ..Default::default()
+    };
+    let hdr = decode_rust(&compressed_data, 8, 4, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA).unwrap();
+    let padded = decode_with_options(&compressed_data, 8, 4, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA, &options).unwrap();
+    assert_eq!(padded.len(), 100 * 4);
+    assert!(padded[100..196] == hdr[96..192]);
+
+    let too_small = DecodeOptions {
+        row_pitch: row_size - 1,
+        ..Default::default()
+    };
+    assert!(decode_with_options(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA, &too_small).is_err());
+    let tiled = DecodeOptions {
+        row_pitch,
+        layout: OutputLayout::Tiled(8),
+        ..Default::default()
+    };
+    assert!(decode_with_options(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA, &tiled).is_err());
+}
+
+#[test]
+fn single_block_matches_decode() {
+    let files = [
+        (COMPRESSED_BC1, BcnEncoding::Bc1, BcnDecoderFormat::RGBA),
+        (COMPRESSED_BC3, BcnEncoding::Bc3, BcnDecoderFormat::RGBA),
+        (COMPRESSED_BC4, BcnEncoding::Bc4, BcnDecoderFormat::LUM),
+        (COMPRESSED_BC5, BcnEncoding::Bc5, BcnDecoderFormat::RGBA),
+        (COMPRESSED_BC6H, BcnEncoding::Bc6H, BcnDecoderFormat::RGBA),
+    ];
+    for &(file, encoding, format) in files.iter() {
+        let mut compressed_data = Vec::new();
+        File::open(format!("{}{}", TEST_DATA_PATH, file))
+            .and_then(|mut f| f.read_to_end(&mut compressed_data))
+            .unwrap();
+
+        let size = block_size(encoding);
+        let block = &compressed_data[size * 5000..size * 5001];
+        let expected = decode_rust(block, 4, 4, encoding, format).unwrap();
+        let actual: Vec<u8> = match decode_block(encoding, block).unwrap() {
+            Block4x4::Rgba(pixels) => pixels.concat(),
+            Block4x4::Luminance(values) => values.to_vec(),
+            Block4x4::RgbFloat(pixels) => pixels.iter().flatten().flat_map(|c| c.to_ne_bytes()).collect(),
+        };
+        assert!(expected[..actual.len()] == actual[..]);
+
+        assert!(decode_block(encoding, &block[1..]).is_err());
+    }
+}
+
+#[test]
+fn decoder_builder() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC5))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (253, 198);
+    let options = DecodeOptions {
+        strategy: DecodeStrategy::Threaded(3),
+        normal_map: NormalMap::Reconstructed,
+        swap_bc5_channels: true,
+        signed: true,
+        ..Default::default()
+    };
+    let expected = decode_with_options(&compressed_data, width, height, BcnEncoding::Bc5, BcnDecoderFormat::BGRA, &options).unwrap();
+    let decoder = BcnDecoder::new(BcnEncoding::Bc5)
+        .format(BcnDecoderFormat::BGRA)
+        .strategy(DecodeStrategy::Threaded(3))
+        .normal_map(NormalMap::Reconstructed)
+        .swap_bc5_channels(true)
+        .signed(true);
+    assert_eq!(decoder.decode(&compressed_data, width, height).unwrap(), expected);
+    let decoder = BcnDecoder::new(BcnEncoding::Bc5).options(options).format(BcnDecoderFormat::BGRA);
+    assert_eq!(decoder.decode(&compressed_data, width, height).unwrap(), expected);
+
+    assert!(BcnDecoder::new(BcnEncoding::Bc1).signed(true).decode(&compressed_data, width, height).is_err());
+}
+
+#[test]
+fn bc5_octahedral_normals() {
+    // encodes unit vectors octahedrally into a BC5 block with constant
+    // channels and checks that they come back
+    let normals = [[0.0f32, 0.0, 1.0], [0.6, 0.0, 0.8], [0.0, -0.6, -0.8], [-0.48, 0.64, -0.6], [0.36, 0.48, 0.8]];
+    for n in normals.iter() {
+        let l1 = n[0].abs() + n[1].abs() + n[2].abs();
+        let (mut u, mut v) = (n[0] / l1, n[1] / l1);
+        if n[2] < 0.0 {
+            let (fu, fv) = ((1.0 - v.abs()).copysign(u), (1.0 - u.abs()).copysign(v));
+            u = fu;
+            v = fv;
+        }
+        let r = ((u + 1.0) * 127.5).round() as u8;
+        let g = ((v + 1.0) * 127.5).round() as u8;
+        let block = [r, r, 0, 0, 0, 0, 0, 0, g, g, 0, 0, 0, 0, 0, 0];
+
+        let options = DecodeOptions {
+            normal_map: NormalMap::Octahedral,
+            ..Default::default()
+        };
+        let decoded = decode_with_options(&block, 4, 4, BcnEncoding::Bc5, BcnDecoderFormat::RGBA, &options).unwrap();
+        for pixel in decoded.chunks_exact(4) {
+            for c in 0..3 {
+                let value = pixel[c] as f32 / 127.5 - 1.0;
+                assert!((value - n[c]).abs() < 0.03, "{:?} {:?}", n, &pixel[..3]);
+            }
+        }
+    }
+}
+
+#[test]
+fn bc7_mode_heatmap() {
+    // a row of blocks in every mode, a reserved block and a missing one
+    let mut data = Vec::new();
+    for mode in 0..8 {
+        let mut block = [0x5a; 16];
+        block[0] = 1 << mode;
+        data.extend_from_slice(&block);
+    }
+    data.extend_from_slice(&[0; 16]);
+
+    let (width, height) = (40, 4);
+    let modes = block_heatmap(&data, width, height, BcnEncoding::Bc7, Heatmap::Mode).unwrap();
+    let partitions = block_heatmap(&data, width, height, BcnEncoding::Bc7, Heatmap::Partition).unwrap();
+    let block_color = |pixels: &[u8], block: usize| {
+        let offset = 4 * (4 * block + 1);
+        for y in 0..height {
+            assert_eq!(&pixels[4 * (y * width + 4 * block)..][..16], &pixels[offset..offset + 4].repeat(4)[..]);
+        }
+        pixels[offset..offset + 4].to_vec()
+    };
+
+    let colors: Vec<Vec<u8>> = (0..8).map(|b| block_color(&modes, b)).collect();
+    for (i, a) in colors.iter().enumerate() {
+        assert!(colors[i + 1..].iter().all(|b| a != b));
+    }
+    assert_eq!(colors[0], vec![0xff, 0, 0, 0xff]);
+    assert_eq!(block_color(&modes, 8), vec![0, 0, 0, 0xff]);
+    assert_eq!(block_color(&modes, 9), vec![0, 0, 0, 0xff]);
+
+    // modes 4 to 6 have a single subset
+    for b in 4..7 {
+        assert_eq!(block_color(&partitions, b), vec![0xff, 0, 0, 0xff]);
+    }
+    assert!(block_heatmap(&data, width, height, BcnEncoding::Bc1, Heatmap::Mode).is_err());
+}
+
+#[test]
+fn diff_changed_blocks() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (253, 198);
+    let size = 16 * 64 * 50;
+    let original = &compressed_data[..size];
+    let mut patched = original.to_vec();
+    // a byte of the first block, the last byte of the block at (8, 4) and
+    // the whole last block, which is cut to the image
+    patched[3] ^= 1;
+    patched[16 * 64 + 16 * 3 - 1] ^= 0x80;
+    patched.truncate(size - 16);
+
+    let rects = diff_blocks(original, &patched, width, height, BcnEncoding::Bc3).unwrap();
+    assert_eq!(
+        rects,
+        vec![
+            Rect { x: 0, y: 0, width: 4, height: 4 },
+            Rect { x: 8, y: 4, width: 4, height: 4 },
+            Rect { x: 252, y: 196, width: 1, height: 2 },
+        ]
+    );
+    assert!(diff_blocks(original, original, width, height, BcnEncoding::Bc3).unwrap().is_empty());
+}
+
+#[test]
+fn duplicate_block_groups() {
+    let white = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+    let black = [0; 8];
+    let red = [0x00, 0xf8, 0x00, 0xf8, 0, 0, 0, 0];
+    // a 12x8 image, the last block is missing
+    let blocks = [white, black, white, red, white];
+    let data = blocks.concat();
+
+    let report = find_duplicate_blocks(&data, 12, 8, BcnEncoding::Bc1).unwrap();
+    assert_eq!(report.total, 5);
+    assert_eq!(report.unique, 3);
+    assert_eq!(
+        report.groups,
+        vec![DuplicateGroup {
+            offset: 0,
+            positions: vec![(0, 0), (8, 0), (4, 4)],
+        }]
+    );
+}
+
+#[test]
+fn float_output_of_every_encoding() {
+    let (width, height) = (253, 198);
+    for &(file, encoding) in &[
+        (COMPRESSED_BC1, BcnEncoding::Bc1),
+        (COMPRESSED_BC4, BcnEncoding::Bc4),
+        (COMPRESSED_BC6H, BcnEncoding::Bc6H),
+    ] {
+        let mut compressed_data = Vec::new();
+        File::open(format!("{}{}", TEST_DATA_PATH, file))
+            .and_then(|mut f| f.read_to_end(&mut compressed_data))
+            .unwrap();
+
+        let floats = decode_to_f32(&compressed_data, width, height, encoding).unwrap();
+        assert_eq!(floats.len(), 4 * width * height);
+        let format = match encoding {
+            BcnEncoding::Bc4 => BcnDecoderFormat::LUM,
+            _ => BcnDecoderFormat::RGBA,
+        };
+        let decoded = decode_rust(&compressed_data, width, height, encoding, format).unwrap();
+
+        // the decoded image is flipped, the floats are not
+        let (x, y) = (17, 5);
+        let pixel = &floats[4 * (y * width + x)..][..4];
+        let offset = (height - 1 - y) * width + x;
+        match encoding {
+            BcnEncoding::Bc4 => {
+                let l = decoded[offset] as f32 / 255.0;
+                assert_eq!(pixel, &[l, l, l, 1.0][..]);
+            }
+            BcnEncoding::Bc6H => {
+                let p = &decoded[12 * offset..][..12];
+                for c in 0..3 {
+                    assert_eq!(pixel[c], f32::from_ne_bytes([p[4 * c], p[4 * c + 1], p[4 * c + 2], p[4 * c + 3]]));
+                }
+                assert_eq!(pixel[3], 1.0);
+            }
+            _ => {
+                for c in 0..4 {
+                    assert_eq!(pixel[c], decoded[4 * offset + c] as f32 / 255.0);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn signed_float_output() {
+    let options = DecodeOptions {
+        signed: true,
+        ..Default::default()
+    };
+    // endpoints -127 and 127, the second one in the bottom row
+    let bc4 = [0x81, 0x7f, 0, 0, 0, 0x49, 0x92, 0x24];
+    let floats = decode_to_f32_with_options(&bc4, 4, 4, BcnEncoding::Bc4, &options).unwrap();
+    assert_eq!(floats[..4], [-1.0, -1.0, -1.0, 1.0]);
+    assert_eq!(floats[48..52], [1.0, 1.0, 1.0, 1.0]);
+
+    // blue and alpha are left as decoded
+    let bc5 = [bc4, [0x7f, 0x81, 0, 0, 0, 0, 0, 0]].concat();
+    let floats = decode_to_f32_with_options(&bc5, 4, 4, BcnEncoding::Bc5, &options).unwrap();
+    assert_eq!(floats[..4], [-1.0, 1.0, 0.0, 0.0]);
+    assert_eq!(floats[48..52], [1.0, 1.0, 0.0, 0.0]);
+
+    let unsigned = decode_to_f32_with_options(&bc4, 4, 4, BcnEncoding::Bc4, &Default::default()).unwrap();
+    assert_eq!(unsigned, decode_to_f32(&bc4, 4, 4, BcnEncoding::Bc4).unwrap());
+    assert!(decode_to_f32_with_options(&bc4, 4, 4, BcnEncoding::Bc1, &options).is_err());
+}
+
+#[test]
+fn ten_bit_layouts() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (64, 32);
+    let decoded = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA).unwrap();
+    let msb = decode_10bit(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, TenBitLayout::Msb16).unwrap();
+    let packed = decode_10bit(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, TenBitLayout::Packed32).unwrap();
+    assert_eq!(msb.len(), 2 * decoded.len());
+    assert_eq!(packed.len(), decoded.len());
+
+    for (i, pixel) in decoded.chunks_exact(4).enumerate() {
+        let word = u32::from_le_bytes([packed[4 * i], packed[4 * i + 1], packed[4 * i + 2], packed[4 * i + 3]]);
+        for (c, &v) in pixel.iter().enumerate() {
+            let word16 = u16::from_le_bytes([msb[8 * i + 2 * c], msb[8 * i + 2 * c + 1]]);
+            // the top 8 of the 10 bits are the 8-bit value
+            assert_eq!(word16 >> 8, v as u16);
+            assert_eq!(word16 & 0x3f, 0);
+            if c < 3 {
+                assert_eq!(word >> (10 * c) & 0x3ff, (word16 >> 6) as u32);
+            } else {
+                assert_eq!(word >> 30, (v >> 6) as u32);
+            }
+        }
+    }
+
+    // full white and black widen to the full range
+    let white = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+    let msb = decode_10bit(&white, 4, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, TenBitLayout::Msb16).unwrap();
+    assert!(msb.chunks_exact(2).all(|w| w == [0xc0, 0xff]));
+    let result = decode_10bit(&white, 4, 4, BcnEncoding::Bc1, BcnDecoderFormat::ARGB, TenBitLayout::Packed32);
+    assert!(result.is_err());
+}
+
+#[test]
+fn raw_luminance_alpha_formats() {
+    let data = [0x10, 0x80, 0x20, 0x90, 0x30, 0xa0, 0x40, 0xb0];
+    let rgba = |raw, format| decode_raw(&data, 2, 2, raw, format).unwrap();
+
+    assert_eq!(&rgba(RawFormat::L8, BcnDecoderFormat::RGBA)[4..8], &[0x80, 0x80, 0x80, 0xff]);
+    assert_eq!(rgba(RawFormat::L8, BcnDecoderFormat::LUM), &data[..4]);
+    assert_eq!(&rgba(RawFormat::A8, BcnDecoderFormat::ARGB)[4..8], &[0x80, 0, 0, 0]);
+    assert_eq!(&rgba(RawFormat::A8L8, BcnDecoderFormat::BGRA)[4..8], &[0x20, 0x20, 0x20, 0x90]);
+    assert_eq!(&rgba(RawFormat::R8G8, BcnDecoderFormat::ABGR)[12..], &[0xff, 0, 0xb0, 0x40]);
+
+    assert!(decode_raw(&data, 2, 2, RawFormat::A8, BcnDecoderFormat::LUM).is_err());
+    assert!(decode_raw(&data, 3, 2, RawFormat::A8L8, BcnDecoderFormat::RGBA).is_err());
+}
+
+#[test]
+fn raw_half_float_formats() {
+    // 1.0, -2.5, 0.5, 65504 as half floats
+    let data = [0x00, 0x3c, 0x00, 0xc1, 0x00, 0x38, 0xff, 0x7b];
+    let floats = |raw| {
+        let bytes = decode_raw_float(&data, 1, 1, raw, FloatPrecision::F32).unwrap();
+        bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(floats(RawFormat::R16F), vec![1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(floats(RawFormat::RG16F), vec![1.0, -2.5, 0.0, 1.0]);
+    assert_eq!(floats(RawFormat::RGBA16F), vec![1.0, -2.5, 0.5, 65504.0]);
+
+    let halves = decode_raw_float(&data, 1, 1, RawFormat::RGBA16F, FloatPrecision::F16).unwrap();
+    let halves: Vec<u16> = halves.chunks_exact(2).map(|b| u16::from_ne_bytes([b[0], b[1]])).collect();
+    assert_eq!(halves, vec![0x3c00, 0xc100, 0x3800, 0x7bff]);
+    let rgba = decode_raw(&data, 1, 1, RawFormat::RGBA16F, BcnDecoderFormat::RGBA).unwrap();
+    assert_eq!(rgba, vec![0xff, 0, 0x80, 0xff]);
+
+    // 8-bit sources come out normalized
+    let halves = decode_raw_float(&[0xff, 0], 1, 1, RawFormat::A8L8, FloatPrecision::F16).unwrap();
+    assert_eq!(&halves[..2], &0x3c00u16.to_ne_bytes());
+    assert_eq!(&halves[6..], &0u16.to_ne_bytes());
+
+    // every finite half survives the round trip through a float
+    for h in 0..=0xffffu16 {
+        if h & 0x7c00 != 0x7c00 || h & 0x3ff == 0 {
+            assert_eq!(float_to_half(half_to_float(h)), h);
+        }
+    }
+    assert_eq!(float_to_half(1e6), 0x7c00);
+    assert_eq!(float_to_half(1e-9), 0);
+}
+
+#[test]
+fn raw_24_bit_padded_rows() {
+    // 2x2 pixels, rows padded to 8 bytes
+    let data = [1, 2, 3, 4, 5, 6, 0xee, 0xee, 7, 8, 9, 10, 11, 12];
+    let rgba = decode_raw_with_pitch(&data, 2, 2, RawFormat::R8G8B8, BcnDecoderFormat::RGBA, 8).unwrap();
+    assert_eq!(rgba, vec![3, 2, 1, 0xff, 6, 5, 4, 0xff, 9, 8, 7, 0xff, 12, 11, 10, 0xff]);
+    let bgra = decode_raw_with_pitch(&data, 2, 2, RawFormat::B8G8R8, BcnDecoderFormat::BGRA, 8).unwrap();
+    assert_eq!(bgra, rgba);
+
+    let tight = decode_raw(&data[..12], 2, 2, RawFormat::B8G8R8, BcnDecoderFormat::RGBA).unwrap();
+    assert_eq!(&tight[4..8], &[4, 5, 6, 0xff]);
+    assert!(decode_raw_with_pitch(&data, 2, 2, RawFormat::R8G8B8, BcnDecoderFormat::RGBA, 5).is_err());
+    assert!(decode_raw_with_pitch(&data, 2, 2, RawFormat::R8G8B8, BcnDecoderFormat::RGBA, 9).is_err());
+}
+
+#[test]
+fn raw_16_bit_packed_formats() {
+    // red, green, blue and a mix, little endian
+    let data565 = [0x00, 0xf8, 0xe0, 0x07, 0x1f, 0x00, 0x10, 0x84];
+    let rgba = decode_raw(&data565, 4, 1, RawFormat::B5G6R5, BcnDecoderFormat::RGBA).unwrap();
+    assert_eq!(rgba, vec![0xff, 0, 0, 0xff, 0, 0xff, 0, 0xff, 0, 0, 0xff, 0xff, 0x84, 0x82, 0x84, 0xff]);
+
+    // opaque red, transparent green, opaque blue, opaque mix
+    let data5551 = [0x00, 0xfc, 0xe0, 0x03, 0x1f, 0x80, 0x10, 0xc2];
+    let rgba = decode_raw(&data5551, 4, 1, RawFormat::B5G5R5A1, BcnDecoderFormat::RGBA).unwrap();
+    assert_eq!(rgba, vec![0xff, 0, 0, 0xff, 0, 0xff, 0, 0, 0, 0, 0xff, 0xff, 0x84, 0x84, 0x84, 0xff]);
+}
+
+#[test]
+fn texture_view_access() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (253, 198);
+    let rgba = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA).unwrap();
+    let argb = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::ARGB).unwrap();
+    let rgba_view = TextureView::from_decoded(&rgba, width, height, BcnDecoderFormat::RGBA).unwrap();
+    let argb_view = TextureView::from_decoded(&argb, width, height, BcnDecoderFormat::ARGB).unwrap();
+
+    // the decoded image is flipped, the view is not
+    let (x, y) = (100, 7);
+    let offset = 4 * ((height - 1 - y) * width + x);
+    assert_eq!(rgba_view.pixel(x, y), &rgba[offset..offset + 4]);
+    assert_eq!(rgba_view.rgba(x, y), argb_view.rgba(x, y));
+    assert_eq!(argb_view.alpha(x, y), argb[offset]);
+    assert_eq!(argb_view.blue(x, y), rgba[offset + 2]);
+    assert_eq!(rgba_view.row(y).len(), 4 * width);
+
+    // rows padded to 1024 bytes, top row first
+    let mut padded = vec![0; 1024 * 4];
+    for y in 0..4 {
+        padded[1024 * y..][..4 * width].copy_from_slice(rgba_view.row(y));
+    }
+    let padded_view = TextureView::new(&padded, width, 4, 1024, BcnDecoderFormat::RGBA).unwrap();
+    assert_eq!(padded_view.pixel(x, 3), rgba_view.pixel(x, 3));
+    assert!(TextureView::new(&padded, width, 5, 1024, BcnDecoderFormat::RGBA).is_err());
+    assert!(TextureView::new(&padded, width, 4, 1000, BcnDecoderFormat::RGBA).is_err());
+}
+
+#[test]
+fn golden_comparison() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC1))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (256, 256);
+    let decoded = decode_rust(&compressed_data, width, height, BcnEncoding::Bc1, BcnDecoderFormat::RGBA).unwrap();
+    let golden = std::env::temp_dir().join(format!("bcndecode-golden-{}", std::process::id())).join("bc1.rgba");
+    testutil::update_golden(&decoded, &golden).unwrap();
+    testutil::check_golden(&decoded, width, height, &[0, 0, 0, 0], &golden).unwrap();
+
+    // one pixel off by 3 in green only passes with enough tolerance
+    let mut changed = decoded.clone();
+    changed[4 * (10 * width + 20) + 1] ^= 3;
+    let comparison = testutil::compare(&changed, &decoded, width, height, &[2, 2, 2, 0]);
+    assert_eq!(comparison.mismatched, 1);
+    assert_eq!(comparison.first_mismatch, Some((20, 10)));
+    assert_eq!(comparison.max_difference, vec![0, 3, 0, 0]);
+    assert!(testutil::compare(&changed, &decoded, width, height, &[0, 3, 0, 0]).is_match());
+
+    assert!(testutil::check_golden(&changed, width, height, &[2, 2, 2, 0], &golden).is_err());
+    let diff = std::fs::read(golden.with_extension("rgba.diff.ppm")).unwrap();
+    assert!(diff.starts_with(b"P6\n256 256\n255\n"));
+    std::fs::remove_dir_all(golden.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn decode_into_uninit_staging() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (253, 198);
+    let expected = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA).unwrap();
+
+    let mut staging = vec![MaybeUninit::uninit(); expected.len() + 100];
+    let decoded =
+        decode_into_uninit(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, &mut staging).unwrap();
+    assert_eq!(decoded, &expected[..]);
+
+    let mut small = vec![MaybeUninit::uninit(); expected.len() - 1];
+    assert!(
+        decode_into_uninit(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, &mut small).is_err()
+    );
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn decode_to_file() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (253, 198);
+    let options = DecodeOptions::default();
+    let expected = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA).unwrap();
+    let path = std::env::temp_dir().join(format!("bcndecode-mmap-{}.bgra", std::process::id()));
+
+    let map = ::decode_to_file(&path, &compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, &options)
+        .unwrap();
+    assert_eq!(&map[..], &expected[..]);
+    map.flush().unwrap();
+    drop(map);
+    assert_eq!(std::fs::read(&path).unwrap(), expected);
+
+    assert!(::decode_to_file(&path, &compressed_data, 0, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA, &options).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn dds_to_png() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let mut data = dds_header(b"DXT5", 8, 8, 1, 0, None);
+    data.extend_from_slice(&compressed_data[..64]);
+    let dir = std::env::temp_dir();
+    let path_in = dir.join(format!("bcndecode-png-{}.dds", std::process::id()));
+    let path_out = path_in.with_extension("png");
+    std::fs::write(&path_in, &data).unwrap();
+    dds::decode_to_png(&path_in, &path_out).unwrap();
+
+    let decoder = png::Decoder::new(File::open(&path_out).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!((info.width, info.height, info.color_type), (8, 8, png::ColorType::Rgba));
+    let expected = dds::decode_file(&data[..], BcnDecoderFormat::RGBA).unwrap();
+    assert!(pixels == expected.surfaces[0].pixels);
+
+    std::fs::write(&path_in, &data[..data.len() - 1]).unwrap();
+    assert!(dds::decode_to_png(&path_in, &path_out).is_err());
+    std::fs::remove_file(&path_in).unwrap();
+    std::fs::remove_file(&path_out).unwrap();
+}
+
+#[test]
+fn decode_stats() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC1))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let options = DecodeOptions::default();
+    let (image, stats) =
+        decode_with_stats(&compressed_data, 253, 198, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, &options).unwrap();
+    assert_eq!(image, decode_rust(&compressed_data, 253, 198, BcnEncoding::Bc1, BcnDecoderFormat::RGBA).unwrap());
+    assert_eq!(stats.blocks, 64 * 50);
+    assert_eq!(stats.source_bytes, 8 * 64 * 50);
+    assert_eq!(stats.output_bytes, 4 * 253 * 198);
+
+    // blocks past the end of a truncated source are not counted
+    let truncated = &compressed_data[..8 * 100 + 3];
+    let (_, stats) = decode_with_stats(truncated, 253, 198, BcnEncoding::Bc1, BcnDecoderFormat::RGBA, &options).unwrap();
+    assert_eq!((stats.blocks, stats.source_bytes), (100, 800));
+    assert_ne!(stats, DecodeStats::default());
+}
+
+#[test]
+fn required_byte_ranges() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // decoding just the fetched bytes gives the same rectangle
+    let (width, height) = (256, 256);
+    let rect = Rect { x: 37, y: 90, width: 20, height: 9 };
+    let range = required_byte_range(width, height, BcnEncoding::Bc3, &rect).unwrap();
+    assert_eq!(range, 16 * (64 * 22 + 9)..16 * (64 * 24 + 15));
+    let mut fetched = vec![0; compressed_data.len()];
+    fetched[range.clone()].copy_from_slice(&compressed_data[range]);
+    assert_eq!(
+        atlas::decode_rects(&fetched, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA, &[rect]).unwrap(),
+        atlas::decode_rects(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::RGBA, &[rect]).unwrap()
+    );
+    assert!(required_byte_range(width, height, BcnEncoding::Bc3, &Rect { x: 250, y: 0, width: 7, height: 1 }).is_err());
+
+    // 6x3 BC1 chain: 6x3, 3x1, 1x1
+    assert_eq!(mip_byte_range(6, 3, BcnEncoding::Bc1, 0).unwrap(), 0..16);
+    assert_eq!(mip_byte_range(6, 3, BcnEncoding::Bc1, 2).unwrap(), 24..32);
+    assert!(mip_byte_range(6, 3, BcnEncoding::Bc1, 3).is_err());
+
+    // the 2x2 level of the 8x8 ctex texture comes after the 52 byte header
+    let mut header = b"GST2".to_vec();
+    header.extend_from_slice(&[0; 32]);
+    header.extend_from_slice(&[0, 0, 0, 0, 8, 0, 8, 0, 3, 0, 0, 0, 17, 0, 0, 0]);
+    assert_eq!(godot::level_byte_range(&header, 2).unwrap(), 52 + 40..52 + 48);
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn decode_arbitrary_parameters() {
+    use arbitrary::Unstructured;
+
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC6H))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    // the test data stands in for fuzz input; decoding may fail but must
+    // not panic
+    let mut u = Unstructured::new(&compressed_data[..1 << 16]);
+    let decode_next = |u: &mut Unstructured| -> arbitrary::Result<()> {
+        let encoding: BcnEncoding = u.arbitrary()?;
+        let format: BcnDecoderFormat = u.arbitrary()?;
+        let options: DecodeOptions = u.arbitrary()?;
+        let width = u.int_in_range(1..=64)?;
+        let height = u.int_in_range(1..=64)?;
+        let _ = ::decode_with_options(u.bytes(256)?, width, height, encoding, format, &options);
+        Ok(())
+    };
+    let mut count = 0;
+    while decode_next(&mut u).is_ok() {
+        count += 1;
+    }
+    assert!(count > 100);
+}
+
+#[cfg(feature = "rgb")]
+#[test]
+fn decode_rgb_crate_pixels() {
+    let mut compressed_data = Vec::new();
+    File::open(format!("{}{}", TEST_DATA_PATH, COMPRESSED_BC3))
+        .and_then(|mut f| f.read_to_end(&mut compressed_data))
+        .unwrap();
+
+    let (width, height) = (253, 198);
+    let pixels = ::decode_rgba8(&compressed_data, width, height, BcnEncoding::Bc3).unwrap();
+    let floats = ::decode_rgb_f32(&compressed_data, width, height, BcnEncoding::Bc3).unwrap();
+    let decoded = decode_rust(&compressed_data, width, height, BcnEncoding::Bc3, BcnDecoderFormat::BGRA).unwrap();
+    let view = TextureView::from_decoded(&decoded, width, height, BcnDecoderFormat::BGRA).unwrap();
+    let typed = TextureView::from_rgba8(&pixels, width, height).unwrap();
+    for &(x, y) in &[(0, 0), (252, 0), (17, 100), (252, 197)] {
+        assert_eq!(pixels[y * width + x], view.rgba8(x, y));
+        assert_eq!(typed.rgba(x, y), view.rgba(x, y));
+        assert_eq!(floats[y * width + x].g, view.green(x, y) as f32 / 255.0);
+    }
+    assert!(::decode_rgba8(&compressed_data, width, height, BcnEncoding::Bc6H).is_err());
+}
+
+#[test]
+fn synthetic_textures() {
+    let (width, height) = (13, 10);
+    let black_white = Pattern::Checkerboard {
+        size: 3,
+        colors: [[0.0, 0.0, 0.0, 1.0], [1.0, 1.0, 1.0, 0.0]],
+    };
+    let checkerboard = Pattern::Checkerboard {
+        size: 3,
+        colors: [[0.0, 0.0, 0.0, 1.0], [1.0, 1.0, 1.0, 1.0]],
+    };
+    let gradient = Pattern::HorizontalGradient([0.0, 0.2, 1.0, 1.0], [1.0, 0.6, 0.0, 0.5]);
+    let encodings = [
+        (BcnEncoding::Bc1, BcnDecoderFormat::RGBA, 16),
+        (BcnEncoding::Bc2, BcnDecoderFormat::RGBA, 16),
+        (BcnEncoding::Bc3, BcnDecoderFormat::RGBA, 16),
+        (BcnEncoding::Bc4, BcnDecoderFormat::LUM, 8),
+        (BcnEncoding::Bc5, BcnDecoderFormat::RGBA, 8),
+        (BcnEncoding::Bc7, BcnDecoderFormat::RGBA, 8),
+    ];
+    for &(encoding, format, tolerance) in encodings.iter() {
+        for &order in [EndpointOrder::Descending, EndpointOrder::Ascending].iter() {
+            let options = SynthOptions {
+                endpoint_order: order,
+                ..Default::default()
+            };
+            for (pattern, tolerance) in [(checkerboard, 0), (gradient, tolerance)].iter() {
+                let data = synthesize(width, height, encoding, pattern, &options).unwrap();
+                let decoded = decode_rust(&data, width, height, encoding, format).unwrap();
+                let view = TextureView::from_decoded(&decoded, width, height, format).unwrap();
+                for y in 0..height {
+                    for x in 0..width {
+                        let expected = pattern.color(x, y, width, height);
+                        let pixel = view.rgba(x, y);
+                        let channels = match encoding {
+                            BcnEncoding::Bc1 => 3,
+                            BcnEncoding::Bc4 => 1,
+                            BcnEncoding::Bc5 => 2,
+                            _ => 4,
+                        };
+                        for c in 0..channels {
+                            let value = (expected[c] * 255.0).round() as i32;
+                            assert!((pixel[c] as i32 - value).abs() <= *tolerance, "{} {} {:?}", x, y, pixel);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // BC1 punch-through alpha, and the ascending endpoint order
+    let data = synthesize(8, 4, BcnEncoding::Bc1, &black_white, &SynthOptions::default()).unwrap();
+    let decoded = decode_rust(&data, 8, 4, BcnEncoding::Bc1, BcnDecoderFormat::RGBA).unwrap();
+    assert_eq!(&decoded[..16], &[0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 0][..]);
+    let ascending = SynthOptions {
+        endpoint_order: EndpointOrder::Ascending,
+        ..Default::default()
+    };
+    let data = synthesize(4, 4, BcnEncoding::Bc3, &gradient, &ascending).unwrap();
+    assert!(data[0] <= data[1]);
+    assert!(u16::from_le_bytes([data[8], data[9]]) <= u16::from_le_bytes([data[10], data[11]]));
+
+    // every BC6H mode, with endpoints of 6 to 16 bits; the gradient is
+    // shallow enough for the deltas of the transformed modes
+    let shallow = Pattern::HorizontalGradient([1.0, 2.0, 0.5, 1.0], [1.002, 2.004, 0.501, 1.0]);
+    let hdr = Pattern::HorizontalGradient([0.25, 1.0, 4.0, 1.0], [0.5, 1.5, 3.0, 1.0]);
+    let precision = [10, 7, 11, 11, 11, 9, 8, 8, 8, 6, 10, 11, 12, 16];
+    for (mode, &precision) in precision.iter().enumerate() {
+        let options = SynthOptions {
+            bc6h_mode: mode as u8,
+            ..Default::default()
+        };
+        let mut patterns = vec![(shallow, 0.01)];
+        if mode == 9 || mode == 10 {
+            patterns.push((hdr, 0.05));
+        }
+        for &(pattern, margin) in patterns.iter() {
+            let data = synthesize(8, 4, BcnEncoding::Bc6H, &pattern, &options).unwrap();
+            assert_eq!(block_mode(BcnEncoding::Bc6H, &data[16..]), Some((mode as u8, 0)));
+            let decoded = decode_to_f32(&data, 8, 4, BcnEncoding::Bc6H).unwrap();
+            for x in 0..8 {
+                let expected = pattern.color(x, 0, 8, 4);
+                for c in 0..3 {
+                    // endpoints lose up to half a quantization step of the half bits
+                    let step = 2f32.powi(15 - precision) / 1024.0;
+                    let error = (decoded[4 * x + c] - expected[c]).abs() / expected[c];
+                    assert!(error < step + margin, "mode {} pixel {} {}", mode, x, error);
+                }
+            }
+        }
+    }
+    assert!(synthesize(4, 4, BcnEncoding::Bc6H, &hdr, &SynthOptions { bc6h_mode: 14, ..Default::default() }).is_err());
+}
@@ -0,0 +1,228 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Helpers for image regression tests of crates built on this one.
+//!
+//! Goldens are raw pixel buffers, stored as they come out of the decoder.
+//! `check_golden` compares a decoded buffer against its golden with a
+//! per-channel tolerance and, on a mismatch, writes a diff image next to
+//! the golden. Run the tests with `BCNDECODE_UPDATE_GOLDENS=1` to write the
+//! current output as the new goldens instead:
+//!
+//! ```no_run
+//! use bcndecode::testutil::check_golden;
+//! # let decoded = vec![0u8; 64];
+//!
+//! // allow BC7 decoders to be off by one in the color channels
+//! check_golden(&decoded, 4, 4, &[1, 1, 1, 0], "tests/goldens/icon.rgba").unwrap();
+//! ```
+//!
+//! The module is only compiled when the `testutil` feature is enabled.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that makes `check_golden` overwrite the goldens.
+pub const UPDATE_GOLDENS_VAR: &str = "BCNDECODE_UPDATE_GOLDENS";
+
+/// Outcome of comparing a buffer against its expected contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comparison {
+    /// Number of pixels with a channel outside the tolerance
+    pub mismatched: usize,
+    /// Largest difference seen in every channel
+    pub max_difference: Vec<u8>,
+    /// Coordinates of the first mismatched pixel
+    pub first_mismatch: Option<(usize, usize)>,
+}
+
+impl Comparison {
+    /// Returns true if every pixel is within the tolerance.
+    pub fn is_match(&self) -> bool {
+        self.mismatched == 0
+    }
+}
+
+/// Compares two images with the given tolerance for every channel.
+///
+/// The number of channels is the length of `tolerance`, so `&[0]` compares
+/// a BC4 image exactly and `&[2, 2, 2, 0]` allows the color of an RGBA
+/// image to differ by 2.
+///
+/// # Panics
+///
+/// Panics if `tolerance` is empty or a buffer does not hold `width * height`
+/// pixels.
+pub fn compare(actual: &[u8], expected: &[u8], width: usize, height: usize, tolerance: &[u8]) -> Comparison {
+    let channels = tolerance.len();
+    assert!(channels > 0, "tolerance must have an entry for every channel");
+    assert_eq!(actual.len(), width * height * channels, "actual buffer has the wrong size");
+    assert_eq!(expected.len(), width * height * channels, "expected buffer has the wrong size");
+
+    let mut comparison = Comparison {
+        mismatched: 0,
+        max_difference: vec![0; channels],
+        first_mismatch: None,
+    };
+    let pixels = actual.chunks(channels).zip(expected.chunks(channels));
+    for (i, (a, e)) in pixels.enumerate() {
+        let mut mismatch = false;
+        for c in 0..channels {
+            let difference = a[c].abs_diff(e[c]);
+            comparison.max_difference[c] = comparison.max_difference[c].max(difference);
+            mismatch |= difference > tolerance[c];
+        }
+        if mismatch {
+            comparison.mismatched += 1;
+            comparison.first_mismatch.get_or_insert((i % width, i / width));
+        }
+    }
+    comparison
+}
+
+/// Builds an RGB image that highlights the differences of two images.
+///
+/// Pixels outside the tolerance are red, brighter the larger the
+/// difference; all others show the expected image as dimmed gray. Arguments
+/// are the same as for `compare`.
+///
+/// # Panics
+///
+/// Panics under the same conditions as `compare`.
+pub fn diff_image(actual: &[u8], expected: &[u8], width: usize, height: usize, tolerance: &[u8]) -> Vec<u8> {
+    let channels = tolerance.len();
+    assert!(channels > 0, "tolerance must have an entry for every channel");
+    assert_eq!(actual.len(), width * height * channels, "actual buffer has the wrong size");
+    assert_eq!(expected.len(), width * height * channels, "expected buffer has the wrong size");
+
+    let mut image = Vec::with_capacity(width * height * 3);
+    for (a, e) in actual.chunks(channels).zip(expected.chunks(channels)) {
+        let mut largest = 0;
+        let mut mismatch = false;
+        for c in 0..channels {
+            let difference = a[c].abs_diff(e[c]);
+            largest = largest.max(difference);
+            mismatch |= difference > tolerance[c];
+        }
+        if mismatch {
+            image.extend_from_slice(&[128 + largest / 2, 0, 0]);
+        } else {
+            let gray = (e[..channels.min(3)].iter().map(|&v| v as usize).sum::<usize>() / channels.min(3)) as u8 / 4;
+            image.extend_from_slice(&[gray, gray, gray]);
+        }
+    }
+    image
+}
+
+/// Writes an RGB image as a binary PPM file, which most image viewers open.
+///
+/// # Errors
+///
+/// This function will return an error if the file cannot be written.
+pub fn write_ppm<P: AsRef<Path>>(path: P, rgb: &[u8], width: usize, height: usize) -> io::Result<()> {
+    let mut file = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    file.extend_from_slice(rgb);
+    fs::write(path, file)
+}
+
+/// Compares a decoded buffer against the golden file at `path`.
+///
+/// If `BCNDECODE_UPDATE_GOLDENS` is set, the buffer is written to `path`
+/// instead. On a mismatch, a diff image made by `diff_image` is written to
+/// `path` with `.diff.ppm` appended. See `compare` for `tolerance`.
+///
+/// # Errors
+///
+/// This function will return an error if the golden cannot be read or
+/// written, has the wrong size, or does not match the buffer; the message
+/// describes the mismatch.
+pub fn check_golden<P: AsRef<Path>>(
+    actual: &[u8],
+    width: usize,
+    height: usize,
+    tolerance: &[u8],
+    path: P,
+) -> io::Result<()> {
+    let path = path.as_ref();
+    if env::var_os(UPDATE_GOLDENS_VAR).is_some() {
+        return update_golden(actual, path);
+    }
+
+    let expected = fs::read(path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("cannot read golden {}: {}, set {} to create it", path.display(), e, UPDATE_GOLDENS_VAR),
+        )
+    })?;
+    if expected.len() != actual.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "golden {} has {} bytes, the decoded buffer {}",
+                path.display(),
+                expected.len(),
+                actual.len()
+            ),
+        ));
+    }
+
+    let comparison = compare(actual, &expected, width, height, tolerance);
+    if comparison.is_match() {
+        return Ok(());
+    }
+    let diff_path = diff_path(path);
+    write_ppm(&diff_path, &diff_image(actual, &expected, width, height, tolerance), width, height)?;
+    let (x, y) = comparison.first_mismatch.unwrap();
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "{} of {} pixels differ from golden {}, first at ({}, {}), largest differences {:?}, diff written to {}",
+            comparison.mismatched,
+            width * height,
+            path.display(),
+            x,
+            y,
+            comparison.max_difference,
+            diff_path.display()
+        ),
+    ))
+}
+
+/// Writes a decoded buffer as the golden file at `path`, creating missing
+/// directories.
+///
+/// # Errors
+///
+/// This function will return an error if the file cannot be written.
+pub fn update_golden<P: AsRef<Path>>(actual: &[u8], path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, actual)
+}
+
+fn diff_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".diff.ppm");
+    PathBuf::from(name)
+}
@@ -0,0 +1,275 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Out-of-core decoding of huge images, one tile at a time.
+
+use std::convert::TryFrom;
+
+use super::decode::{block_size, decode_rust, output_params, pixel_size};
+use super::{BcnDecoderFormat, BcnEncoding, Error, Rect};
+
+/// Decodes the image in square tiles, handing every tile to `f` as soon as
+/// it is decoded.
+///
+/// Only one tile is held in memory at a time, so images can be processed
+/// that would not fit in memory decoded. Tiles are visited row by row, top
+/// row first; tiles on the right and bottom edge are cut to the image.
+/// Every tile is passed with its position and its pixels, top row first, in
+/// the pixel layout of `decode` and tightly packed. Blocks missing from
+/// `source` decode to zeros. Decoding stops at the first error `f` returns.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the
+/// given parameters, `tile_size` is 0, or `f` returns an error.
+///
+/// # Examples
+///
+/// ```
+/// // a 8x8 BC1 image of four white blocks, in 4x4 tiles
+/// let data = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0].repeat(4);
+/// let mut tiles = 0;
+/// bcndecode::decode_tiles(
+///     &data,
+///     8,
+///     8,
+///     bcndecode::BcnEncoding::Bc1,
+///     bcndecode::BcnDecoderFormat::RGBA,
+///     4,
+///     |rect, pixels| -> Result<(), bcndecode::Error> {
+///         assert_eq!(pixels.len(), 4 * rect.width * rect.height);
+///         tiles += 1;
+///         Ok(())
+///     },
+/// ).unwrap();
+/// assert_eq!(tiles, 4);
+/// ```
+pub fn decode_tiles<F, E>(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    tile_size: usize,
+    mut f: F,
+) -> Result<(), E>
+where
+    F: FnMut(Rect, &[u8]) -> Result<(), E>,
+    E: From<Error>,
+{
+    output_params(width, height, encoding, format)?;
+    if tile_size == 0 {
+        return Err(Error::InvalidImageSize.into());
+    }
+
+    let pixel_size = pixel_size(encoding, format);
+    let mut blocks = Vec::new();
+    let mut pixels = Vec::new();
+    for tile_y in (0..height).step_by(tile_size) {
+        for tile_x in (0..width).step_by(tile_size) {
+            let rect = Rect {
+                x: tile_x,
+                y: tile_y,
+                width: tile_size.min(width - tile_x),
+                height: tile_size.min(height - tile_y),
+            };
+
+            let (decoded, decoded_width) = decode_covering_blocks(source, width, encoding, format, rect, &mut blocks)?;
+
+            pixels.clear();
+            let (dx, dy) = (rect.x % 4, rect.y % 4);
+            for y in dy..dy + rect.height {
+                let start = pixel_size * (y * decoded_width + dx);
+                pixels.extend_from_slice(&decoded[start..start + pixel_size * rect.width]);
+            }
+            f(rect, &pixels)?;
+        }
+    }
+    Ok(())
+}
+
+/// Decodes the blocks covering `rect` as an image of their own, which is a
+/// multiple of the block size and so not flipped. Returns the pixels and
+/// the width of that image; `blocks` is scratch space.
+fn decode_covering_blocks(
+    source: &[u8],
+    width: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    rect: Rect,
+    blocks: &mut Vec<u8>,
+) -> Result<(Vec<u8>, usize), Error> {
+    let block_size = block_size(encoding);
+    let blocks_per_row = width.div_ceil(4);
+    let (bx0, bx1) = (rect.x / 4, (rect.x + rect.width).div_ceil(4));
+    let (by0, by1) = (rect.y / 4, (rect.y + rect.height).div_ceil(4));
+
+    blocks.clear();
+    for by in by0..by1 {
+        let start = (by * blocks_per_row + bx0) * block_size;
+        let end = start + (bx1 - bx0) * block_size;
+        if end > source.len() {
+            // the rest of the blocks decode to zeros
+            blocks.extend_from_slice(&source[start.min(source.len())..]);
+            break;
+        }
+        blocks.extend_from_slice(&source[start..end]);
+    }
+    let decoded_width = 4 * (bx1 - bx0);
+    let decoded = decode_rust(blocks, decoded_width, 4 * (by1 - by0), encoding, format)?;
+    Ok((decoded, decoded_width))
+}
+
+/// Pages of a virtual texture that are resident in memory.
+#[derive(Debug, Clone, Copy)]
+pub struct ResidencyMask<'a> {
+    /// Width and height of a page in pixels, a multiple of 4
+    pub page_size: usize,
+    /// One bit per page, least significant bit first, with the pages
+    /// numbered row by row from the top left
+    pub bits: &'a [u8],
+}
+
+impl<'a> ResidencyMask<'a> {
+    /// Returns whether the page with the given index is resident. Pages
+    /// beyond the end of the mask are not.
+    pub fn is_resident(&self, page: usize) -> bool {
+        self.bits.get(page / 8).is_some_and(|&bits| bits & (1 << (page % 8)) != 0)
+    }
+}
+
+/// Decodes the resident pages of an image into `output`.
+///
+/// `output` has the layout of `decode`, flipped if the image is not a
+/// multiple of the block size; only the pixels of resident pages are
+/// written, the rest of it is left untouched. The blocks of other pages are
+/// not read at all.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the
+/// given parameters, the page size is not a positive multiple of 4, or
+/// `output` is too small for the image.
+pub fn decode_resident(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    mask: &ResidencyMask,
+    output: &mut [u8],
+) -> Result<(), Error> {
+    output_params(width, height, encoding, format)?;
+    let page_size = mask.page_size;
+    let pixel_size = pixel_size(encoding, format);
+    if page_size == 0 || !page_size.is_multiple_of(4) || output.len() < pixel_size * width * height {
+        return Err(Error::InvalidImageSize);
+    }
+
+    let flipped = ((width & 3) | (height & 3)) != 0;
+    let pages_per_row = width.div_ceil(page_size);
+    let mut blocks = Vec::new();
+    for page in 0..pages_per_row * height.div_ceil(page_size) {
+        if !mask.is_resident(page) {
+            continue;
+        }
+        let (x, y) = (page_size * (page % pages_per_row), page_size * (page / pages_per_row));
+        let rect = Rect {
+            x,
+            y,
+            width: page_size.min(width - x),
+            height: page_size.min(height - y),
+        };
+        let (decoded, decoded_width) = decode_covering_blocks(source, width, encoding, format, rect, &mut blocks)?;
+
+        let row_size = pixel_size * rect.width;
+        for row in 0..rect.height {
+            let src = pixel_size * row * decoded_width;
+            let dst_row = if flipped { height - 1 - (y + row) } else { y + row };
+            let dst = pixel_size * (dst_row * width + x);
+            output[dst..dst + row_size].copy_from_slice(&decoded[src..src + row_size]);
+        }
+    }
+    Ok(())
+}
+
+/// Decodes an image too large for a single buffer into bands of rows.
+///
+/// The bands are `band_height` rows high, rounded up to a multiple of 4,
+/// except for the last one. Each band is returned as a buffer of its own,
+/// top row first, in the pixel layout of `decode` and tightly packed. The
+/// sizes and offsets are computed in 64 bits and checked for overflow, so
+/// only the bands have to fit in memory, not the whole image.
+///
+/// # Errors
+///
+/// This function will return an error if the data cannot be decoded with the
+/// given parameters, `band_height` is 0, or a band is too large to be
+/// allocated.
+pub fn decode_chunked(
+    source: &[u8],
+    width: u64,
+    height: u64,
+    encoding: BcnEncoding,
+    format: BcnDecoderFormat,
+    band_height: u64,
+) -> Result<Vec<Vec<u8>>, Error> {
+    if height == 0 || band_height == 0 {
+        return Err(Error::InvalidImageSize);
+    }
+    let band_width = usize::try_from(width).map_err(|_| Error::InvalidImageSize)?;
+    let pixel_size = pixel_size(encoding, format) as u64;
+    let band_rows = band_height.div_ceil(4).checked_mul(4).ok_or(Error::InvalidImageSize)?;
+    let block_row_size = width
+        .div_ceil(4)
+        .checked_mul(block_size(encoding) as u64)
+        .ok_or(Error::InvalidImageSize)?;
+
+    let mut bands = Vec::new();
+    let mut y = 0;
+    while y < height {
+        let rows = band_rows.min(height - y);
+        let band_size = pixel_size
+            .checked_mul(width)
+            .and_then(|row_size| usize::try_from(row_size).ok())
+            .and_then(|row_size| row_size.checked_mul(rows as usize))
+            .ok_or(Error::InvalidImageSize)?;
+
+        // the block rows of the band, as far as the source holds them
+        let start = (y / 4).checked_mul(block_row_size).ok_or(Error::InvalidImageSize)?;
+        let end = start.saturating_add(rows.div_ceil(4).saturating_mul(block_row_size));
+        let clamp = |offset: u64| usize::try_from(offset).unwrap_or(usize::MAX).min(source.len());
+        let decoded = decode_rust(&source[clamp(start)..clamp(end)], band_width, rows as usize, encoding, format)?;
+
+        // the decoder flips bands that are not a multiple of the block size
+        let row_size = band_size / rows as usize;
+        let band = if ((band_width & 3) | (rows as usize & 3)) != 0 {
+            decoded[..band_size].rchunks(row_size).flatten().copied().collect()
+        } else {
+            let mut decoded = decoded;
+            decoded.truncate(band_size);
+            decoded
+        };
+        bands.push(band);
+        y += rows;
+    }
+    Ok(bands)
+}
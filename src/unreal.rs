@@ -0,0 +1,120 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Mapping of Unreal Engine pixel formats to decode parameters.
+//!
+//! Cooked textures (.uasset/.ubulk) store the pixel format of their platform
+//! data as the name of an `EPixelFormat` value, e.g. `PF_DXT5`. The numeric
+//! values of that enum change between engine versions, so the mapping here
+//! is keyed on the names.
+
+use super::{BcnDecoderFormat, BcnEncoding, Error};
+
+/// Block-compressed `EPixelFormat` values of Unreal Engine.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum UnrealPixelFormat {
+    /// PF_DXT1
+    Dxt1,
+    /// PF_DXT3
+    Dxt3,
+    /// PF_DXT5
+    Dxt5,
+    /// PF_BC4
+    Bc4,
+    /// PF_BC5
+    Bc5,
+    /// PF_BC6H
+    Bc6H,
+    /// PF_BC7
+    Bc7,
+}
+
+impl UnrealPixelFormat {
+    /// Looks up the pixel format from its `EPixelFormat` name, e.g. `PF_DXT1`.
+    /// Returns `None` for names that are not block-compressed formats.
+    pub fn from_name(name: &str) -> Option<UnrealPixelFormat> {
+        match name {
+            "PF_DXT1" => Some(UnrealPixelFormat::Dxt1),
+            "PF_DXT3" => Some(UnrealPixelFormat::Dxt3),
+            "PF_DXT5" => Some(UnrealPixelFormat::Dxt5),
+            "PF_BC4" => Some(UnrealPixelFormat::Bc4),
+            "PF_BC5" => Some(UnrealPixelFormat::Bc5),
+            "PF_BC6H" => Some(UnrealPixelFormat::Bc6H),
+            "PF_BC7" => Some(UnrealPixelFormat::Bc7),
+            _ => None,
+        }
+    }
+
+    /// Returns the `EPixelFormat` name of the pixel format.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            UnrealPixelFormat::Dxt1 => "PF_DXT1",
+            UnrealPixelFormat::Dxt3 => "PF_DXT3",
+            UnrealPixelFormat::Dxt5 => "PF_DXT5",
+            UnrealPixelFormat::Bc4 => "PF_BC4",
+            UnrealPixelFormat::Bc5 => "PF_BC5",
+            UnrealPixelFormat::Bc6H => "PF_BC6H",
+            UnrealPixelFormat::Bc7 => "PF_BC7",
+        }
+    }
+
+    /// Returns the encoding of the source data.
+    pub fn encoding(&self) -> BcnEncoding {
+        match *self {
+            UnrealPixelFormat::Dxt1 => BcnEncoding::Bc1,
+            UnrealPixelFormat::Dxt3 => BcnEncoding::Bc2,
+            UnrealPixelFormat::Dxt5 => BcnEncoding::Bc3,
+            UnrealPixelFormat::Bc4 => BcnEncoding::Bc4,
+            UnrealPixelFormat::Bc5 => BcnEncoding::Bc5,
+            UnrealPixelFormat::Bc6H => BcnEncoding::Bc6H,
+            UnrealPixelFormat::Bc7 => BcnEncoding::Bc7,
+        }
+    }
+
+    /// Returns the output format the decoder should be asked for.
+    pub fn decoder_format(&self) -> BcnDecoderFormat {
+        match *self {
+            UnrealPixelFormat::Bc4 => BcnDecoderFormat::LUM,
+            _ => BcnDecoderFormat::RGBA,
+        }
+    }
+}
+
+/// Returns the encoding and output format to decode a texture whose platform
+/// data is tagged with the given `EPixelFormat` name.
+///
+/// # Errors
+///
+/// Returns `Error::InvalidPixelFormat` if the name is not a block-compressed
+/// format.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::unreal;
+///
+/// let (encoding, format) = unreal::decode_params("PF_DXT5").unwrap();
+/// # let _ = (encoding, format);
+/// ```
+pub fn decode_params(name: &str) -> Result<(BcnEncoding, BcnDecoderFormat), Error> {
+    let pixel_format = UnrealPixelFormat::from_name(name).ok_or(Error::InvalidPixelFormat)?;
+    Ok((pixel_format.encoding(), pixel_format.decoder_format()))
+}
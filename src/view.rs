@@ -0,0 +1,250 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Typed access to decoded pixels.
+
+#[cfg(feature = "rgb")]
+use rgb::{ComponentBytes, RGBA8};
+
+use super::{BcnDecoderFormat, Channel, Error, Swizzle};
+
+/// A borrowed view of decoded 8-bit pixels.
+///
+/// Knows the layout of the buffer, so pixels and channels can be read by
+/// their position instead of computing byte offsets by hand. Positions are
+/// counted from the top left pixel, whatever the order of the rows in the
+/// buffer.
+///
+/// # Examples
+///
+/// ```
+/// use bcndecode::{BcnDecoderFormat, BcnEncoding, TextureView};
+///
+/// // a 4x4 white BC1 block
+/// let block = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+/// let pixels = bcndecode::decode(&block, 4, 4, BcnEncoding::Bc1, BcnDecoderFormat::BGRA).unwrap();
+/// let view = TextureView::from_decoded(&pixels, 4, 4, BcnDecoderFormat::BGRA).unwrap();
+/// assert_eq!(view.rgba(3, 2), [0xff; 4]);
+/// assert_eq!(view.row(1).len(), 16);
+/// ```
+#[derive(Clone, Copy)]
+pub struct TextureView<'a> {
+    data: &'a [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    format: BcnDecoderFormat,
+    bottom_up: bool,
+}
+
+impl<'a> TextureView<'a> {
+    /// Creates a view of rows that are `stride` bytes apart, top row first.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidImageSize` if the stride is smaller than a
+    /// row or `data` is too short for the image.
+    pub fn new(
+        data: &'a [u8],
+        width: usize,
+        height: usize,
+        stride: usize,
+        format: BcnDecoderFormat,
+    ) -> Result<TextureView<'a>, Error> {
+        let row_size = width.checked_mul(pixel_size(format)).ok_or(Error::InvalidImageSize)?;
+        let size = match height {
+            0 => Some(0),
+            _ => stride.checked_mul(height - 1).and_then(|size| size.checked_add(row_size)),
+        };
+        if stride < row_size || size.is_none_or(|size| size > data.len()) {
+            return Err(Error::InvalidImageSize);
+        }
+        Ok(TextureView {
+            data,
+            width,
+            height,
+            stride,
+            format,
+            bottom_up: false,
+        })
+    }
+
+    /// Creates a view of the output of `decode` for an image of the given
+    /// size, which is stored bottom row first if the size is not a multiple
+    /// of the block size.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidImageSize` if `data` is too short for the
+    /// image.
+    pub fn from_decoded(
+        data: &'a [u8],
+        width: usize,
+        height: usize,
+        format: BcnDecoderFormat,
+    ) -> Result<TextureView<'a>, Error> {
+        let stride = width.checked_mul(pixel_size(format)).ok_or(Error::InvalidImageSize)?;
+        let mut view = TextureView::new(data, width, height, stride, format)?;
+        view.bottom_up = ((width & 3) | (height & 3)) != 0;
+        Ok(view)
+    }
+
+    /// Creates a view of `rgb` crate pixels, row by row, top row first.
+    ///
+    /// Requires the `rgb` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidImageSize` if `pixels` is too short for the
+    /// image.
+    #[cfg(feature = "rgb")]
+    pub fn from_rgba8(pixels: &'a [RGBA8], width: usize, height: usize) -> Result<TextureView<'a>, Error> {
+        let stride = width.checked_mul(4).ok_or(Error::InvalidImageSize)?;
+        TextureView::new(pixels.as_bytes(), width, height, stride, BcnDecoderFormat::RGBA)
+    }
+
+    /// Returns the width of the image in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height of the image in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the distance of the rows in bytes.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns the pixel format of the buffer.
+    pub fn format(&self) -> BcnDecoderFormat {
+        self.format
+    }
+
+    /// Returns the pixels of row `y`, counted from the top.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is outside the image.
+    pub fn row(&self, y: usize) -> &'a [u8] {
+        assert!(y < self.height, "row {} outside an image of height {}", y, self.height);
+        let row = if self.bottom_up { self.height - 1 - y } else { y };
+        &self.data[row * self.stride..][..self.width * pixel_size(self.format)]
+    }
+
+    /// Returns the bytes of the pixel at `(x, y)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the image.
+    pub fn pixel(&self, x: usize, y: usize) -> &'a [u8] {
+        assert!(x < self.width, "column {} outside an image of width {}", x, self.width);
+        let size = pixel_size(self.format);
+        &self.row(y)[x * size..][..size]
+    }
+
+    /// Returns the pixel at `(x, y)` in RGBA order. Luminance is repeated
+    /// in red, green and blue; formats without alpha get an opaque one.
+    /// Color channels missing from a custom format are 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the image.
+    pub fn rgba(&self, x: usize, y: usize) -> [u8; 4] {
+        let p = self.pixel(x, y);
+        match self.format {
+            BcnDecoderFormat::RGBA => [p[0], p[1], p[2], p[3]],
+            BcnDecoderFormat::BGRA => [p[2], p[1], p[0], p[3]],
+            BcnDecoderFormat::ARGB => [p[1], p[2], p[3], p[0]],
+            BcnDecoderFormat::ABGR => [p[3], p[2], p[1], p[0]],
+            BcnDecoderFormat::LUM => [p[0], p[0], p[0], 0xff],
+            BcnDecoderFormat::RGB => [p[0], p[1], p[2], 0xff],
+            BcnDecoderFormat::BGR => [p[2], p[1], p[0], 0xff],
+            BcnDecoderFormat::Custom(Swizzle(channels)) => {
+                let find = |channel, missing| channels.iter().position(|&c| c == channel).map_or(missing, |i| p[i]);
+                [
+                    find(Channel::Red, 0),
+                    find(Channel::Green, 0),
+                    find(Channel::Blue, 0),
+                    find(Channel::Alpha, 0xff),
+                ]
+            }
+        }
+    }
+
+    /// Returns the pixel at `(x, y)` as an `rgb` crate pixel, like `rgba`.
+    ///
+    /// Requires the `rgb` feature.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the image.
+    #[cfg(feature = "rgb")]
+    pub fn rgba8(&self, x: usize, y: usize) -> RGBA8 {
+        let [r, g, b, a] = self.rgba(x, y);
+        RGBA8::new(r, g, b, a)
+    }
+
+    /// Returns the red channel of the pixel at `(x, y)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the image.
+    pub fn red(&self, x: usize, y: usize) -> u8 {
+        self.rgba(x, y)[0]
+    }
+
+    /// Returns the green channel of the pixel at `(x, y)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the image.
+    pub fn green(&self, x: usize, y: usize) -> u8 {
+        self.rgba(x, y)[1]
+    }
+
+    /// Returns the blue channel of the pixel at `(x, y)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the image.
+    pub fn blue(&self, x: usize, y: usize) -> u8 {
+        self.rgba(x, y)[2]
+    }
+
+    /// Returns the alpha channel of the pixel at `(x, y)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the image.
+    pub fn alpha(&self, x: usize, y: usize) -> u8 {
+        self.rgba(x, y)[3]
+    }
+}
+
+fn pixel_size(format: BcnDecoderFormat) -> usize {
+    match format {
+        BcnDecoderFormat::LUM => 1,
+        BcnDecoderFormat::RGB | BcnDecoderFormat::BGR => 3,
+        _ => 4,
+    }
+}
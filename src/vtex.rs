@@ -0,0 +1,244 @@
+// Copyright (c) Istvan Fehervari
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Parser for Source 2 compiled textures (.vtex_c).
+//!
+//! A compiled Source 2 resource starts with a block table; the `DATA` block
+//! of a texture holds the VTEX header and is followed directly by the pixel
+//! data. Mip levels are stored smallest first, each level holding all depth
+//! slices and cube faces of that size.
+
+use super::reader::{read_u16, read_u32, read_u8};
+use super::{decode, decode_etc, BcnDecoderFormat, BcnEncoding, Error, EtcFormat, MipLevel};
+
+const RESOURCE_HEADER_VERSION: u16 = 12;
+const VTEX_VERSION: u16 = 1;
+
+// VTexFlags
+const FLAG_CUBE_TEXTURE: u16 = 0x10;
+
+// VTexExtraData
+const EXTRA_DATA_COMPRESSED_MIP_SIZE: u32 = 4;
+
+// VTexFormat
+const FORMAT_DXT1: u8 = 1;
+const FORMAT_DXT5: u8 = 2;
+const FORMAT_BC6H: u8 = 19;
+const FORMAT_BC7: u8 = 20;
+const FORMAT_ATI2N: u8 = 21;
+const FORMAT_ETC2: u8 = 23;
+const FORMAT_ETC2_EAC: u8 = 24;
+const FORMAT_R11_EAC: u8 = 25;
+const FORMAT_RG11_EAC: u8 = 26;
+const FORMAT_ATI1N: u8 = 27;
+
+/// Encoding of the image data of a Source 2 texture.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum VtexEncoding {
+    /// A BCn encoding, decoded with `decode`
+    Bcn(BcnEncoding),
+    /// An ETC2 or EAC format, decoded with `decode_etc`
+    Etc(EtcFormat),
+}
+
+impl VtexEncoding {
+    /// Returns the size of the compressed data of an image of the given
+    /// size.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidImageSize` if the size overflows.
+    pub fn compressed_size(self, width: usize, height: usize) -> Result<usize, Error> {
+        match self {
+            VtexEncoding::Bcn(encoding) => encoding.compressed_size(width, height),
+            VtexEncoding::Etc(etc_format) => width
+                .div_ceil(4)
+                .checked_mul(height.div_ceil(4))
+                .and_then(|blocks| blocks.checked_mul(etc_format.block_size()))
+                .ok_or(Error::InvalidImageSize),
+        }
+    }
+}
+
+/// A parsed Source 2 texture.
+#[derive(Debug)]
+pub struct VtexTexture<'a> {
+    /// Width of the base level in pixels
+    pub width: usize,
+    /// Height of the base level in pixels
+    pub height: usize,
+    /// Number of depth slices
+    pub depth: usize,
+    /// Number of cube faces (6 for cubemaps, 1 otherwise)
+    pub faces: usize,
+    /// The raw `VTexFlags` value
+    pub flags: u16,
+    /// The raw `VTexFormat` value
+    pub format: u8,
+    /// The mip levels, largest first. Each level holds `depth * faces` surfaces.
+    pub mipmaps: Vec<MipLevel<'a>>,
+}
+
+impl<'a> VtexTexture<'a> {
+    /// Returns the encoding of the stored image data, if it can be decoded.
+    pub fn encoding(&self) -> Option<VtexEncoding> {
+        format_encoding(self.format)
+    }
+
+    /// Returns a single depth slice or cube face of the given mip level.
+    pub fn surface(&self, level: usize, index: usize) -> Option<MipLevel<'a>> {
+        let encoding = self.encoding()?;
+        let mip = self.mipmaps.get(level)?;
+        if index >= self.depth * self.faces {
+            return None;
+        }
+        let size = encoding.compressed_size(mip.width, mip.height).ok()?;
+        Some(MipLevel {
+            width: mip.width,
+            height: mip.height,
+            data: &mip.data[index * size..(index + 1) * size],
+        })
+    }
+
+    /// Decodes the first surface of the given mip level. ETC2 and EAC
+    /// surfaces are decoded with `decode_etc`, which never flips the rows.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::FeatureNotImplemented` if the stored format cannot be
+    /// decoded, `Error::InvalidImageSize` if the level does not exist, or any
+    /// error returned by `decode` or `decode_etc`.
+    pub fn decode(&self, level: usize, format: BcnDecoderFormat) -> Result<Vec<u8>, Error> {
+        let encoding = self.encoding().ok_or(Error::FeatureNotImplemented)?;
+        let mip = self.surface(level, 0).ok_or(Error::InvalidImageSize)?;
+        match encoding {
+            VtexEncoding::Bcn(encoding) => decode(mip.data, mip.width, mip.height, encoding, format),
+            VtexEncoding::Etc(etc_format) => decode_etc(mip.data, mip.width, mip.height, etc_format, format),
+        }
+    }
+}
+
+/// Returns the encoding of a `VTexFormat` value, if it is supported.
+pub fn format_encoding(format: u8) -> Option<VtexEncoding> {
+    match format {
+        FORMAT_DXT1 => Some(VtexEncoding::Bcn(BcnEncoding::Bc1)),
+        FORMAT_DXT5 => Some(VtexEncoding::Bcn(BcnEncoding::Bc3)),
+        FORMAT_ATI1N => Some(VtexEncoding::Bcn(BcnEncoding::Bc4)),
+        FORMAT_ATI2N => Some(VtexEncoding::Bcn(BcnEncoding::Bc5)),
+        FORMAT_BC6H => Some(VtexEncoding::Bcn(BcnEncoding::Bc6H)),
+        FORMAT_BC7 => Some(VtexEncoding::Bcn(BcnEncoding::Bc7)),
+        FORMAT_ETC2 => Some(VtexEncoding::Etc(EtcFormat::Etc2Rgb8)),
+        FORMAT_ETC2_EAC => Some(VtexEncoding::Etc(EtcFormat::Etc2Rgba8)),
+        FORMAT_R11_EAC => Some(VtexEncoding::Etc(EtcFormat::EacR11)),
+        FORMAT_RG11_EAC => Some(VtexEncoding::Etc(EtcFormat::EacRg11)),
+        _ => None,
+    }
+}
+
+/// Parses a compiled Source 2 texture resource.
+///
+/// # Errors
+///
+/// Returns `Error::ImageDecodingError` if the resource is malformed or has no
+/// `DATA` block, or `Error::FeatureNotImplemented` if the texture format is not
+/// supported or its mip levels are LZ4-compressed.
+pub fn parse<'a>(data: &'a [u8]) -> Result<VtexTexture<'a>, Error> {
+    if read_u16(data, 4)? != RESOURCE_HEADER_VERSION {
+        return Err(Error::ImageDecodingError);
+    }
+
+    // block offsets are relative to the position of the offset field
+    let block_table = 8 + read_u32(data, 8)? as usize;
+    let block_count = read_u32(data, 12)? as usize;
+
+    let mut data_block = None;
+    for i in 0..block_count {
+        let entry = block_table + 12 * i;
+        if data.get(entry..entry + 4).ok_or(Error::ImageDecodingError)? == b"DATA" {
+            let offset = entry + 4 + read_u32(data, entry + 4)? as usize;
+            let size = read_u32(data, entry + 8)? as usize;
+            data_block = Some((offset, size));
+            break;
+        }
+    }
+    let (offset, size) = data_block.ok_or(Error::ImageDecodingError)?;
+
+    if read_u16(data, offset)? != VTEX_VERSION {
+        return Err(Error::ImageDecodingError);
+    }
+    let flags = read_u16(data, offset + 2)?;
+    let width = read_u16(data, offset + 20)? as usize;
+    let height = read_u16(data, offset + 22)? as usize;
+    let depth = (read_u16(data, offset + 24)? as usize).max(1);
+    let format = read_u8(data, offset + 26)?;
+    let mip_count = (read_u8(data, offset + 27)? as usize).max(1);
+    let extra_data = offset + 32 + read_u32(data, offset + 32)? as usize;
+    let extra_data_count = read_u32(data, offset + 36)? as usize;
+
+    for i in 0..extra_data_count {
+        if read_u32(data, extra_data + 12 * i)? == EXTRA_DATA_COMPRESSED_MIP_SIZE {
+            return Err(Error::FeatureNotImplemented);
+        }
+    }
+
+    let encoding = format_encoding(format).ok_or(Error::FeatureNotImplemented)?;
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidImageSize);
+    }
+    let faces = if flags & FLAG_CUBE_TEXTURE != 0 { 6 } else { 1 };
+    // every level but the last halves the larger side
+    if mip_count > (usize::BITS - width.max(height).leading_zeros()) as usize {
+        return Err(Error::ImageDecodingError);
+    }
+
+    // the pixel data follows the DATA block, smallest level first
+    let mut mipmaps = Vec::with_capacity(mip_count);
+    let mut position = offset + size;
+    for level in (0..mip_count).rev() {
+        let w = (width >> level).max(1);
+        let h = (height >> level).max(1);
+        let level_size = encoding
+            .compressed_size(w, h)?
+            .checked_mul(depth * faces)
+            .ok_or(Error::ImageDecodingError)?;
+        let level_data = position
+            .checked_add(level_size)
+            .and_then(|end| data.get(position..end))
+            .ok_or(Error::ImageDecodingError)?;
+        mipmaps.push(MipLevel {
+            width: w,
+            height: h,
+            data: level_data,
+        });
+        position += level_size;
+    }
+    mipmaps.reverse();
+
+    Ok(VtexTexture {
+        width,
+        height,
+        depth,
+        faces,
+        flags,
+        format,
+        mipmaps,
+    })
+}